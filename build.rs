@@ -0,0 +1,40 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "capi")]
+    generate_header();
+}
+
+/// Writes the C header for the `capi` module to `sexpr.h` in `OUT_DIR`, and
+/// to the path in `SEXPR_HEADER` as well if it is set, such as
+/// `include/sexpr.h` to update the header kept in the repository.
+#[cfg(feature = "capi")]
+fn generate_header() {
+    use std::env;
+    use std::path::Path;
+
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=SEXPR_HEADER");
+
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let dir = Path::new(&dir);
+    let config = cbindgen::Config::from_file(dir.join("cbindgen.toml")).unwrap();
+    let header = cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(dir.join("src/capi.rs"))
+        .generate()
+        .expect("failed to generate sexpr.h");
+    header.write_to_file(Path::new(&env::var("OUT_DIR").unwrap()).join("sexpr.h"));
+    if let Some(path) = env::var_os("SEXPR_HEADER") {
+        header.write_to_file(dir.join(path));
+    }
+}
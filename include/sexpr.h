@@ -0,0 +1,165 @@
+#ifndef SEXPR_H
+#define SEXPR_H
+
+/* Generated by cbindgen from src/capi.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The kind of a `SexprValue`, from `sexpr_kind`.
+typedef enum SexprKind {
+  // `#nil`.
+  SEXPR_KIND_NIL,
+  // `#t` or `#f`.
+  SEXPR_KIND_BOOLEAN,
+  // An integer or float.
+  SEXPR_KIND_NUMBER,
+  // A symbol, such as `define`.
+  SEXPR_KIND_SYMBOL,
+  // A keyword, such as `#:port`.
+  SEXPR_KIND_KEYWORD,
+  // A string.
+  SEXPR_KIND_STRING,
+  // A list.
+  SEXPR_KIND_LIST,
+  // A pair, with a car and a cdr.
+  SEXPR_KIND_PAIR,
+  // A byte string, such as `#x"DEADBEEF"`.
+  SEXPR_KIND_BYTES,
+  // Not a value, for a null handle.
+  SEXPR_KIND_INVALID,
+} SexprKind;
+
+// A parsed value. Only ever handled through a pointer.
+typedef struct SexprValue SexprValue;
+
+// Parses `len` bytes of UTF-8 text at `text` into a value.
+//
+// Returns null if the text is not a single S-expression. In that case, if
+// `error` is not null, `*error` is set to a message that must be released
+// with `sexpr_string_free`.
+//
+// # Safety
+//
+// `text` must point to `len` readable bytes, and `error` must be null or
+// point to writable memory for a pointer.
+struct SexprValue *sexpr_parse(const char *text, size_t len, char **error);
+
+// Releases a value returned by `sexpr_parse`. Does nothing if `value` is
+// null.
+//
+// # Safety
+//
+// `value` must be null or a handle from `sexpr_parse` that has not been
+// freed, and no value borrowed from it may be used afterwards.
+void sexpr_free(struct SexprValue *value);
+
+// The kind of `value`, or `Invalid` if `value` is null.
+//
+// # Safety
+//
+// `value` must be null or a live handle.
+enum SexprKind sexpr_kind(const struct SexprValue *value);
+
+// Stores the value of a boolean in `*out`. Returns false, leaving `*out`
+// alone, if `value` is not a boolean.
+//
+// # Safety
+//
+// `value` must be a live handle and `out` must point to writable memory.
+bool sexpr_as_bool(const struct SexprValue *value, bool *out);
+
+// Stores a number that fits an `int64_t` in `*out`. Returns false, leaving
+// `*out` alone, otherwise.
+//
+// # Safety
+//
+// `value` must be a live handle and `out` must point to writable memory.
+bool sexpr_as_i64(const struct SexprValue *value, int64_t *out);
+
+// Stores a number that fits a `uint64_t` in `*out`. Returns false, leaving
+// `*out` alone, otherwise.
+//
+// # Safety
+//
+// `value` must be a live handle and `out` must point to writable memory.
+bool sexpr_as_u64(const struct SexprValue *value, uint64_t *out);
+
+// Stores any number, converted to a `double`, in `*out`. Returns false,
+// leaving `*out` alone, if `value` is not a number.
+//
+// # Safety
+//
+// `value` must be a live handle and `out` must point to writable memory.
+bool sexpr_as_f64(const struct SexprValue *value, double *out);
+
+// The UTF-8 text of a symbol, keyword or string, which is not
+// NUL-terminated, with its length stored in `*len`. A keyword's text does
+// not include its `#:` prefix. Returns null if `value` is not one of
+// these.
+//
+// # Safety
+//
+// `value` must be a live handle and `len` must point to writable memory.
+const char *sexpr_atom_text(const struct SexprValue *value, size_t *len);
+
+// The contents of a byte string, with their length stored in `*len`.
+// Returns null if `value` is not a byte string.
+//
+// # Safety
+//
+// `value` must be a live handle and `len` must point to writable memory.
+const uint8_t *sexpr_bytes(const struct SexprValue *value, size_t *len);
+
+// The number of elements of a list, or 0 if `value` is not a list.
+//
+// # Safety
+//
+// `value` must be a live handle.
+size_t sexpr_list_len(const struct SexprValue *value);
+
+// The element of a list at `index`, borrowed from `value`. Returns null if
+// `value` is not a list or `index` is out of range.
+//
+// # Safety
+//
+// `value` must be a live handle.
+const struct SexprValue *sexpr_list_get(const struct SexprValue *value, size_t index);
+
+// The car of a pair, borrowed from `value`, or null if `value` is not a
+// pair.
+//
+// # Safety
+//
+// `value` must be a live handle.
+const struct SexprValue *sexpr_pair_car(const struct SexprValue *value);
+
+// The cdr of a pair, borrowed from `value`, or null if `value` is not a
+// pair.
+//
+// # Safety
+//
+// `value` must be a live handle.
+const struct SexprValue *sexpr_pair_cdr(const struct SexprValue *value);
+
+// Writes `value` as S-expression text, pretty-printed if `pretty` is true.
+// The result is NUL-terminated and must be released with
+// `sexpr_string_free`. Returns null if the value cannot be written.
+//
+// # Safety
+//
+// `value` must be a live handle.
+char *sexpr_to_string(const struct SexprValue *value, bool pretty);
+
+// Releases a string returned by `sexpr_to_string` or through the error
+// pointer of `sexpr_parse`. Does nothing if `s` is null.
+//
+// # Safety
+//
+// `s` must be null or a string from this library that has not been freed.
+void sexpr_string_free(char *s);
+
+#endif /* SEXPR_H */
@@ -5,18 +5,64 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
-use crate::error::Error;
+use crate::error::{Error, ErrorCode};
 use serde::de::{self, Visitor};
 use serde::{forward_to_deserialize_any, Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::{self, Display};
 
 use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Symbols and keywords have no counterpart in the serde data model, so they
+// travel as newtype structs with reserved names around their text:
+//
+// - Serializing, `Atom`, `SexpRef`, `Symbol` and `Keyword` call
+//   `serialize_newtype_struct` with `SYMBOL_TOKEN` or `KEYWORD_TOKEN`. The
+//   S-expression serializer writes the text as a bare symbol, or as a
+//   keyword with its `#:` prefix, quoting it between bars where needed (see
+//   `Formatter::write_symbol`). The `Sexp` serializer keeps the atom kind.
+//   Other serializers see an ordinary newtype around a string.
+// - Deserializing, the S-expression deserializer hands a symbol or keyword
+//   to `visit_newtype_struct`, around a string holding the symbol's name or
+//   the keyword with its `#:` prefix; `Atom::from_token_text` tells them
+//   apart again.
+
+fn check_bare_symbol(s: String) -> Result<String, Error> {
+    if crate::ser::symbol_needs_bars(&s) {
+        Err(Error::syntax(ErrorCode::InvalidSymbol(s), 0, 0))
+    } else {
+        Ok(s)
+    }
+}
+
+/// Name of the newtype struct a symbol serializes itself as.
+pub(crate) const SYMBOL_TOKEN: &str = "$sexpr::private::Symbol";
+
+/// Name of the newtype struct a keyword serializes itself as.
+pub(crate) const KEYWORD_TOKEN: &str = "$sexpr::private::Keyword";
 
 /// Represents a Sexp atom, whether symbol, keyword or string.
-#[derive(Clone, Debug, PartialEq)]
+///
+/// Match on the variants to tell the kinds apart, or use `as_symbol`,
+/// `as_keyword` and `as_string` to borrow the text of one kind.
+///
+/// ```rust
+/// # use sexpr::sexp::Atom;
+/// let atom = Atom::keyword("key");
+/// match atom {
+///     Atom::Keyword(ref k) => assert_eq!(k, "key"),
+///     _ => unreachable!(),
+/// }
+/// assert_eq!(atom.as_keyword(), Some("key"));
+/// assert_eq!(atom.as_symbol(), None);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Atom {
+    /// A bare symbol such as `foo`.
     Symbol(String),
+    /// A keyword such as `#:foo`, without its `#:` prefix.
     Keyword(String),
+    /// A string such as `"foo"`, without its quotes.
     String(String),
 }
 
@@ -53,32 +99,106 @@ impl Atom {
         Atom::Symbol(s)
     }
 
+    /// Creates a symbol if `s` can be written bare, without bars: it starts
+    /// with a letter and holds no whitespace, brackets, `"`, `;`, `|` or
+    /// `\\`. Useful where a name from outside the program should never turn
+    /// into a quoted symbol.
+    ///
+    /// ```rust
+    /// # use sexpr::sexp::Atom;
+    /// assert_eq!(Atom::try_new_symbol("open-file".into()).unwrap(), Atom::symbol("open-file"));
+    /// assert!(Atom::try_new_symbol("has space)".into()).is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::InvalidSymbol` if `s` would need bars.
+    pub fn try_new_symbol(s: String) -> Result<Self, Error> {
+        check_bare_symbol(s).map(Atom::Symbol)
+    }
+
+    /// Creates a keyword if its name, without the `#:` prefix, can be
+    /// written bare. See `try_new_symbol`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::InvalidSymbol` if `s` would need bars.
+    pub fn try_new_keyword(s: String) -> Result<Self, Error> {
+        check_bare_symbol(s).map(Atom::Keyword)
+    }
+
+    /// Creates a symbol with exactly this name, without looking at its
+    /// contents.
+    #[inline]
+    pub fn symbol<S: Into<String>>(s: S) -> Self {
+        Atom::Symbol(s.into())
+    }
+
+    /// Creates a keyword with exactly this name, which should not include
+    /// the `#:` prefix.
+    #[inline]
+    pub fn keyword<S: Into<String>>(s: S) -> Self {
+        Atom::Keyword(s.into())
+    }
+
+    /// Creates a string with exactly these contents, without looking at
+    /// them.
+    #[inline]
+    pub fn string<S: Into<String>>(s: S) -> Self {
+        Atom::String(s.into())
+    }
+
     /// Returns an Atom appropriate for it's contents.
     ///
-    /// Criteria for discriminating variants can be configured as appropriate.
+    /// Text starting with `#:` becomes a keyword and text enclosed in double
+    /// or single quotes becomes a string, with the prefix or quotes removed.
+    /// Anything else becomes a symbol. Use `Atom::symbol`, `Atom::keyword`
+    /// or `Atom::string` to pick the kind explicitly.
+    ///
     /// # Examples
+    ///
+    /// ```rust
+    /// # use sexpr::sexp::Atom;
+    /// assert_eq!(Atom::discriminate("#:key".into()), Atom::keyword("key"));
+    /// assert_eq!(Atom::discriminate("\"text\"".into()), Atom::string("text"));
+    /// assert_eq!(Atom::discriminate("'text'".into()), Atom::string("text"));
+    /// assert_eq!(Atom::discriminate("name".into()), Atom::symbol("name"));
+    /// ```
     pub fn discriminate(s: String) -> Self {
         if s.starts_with("#:") {
             let (_, keyword) = s.split_at(2);
             Atom::Keyword(String::from(keyword))
-        } else if (s.starts_with('"') && s.ends_with('"'))
-            || (s.starts_with('\'') && s.ends_with('\''))
+        } else if s.len() >= 2
+            && ((s.starts_with('"') && s.ends_with('"'))
+                || (s.starts_with('\'') && s.ends_with('\'')))
         {
-            Atom::String(String::from(&s[1..s.len()]))
+            Atom::String(String::from(&s[1..s.len() - 1]))
         } else {
             Atom::Symbol(s)
         }
     }
 
+    /// Makes the atom for the text the deserializer passes to
+    /// `visit_newtype_struct`: a keyword if it has the `#:` prefix, else a
+    /// symbol.
+    pub(crate) fn from_token_text(text: String) -> Self {
+        match text.strip_prefix("#:") {
+            Some(keyword) => Atom::Keyword(keyword.to_owned()),
+            None => Atom::Symbol(text),
+        }
+    }
+
+    /// Guesses the kind of atom from the text, as `Atom::discriminate` does.
+    /// To read an atom as the parser would, use `str::parse`.
     #[inline]
-    #[allow(clippy::should_implement_trait)]
-    pub fn from_str(s: &str) -> Self {
+    pub fn from_str_guess(s: &str) -> Self {
         Atom::discriminate(String::from(s))
     }
 
+    /// Makes a symbol with exactly this name, as `From<String>` does.
     #[inline]
     pub fn from_string(s: String) -> Self {
-        Atom::discriminate(s)
+        Atom::Symbol(s)
     }
 
     #[inline]
@@ -90,18 +210,84 @@ impl Atom {
         }
     }
 
+    /// Consumes the atom and returns its text, whatever its kind.
     #[inline]
-    pub fn as_string(&self) -> String {
-        let s = match self {
-            Atom::Symbol(ref s) => s,
-            Atom::Keyword(ref s) => s,
-            Atom::String(ref s) => s,
-        };
+    pub fn into_string(self) -> String {
+        match self {
+            Atom::Symbol(s) | Atom::Keyword(s) | Atom::String(s) => s,
+        }
+    }
+
+    /// Returns the name of a symbol, or `None` for other atoms.
+    #[inline]
+    pub fn as_symbol(&self) -> Option<&str> {
+        match self {
+            Atom::Symbol(ref s) => Some(s),
+            Atom::Keyword(_) | Atom::String(_) => None,
+        }
+    }
+
+    /// Returns the name of a keyword without its `#:` prefix, or `None` for
+    /// other atoms.
+    #[inline]
+    pub fn as_keyword(&self) -> Option<&str> {
+        match self {
+            Atom::Keyword(ref s) => Some(s),
+            Atom::Symbol(_) | Atom::String(_) => None,
+        }
+    }
 
-        s.clone()
+    /// Returns the contents of a string, or `None` for other atoms.
+    #[inline]
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            Atom::String(ref s) => Some(s),
+            Atom::Symbol(_) | Atom::Keyword(_) => None,
+        }
+    }
+
+    /// Whether this is a symbol named like the ones `gensym` makes, a prefix
+    /// followed by `#` and a number.
+    ///
+    /// Only the name is looked at, so a symbol read from text such as
+    /// `tmp#3` is a gensym too.
+    pub fn is_gensym(&self) -> bool {
+        let name = match self {
+            Atom::Symbol(ref name) => name,
+            Atom::Keyword(_) | Atom::String(_) => return false,
+        };
+        match name.rfind('#') {
+            Some(i) => {
+                let digits = &name[i + 1..];
+                !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+            }
+            None => false,
+        }
     }
 }
 
+static GENSYM_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Makes a new symbol, named `prefix` followed by `#` and a number, that is
+/// distinct from every other symbol `gensym` returns in this process.
+///
+/// Code generators use these for temporaries that must not collide with
+/// each other. They can still collide with a symbol written by hand with
+/// the same name, which is why the `#` is there: ordinary code rarely
+/// spells names that way.
+///
+/// ```rust
+/// let a = sexpr::gensym("tmp");
+/// let b = sexpr::gensym("tmp");
+/// assert_ne!(a, b);
+/// assert!(a.as_str().starts_with("tmp#"));
+/// assert!(a.is_gensym());
+/// ```
+pub fn gensym(prefix: &str) -> Atom {
+    let n = GENSYM_COUNTER.fetch_add(1, Ordering::Relaxed);
+    Atom::Symbol(format!("{}#{}", prefix, n))
+}
+
 impl fmt::Display for Atom {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -119,8 +305,8 @@ impl Serialize for Atom {
         S: Serializer,
     {
         match self {
-            Atom::Symbol(ref s) => serializer.serialize_newtype_struct("Symbol", s),
-            Atom::Keyword(ref s) => serializer.serialize_str(s),
+            Atom::Symbol(ref s) => serializer.serialize_newtype_struct(SYMBOL_TOKEN, s),
+            Atom::Keyword(ref s) => serializer.serialize_newtype_struct(KEYWORD_TOKEN, s),
             Atom::String(ref s) => serializer.serialize_str(s),
         }
     }
@@ -149,12 +335,46 @@ impl<'de> Deserialize<'de> for Atom {
                 self.visit_string(String::from(value))
             }
 
+            /// Strings are read as they are, as for a `Sexp`; symbols and
+            /// keywords arrive through `visit_newtype_struct`.
             #[inline]
             fn visit_string<E>(self, value: String) -> Result<Atom, E>
             where
                 E: de::Error,
             {
-                Ok(Atom::from_string(value))
+                Ok(Atom::String(value))
+            }
+
+            /// A byte string is read as a string if it is UTF-8.
+            #[inline]
+            fn visit_bytes<E>(self, value: &[u8]) -> Result<Atom, E>
+            where
+                E: de::Error,
+            {
+                self.visit_byte_buf(value.to_vec())
+            }
+
+            #[inline]
+            fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Atom, E>
+            where
+                E: de::Error,
+            {
+                match String::from_utf8(value) {
+                    Ok(s) => Ok(Atom::String(s)),
+                    Err(err) => Err(de::Error::invalid_value(
+                        de::Unexpected::Bytes(err.as_bytes()),
+                        &self,
+                    )),
+                }
+            }
+
+            #[inline]
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Atom, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                let text = String::deserialize(deserializer)?;
+                Ok(Atom::from_token_text(text))
             }
         }
 
@@ -206,23 +426,188 @@ impl<'de, 'a> Deserializer<'de> for &'a Atom {
     }
 }
 
+/// Makes a symbol with exactly this name, as `Atom::symbol` does. Use
+/// `Atom::discriminate` to guess the kind from the text instead.
 impl From<String> for Atom {
     #[inline]
     fn from(s: String) -> Self {
-        Atom::from_string(s)
+        Atom::Symbol(s)
     }
 }
 
+/// Makes a symbol with exactly this name, so `Atom::from("x")` is the symbol
+/// `x` and `Atom::from("\"x\"")` the symbol `"x"`, quotes and all.
 impl<'a> From<&'a str> for Atom {
     #[inline]
     fn from(s: &'a str) -> Self {
-        Atom::from_str(s)
+        Atom::Symbol(String::from(s))
     }
 }
 
+/// Makes a symbol with exactly this name, as `Atom::symbol` does.
 impl<'a> From<Cow<'a, str>> for Atom {
     #[inline]
     fn from(s: Cow<'a, str>) -> Self {
-        Atom::from_string(s.to_string())
+        Atom::Symbol(s.into_owned())
     }
 }
+
+/// A symbol name, for struct fields that must be written as a bare symbol
+/// rather than a quoted string.
+///
+/// ```rust
+/// # use serde_derive::{Deserialize, Serialize};
+/// use sexpr::sexp::Symbol;
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Form {
+///     head: Symbol,
+///     name: String,
+/// }
+///
+/// let form = Form { head: Symbol::new("define"), name: "x".to_owned() };
+/// assert_eq!(sexpr::to_string(&form).unwrap(), r#"("head".define "name"."x")"#);
+///
+/// let read: Form = sexpr::from_str(r#"((head . define) (name . "x"))"#).unwrap();
+/// assert_eq!(read, form);
+/// ```
+///
+/// When deserializing, a string is accepted as well as a symbol, so the
+/// name can also come from formats that have no symbols. A keyword is
+/// rejected.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(pub String);
+
+/// A keyword name without its `#:` prefix, for struct fields that must be
+/// written as a keyword such as `#:foo`.
+///
+/// ```rust
+/// use sexpr::sexp::Keyword;
+///
+/// let s = sexpr::to_string(&(Keyword::new("size"), 3)).unwrap();
+/// assert_eq!(s, "(#:size 3)");
+/// let back: (Keyword, u8) = sexpr::from_str(&s).unwrap();
+/// assert_eq!(back.0.as_str(), "size");
+/// ```
+///
+/// When deserializing, a string is accepted as well as a keyword, with a
+/// leading `#:` removed if it has one.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Keyword(pub String);
+
+macro_rules! impl_name {
+    ($ty:ident, $token:ident, $expecting:expr, $accept:expr) => {
+        impl $ty {
+            /// Creates the name from the given text.
+            #[inline]
+            pub fn new<S: Into<String>>(s: S) -> Self {
+                $ty(s.into())
+            }
+
+            /// Returns the name as a string slice.
+            #[inline]
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            /// Unwraps the name.
+            #[inline]
+            pub fn into_string(self) -> String {
+                self.0
+            }
+        }
+
+        impl fmt::Display for $ty {
+            fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                Display::fmt(&self.0, formatter)
+            }
+        }
+
+        impl AsRef<str> for $ty {
+            #[inline]
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl From<String> for $ty {
+            #[inline]
+            fn from(s: String) -> Self {
+                $ty(s)
+            }
+        }
+
+        impl<'a> From<&'a str> for $ty {
+            #[inline]
+            fn from(s: &'a str) -> Self {
+                $ty(s.to_owned())
+            }
+        }
+
+        impl From<$ty> for Atom {
+            #[inline]
+            fn from(name: $ty) -> Self {
+                Atom::$ty(name.0)
+            }
+        }
+
+        impl Serialize for $ty {
+            #[inline]
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.serialize_newtype_struct($token, &self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D>(deserializer: D) -> Result<$ty, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                struct NameVisitor;
+
+                impl<'de> Visitor<'de> for NameVisitor {
+                    type Value = $ty;
+
+                    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                        formatter.write_str($expecting)
+                    }
+
+                    #[inline]
+                    fn visit_str<E>(self, value: &str) -> Result<$ty, E>
+                    where
+                        E: de::Error,
+                    {
+                        let accept: fn(&str) -> Option<&str> = $accept;
+                        match accept(value) {
+                            Some(name) => Ok($ty(name.to_owned())),
+                            None => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+                        }
+                    }
+
+                    #[inline]
+                    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<$ty, D::Error>
+                    where
+                        D: Deserializer<'de>,
+                    {
+                        deserializer.deserialize_str(self)
+                    }
+                }
+
+                deserializer.deserialize_newtype_struct($token, NameVisitor)
+            }
+        }
+    };
+}
+
+impl_name!(
+    Symbol,
+    SYMBOL_TOKEN,
+    "a symbol",
+    |s| if s.starts_with("#:") { None } else { Some(s) }
+);
+impl_name!(Keyword, KEYWORD_TOKEN, "a keyword", |s| Some(
+    s.strip_prefix("#:").unwrap_or(s)
+));
@@ -0,0 +1,215 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `sexp`, a command line tool for S-expression files. Requires the `cli`
+//! feature.
+//!
+//! Every command reads the named file, or standard input if the file is
+//! missing or `-`, and writes to standard output. An input may hold any
+//! number of top-level forms.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+
+use sexpr::sexp::Atom;
+use sexpr::{Deserializer, FormatStyle, Sexp};
+
+const USAGE: &str = "\
+usage: sexp <command> [args]
+
+commands:
+    fmt [FILE]          re-print FILE in the standard layout, keeping comments
+    validate [FILE...]  check that each FILE reads as S-expressions
+    to-json [FILE]      print each form in FILE as a line of JSON
+    from-json [FILE]    print the JSON document in FILE as an S-expression
+    get PATH [FILE]     print the value at PATH in each form in FILE
+
+A PATH is a list of steps separated by `.`. A number picks an element of a
+list; anything else picks the value of a key in an alist or a keyword in a
+plist, as in `hosts.0.port`.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args.as_slice() {
+        ["fmt", file @ ..] if file.len() <= 1 => fmt(file.first().copied()),
+        ["validate"] => validate(None),
+        ["validate", files @ ..] => validate_all(files),
+        ["to-json", file @ ..] if file.len() <= 1 => to_json(file.first().copied()),
+        ["from-json", file @ ..] if file.len() <= 1 => from_json(file.first().copied()),
+        ["get", path, file @ ..] if file.len() <= 1 => get(path, file.first().copied()),
+        ["help"] | ["--help"] | ["-h"] => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(msg) = result {
+        eprintln!("sexp: {}", msg);
+        process::exit(1);
+    }
+}
+
+type Result<T> = std::result::Result<T, String>;
+
+fn fmt(file: Option<&str>) -> Result<()> {
+    let input = read_input(file)?;
+    let output = sexpr::reformat(&input, &FormatStyle::new()).map_err(|err| located(file, err))?;
+    write_output(output.as_bytes())
+}
+
+fn validate(file: Option<&str>) -> Result<()> {
+    read_forms(file).map(|_| ())
+}
+
+/// Checks every file rather than stopping at the first bad one, reporting
+/// each failure as it is found.
+fn validate_all(files: &[&str]) -> Result<()> {
+    let mut failed = 0;
+    for file in files {
+        if let Err(msg) = validate(Some(file)) {
+            eprintln!("sexp: {}", msg);
+            failed += 1;
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!(
+            "{} of {} files failed to validate",
+            failed,
+            files.len()
+        )),
+    }
+}
+
+fn to_json(file: Option<&str>) -> Result<()> {
+    let mut out = String::new();
+    for form in read_forms(file)? {
+        out.push_str(&form.to_json_string());
+        out.push('\n');
+    }
+    write_output(out.as_bytes())
+}
+
+fn from_json(file: Option<&str>) -> Result<()> {
+    let input = read_input(file)?;
+    let value: serde_json::Value =
+        serde_json::from_str(&input).map_err(|err| format!("{}: {}", name(file), err))?;
+    let text = json_to_sexp(value).to_string();
+    let out = sexpr::reformat(&text, &FormatStyle::new()).map_err(|err| err.to_string())?;
+    write_output(out.as_bytes())
+}
+
+/// Converts JSON to a `Sexp` that reads back from text. An object becomes a
+/// list of `(key value)` entries, which `get` looks keys up in.
+fn json_to_sexp(value: serde_json::Value) -> Sexp {
+    use serde_json::Value;
+
+    match value {
+        Value::Null => Sexp::Nil,
+        Value::Bool(b) => Sexp::Boolean(b),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => Sexp::from(u),
+            (None, Some(i)) => Sexp::from(i),
+            _ => Sexp::from(n.as_f64().unwrap_or(0.0)),
+        },
+        Value::String(s) => Sexp::Atom(Atom::string(s)),
+        Value::Array(elems) => Sexp::List(elems.into_iter().map(json_to_sexp).collect()),
+        Value::Object(entries) => Sexp::List(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    Sexp::List(vec![Sexp::Atom(Atom::string(key)), json_to_sexp(value)])
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn get(path: &str, file: Option<&str>) -> Result<()> {
+    let mut out = String::new();
+    for form in read_forms(file)? {
+        if let Some(value) = lookup(&form, path) {
+            out.push_str(&format!("{:#}\n", value));
+        }
+    }
+    write_output(out.as_bytes())
+}
+
+/// Follows `path` down from `form`, or returns `None` if a step is missing.
+fn lookup<'a>(form: &'a Sexp, path: &str) -> Option<&'a Sexp> {
+    path.split('.')
+        .filter(|step| !step.is_empty())
+        .try_fold(form, |sexp, step| match step.parse::<usize>() {
+            Ok(i) => match *sexp {
+                Sexp::List(ref elems) => elems.get(i),
+                _ => None,
+            },
+            Err(_) => sexp.alist_get(step).or_else(|| sexp.plist_get(step)),
+        })
+}
+
+fn read_forms(file: Option<&str>) -> Result<Vec<Sexp>> {
+    let input = read_input(file)?;
+    Deserializer::from_str(&input)
+        .into_iter::<Sexp>()
+        .collect::<sexpr::Result<_>>()
+        .map_err(|err| located(file, err))
+}
+
+fn read_input(file: Option<&str>) -> Result<String> {
+    let mut input = String::new();
+    let read = match file {
+        None | Some("-") => io::stdin().read_to_string(&mut input).map(|_| ()),
+        Some(path) => fs::read_to_string(path).map(|text| input = text),
+    };
+    read.map_err(|err| format!("{}: {}", name(file), err))?;
+    Ok(input)
+}
+
+fn write_output(bytes: &[u8]) -> Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match stdout.write_all(bytes).and_then(|()| stdout.flush()) {
+        Ok(()) => Ok(()),
+        // Output piped into `head` and the like is cut short on purpose.
+        Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn name(file: Option<&str>) -> &str {
+    match file {
+        None | Some("-") => "<stdin>",
+        Some(path) => path,
+    }
+}
+
+/// Formats a parse error as `file:line:column: message`.
+fn located(file: Option<&str>, err: sexpr::Error) -> String {
+    if err.line() == 0 {
+        format!("{}: {}", name(file), err)
+    } else {
+        let msg = err.to_string();
+        let suffix = format!(" at line {} column {}", err.line(), err.column());
+        format!(
+            "{}:{}:{}: {}",
+            name(file),
+            err.line(),
+            err.column(),
+            msg.strip_suffix(&suffix).unwrap_or(&msg)
+        )
+    }
+}
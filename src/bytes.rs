@@ -0,0 +1,233 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The text encodings of byte strings, and wrappers that pick one per field.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+/// Name of the newtype struct a `HexBytes` passes through serde as. The
+/// S-expression serializer writes the bytes as hex whatever its
+/// `BytesStyle`.
+pub(crate) const HEX_BYTES_TOKEN: &str = "$sexpr::private::HexBytes";
+
+/// Name of the newtype struct a `Base64Bytes` passes through serde as.
+pub(crate) const BASE64_BYTES_TOKEN: &str = "$sexpr::private::Base64Bytes";
+
+/// Bytes that are always written in hex: `#x"DEADBEEF"`, or `#DEADBEEF#`
+/// as the transport tokens of Rivest's S-expressions write them if the
+/// serializer's `HexStyle` says so. Useful for fields such as digests
+/// and keys that should be readable whatever `BytesStyle` the rest of the
+/// data uses.
+///
+/// Reading accepts any form of byte string, not only hex.
+///
+/// ```rust
+/// use serde_derive::Serialize;
+/// use sexpr::{Base64Bytes, HexBytes};
+///
+/// #[derive(Serialize)]
+/// struct Signed {
+///     digest: HexBytes,
+///     signature: Base64Bytes,
+/// }
+///
+/// let signed = Signed {
+///     digest: HexBytes(vec![0xde, 0xad, 0xbe, 0xef]),
+///     signature: Base64Bytes(b"hello".to_vec()),
+/// };
+/// assert_eq!(
+///     sexpr::to_string(&signed).unwrap(),
+///     "(\"digest\".#x\"DEADBEEF\" \"signature\".|aGVsbG8=|)"
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HexBytes(pub Vec<u8>);
+
+/// Bytes that are always written in base64 between bars, as the transport
+/// tokens of Rivest's S-expressions write them: `|aGVsbG8=|`. A `Sexp`
+/// reads them back as `Sexp::Bytes` with `DeserializerOptions::base64_bars`.
+/// See `HexBytes`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Base64Bytes(pub Vec<u8>);
+
+macro_rules! impl_bytes_wrapper {
+    ($name:ident, $token:expr) => {
+        impl From<Vec<u8>> for $name {
+            fn from(bytes: Vec<u8>) -> Self {
+                $name(bytes)
+            }
+        }
+
+        impl From<$name> for Vec<u8> {
+            fn from(bytes: $name) -> Self {
+                bytes.0
+            }
+        }
+
+        impl Deref for $name {
+            type Target = Vec<u8>;
+
+            fn deref(&self) -> &Vec<u8> {
+                &self.0
+            }
+        }
+
+        impl DerefMut for $name {
+            fn deref_mut(&mut self) -> &mut Vec<u8> {
+                &mut self.0
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.serialize_newtype_struct($token, &ByteSlice(&self.0))
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_byte_buf(ByteBufVisitor).map($name)
+            }
+        }
+    };
+}
+
+impl_bytes_wrapper!(HexBytes, HEX_BYTES_TOKEN);
+impl_bytes_wrapper!(Base64Bytes, BASE64_BYTES_TOKEN);
+
+/// Serializes as a byte string rather than a sequence.
+pub(crate) struct ByteSlice<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Serialize for ByteSlice<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+struct ByteBufVisitor;
+
+impl<'de> Visitor<'de> for ByteBufVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("byte array")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(bytes.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(bytes)
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Vec<u8>, E> {
+        Ok(s.as_bytes().to_vec())
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Vec<u8>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes` as standard base64, padded with `=`.
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes standard base64, with or without its `=` padding. Returns `None`
+/// if `text` is not base64.
+pub(crate) fn decode_base64(text: &[u8]) -> Option<Vec<u8>> {
+    let digits = match text.iter().position(|&b| b == b'=') {
+        Some(pad) if text[pad..].iter().all(|&b| b == b'=') && text.len().is_multiple_of(4) => {
+            &text[..pad]
+        }
+        Some(_) => return None,
+        None => text,
+    };
+    if digits.len() % 4 == 1 || text.len() - digits.len() > 2 {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let mut n = 0u32;
+        for (i, &digit) in chunk.iter().enumerate() {
+            let value = BASE64_ALPHABET.iter().position(|&b| b == digit)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        out.extend_from_slice(&bytes[..chunk.len() - 1]);
+    }
+    Some(out)
+}
+
+/// Encodes `bytes` as pairs of upper case hex digits.
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for &b in bytes {
+        out.push(DIGITS[(b >> 4) as usize] as char);
+        out.push(DIGITS[(b & 0xf) as usize] as char);
+    }
+    out
+}
+
+/// Decodes pairs of hex digits in either case. Returns `None` if `text` is
+/// not hex.
+pub(crate) fn decode_hex(text: &[u8]) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    text.chunks(2)
+        .map(|pair| {
+            let high = (pair[0] as char).to_digit(16)?;
+            let low = (pair[1] as char).to_digit(16)?;
+            Some((high << 4 | low) as u8)
+        })
+        .collect()
+}
@@ -0,0 +1,373 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A C interface to the parser, for bindings in other languages. Requires
+//! the `capi` feature.
+//!
+//! The header is `include/sexpr.h`. Building with the feature generates it
+//! into Cargo's `OUT_DIR`, and also to the path in the `SEXPR_HEADER`
+//! environment variable if that is set, so
+//! `SEXPR_HEADER=include/sexpr.h cargo build --features capi` updates it. To
+//! get a library C can link against, build with
+//! `cargo rustc --release --features capi --crate-type cdylib` (or
+//! `staticlib`).
+//!
+//! `sexpr_parse` returns an owned `SexprValue`, which must be released with
+//! `sexpr_free`. The values reached from it, such as list elements, are
+//! borrowed and stay valid until their root is freed. Strings returned by
+//! `sexpr_to_string` or through an error pointer are owned by the caller and
+//! must be released with `sexpr_string_free`.
+//!
+//! Text passed in or handed out is UTF-8 with an explicit length, since
+//! strings may contain NUL bytes.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic;
+use std::ptr;
+use std::slice;
+
+use crate::atom::Atom;
+use crate::number::Number;
+use crate::sexp::Sexp;
+
+/// A parsed value. Only ever handled through a pointer.
+pub struct SexprValue {
+    _private: [u8; 0],
+}
+
+/// The kind of a `SexprValue`, from `sexpr_kind`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SexprKind {
+    /// `#nil`.
+    Nil,
+    /// `#t` or `#f`.
+    Boolean,
+    /// An integer or float.
+    Number,
+    /// A symbol, such as `define`.
+    Symbol,
+    /// A keyword, such as `#:port`.
+    Keyword,
+    /// A string.
+    String,
+    /// A list.
+    List,
+    /// A pair, with a car and a cdr.
+    Pair,
+    /// A byte string, such as `#x"DEADBEEF"`.
+    Bytes,
+    /// Not a value, for a null handle.
+    Invalid,
+}
+
+fn into_handle(sexp: Sexp) -> *mut SexprValue {
+    Box::into_raw(Box::new(sexp)) as *mut SexprValue
+}
+
+fn as_handle(sexp: &Sexp) -> *const SexprValue {
+    sexp as *const Sexp as *const SexprValue
+}
+
+/// # Safety
+///
+/// `value` must be null or a live handle.
+unsafe fn as_sexp<'a>(value: *const SexprValue) -> Option<&'a Sexp> {
+    (value as *const Sexp).as_ref()
+}
+
+/// # Safety
+///
+/// `value` must be null or a live handle.
+unsafe fn number<'a>(value: *const SexprValue) -> Option<&'a Number> {
+    match as_sexp(value) {
+        Some(Sexp::Number(n)) => Some(n),
+        _ => None,
+    }
+}
+
+/// Makes a C string, dropping any NUL bytes, which C strings cannot hold.
+fn c_string(s: String) -> *mut c_char {
+    let bytes: Vec<u8> = s.into_bytes().into_iter().filter(|&b| b != 0).collect();
+    CString::new(bytes).unwrap().into_raw()
+}
+
+/// Parses `len` bytes of UTF-8 text at `text` into a value.
+///
+/// Returns null if the text is not a single S-expression. In that case, if
+/// `error` is not null, `*error` is set to a message that must be released
+/// with `sexpr_string_free`.
+///
+/// # Safety
+///
+/// `text` must point to `len` readable bytes, and `error` must be null or
+/// point to writable memory for a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_parse(
+    text: *const c_char,
+    len: usize,
+    error: *mut *mut c_char,
+) -> *mut SexprValue {
+    let bytes = if len == 0 {
+        &[][..]
+    } else {
+        slice::from_raw_parts(text as *const u8, len)
+    };
+    // A panic must not unwind into C.
+    let message = match panic::catch_unwind(|| crate::de::from_slice::<Sexp>(bytes)) {
+        Ok(Ok(sexp)) => return into_handle(sexp),
+        Ok(Err(err)) => err.to_string(),
+        Err(_) => "internal error while parsing".to_owned(),
+    };
+    if !error.is_null() {
+        *error = c_string(message);
+    }
+    ptr::null_mut()
+}
+
+/// Releases a value returned by `sexpr_parse`. Does nothing if `value` is
+/// null.
+///
+/// # Safety
+///
+/// `value` must be null or a handle from `sexpr_parse` that has not been
+/// freed, and no value borrowed from it may be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_free(value: *mut SexprValue) {
+    if !value.is_null() {
+        drop(Box::from_raw(value as *mut Sexp));
+    }
+}
+
+/// The kind of `value`, or `Invalid` if `value` is null.
+///
+/// # Safety
+///
+/// `value` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_kind(value: *const SexprValue) -> SexprKind {
+    match as_sexp(value) {
+        None => SexprKind::Invalid,
+        Some(Sexp::Nil) => SexprKind::Nil,
+        Some(Sexp::Boolean(_)) => SexprKind::Boolean,
+        Some(Sexp::Number(_)) => SexprKind::Number,
+        Some(Sexp::Atom(Atom::Symbol(_))) => SexprKind::Symbol,
+        Some(Sexp::Atom(Atom::Keyword(_))) => SexprKind::Keyword,
+        Some(Sexp::Atom(Atom::String(_))) => SexprKind::String,
+        Some(Sexp::List(_)) => SexprKind::List,
+        Some(Sexp::Pair(..)) => SexprKind::Pair,
+        Some(Sexp::Bytes(_)) => SexprKind::Bytes,
+    }
+}
+
+/// Stores the value of a boolean in `*out`. Returns false, leaving `*out`
+/// alone, if `value` is not a boolean.
+///
+/// # Safety
+///
+/// `value` must be a live handle and `out` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_as_bool(value: *const SexprValue, out: *mut bool) -> bool {
+    match as_sexp(value) {
+        Some(&Sexp::Boolean(b)) => {
+            *out = b;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Stores a number that fits an `int64_t` in `*out`. Returns false, leaving
+/// `*out` alone, otherwise.
+///
+/// # Safety
+///
+/// `value` must be a live handle and `out` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_as_i64(value: *const SexprValue, out: *mut i64) -> bool {
+    match number(value).and_then(|n| n.as_i64()) {
+        Some(n) => {
+            *out = n;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Stores a number that fits a `uint64_t` in `*out`. Returns false, leaving
+/// `*out` alone, otherwise.
+///
+/// # Safety
+///
+/// `value` must be a live handle and `out` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_as_u64(value: *const SexprValue, out: *mut u64) -> bool {
+    match number(value).and_then(|n| n.as_u64()) {
+        Some(n) => {
+            *out = n;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Stores any number, converted to a `double`, in `*out`. Returns false,
+/// leaving `*out` alone, if `value` is not a number.
+///
+/// # Safety
+///
+/// `value` must be a live handle and `out` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_as_f64(value: *const SexprValue, out: *mut f64) -> bool {
+    match number(value).and_then(|n| n.as_f64()) {
+        Some(n) => {
+            *out = n;
+            true
+        }
+        None => false,
+    }
+}
+
+/// The UTF-8 text of a symbol, keyword or string, which is not
+/// NUL-terminated, with its length stored in `*len`. A keyword's text does
+/// not include its `#:` prefix. Returns null if `value` is not one of
+/// these.
+///
+/// # Safety
+///
+/// `value` must be a live handle and `len` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_atom_text(
+    value: *const SexprValue,
+    len: *mut usize,
+) -> *const c_char {
+    match as_sexp(value) {
+        Some(Sexp::Atom(atom)) => {
+            let text = atom.as_str();
+            *len = text.len();
+            text.as_ptr() as *const c_char
+        }
+        _ => ptr::null(),
+    }
+}
+
+/// The contents of a byte string, with their length stored in `*len`.
+/// Returns null if `value` is not a byte string.
+///
+/// # Safety
+///
+/// `value` must be a live handle and `len` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_bytes(value: *const SexprValue, len: *mut usize) -> *const u8 {
+    match as_sexp(value) {
+        Some(Sexp::Bytes(bytes)) => {
+            *len = bytes.len();
+            bytes.as_ptr()
+        }
+        _ => ptr::null(),
+    }
+}
+
+/// The number of elements of a list, or 0 if `value` is not a list.
+///
+/// # Safety
+///
+/// `value` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_list_len(value: *const SexprValue) -> usize {
+    match as_sexp(value) {
+        Some(Sexp::List(elems)) => elems.len(),
+        _ => 0,
+    }
+}
+
+/// The element of a list at `index`, borrowed from `value`. Returns null if
+/// `value` is not a list or `index` is out of range.
+///
+/// # Safety
+///
+/// `value` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_list_get(
+    value: *const SexprValue,
+    index: usize,
+) -> *const SexprValue {
+    match as_sexp(value) {
+        Some(Sexp::List(elems)) => elems.get(index).map_or(ptr::null(), as_handle),
+        _ => ptr::null(),
+    }
+}
+
+/// The car of a pair, borrowed from `value`, or null if `value` is not a
+/// pair.
+///
+/// # Safety
+///
+/// `value` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_pair_car(value: *const SexprValue) -> *const SexprValue {
+    match as_sexp(value).and_then(Sexp::as_pair) {
+        Some((car, _)) => as_handle(car),
+        None => ptr::null(),
+    }
+}
+
+/// The cdr of a pair, borrowed from `value`, or null if `value` is not a
+/// pair.
+///
+/// # Safety
+///
+/// `value` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_pair_cdr(value: *const SexprValue) -> *const SexprValue {
+    match as_sexp(value).and_then(Sexp::as_pair) {
+        Some((_, cdr)) => as_handle(cdr),
+        None => ptr::null(),
+    }
+}
+
+/// Writes `value` as S-expression text, pretty-printed if `pretty` is true.
+/// The result is NUL-terminated and must be released with
+/// `sexpr_string_free`. Returns null if the value cannot be written.
+///
+/// # Safety
+///
+/// `value` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_to_string(value: *const SexprValue, pretty: bool) -> *mut c_char {
+    let sexp = match as_sexp(value) {
+        Some(sexp) => sexp,
+        None => return ptr::null_mut(),
+    };
+    // A panic must not unwind into C.
+    let text = panic::catch_unwind(|| {
+        if pretty {
+            crate::ser::to_string_pretty(sexp)
+        } else {
+            crate::ser::to_string(sexp)
+        }
+    });
+    match text {
+        Ok(Ok(text)) => c_string(text),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Releases a string returned by `sexpr_to_string` or through the error
+/// pointer of `sexpr_parse`. Does nothing if `s` is null.
+///
+/// # Safety
+///
+/// `s` must be null or a string from this library that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn sexpr_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
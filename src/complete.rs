@@ -0,0 +1,83 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Checking whether text typed so far holds whole S-expressions.
+
+use serde::de::Deserialize;
+
+use crate::de::{Deserializer, DeserializerOptions};
+use crate::read::{Scanner, Step};
+use crate::sexp::Sexp;
+
+/// Whether some text holds complete S-expressions, as decided by
+/// `is_complete`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Completeness {
+    /// The text is a sequence of complete values, possibly none.
+    Complete,
+
+    /// The text is the start of a value that more input could complete.
+    Incomplete {
+        /// The number of lists opened and not yet closed.
+        open_parens: usize,
+        /// Whether the text ends inside a string or a symbol between bars.
+        in_string: bool,
+    },
+
+    /// The text has an error that no further input can fix, such as an
+    /// unmatched `)`.
+    Invalid,
+}
+
+/// Decides whether `input` holds complete S-expressions, so that a REPL can
+/// tell whether to evaluate what was typed or prompt for another line.
+///
+/// The input is parsed as the deserializer would parse it, without keeping
+/// the values.
+///
+/// ```rust
+/// use sexpr::Completeness;
+///
+/// assert_eq!(sexpr::is_complete("(define x 1)"), Completeness::Complete);
+/// assert_eq!(
+///     sexpr::is_complete("(define (f x)\n  (g \"x"),
+///     Completeness::Incomplete { open_parens: 2, in_string: true }
+/// );
+/// assert_eq!(sexpr::is_complete("(f))"), Completeness::Invalid);
+/// ```
+pub fn is_complete(input: &str) -> Completeness {
+    let mut de = Deserializer::from_str(input);
+    loop {
+        if de.end().is_ok() {
+            return Completeness::Complete;
+        }
+        match Sexp::deserialize(&mut de) {
+            Ok(_) => {}
+            Err(ref err) if err.is_eof() => return incomplete(input.as_bytes()),
+            Err(_) => return Completeness::Invalid,
+        }
+    }
+}
+
+/// Counts the lists left open at the end of `input`, which is known to be
+/// the start of a valid value.
+fn incomplete(input: &[u8]) -> Completeness {
+    let options = DeserializerOptions::default();
+    let mut scanner = Scanner::new();
+    let mut i = 0;
+    while i < input.len() {
+        if scanner.step(&input[i..], &options) != Step::EndBefore {
+            i += 1;
+        }
+    }
+
+    Completeness::Incomplete {
+        open_parens: scanner.depth(),
+        in_string: scanner.in_string(),
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! S-expression files as a source for the [`config`](https://docs.rs/config)
+//! crate. Requires the `config` feature.
+//!
+//! `SexpFormat` lets a `.sexp` file take its place in a layered
+//! configuration next to TOML or YAML files:
+//!
+//! ```rust
+//! use config::{Config, File};
+//! use sexpr::config::SexpFormat;
+//!
+//! let text = r#"((name "app") (port 80) (hosts "a" "b") (log #:level "debug"))"#;
+//! let settings = Config::builder()
+//!     .add_source(File::from_str(text, SexpFormat))
+//!     .set_override("port", 8080)
+//!     .unwrap()
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(settings.get_string("name").unwrap(), "app");
+//! assert_eq!(settings.get_int("port").unwrap(), 8080);
+//! assert_eq!(settings.get::<Vec<String>>("hosts").unwrap(), ["a", "b"]);
+//! assert_eq!(settings.get_string("log.level").unwrap(), "debug");
+//! ```
+//!
+//! The file holds one form, a table of settings. A list is read as a table
+//! if it is an alist or a plist:
+//!
+//! - In an alist every entry is a list `(key value ...)` with an atom as its
+//!   key. An entry with one value after its key sets the key to that value,
+//!   and one with more sets it to an array of them, so a one-element array
+//!   is written `(key (value))`. The values after the key are a table
+//!   instead if they are entries themselves, as in `(server (port 80))`.
+//! - A plist alternates keywords and values, as in `(#:port 80)`.
+//!
+//! Any other list is an array. Symbols and keywords become strings, and
+//! `#nil` unsets a key.
+
+use ::config::{FileStoredFormat, Format, Map, Value, ValueKind};
+use serde::de::Error as _;
+
+use crate::atom::Atom;
+use crate::error::Error;
+use crate::sexp::Sexp;
+
+/// The S-expression file format for the `config` crate, for `.sexp` files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SexpFormat;
+
+impl Format for SexpFormat {
+    fn parse(
+        &self,
+        uri: Option<&String>,
+        text: &str,
+    ) -> Result<Map<String, Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let sexp: Sexp = crate::de::from_str(text)?;
+        match sexp {
+            Sexp::Nil => Ok(Map::new()),
+            Sexp::List(ref elems) if elems.is_empty() => Ok(Map::new()),
+            sexp => match to_kind(uri, sexp) {
+                ValueKind::Table(table) => Ok(table),
+                _ => Err(Box::new(Error::custom(
+                    "expected an alist or plist of settings",
+                ))),
+            },
+        }
+    }
+}
+
+impl FileStoredFormat for SexpFormat {
+    fn file_extensions(&self) -> &'static [&'static str] {
+        &["sexp"]
+    }
+}
+
+fn to_kind(uri: Option<&String>, sexp: Sexp) -> ValueKind {
+    match sexp {
+        Sexp::Nil => ValueKind::Nil,
+        Sexp::Boolean(b) => ValueKind::Boolean(b),
+        Sexp::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => ValueKind::I64(i),
+            (None, Some(u)) => ValueKind::U64(u),
+            (None, None) => ValueKind::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Sexp::Atom(atom) => ValueKind::String(atom.into_string()),
+        Sexp::Bytes(bytes) => ValueKind::Array(
+            bytes
+                .into_iter()
+                .map(|b| Value::new(uri, ValueKind::U64(u64::from(b))))
+                .collect(),
+        ),
+        Sexp::List(elems) => {
+            if is_plist(&elems) {
+                let mut table = Map::new();
+                let mut props = elems.into_iter();
+                while let (Some(key), Some(value)) = (props.next(), props.next()) {
+                    if let Sexp::Atom(key) = key {
+                        table.insert(key.into_string(), to_value(uri, value));
+                    }
+                }
+                ValueKind::Table(table)
+            } else if elems.iter().all(is_entry) && !elems.is_empty() {
+                let mut table = Map::new();
+                for entry in elems {
+                    if let Some((key, value)) = into_entry(uri, entry) {
+                        table.insert(key, value);
+                    }
+                }
+                ValueKind::Table(table)
+            } else {
+                ValueKind::Array(elems.into_iter().map(|elem| to_value(uri, elem)).collect())
+            }
+        }
+        pair @ Sexp::Pair(..) => {
+            let (car, cdr) = pair.into_pair().unwrap_or((Sexp::Nil, Sexp::Nil));
+            ValueKind::Array(vec![to_value(uri, car), to_value(uri, cdr)])
+        }
+    }
+}
+
+fn to_value(uri: Option<&String>, sexp: Sexp) -> Value {
+    Value::new(uri, to_kind(uri, sexp))
+}
+
+fn is_plist(elems: &[Sexp]) -> bool {
+    !elems.is_empty()
+        && elems.len().is_multiple_of(2)
+        && elems
+            .iter()
+            .step_by(2)
+            .all(|key| matches!(*key, Sexp::Atom(Atom::Keyword(_))))
+}
+
+/// Whether `sexp` is an alist entry, `(key value ...)`.
+fn is_entry(sexp: &Sexp) -> bool {
+    match *sexp {
+        Sexp::List(ref kv) => kv.len() >= 2 && matches!(kv[0], Sexp::Atom(_)),
+        _ => false,
+    }
+}
+
+fn into_entry(uri: Option<&String>, entry: Sexp) -> Option<(String, Value)> {
+    let mut kv = match entry {
+        Sexp::List(kv) => kv.into_iter(),
+        _ => return None,
+    };
+    let key = match kv.next()? {
+        Sexp::Atom(key) => key.into_string(),
+        _ => return None,
+    };
+    let mut values: Vec<Sexp> = kv.collect();
+    // `(server (port 80))` holds a table of one entry, not the entry itself.
+    let value = if values.len() == 1 && !is_entry(&values[0]) {
+        values.pop()?
+    } else {
+        Sexp::List(values)
+    };
+    Some((key, to_value(uri, value)))
+}
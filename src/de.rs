@@ -8,18 +8,29 @@
 
 //! Deserialize S-expression data to a Rust data structure.
 
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::io;
 use std::marker::PhantomData;
+use std::mem;
+use std::result;
+use std::str;
 use std::{i32, u64};
 
 use super::error::{Error, ErrorCode, Result};
 use serde::de::{self, Unexpected};
 use serde::forward_to_deserialize_any;
 
-use crate::read::{self, Reference};
+use crate::raw::RAW_TOKEN;
+use crate::read;
+use crate::rest::REST_TOKEN;
 
 use crate::atom::Atom;
-pub use crate::read::{IoRead, Read, SliceRead, StrRead};
+pub use crate::incremental::{IncrementalParser, Parsed};
+#[cfg(feature = "memmap2")]
+pub use crate::read::MmapRead;
+pub use crate::read::{IoRead, Position, Read, Reference, SliceRead, StrRead};
+use crate::sexp::{DocumentItem, Sexp, SexpDocument, SpannedSexp, SpannedValue, PAIR_TOKEN};
 
 //////////////////////////////////////////////////////////////////////////////
 
@@ -28,6 +39,202 @@ pub struct Deserializer<R> {
     read: R,
     str_buf: Vec<u8>,
     remaining_depth: u8,
+    options: DeserializerOptions,
+    /// Whether the start of the input may still hold a byte order mark or
+    /// `#!` line to skip.
+    at_preamble: bool,
+    /// The counts kept for `Deserializer::stats`, if asked for.
+    stats: Option<ParseStats>,
+    /// The atoms defined by `#N=` so far, if the options read datum labels.
+    datum_labels: HashMap<u64, Atom>,
+    /// The closing bracket of each list `find_form` has scanned into and not
+    /// yet out of.
+    find_closes: Vec<u8>,
+    /// Where the element of a tuple being read starts, so that a `Rest`
+    /// there reads the rest of the tuple's list.
+    rest_at: Option<usize>,
+    /// Whether the value being read is wanted as a plain number, so that a
+    /// number is not handed over as its literal text.
+    #[cfg(feature = "arbitrary_precision")]
+    plain_number: bool,
+}
+
+/// Options controlling which S-expression dialect the `Deserializer` accepts.
+///
+/// The default options accept only the syntax understood by
+/// `sexpr::from_str`.
+///
+/// ```
+/// use sexpr::de::{Deserializer, DeserializerOptions};
+/// use serde::Deserialize;
+///
+/// let options = DeserializerOptions {
+///     square_brackets: true,
+///     ..DeserializerOptions::default()
+/// };
+/// let mut de = Deserializer::from_str_with_options("[1 2 3]", options);
+/// let v: Vec<u32> = Deserialize::deserialize(&mut de).unwrap();
+/// assert_eq!(v, vec![1, 2, 3]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeserializerOptions {
+    /// Accept byte strings written as a run of hexadecimal digit pairs
+    /// enclosed in `#` characters, as in `(q #61626364#)`. They read as bytes,
+    /// or as a string if the target asks for one and they are UTF-8.
+    pub hex_escapes: bool,
+
+    /// Accept `[` and `]` as an alternative pair of list delimiters. Brackets
+    /// must still be matched with their own kind.
+    pub square_brackets: bool,
+
+    /// Fold symbols to lower case and accept `#T`, `#F` and `#NIL` in any
+    /// case. Enum variant names are symbols too, so they match only lower
+    /// case variant names.
+    pub case_insensitive: bool,
+
+    /// Fail with an error instead of reading an integer that fits neither a
+    /// `u64` nor an `i64` as a lossy `f64`.
+    pub lossless_integers: bool,
+
+    /// Skip a UTF-8 byte order mark at the start of the input, as written by
+    /// some Windows editors.
+    pub skip_bom: bool,
+
+    /// Skip the first line of the input if it starts with `#!`, as in an
+    /// executable script.
+    pub skip_shebang: bool,
+
+    /// Read the empty list, written `()` with nothing inside, as nil: a
+    /// `Sexp::Nil`, a unit or a `None`, as `NilStyle::EmptyList` writes
+    /// them. A type that expects a list, such as a `Vec` or a struct, still
+    /// reads it as empty. `( )` is always a list.
+    pub empty_list_as_nil: bool,
+
+    /// Read the symbol `nil` as nil wherever a unit is accepted, as
+    /// `NilStyle::Symbol` writes it. An `Option` reads `nil` as `None`
+    /// either way.
+    pub nil_symbol: bool,
+
+    /// Read datum labels on symbols, keywords and strings, as
+    /// `Serializer::with_datum_labels` writes them: `#0=temperature`
+    /// defines a label and reads as the atom, and `#0#` reads as the atom it
+    /// was defined for, in this value or one before it in the stream. Hex
+    /// digits between `#` characters that start with a decimal digit, such
+    /// as `#12AB#`, read as a label instead; the serializer writes such
+    /// bytes as `#x"12AB"` when it writes labels.
+    pub datum_labels: bool,
+
+    /// Skip the entries of an alist read as a struct or map that are not a
+    /// list headed by a key, such as `b` and `()` in `((a . 1) b ())`,
+    /// instead of failing with `ErrorKind::MalformedAlistEntry`. Suits hand-written
+    /// configuration that should load despite stray entries.
+    pub skip_malformed_entries: bool,
+
+    /// Accept only space, tab, CR and LF as whitespace. By default vertical
+    /// tab, form feed and the other characters Unicode calls whitespace,
+    /// such as a no-break space (U+00A0) or a line separator (U+2028),
+    /// separate values too, and end a symbol that is not between bars.
+    pub strict_whitespace: bool,
+
+    /// Coerce values of the wrong type to the number or boolean a field
+    /// expects, for sloppy hand-written configuration: a string such as
+    /// `"42"` or `"1.5"` reads as the number it holds, and the symbols and
+    /// strings `t`, `true`, `yes`, `nil`, `false` and `no`, in any case, read
+    /// as booleans, as does `#nil` as false. Integers read as floats either
+    /// way.
+    pub lenient_scalars: bool,
+
+    /// Read a symbol ending in a colon, such as `width:`, as a keyword
+    /// without the colon, as DSSSL and some Schemes write keywords. `#:width`
+    /// is a keyword either way, and a symbol between bars, such as
+    /// `|width:|`, stays a symbol.
+    pub trailing_colon_keywords: bool,
+
+    /// Fail with `ErrorKind::CommentNotAllowed` on a `;` comment instead of
+    /// skipping it as whitespace.
+    pub reject_comments: bool,
+
+    /// Fail with `ErrorKind::DuplicateKey` on an alist read as a struct or
+    /// map that repeats a key, whether or not the struct has a field of that
+    /// name. Keys are compared by their text, so the symbol `a` and the
+    /// string `"a"` are the same key. Without it, a map keeps the last value
+    /// of a repeated key.
+    pub deny_duplicate_keys: bool,
+
+    /// Accept `#true` and `#false`, as R7RS Scheme writes them, besides `#t`
+    /// and `#f`.
+    pub long_booleans: bool,
+
+    /// Read the symbols `t` and `nil` as `true` and `false` where a `bool`
+    /// is expected, as Emacs Lisp writes them. Only a `bool` reads them so:
+    /// a `Symbol` or `Sexp` still reads them as symbols, and an
+    /// `Option<bool>` reads `nil` as `None`. `#t` and `#f` are booleans
+    /// either way.
+    pub symbol_booleans: bool,
+
+    /// Read an alist entry whose value is nil, as in `(port . #nil)`, as if
+    /// the entry were missing, so that a struct field takes its
+    /// `#[serde(default)]` value. An `Option` field is `None` either way,
+    /// and a field with neither fails as missing. With `nil_symbol`,
+    /// `(port . nil)` is read this way too. Only the dotted form is read
+    /// this way, since `(port #nil)` is a list holding nil.
+    pub nil_as_default: bool,
+
+    /// Read a symbol between bars, such as `|aGk=|`, as the byte string its
+    /// base64 encodes, as the transport tokens of Rivest's S-expressions
+    /// write them and as `BytesStyle::Base64` writes bytes. Text between
+    /// bars that is not base64 fails with `ErrorKind::InvalidBase64`.
+    pub base64_bars: bool,
+}
+
+impl DeserializerOptions {
+    /// Options that accept one canonical spelling of each value, for
+    /// protocol endpoints where accepting a variation of the syntax would be
+    /// a hazard. Every extension is left off, as with the default options,
+    /// so square brackets, `#true`, `#T` and symbols read as booleans are
+    /// rejected; on top of that comments, whitespace other than space, tab,
+    /// CR and LF, alists that repeat a key and integers too large to read
+    /// exactly are rejected too.
+    ///
+    /// ```
+    /// use serde::Deserialize as _;
+    /// use serde_derive::Deserialize;
+    /// use sexpr::de::{Deserializer, DeserializerOptions};
+    /// use sexpr::error::ErrorKind;
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct Point {
+    ///     x: i32,
+    ///     y: i32,
+    /// }
+    ///
+    /// let read = |input| {
+    ///     let mut de = Deserializer::from_str_with_options(input, DeserializerOptions::strict());
+    ///     Point::deserialize(&mut de)
+    /// };
+    /// assert_eq!(read("((x . 1) (y . 2))").unwrap().y, 2);
+    /// assert_eq!(read("((x . 1) (x . 2))").unwrap_err().kind(), ErrorKind::DuplicateKey);
+    /// assert_eq!(read("((x . 1) ; one\n(y . 2))").unwrap_err().kind(), ErrorKind::CommentNotAllowed);
+    /// assert!(read("[(x . 1) (y . 2)]").is_err());
+    /// ```
+    pub fn strict() -> Self {
+        DeserializerOptions {
+            lossless_integers: true,
+            strict_whitespace: true,
+            reject_comments: true,
+            deny_duplicate_keys: true,
+            ..DeserializerOptions::default()
+        }
+    }
+
+    /// The keyword a symbol such as `width:` stands for, if the options read
+    /// a trailing colon as marking a keyword.
+    fn trailing_colon_keyword<'s>(&self, symbol: &'s str) -> Option<&'s str> {
+        match symbol.strip_suffix(':') {
+            Some(keyword) if self.trailing_colon_keywords && !keyword.is_empty() => Some(keyword),
+            _ => None,
+        }
+    }
 }
 
 impl<'de, R> Deserializer<R>
@@ -43,12 +250,97 @@ where
     ///   - Deserializer::from_bytes
     ///   - Deserializer::from_reader
     pub fn new(read: R) -> Self {
+        Deserializer::with_options(read, DeserializerOptions::default())
+    }
+
+    /// Create a S-expression deserializer from one of the possible sexpr input
+    /// sources, accepting the dialect described by `options`.
+    pub fn with_options(read: R, options: DeserializerOptions) -> Self {
+        Deserializer::with_str_buf(read, options, Vec::with_capacity(128))
+    }
+
+    fn with_str_buf(read: R, options: DeserializerOptions, str_buf: Vec<u8>) -> Self {
         Deserializer {
             read,
-            str_buf: Vec::with_capacity(128),
+            str_buf,
             remaining_depth: 128,
+            at_preamble: options.skip_bom || options.skip_shebang,
+            options,
+            stats: None,
+            datum_labels: HashMap::new(),
+            find_closes: Vec::new(),
+            rest_at: None,
+            #[cfg(feature = "arbitrary_precision")]
+            plain_number: false,
         }
     }
+
+    /// Keeps counts of what is parsed, to be read back with `stats`.
+    /// Counting is off by default.
+    pub fn with_stats(mut self, collect: bool) -> Self {
+        self.stats = if collect {
+            Some(ParseStats::default())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// What has been parsed so far, or `None` unless `with_stats` turned
+    /// counting on.
+    pub fn stats(&self) -> Option<ParseStats> {
+        self.stats.map(|stats| ParseStats {
+            bytes_read: self.read.byte_offset(),
+            string_escapes: self.read.escapes(),
+            ..stats
+        })
+    }
+}
+
+/// Counts describing one parse, from `from_str_with_stats` or
+/// `Deserializer::stats`.
+///
+/// ```
+/// use sexpr::Sexp;
+///
+/// let input = r#"(define greeting "hello\tworld" (list 1 (list 2)))"#;
+/// let (_, stats): (Sexp, _) = sexpr::from_str_with_stats(input).unwrap();
+/// assert_eq!(stats.bytes_read, input.len());
+/// assert_eq!(stats.atoms, 7);
+/// assert_eq!(stats.max_depth, 3);
+/// assert_eq!(stats.string_escapes, 1);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    /// Bytes of input consumed, including whitespace and comments.
+    pub bytes_read: usize,
+    /// Values read that are not lists: symbols, keywords, strings, numbers,
+    /// booleans and `#nil`. Values skipped without being decoded, such as
+    /// the values of unknown struct fields, are not counted.
+    pub atoms: usize,
+    /// The deepest nesting of lists reached, 0 if no list was read.
+    pub max_depth: usize,
+    /// Escape sequences such as `\n` decoded in strings. Always 0 for an
+    /// input source not provided by this crate.
+    pub string_escapes: usize,
+}
+
+/// A buffer that can be reused across calls to `from_str_buffered`.
+///
+/// While parsing, strings that contain escapes are unescaped into a scratch
+/// buffer. `from_str` allocates a new one for every call; a loop parsing many
+/// small inputs can instead hand the same `Scratch` to each call.
+#[derive(Debug, Default)]
+pub struct Scratch {
+    buf: Vec<u8>,
+}
+
+impl Scratch {
+    /// Creates an empty scratch buffer. It grows to fit the longest string
+    /// parsed with it.
+    pub fn new() -> Self {
+        Scratch::default()
+    }
 }
 
 impl<R> Deserializer<read::IoRead<R>>
@@ -59,6 +351,12 @@ where
     pub fn from_reader(reader: R) -> Self {
         Deserializer::new(read::IoRead::new(reader))
     }
+
+    /// Creates a S-expression deserializer from an `io::Read`, accepting the
+    /// dialect described by `options`.
+    pub fn from_reader_with_options(reader: R, options: DeserializerOptions) -> Self {
+        Deserializer::with_options(read::IoRead::new(reader), options)
+    }
 }
 
 impl<'a> Deserializer<read::SliceRead<'a>> {
@@ -66,6 +364,12 @@ impl<'a> Deserializer<read::SliceRead<'a>> {
     pub fn from_slice(bytes: &'a [u8]) -> Self {
         Deserializer::new(read::SliceRead::new(bytes))
     }
+
+    /// Creates a S-expression deserializer from a `&[u8]`, accepting the
+    /// dialect described by `options`.
+    pub fn from_slice_with_options(bytes: &'a [u8], options: DeserializerOptions) -> Self {
+        Deserializer::with_options(read::SliceRead::new(bytes), options)
+    }
 }
 
 impl<'a> Deserializer<read::StrRead<'a>> {
@@ -74,6 +378,67 @@ impl<'a> Deserializer<read::StrRead<'a>> {
     pub fn from_str(s: &'a str) -> Self {
         Deserializer::new(read::StrRead::new(s))
     }
+
+    /// Creates a S-expression deserializer from a `&str`, accepting the
+    /// dialect described by `options`.
+    pub fn from_str_with_options(s: &'a str, options: DeserializerOptions) -> Self {
+        Deserializer::with_options(read::StrRead::new(s), options)
+    }
+}
+
+/// The reader a gzip-compressed input is parsed from.
+#[cfg(feature = "gzip")]
+type GzipReader<R> = io::BufReader<flate2::read::MultiGzDecoder<R>>;
+
+#[cfg(feature = "gzip")]
+impl<R> Deserializer<read::IoRead<GzipReader<R>>>
+where
+    R: io::Read,
+{
+    /// Creates a S-expression deserializer from an `io::Read` of gzip data,
+    /// such as a `.sexp.gz` file. A file of several gzip members, as written
+    /// by appending to it, is read as one input. Requires the `gzip`
+    /// feature.
+    pub fn from_reader_gzip(reader: R) -> Self {
+        Deserializer::from_reader(io::BufReader::new(flate2::read::MultiGzDecoder::new(
+            reader,
+        )))
+    }
+}
+
+/// The reader a zstd-compressed input is parsed from.
+#[cfg(feature = "zstd")]
+type ZstdReader<R> = io::BufReader<zstd::Decoder<'static, io::BufReader<R>>>;
+
+#[cfg(feature = "zstd")]
+impl<R> Deserializer<read::IoRead<ZstdReader<R>>>
+where
+    R: io::Read,
+{
+    /// Creates a S-expression deserializer from an `io::Read` of zstd data,
+    /// such as a `.sexp.zst` file. Requires the `zstd` feature.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the decompressor cannot be set up.
+    pub fn from_reader_zstd(reader: R) -> Result<Self> {
+        let decoder = zstd::Decoder::new(reader).map_err(Error::io)?;
+        Ok(Deserializer::from_reader(io::BufReader::new(decoder)))
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl<'a> Deserializer<read::MmapRead<'a>> {
+    /// Creates a S-expression deserializer from a memory-mapped file.
+    pub fn from_mmap(map: &'a memmap2::Mmap) -> Self {
+        Deserializer::new(read::MmapRead::new(map))
+    }
+
+    /// Creates a S-expression deserializer from a memory-mapped file,
+    /// accepting the dialect described by `options`.
+    pub fn from_mmap_with_options(map: &'a memmap2::Mmap, options: DeserializerOptions) -> Self {
+        Deserializer::with_options(read::MmapRead::new(map), options)
+    }
 }
 
 macro_rules! overflow {
@@ -112,6 +477,58 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         }
     }
 
+    /// Parses every top-level form of the input without building anything
+    /// out of them, and collects the syntax errors found instead of stopping
+    /// at the first. See `sexpr::validate_syntax`.
+    ///
+    /// After an error, reading starts over at the next line that begins with
+    /// an opening bracket, so the errors after the first are those of the
+    /// forms that follow it. Errors reading the input itself end the check.
+    pub fn validate_syntax(mut self) -> result::Result<(), Vec<Error>> {
+        let mut errors = Vec::new();
+        loop {
+            match self.parse_whitespace() {
+                Ok(None) => break,
+                Ok(Some(_)) => {}
+                Err(err) => {
+                    errors.push(err);
+                    break;
+                }
+            }
+            if let Err(err) = de::Deserializer::deserialize_any(&mut self, Validate) {
+                let stop = err.is_eof() || err.is_io();
+                errors.push(err);
+                if stop || !self.skip_to_next_form() {
+                    break;
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Skips to the start of the next line that begins with an opening
+    /// bracket, for `validate_syntax` to go on after an error. Returns false
+    /// if the input ends first.
+    fn skip_to_next_form(&mut self) -> bool {
+        // The error may have left the depth anywhere.
+        self.remaining_depth = 128;
+        loop {
+            match self.next_char() {
+                Ok(Some(b'\n')) => match self.peek() {
+                    Ok(Some(b)) if self.list_close(b).is_some() => return true,
+                    Ok(_) => {}
+                    Err(_) => return false,
+                },
+                Ok(Some(_)) => {}
+                Ok(None) | Err(_) => return false,
+            }
+        }
+    }
+
     /// Turn a Sexp deserializer into an iterator over values of type T.
     // TODO: Deserializer<R> cannot implement `IntoIterator`, as the
     // returned iterator is generic over `T`.
@@ -131,6 +548,102 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         }
     }
 
+    /// Finds the first list whose head is the symbol `head`, at the top level
+    /// or nested in other lists, and parses it into a `Sexp`. Returns `None`
+    /// if the input ends without one.
+    ///
+    /// Everything before the list is only scanned, not parsed into values,
+    /// so a single form can be pulled out of a large file without the memory
+    /// or time it takes to parse all of it. Nothing after the list is read,
+    /// and calling `find_form` again goes on scanning from there, through
+    /// the rest of the lists that enclose it.
+    ///
+    /// ```rust
+    /// use sexpr::{Deserializer, Sexp};
+    ///
+    /// let netlist = r#"(export (version "E") (components (comp (ref R1))) (nets (net (code 1))))"#;
+    /// let mut de = Deserializer::from_str(netlist);
+    /// let components = de.find_form("components").unwrap().unwrap();
+    /// assert_eq!(components.to_string(), "(components (comp (ref R1)))");
+    /// let net = de.find_form("net").unwrap().unwrap();
+    /// assert_eq!(net.to_string(), "(net (code 1))");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if the input is not well formed up to the end of the list, or
+    /// if the list itself cannot be parsed.
+    pub fn find_form(&mut self, head: &str) -> Result<Option<Sexp>> {
+        self.skip_preamble()?;
+        let mut closes = mem::take(&mut self.find_closes);
+        let found = self.scan_for_form(head, &mut closes);
+        self.find_closes = closes;
+        found
+    }
+
+    fn scan_for_form(&mut self, head: &str, closes: &mut Vec<u8>) -> Result<Option<Sexp>> {
+        loop {
+            let peek = match self.parse_whitespace()? {
+                Some(peek) => peek,
+                None if closes.is_empty() => return Ok(None),
+                None => return Err(self.peek_error(ErrorCode::EofWhileParsingList)),
+            };
+            if closes.last() == Some(&peek) {
+                self.eat_char();
+                closes.pop();
+                continue;
+            }
+            if self.is_list_close(peek) {
+                return Err(self.peek_error(if closes.is_empty() {
+                    ErrorCode::ExpectedSomeValue
+                } else {
+                    ErrorCode::MismatchedBracket
+                }));
+            }
+            let close = match self.list_close(peek) {
+                Some(close) => close,
+                None => {
+                    self.skip_value()?;
+                    continue;
+                }
+            };
+
+            self.eat_char();
+            let found = match self.parse_whitespace()? {
+                Some(b'|') => {
+                    self.eat_char();
+                    self.str_buf.clear();
+                    self.parse_bar_symbol()? == head
+                }
+                Some(b'a'..=b'z') | Some(b'A'..=b'Z') => {
+                    self.str_buf.clear();
+                    match self.read.parse_symbol(&mut self.str_buf, &self.options)? {
+                        Reference::Borrowed(s) | Reference::Copied(s) => s == head,
+                    }
+                }
+                _ => false,
+            };
+            if found {
+                return self.parse_rest_of_form(peek, Sexp::from(head)).map(Some);
+            }
+            closes.push(close);
+        }
+    }
+
+    /// Parses the rest of a list opened by `open`, once the bracket and the
+    /// `first` element have been consumed.
+    fn parse_rest_of_form(&mut self, open: u8, first: Sexp) -> Result<Sexp> {
+        self.remaining_depth -= 1;
+        if self.remaining_depth == 0 {
+            return Err(self.peek_error(ErrorCode::RecursionLimitExceeded));
+        }
+        let form = crate::sexp::read_list(vec![first], SeqAccess::new(self));
+        self.remaining_depth += 1;
+        let form = form?;
+        self.end_seq(open)?;
+        Ok(form)
+    }
+
     fn peek(&mut self) -> Result<Option<u8>> {
         self.read.peek().map_err(Error::io)
     }
@@ -151,6 +664,21 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         Ok(self.next_char()?.unwrap_or(b'\x00'))
     }
 
+    /// Counts a value that is not a list, if stats are kept.
+    fn count_atom(&mut self) {
+        if let Some(ref mut stats) = self.stats {
+            stats.atoms += 1;
+        }
+    }
+
+    /// Records the depth of the list just entered, if stats are kept.
+    fn count_depth(&mut self) {
+        if let Some(ref mut stats) = self.stats {
+            let depth = usize::from(128 - self.remaining_depth);
+            stats.max_depth = stats.max_depth.max(depth);
+        }
+    }
+
     /// Error caused by a byte from next_char().
     fn error(&mut self, reason: ErrorCode) -> Error {
         let pos = self.read.position();
@@ -164,13 +692,37 @@ impl<'de, R: Read<'de>> Deserializer<R> {
     }
 
     /// Returns the first non-whitespace byte without consuming it, or `None` if
-    /// EOF is encountered.
+    /// EOF is encountered. Line comments beginning with `;` count as
+    /// whitespace.
     fn parse_whitespace(&mut self) -> Result<Option<u8>> {
+        self.skip_preamble()?;
         loop {
             match self.peek()? {
                 Some(b' ') | Some(b'\n') | Some(b'\t') | Some(b'\r') => {
                     self.eat_char();
                 }
+                Some(b';') if self.options.reject_comments => {
+                    return Err(self.peek_error(ErrorCode::CommentNotAllowed));
+                }
+                Some(b';') => {
+                    self.eat_char();
+                    while let Some(b) = self.peek()? {
+                        if b == b'\n' || b == b'\r' {
+                            break;
+                        }
+                        self.eat_char();
+                    }
+                }
+                Some(b) if b >= 0x80 || b == b'\x0B' || b == b'\x0C' => {
+                    let len = read::peek_whitespace_len(&mut self.read, &self.options)?;
+                    if len == 0 {
+                        return Ok(Some(b));
+                    }
+                    for _ in 0..len {
+                        self.eat_char();
+                        self.peek()?;
+                    }
+                }
                 other => {
                     return Ok(other);
                 }
@@ -178,6 +730,25 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         }
     }
 
+    /// Returns the byte that closes a list opened by `open`, or `None` if
+    /// `open` does not begin a list in the configured dialect.
+    fn list_close(&self, open: u8) -> Option<u8> {
+        match open {
+            b'(' => Some(b')'),
+            b'[' if self.options.square_brackets => Some(b']'),
+            _ => None,
+        }
+    }
+
+    /// Returns true if `b` closes a list in the configured dialect.
+    fn is_list_close(&self, b: u8) -> bool {
+        match b {
+            b')' => true,
+            b']' => self.options.square_brackets,
+            _ => false,
+        }
+    }
+
     fn parse_value<V>(&mut self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
@@ -188,25 +759,101 @@ impl<'de, R: Read<'de>> Deserializer<R> {
                 return Err(self.peek_error(ErrorCode::EofWhileParsingValue));
             }
         };
+        if self.list_close(peek).is_none() {
+            self.count_atom();
+        }
+        #[cfg(feature = "arbitrary_precision")]
+        let keep_literal = !mem::replace(&mut self.plain_number, false);
 
         let value = match peek {
             b'#' => {
                 self.eat_char();
-                match self.next_char()? {
-                    Some(b't') => visitor.visit_bool(true),
-                    Some(b'f') => visitor.visit_bool(false),
-                    Some(b'n') => {
-                        self.parse_ident(b"il")?;
-                        visitor.visit_bool(true)
+                let ch = match self.next_char()? {
+                    Some(ch) => ch,
+                    None => return Err(self.peek_error(ErrorCode::EofWhileParsingValue)),
+                };
+                if self.options.datum_labels && ch.is_ascii_digit() {
+                    match self.parse_datum_label(ch)? {
+                        Atom::String(s) => visitor.visit_string(s),
+                        Atom::Keyword(k) => {
+                            visitor.visit_newtype_struct(
+                                de::IntoDeserializer::<Error>::into_deserializer(format!(
+                                    "#:{}",
+                                    k
+                                )),
+                            )
+                        }
+                        symbol => visitor.visit_newtype_struct(symbol),
+                    }
+                } else if ch == b':' {
+                    // The keyword is handed over with its `#:` prefix, which
+                    // is how its visitor tells it apart from a symbol.
+                    self.str_buf.clear();
+                    self.str_buf.extend_from_slice(b"#:");
+                    let keyword = if self.peek()? == Some(b'|') {
+                        self.eat_char();
+                        self.parse_bar_symbol()?
+                    } else {
+                        match self.read.parse_symbol(&mut self.str_buf, &self.options)? {
+                            Reference::Borrowed(s) | Reference::Copied(s) => s,
+                        }
+                    };
+                    if keyword.len() == 2 {
+                        return Err(self.peek_error(ErrorCode::ExpectedSomeIdent));
+                    }
+                    visitor.visit_newtype_struct(de::IntoDeserializer::<Error>::into_deserializer(
+                        keyword,
+                    ))
+                } else if self.fold_case(ch) == b'x' && self.peek()? == Some(b'"') {
+                    visitor.visit_byte_buf(self.parse_prefixed_hex()?)
+                } else if self.options.hex_escapes && self.starts_hex_str(ch)? {
+                    self.parse_hex_str(ch)?;
+                    visitor.visit_bytes(&self.str_buf)
+                } else {
+                    match self.fold_case(ch) {
+                        b't' => {
+                            self.parse_long_boolean(b"rue")?;
+                            visitor.visit_bool(true)
+                        }
+                        b'f' => {
+                            self.parse_long_boolean(b"alse")?;
+                            visitor.visit_bool(false)
+                        }
+                        b'n' => {
+                            self.parse_ident(b"il")?;
+                            visitor.visit_unit()
+                        }
+                        // A bytevector, `#u8(1 2 3)`, is read as its list.
+                        b'u' => {
+                            self.parse_ident(b"8")?;
+                            match self.peek()? {
+                                Some(open) if self.list_close(open).is_some() => {
+                                    self.parse_list(open, visitor)
+                                }
+                                _ => Err(self.peek_error(ErrorCode::ExpectedList)),
+                            }
+                        }
+                        _ => Err(self.peek_error(ErrorCode::ExpectedSomeIdent)),
                     }
-                    Some(_) => Err(self.peek_error(ErrorCode::ExpectedSomeIdent)),
-                    None => Err(self.peek_error(ErrorCode::EofWhileParsingValue)),
                 }
             }
+            b'+' => {
+                self.eat_char();
+                visitor.visit_f64(self.parse_special_float(true)?)
+            }
             b'-' => {
                 self.eat_char();
-                self.parse_integer(false)?.visit(visitor)
+                match self.peek_or_null()? {
+                    b'i' | b'n' | b'I' | b'N' => {
+                        visitor.visit_f64(self.parse_special_float(false)?)
+                    }
+                    #[cfg(feature = "arbitrary_precision")]
+                    _ if keep_literal => self.parse_number_literal(false, visitor),
+                    _ => self.parse_integer(false)?.visit(visitor),
+                }
             }
+            #[cfg(feature = "arbitrary_precision")]
+            b'0'..=b'9' if keep_literal => self.parse_number_literal(true, visitor),
             b'0'..=b'9' => self.parse_integer(true)?.visit(visitor),
             b'"' => {
                 self.eat_char();
@@ -216,29 +863,40 @@ impl<'de, R: Read<'de>> Deserializer<R> {
                     Reference::Copied(s) => visitor.visit_str(s),
                 }
             }
-            b'(' => {
-                self.remaining_depth -= 1;
-                if self.remaining_depth == 0 {
-                    return Err(self.peek_error(ErrorCode::RecursionLimitExceeded));
-                }
-
+            b'(' | b'[' if self.list_close(peek).is_some() => self.parse_list(peek, visitor),
+            b'|' if self.options.base64_bars => {
                 self.eat_char();
-                let ret = visitor.visit_seq(SeqAccess::new(self));
-
-                self.remaining_depth += 1;
-
-                self.parse_whitespace()?;
-
-                match (ret, self.end_seq()) {
-                    (Ok(ret), Ok(())) => Ok(ret),
-                    (Err(err), _) | (_, Err(err)) => Err(err),
-                }
+                self.str_buf.clear();
+                let bytes = match crate::bytes::decode_base64(self.parse_bar_symbol()?.as_bytes()) {
+                    Some(bytes) => bytes,
+                    None => return Err(self.error(ErrorCode::InvalidBase64)),
+                };
+                visitor.visit_byte_buf(bytes)
+            }
+            b'|' => {
+                self.eat_char();
+                self.str_buf.clear();
+                let symbol = self.parse_bar_symbol()?;
+                visitor
+                    .visit_newtype_struct(de::IntoDeserializer::<Error>::into_deserializer(symbol))
             }
             b'a'..=b'z' | b'A'..=b'Z' => {
                 self.str_buf.clear();
-                match self.read.parse_symbol(&mut self.str_buf)? {
-                    Reference::Borrowed(s) => visitor.visit_newtype_struct(Atom::from_str(s)),
-                    Reference::Copied(s) => visitor.visit_newtype_struct(Atom::from_str(s)),
+                match self.read.parse_symbol(&mut self.str_buf, &self.options)? {
+                    Reference::Borrowed(s) | Reference::Copied(s) => {
+                        if self.options.nil_symbol && s == "nil" {
+                            visitor.visit_unit()
+                        } else if let Some(keyword) = self.options.trailing_colon_keyword(s) {
+                            visitor.visit_newtype_struct(
+                                de::IntoDeserializer::<Error>::into_deserializer(format!(
+                                    "#:{}",
+                                    keyword
+                                )),
+                            )
+                        } else {
+                            visitor.visit_newtype_struct(Atom::symbol(s))
+                        }
+                    }
                 }
             }
             _ => Err(self.peek_error(ErrorCode::ExpectedSomeValue)),
@@ -256,9 +914,128 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         }
     }
 
+    fn peek_second(&mut self) -> Result<Option<u8>> {
+        self.read.peek_second().map_err(Error::io)
+    }
+
+    /// Returns true if a `.` standing alone comes next, as in `(a . b)`,
+    /// rather than one that starts an atom, as in `(a .5)`.
+    fn peek_dot(&mut self) -> Result<bool> {
+        if self.parse_whitespace()? != Some(b'.') {
+            return Ok(false);
+        }
+        Ok(match self.peek_second()? {
+            None | Some(b'(') | Some(b'[') | Some(b'"') | Some(b';') => true,
+            Some(ch) => read::is_symbol_delimiter(ch, &self.options),
+        })
+    }
+
+    /// Returns the byte after the peeked one, folded to lower case if the
+    /// options ignore case.
+    fn peek_second_folded(&mut self) -> Result<Option<u8>> {
+        let second = self.peek_second()?;
+        Ok(second.map(|ch| self.fold_case(ch)))
+    }
+
+    /// Returns true if `peek` opens an empty list written `()` and the
+    /// options read it as nil.
+    fn starts_nil_list(&mut self, peek: u8) -> Result<bool> {
+        match self.list_close(peek) {
+            Some(close) if self.options.empty_list_as_nil => Ok(self.peek_second()? == Some(close)),
+            _ => Ok(false),
+        }
+    }
+
+    /// Parses a symbol that starts with a letter as the atom it stands for,
+    /// a keyword if the options read its trailing colon as one.
+    fn parse_symbol_atom(&mut self) -> Result<Atom> {
+        self.count_atom();
+        self.str_buf.clear();
+        let symbol = match self.read.parse_symbol(&mut self.str_buf, &self.options)? {
+            Reference::Borrowed(s) | Reference::Copied(s) => s,
+        };
+        Ok(match self.options.trailing_colon_keyword(symbol) {
+            Some(keyword) => Atom::Keyword(keyword.to_owned()),
+            None => Atom::symbol(symbol),
+        })
+    }
+
+    /// Consumes `#nil`, or an empty list written `()` if the options read it
+    /// as nil, if one comes next.
+    fn parse_nil(&mut self) -> Result<bool> {
+        match self.parse_whitespace()? {
+            Some(b'#') if self.peek_second_folded()? == Some(b'n') => {
+                self.count_atom();
+                self.eat_char();
+                self.next_char()?;
+                self.parse_ident(b"il")?;
+                Ok(true)
+            }
+            _ => self.parse_nil_list(),
+        }
+    }
+
+    /// Consumes an empty list written `()` if one comes next and the options
+    /// read it as nil.
+    fn parse_nil_list(&mut self) -> Result<bool> {
+        match self.parse_whitespace()? {
+            Some(peek) if self.starts_nil_list(peek)? => {
+                self.count_atom();
+                self.eat_char();
+                self.next_char()?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Parses a list opened by `open`, which has been peeked but not
+    /// consumed.
+    fn parse_list<V>(&mut self, open: u8, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.remaining_depth -= 1;
+        if self.remaining_depth == 0 {
+            return Err(self.peek_error(ErrorCode::RecursionLimitExceeded));
+        }
+        trace_event!(trace, depth = 128 - self.remaining_depth, "list start");
+        self.count_depth();
+
+        self.eat_char();
+        let ret = visitor.visit_seq(SeqAccess::new(self));
+
+        self.remaining_depth += 1;
+
+        self.parse_whitespace()?;
+
+        match (ret, self.end_seq(open)) {
+            (Ok(ret), Ok(())) => Ok(ret),
+            (Err(err), _) | (_, Err(err)) => Err(err),
+        }
+    }
+
+    /// Parses the rest of `+nan.0`, `+inf.0`, `-nan.0` or `-inf.0` once the
+    /// sign has been consumed.
+    fn parse_special_float(&mut self, pos: bool) -> Result<f64> {
+        let value = match self.next_char()?.map(|ch| self.fold_case(ch)) {
+            Some(b'i') => {
+                self.parse_ident(b"nf.0")?;
+                f64::INFINITY
+            }
+            Some(b'n') => {
+                self.parse_ident(b"an.0")?;
+                f64::NAN
+            }
+            _ => return Err(self.error(ErrorCode::InvalidNumber)),
+        };
+        Ok(if pos { value } else { -value })
+    }
+
     fn parse_ident(&mut self, ident: &[u8]) -> Result<()> {
         for c in ident {
-            if Some(*c) != self.next_char()? {
+            let next = self.next_char()?.map(|ch| self.fold_case(ch));
+            if Some(*c) != next {
                 return Err(self.error(ErrorCode::ExpectedSomeIdent));
             }
         }
@@ -266,28 +1043,238 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         Ok(())
     }
 
-    fn parse_integer(&mut self, pos: bool) -> Result<Number> {
-        match self.next_char_or_null()? {
-            b'0' => {
-                // There can be only one leading '0'.
-                match self.peek_or_null()? {
-                    b'0'..=b'9' => Err(self.peek_error(ErrorCode::InvalidNumber)),
-                    _ => self.parse_number(pos, 0),
-                }
+    /// Reads the rest of `#true` or `#false` after its first letter, if the
+    /// options accept the long form and the input has it.
+    fn parse_long_boolean(&mut self, rest: &[u8]) -> Result<()> {
+        match self.peek()? {
+            Some(ch) if self.options.long_booleans && self.fold_case(ch) == rest[0] => {
+                self.parse_ident(rest)
             }
-            c @ b'1'..=b'9' => {
-                let mut res = u64::from(c - b'0');
+            _ => Ok(()),
+        }
+    }
 
-                loop {
-                    match self.peek_or_null()? {
-                        c @ b'0'..=b'9' => {
-                            self.eat_char();
-                            let digit = u64::from(c - b'0');
+    fn fold_case(&self, ch: u8) -> u8 {
+        if self.options.case_insensitive {
+            ch.to_ascii_lowercase()
+        } else {
+            ch
+        }
+    }
 
-                            // We need to be careful with overflow. If we can, try to keep the
-                            // number as a `u64` until we grow too large. At that point, switch to
-                            // parsing the value as a `f64`.
+    /// Decides whether `first`, the byte following a `#`, begins a hex string
+    /// rather than one of `#t`, `#f` or `#nil`. A leading `f` is only read as
+    /// a hex digit when another hex digit or the closing `#` follows it.
+    fn starts_hex_str(&mut self, first: u8) -> Result<bool> {
+        Ok(match first {
+            b'f' | b'F' => match self.peek()? {
+                Some(b'#') => true,
+                Some(b) => (b as char).is_ascii_hexdigit(),
+                None => false,
+            },
+            b => (b as char).is_ascii_hexdigit(),
+        })
+    }
+
+    /// Parses the rest of a symbol written between bars, such as `|a b|`,
+    /// onto the end of `str_buf` and returns all of `str_buf`. Assumes the
+    /// opening bar was consumed. Inside the bars `\|` and `\\` stand for a
+    /// bar and a backslash, and every other character is taken as it is.
+    /// Returns true if what comes next can be read by `MapKey`.
+    fn starts_map_key(&mut self) -> Result<bool> {
+        match self.parse_whitespace()? {
+            Some(b'"') | Some(b'|') | Some(b'a'..=b'z') | Some(b'A'..=b'Z') => Ok(true),
+            _ => self.starts_datum_label(),
+        }
+    }
+
+    /// Returns true if a datum label, `#N=` or `#N#`, comes next and the
+    /// options read them.
+    fn starts_datum_label(&mut self) -> Result<bool> {
+        Ok(self.options.datum_labels
+            && self.peek()? == Some(b'#')
+            && matches!(self.peek_second()?, Some(b'0'..=b'9')))
+    }
+
+    /// Parses the rest of a datum label whose `#` and first digit have been
+    /// consumed, returning the atom it stands for. A definition, `#N=`, is
+    /// followed by its atom, which is remembered under `N` for the rest of
+    /// the input.
+    fn parse_datum_label(&mut self, first: u8) -> Result<Atom> {
+        let mut label = u64::from(first - b'0');
+        loop {
+            match self.next_char()? {
+                Some(ch @ b'0'..=b'9') => {
+                    label = match label
+                        .checked_mul(10)
+                        .and_then(|label| label.checked_add(u64::from(ch - b'0')))
+                    {
+                        Some(label) => label,
+                        None => return Err(self.error(ErrorCode::NumberOutOfRange)),
+                    };
+                }
+                Some(b'#') => {
+                    return match self.datum_labels.get(&label) {
+                        Some(atom) => Ok(atom.clone()),
+                        None => Err(self.error(ErrorCode::UnknownDatumLabel)),
+                    };
+                }
+                Some(b'=') => {
+                    let atom = self.parse_labeled_atom()?;
+                    self.datum_labels.insert(label, atom.clone());
+                    return Ok(atom);
+                }
+                Some(_) => return Err(self.error(ErrorCode::ExpectedSomeIdent)),
+                None => return Err(self.error(ErrorCode::EofWhileParsingValue)),
+            }
+        }
+    }
+
+    /// Parses the symbol, keyword or string after a `#N=`.
+    fn parse_labeled_atom(&mut self) -> Result<Atom> {
+        self.str_buf.clear();
+        match self.peek()? {
+            Some(b'"') => {
+                self.eat_char();
+                let s = self.read.parse_str(&mut self.str_buf)?;
+                Ok(Atom::String(s.to_owned()))
+            }
+            Some(b'|') => {
+                self.eat_char();
+                Ok(Atom::Symbol(self.parse_bar_symbol()?.to_owned()))
+            }
+            Some(b'#') if self.peek_second()? == Some(b':') => {
+                self.eat_char();
+                self.next_char()?;
+                let keyword = if self.peek()? == Some(b'|') {
+                    self.eat_char();
+                    self.parse_bar_symbol()?
+                } else {
+                    &self.read.parse_symbol(&mut self.str_buf, &self.options)?
+                };
+                if keyword.is_empty() {
+                    return Err(self.peek_error(ErrorCode::ExpectedSomeIdent));
+                }
+                Ok(Atom::Keyword(keyword.to_owned()))
+            }
+            Some(b'a'..=b'z') | Some(b'A'..=b'Z') => {
+                let symbol = self.read.parse_symbol(&mut self.str_buf, &self.options)?;
+                Ok(Atom::Symbol(symbol.to_owned()))
+            }
+            _ => Err(self.peek_error(ErrorCode::ExpectedSomeIdent)),
+        }
+    }
+
+    fn parse_bar_symbol(&mut self) -> Result<&str> {
+        loop {
+            match self.next_char()? {
+                Some(b'|') => break,
+                Some(b'\\') => match self.next_char()? {
+                    Some(ch @ b'|') | Some(ch @ b'\\') => self.str_buf.push(ch),
+                    Some(_) => return Err(self.error(ErrorCode::InvalidEscape)),
+                    None => return Err(self.error(ErrorCode::EofWhileParsingString)),
+                },
+                Some(ch) => self.str_buf.push(ch),
+                None => return Err(self.error(ErrorCode::EofWhileParsingString)),
+            }
+        }
+        if str::from_utf8(&self.str_buf).is_err() {
+            return Err(self.error(ErrorCode::InvalidUnicodeCodePoint));
+        }
+        // The check above rules out invalid UTF-8.
+        Ok(unsafe { str::from_utf8_unchecked(&self.str_buf) })
+    }
+
+    /// Parses the string of hex digit pairs in `#x"DEADBEEF"`, once the `#x`
+    /// has been consumed and the `"` peeked.
+    fn parse_prefixed_hex(&mut self) -> Result<Vec<u8>> {
+        self.eat_char();
+        self.str_buf.clear();
+        let decoded = match self.read.parse_str_raw(&mut self.str_buf)? {
+            Reference::Borrowed(b) | Reference::Copied(b) => crate::bytes::decode_hex(b),
+        };
+        decoded.ok_or_else(|| self.error(ErrorCode::InvalidEscape))
+    }
+
+    /// Parses a string of hex digit pairs terminated by `#` into `str_buf`.
+    /// Assumes `first` was the first hex digit.
+    fn parse_hex_str(&mut self, first: u8) -> Result<()> {
+        self.str_buf.clear();
+        let mut high = Some(first);
+        loop {
+            let ch = match self.next_char()? {
+                Some(ch) => ch,
+                None => return Err(self.error(ErrorCode::EofWhileParsingString)),
+            };
+            if ch == b'#' {
+                return match high {
+                    Some(_) => Err(self.error(ErrorCode::InvalidEscape)),
+                    None => Ok(()),
+                };
+            }
+            let digit = match (ch as char).to_digit(16) {
+                Some(digit) => digit as u8,
+                None => return Err(self.error(ErrorCode::InvalidEscape)),
+            };
+            high = match high.take() {
+                Some(h) => {
+                    // `first` has already been checked to be a hex digit.
+                    let h = (h as char).to_digit(16).unwrap() as u8;
+                    self.str_buf.push(h << 4 | digit);
+                    None
+                }
+                None => Some(ch),
+            };
+        }
+    }
+
+    /// Parses a number after its sign. If its value would not print as its
+    /// literal, the literal is handed to the visitor instead, for `Number`
+    /// to keep.
+    #[cfg(feature = "arbitrary_precision")]
+    fn parse_number_literal<V>(&mut self, pos: bool, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.read.begin_raw_buffering();
+        let number = self.parse_integer(pos);
+        let raw = self.read.end_raw_buffering();
+        if let (Ok(Number::F64(_)), Ok(raw)) = (&number, raw) {
+            let literal = if pos { raw } else { format!("-{}", raw) };
+            let kept = crate::number::Number::from_literal(&literal)
+                .is_some_and(|n| n.as_literal().is_some());
+            if kept {
+                return visitor.visit_map(crate::number::LiteralAccess::new(literal));
+            }
+        }
+        number?.visit(visitor)
+    }
+
+    fn parse_integer(&mut self, pos: bool) -> Result<Number> {
+        match self.next_char_or_null()? {
+            b'0' => {
+                // There can be only one leading '0'.
+                match self.peek_or_null()? {
+                    b'0'..=b'9' => Err(self.peek_error(ErrorCode::InvalidNumber)),
+                    _ => self.parse_number(pos, 0),
+                }
+            }
+            c @ b'1'..=b'9' => {
+                let mut res = u64::from(c - b'0');
+
+                loop {
+                    match self.peek_or_null()? {
+                        c @ b'0'..=b'9' => {
+                            self.eat_char();
+                            let digit = u64::from(c - b'0');
+
+                            // We need to be careful with overflow. If we can, try to keep the
+                            // number as a `u64` until we grow too large. At that point, switch to
+                            // parsing the value as a `f64`.
                             if overflow!(res * 10 + digit, u64::MAX) {
+                                if self.options.lossless_integers {
+                                    return Err(self.integer_out_of_range(pos, res, Some(c)));
+                                }
                                 return Ok(Number::F64(self.parse_long_integer(
                                     pos, res, 1, // res * 10^1
                                 )?));
@@ -305,6 +1292,31 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         }
     }
 
+    /// Builds the error for an integer literal that does not fit in a `u64`
+    /// or `i64`, consuming any digits of the literal still left in the input
+    /// so that the message can quote it in full.
+    fn integer_out_of_range(&mut self, pos: bool, significand: u64, next: Option<u8>) -> Error {
+        let mut literal = String::new();
+        if !pos {
+            literal.push('-');
+        }
+        literal.push_str(&significand.to_string());
+        if let Some(c) = next {
+            literal.push(c as char);
+            loop {
+                match self.peek() {
+                    Ok(Some(c @ b'0'..=b'9')) => {
+                        self.eat_char();
+                        literal.push(c as char);
+                    }
+                    Ok(_) => break,
+                    Err(err) => return err,
+                }
+            }
+        }
+        self.error(ErrorCode::IntegerOutOfRange(literal))
+    }
+
     fn parse_long_integer(
         &mut self,
         pos: bool,
@@ -344,6 +1356,9 @@ impl<'de, R: Read<'de>> Deserializer<R> {
 
                     // Convert into a float if we underflow.
                     if neg > 0 {
+                        if self.options.lossless_integers {
+                            return Err(self.integer_out_of_range(pos, significand, None));
+                        }
                         Number::F64(-(significand as f64))
                     } else {
                         Number::I64(neg)
@@ -415,12 +1430,135 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         Ok(if pos { f } else { -f })
     }
 
-    fn end_seq(&mut self) -> Result<()> {
+    /// Skips over one value without building strings or numbers out of it.
+    /// The skipping is done by the `Read` implementation, which can scan its
+    /// input directly.
+    fn ignore_value(&mut self) -> Result<()> {
+        self.skip_preamble()?;
+        self.read.ignore_value(&self.options)
+    }
+
+    /// Skips over one value that is not wanted.
+    fn skip_value(&mut self) -> Result<()> {
+        // A skipped value may define a datum label used after it, so it has
+        // to be parsed.
+        if self.options.datum_labels {
+            self.parse_value(de::IgnoredAny).map(|_| ())
+        } else {
+            self.ignore_value()
+        }
+    }
+
+    /// Skips a byte order mark or `#!` line at the start of the input, if
+    /// the options ask for it and this has not been done yet.
+    #[inline]
+    fn skip_preamble(&mut self) -> Result<()> {
+        if self.at_preamble {
+            self.at_preamble = false;
+            self.read.skip_preamble(&self.options)?;
+        }
+        Ok(())
+    }
+
+    /// Captures the text of the next value for `RawSexp`.
+    fn deserialize_raw_value<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.parse_whitespace()?;
+        self.read.begin_raw_buffering();
+        self.ignore_value()?;
+        let raw = self.read.end_raw_buffering()?;
+        visitor.visit_string(raw)
+    }
+
+    /// Parses a value into a `SpannedSexp`. Lists are read here so that each
+    /// element gets its own span; anything else is read as a `Sexp`.
+    fn parse_spanned(&mut self) -> Result<SpannedSexp> {
+        let peek = match self.parse_whitespace()? {
+            Some(b) => b,
+            None => return Err(self.peek_error(ErrorCode::EofWhileParsingValue)),
+        };
+        let start = self.read.byte_offset();
+        let position = self.read.offset_position();
+        let span = |end| Span {
+            start,
+            end,
+            line: position.line,
+            column: position.column,
+        };
+
+        // A bytevector, `#u8(1 2 3)`, is read as its list, so its bytes get
+        // spans of their own too.
+        let open = if peek == b'#' && self.peek_second_folded()? == Some(b'u') {
+            self.count_atom();
+            self.eat_char();
+            self.eat_char();
+            self.parse_ident(b"8")?;
+            match self.peek()? {
+                Some(open) if self.list_close(open).is_some() => open,
+                _ => return Err(self.peek_error(ErrorCode::ExpectedList)),
+            }
+        } else if self.list_close(peek).is_none() || self.starts_nil_list(peek)? {
+            let sexp: Sexp = de::Deserialize::deserialize(&mut *self)?;
+            return Ok(SpannedSexp::leaf(sexp, span(self.read.byte_offset())));
+        } else {
+            peek
+        };
+
+        self.remaining_depth -= 1;
+        if self.remaining_depth == 0 {
+            return Err(self.peek_error(ErrorCode::RecursionLimitExceeded));
+        }
+        trace_event!(trace, depth = 128 - self.remaining_depth, "list start");
+        self.count_depth();
+        self.eat_char();
+        let mut elems = Vec::new();
+        let ret = self.parse_spanned_elems(&mut elems);
+        self.remaining_depth += 1;
+        let tail = ret?;
+        self.end_seq(open)?;
+
+        let value = match tail {
+            Some(tail) => SpannedValue::DottedList(elems, Box::new(tail)),
+            None => SpannedValue::List(elems),
+        };
+        Ok(SpannedSexp {
+            span: span(self.read.byte_offset()),
+            value,
+        })
+    }
+
+    /// Reads the elements of a list into `elems`, up to its end, and returns
+    /// its tail if it is a dotted list, as `SeqAccess` reads one.
+    fn parse_spanned_elems(&mut self, elems: &mut Vec<SpannedSexp>) -> Result<Option<SpannedSexp>> {
+        loop {
+            match self.parse_whitespace()? {
+                Some(b) if self.is_list_close(b) => return Ok(None),
+                Some(b'.') if !elems.is_empty() && self.peek_dot()? => {
+                    self.eat_char();
+                    let tail = self.parse_spanned()?;
+                    return match self.parse_whitespace()? {
+                        Some(b) if self.is_list_close(b) => Ok(Some(tail)),
+                        Some(_) => Err(self.peek_error(ErrorCode::TrailingCharacters)),
+                        None => Err(self.peek_error(ErrorCode::EofWhileParsingList)),
+                    };
+                }
+                Some(_) => elems.push(self.parse_spanned()?),
+                None => return Err(self.peek_error(ErrorCode::EofWhileParsingList)),
+            }
+        }
+    }
+
+    /// Consumes the byte closing a list that was opened by `open`.
+    fn end_seq(&mut self, open: u8) -> Result<()> {
+        let close = self.list_close(open);
         match self.parse_whitespace()? {
-            Some(b')') => {
+            Some(b) if Some(b) == close => {
                 self.eat_char();
                 Ok(())
             }
+            Some(b) if self.is_list_close(b) => Err(self.peek_error(ErrorCode::MismatchedBracket)),
             Some(_) => Err(self.peek_error(ErrorCode::TrailingCharacters)),
             None => Err(self.peek_error(ErrorCode::EofWhileParsingList)),
         }
@@ -461,6 +1599,34 @@ static POW10: [f64; 309] =
      1e290, 1e291, 1e292, 1e293, 1e294, 1e295, 1e296, 1e297, 1e298, 1e299,
      1e300, 1e301, 1e302, 1e303, 1e304, 1e305, 1e306, 1e307, 1e308];
 
+// Reads a number or boolean, coerced to it if the options say to.
+macro_rules! deserialize_lenient {
+    ($($method:ident => $target:expr,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                #[cfg(feature = "arbitrary_precision")]
+                {
+                    self.plain_number = true;
+                }
+                let value = if self.options.lenient_scalars {
+                    let target = $target;
+                    self.deserialize_any(Lenient { visitor, target })
+                } else {
+                    self.deserialize_any(visitor)
+                };
+                #[cfg(feature = "arbitrary_precision")]
+                {
+                    self.plain_number = false;
+                }
+                value
+            }
+        )*
+    };
+}
+
 impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
     type Error = Error;
 
@@ -469,10 +1635,15 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: de::Visitor<'de>,
     {
+        if self.parse_nil_list()? {
+            return visitor.visit_unit();
+        }
         self.parse_value(visitor)
     }
 
-    /// Parses a `nil` as a None, and any other values as a `Some(...)`.
+    /// Parses `#nil` or the symbol `nil` as a None, and any other values as a
+    /// `Some(...)`. The empty list is a None too if the options read it as
+    /// nil.
     #[inline]
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
@@ -480,25 +1651,116 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
     {
         match self.parse_whitespace()? {
             Some(b'n') => {
+                let atom = self.parse_symbol_atom()?;
+                if atom.as_symbol() == Some("nil") {
+                    return visitor.visit_none();
+                }
+                // Any other symbol that starts with `n` is the value itself.
+                let symbol = SymbolDeserializer {
+                    atom,
+                    lenient_scalars: self.options.lenient_scalars,
+                };
+                visitor
+                    .visit_some(symbol)
+                    .map_err(|err| err.fix_position(|code| self.error(code)))
+            }
+            Some(b'#') if self.peek_second_folded()? == Some(b'n') => {
+                self.count_atom();
                 self.eat_char();
+                self.next_char()?;
                 self.parse_ident(b"il")?;
                 visitor.visit_none()
             }
+            _ if self.parse_nil_list()? => visitor.visit_none(),
             _ => visitor.visit_some(self),
         }
     }
 
+    /// Parses a list. Unlike `deserialize_any`, this reads `()` as an empty
+    /// list whatever the options say about nil.
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.parse_value(visitor)
+    }
+
+    /// Parses a map from an alist, reading `()` as empty like
+    /// `deserialize_seq`. See `MapAccess`.
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let peek = match self.parse_whitespace()? {
+            Some(b) => b,
+            None => {
+                return Err(self.peek_error(ErrorCode::EofWhileParsingValue));
+            }
+        };
+        let value = match peek {
+            b'(' | b'[' if self.list_close(peek).is_some() => {
+                self.eat_char();
+                visitor
+                    .visit_map(MapAccess::new(self))
+                    .and_then(|ret| self.end_seq(peek).map(|()| ret))
+            }
+            _ => return self.parse_value(visitor),
+        };
+        match value {
+            Ok(value) => Ok(value),
+            Err(err) => Err(err.fix_position(|code| self.error(code))),
+        }
+    }
+
+    /// Parses a unit struct from `#nil`, or from the symbol
+    /// `Serializer::with_unit_symbols` writes for it.
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.parse_whitespace()? {
+            Some(b) if b.is_ascii_alphabetic() || b == b'|' || self.starts_datum_label()? => {
+                let symbol: String = de::Deserialize::deserialize(MapKey {
+                    de: &mut *self,
+                    text: None,
+                })?;
+                if symbol == crate::ser::unit_struct_symbol(name) {
+                    visitor.visit_unit()
+                } else {
+                    let err: Error = de::Error::invalid_value(Unexpected::Str(&symbol), &visitor);
+                    Err(err.fix_position(|code| self.error(code)))
+                }
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
     /// Parses a newtype struct as the underlying value.
     #[inline]
-    fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if name == RAW_TOKEN {
+            return self.deserialize_raw_value(visitor);
+        }
+        if name == REST_TOKEN && self.rest_at == Some(self.read.byte_offset()) {
+            return visitor.visit_newtype_struct(RestDeserializer { de: self });
+        }
         visitor.visit_newtype_struct(self)
     }
 
-    /// Parses an enum as an s-expression like `(($KEY1 $VALUE1) ($KEY2 $VALUE2))` where $VALUE
-    /// is either a direct Sexp or a sequence.
+    /// Parses an enum variant. A unit variant is its name, as a symbol or a
+    /// string. A variant with content is a list headed by its name and
+    /// followed by the content's elements, as in `(Rect 1 2)` or
+    /// `(Circle (radius . 3))`, or by a dot and the content as one value, as
+    /// in `(Rect . (1 2))`.
+    ///
+    /// The name must spell the variant's serde name exactly, case included,
+    /// so `(circle 3)` is not a `Circle` unless the enum is renamed, as with
+    /// `#[serde(rename_all = "lowercase")]` or `"kebab-case"`. With
+    /// `DeserializerOptions::case_insensitive` the name is folded to lower
+    /// case like any symbol, so only lower case variant names match.
     #[inline]
     fn deserialize_enum<V>(
         self,
@@ -510,39 +1772,53 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
         V: de::Visitor<'de>,
     {
         match self.parse_whitespace()? {
-            Some(b'(') => {
+            Some(open @ b'(') | Some(open @ b'[') if self.list_close(open).is_some() => {
                 self.remaining_depth -= 1;
                 if self.remaining_depth == 0 {
                     return Err(self.peek_error(ErrorCode::RecursionLimitExceeded));
                 }
+                trace_event!(trace, depth = 128 - self.remaining_depth, "list start");
+                self.count_depth();
 
                 self.eat_char();
-                let value = visitor.visit_enum(VariantAccess::new(self))?;
+                let value = match visitor.visit_enum(VariantAccess::new(self)) {
+                    Ok(value) => value,
+                    Err(err) => return Err(err.fix_position(|code| self.error(code))),
+                };
 
                 self.remaining_depth += 1;
 
                 match self.parse_whitespace()? {
-                    Some(b')') => {
+                    Some(b) if Some(b) == self.list_close(open) => {
                         self.eat_char();
                         Ok(value)
                     }
-                    Some(_) => Err(self.error(ErrorCode::ExpectedSomeValue)),
+                    // More elements than the variant takes.
+                    Some(_) => Err(self.peek_error(ErrorCode::TrailingCharacters)),
                     None => Err(self.error(ErrorCode::EofWhileParsingAlist)),
                 }
             }
-            Some(b'"') => visitor.visit_enum(UnitVariantAccess::new(self)),
-            // TODO: ATOMS BROKEN
+            Some(b'"') | Some(b'|') | Some(b'a'..=b'z') | Some(b'A'..=b'Z') => visitor
+                .visit_enum(UnitVariantAccess::new(&mut *self))
+                .map_err(|err| err.fix_position(|code| self.error(code))),
+            Some(b'#') if self.starts_datum_label()? => visitor
+                .visit_enum(UnitVariantAccess::new(&mut *self))
+                .map_err(|err| err.fix_position(|code| self.error(code))),
             Some(_) => Err(self.peek_error(ErrorCode::ExpectedSomeValue)),
             None => Err(self.peek_error(ErrorCode::EofWhileParsingValue)),
         }
     }
 
+    /// Parses a byte string from whichever form appears: a string, a list of
+    /// integers, a bytevector such as `#u8(104 105)`, hex digit pairs such
+    /// as `#6869#` or base64 between bars such as `|aGk=|`.
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        match self.parse_whitespace()? {
+        let value = match self.parse_whitespace()? {
             Some(b'"') => {
+                self.count_atom();
                 self.eat_char();
                 self.str_buf.clear();
                 match self.read.parse_str_raw(&mut self.str_buf)? {
@@ -550,8 +1826,44 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
                     Reference::Copied(b) => visitor.visit_bytes(b),
                 }
             }
-            _ => self.deserialize_any(visitor),
-        }
+            Some(b'#')
+                if matches!(self.peek_second()?, Some(b) if b.is_ascii_hexdigit())
+                    && !self.starts_datum_label()? =>
+            {
+                self.count_atom();
+                self.eat_char();
+                let first = self.next_char_or_null()?;
+                if self.starts_hex_str(first)? {
+                    self.parse_hex_str(first)?;
+                    visitor.visit_bytes(&self.str_buf)
+                } else {
+                    // Only `#f` gets here.
+                    visitor.visit_bool(false)
+                }
+            }
+            Some(b'#') if self.peek_second_folded()? == Some(b'x') => {
+                self.count_atom();
+                self.eat_char();
+                self.next_char()?;
+                if self.peek()? != Some(b'"') {
+                    return Err(self.peek_error(ErrorCode::ExpectedSomeString));
+                }
+                let bytes = self.parse_prefixed_hex()?;
+                visitor.visit_byte_buf(bytes)
+            }
+            Some(b'|') => {
+                self.count_atom();
+                self.eat_char();
+                self.str_buf.clear();
+                let bytes = match crate::bytes::decode_base64(self.parse_bar_symbol()?.as_bytes()) {
+                    Some(bytes) => bytes,
+                    None => return Err(self.error(ErrorCode::InvalidBase64)),
+                };
+                visitor.visit_byte_buf(bytes)
+            }
+            _ => return self.deserialize_any(visitor),
+        };
+        value.map_err(|err: Error| err.fix_position(|code| self.error(code)))
     }
 
     #[inline]
@@ -578,11 +1890,13 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
             }
         };
         let value = match peek {
-            b'(' => {
+            b'(' | b'[' if self.list_close(peek).is_some() => {
                 self.eat_char();
-                let ret = visitor.visit_map(MapAccess::new(self))?;
-                self.end_seq()?;
-                Ok(ret)
+                // Errors from the visitor, such as a missing field, are given
+                // the position where it stopped reading below.
+                visitor
+                    .visit_map(MapAccess::new(self))
+                    .and_then(|ret| self.end_seq(peek).map(|()| ret))
             }
             _ => Err(self.peek_error(ErrorCode::ExpectedList)),
         };
@@ -592,336 +1906,1142 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
         }
     }
 
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string unit
-            unit_struct seq tuple tuple_struct map identifier ignored_any
-    }
-}
-
-// POSSIBLY BROKEN --------------------------------------------------------
-struct SeqAccess<'a, R: 'a> {
-    de: &'a mut Deserializer<R>,
-    first: bool,
-}
-
-impl<'a, R: 'a> SeqAccess<'a, R> {
-    fn new(de: &'a mut Deserializer<R>) -> Self {
-        SeqAccess { de, first: true }
-    }
-}
-
-impl<'de, 'a, R: Read<'de> + 'a> de::SeqAccess<'de> for SeqAccess<'a, R> {
-    type Error = Error;
-
-    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    /// Parses a list of exactly `len` elements, reporting how many were
+    /// found when the arity does not match.
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
-        T: de::DeserializeSeed<'de>,
+        V: de::Visitor<'de>,
     {
-        match self.de.peek()? {
-            Some(b')') => {
-                return Ok(None);
-            }
-            Some(b' ') => {
-                self.de.eat_char();
-            }
-            Some(_) => {
-                self.de.parse_whitespace()?;
-                if self.first {
-                    self.first = false;
-                } else {
-                    return Err(self.de.peek_error(ErrorCode::ExpectedListEltOrEnd));
-                }
-            }
+        let peek = match self.parse_whitespace()? {
+            Some(b) => b,
             None => {
-                return Err(self.de.peek_error(ErrorCode::EofWhileParsingList));
+                return Err(self.peek_error(ErrorCode::EofWhileParsingValue));
             }
+        };
+        if self.list_close(peek).is_none() {
+            return Err(self.peek_error(ErrorCode::ExpectedList));
         }
 
-        if self.de.peek()?.unwrap() == b')' {
-            Ok(None)
-        } else {
-            seed.deserialize(&mut *self.de).map(Some)
+        self.remaining_depth -= 1;
+        if self.remaining_depth == 0 {
+            return Err(self.peek_error(ErrorCode::RecursionLimitExceeded));
         }
-    }
-}
+        trace_event!(trace, depth = 128 - self.remaining_depth, "list start");
+        self.count_depth();
 
-// END POSSIBLY BROKEN --------------------------------------------------------
+        self.eat_char();
+        let ret = visitor.visit_seq(TupleAccess::new(self, len));
 
-/// Deserialize an association list (alist) as a map.
-///
-/// An alist has the a shape of `((key1 . v1) (key2 . v2) ...)`. Note
-/// that the keys may be either strings or symbols. When the values
-/// are themselves lists, the dot may be omitted, for example, the
-/// following two expressions are identical:
-///
-/// ```lisp
-/// ((key some values))
-/// ```
-///
-/// ```lisp
-/// ((key . (some values)))
-/// ```
-struct MapAccess<'a, R: 'a> {
-    de: &'a mut Deserializer<R>,
-}
+        self.remaining_depth += 1;
 
-impl<'a, R: 'a> MapAccess<'a, R> {
-    fn new(de: &'a mut Deserializer<R>) -> Self {
-        MapAccess { de }
-    }
-}
+        let value = match ret {
+            Ok(value) => value,
+            Err(err) => return Err(err.fix_position(|code| self.error(code))),
+        };
 
-impl<'de, 'a, R: Read<'de> + 'a> de::MapAccess<'de> for MapAccess<'a, R> {
-    type Error = Error;
+        // The visitor stops asking for elements once it has `len` of them, so
+        // anything left before the closing bracket is surplus.
+        let pos = self.read.peek_position();
+        let mut found = len;
+        while let Some(b) = self.parse_whitespace()? {
+            if self.is_list_close(b) {
+                break;
+            }
+            de::Deserializer::deserialize_ignored_any(&mut *self, de::IgnoredAny)?;
+            found += 1;
+        }
+        if found != len {
+            return Err(Error::syntax(
+                ErrorCode::InvalidLength(len, found),
+                pos.line,
+                pos.column,
+            ));
+        }
 
-    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
-    where
-        K: de::DeserializeSeed<'de>,
+        self.end_seq(peek)?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
     {
-        match self.de.parse_whitespace()? {
-            Some(b')') => return Ok(None),
-            Some(b'(') => {
-                self.de.eat_char();
-            }
-            Some(_) => {
-                return Err(self.de.peek_error(ErrorCode::ExpectedList));
-            }
-            None => {
-                return Err(self.de.peek_error(ErrorCode::EofWhileParsingAlist));
-            }
-        };
-        seed.deserialize(MapKey { de: &mut *self.de }).map(Some)
+        self.deserialize_tuple(len, visitor)
     }
 
-    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    /// Skips over the value without building strings or numbers out of it.
+    #[inline]
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
-        V: de::DeserializeSeed<'de>,
+        V: de::Visitor<'de>,
     {
-        let value = match self.de.parse_whitespace()? {
-            Some(b'.') => {
-                self.de.eat_char();
-                seed.deserialize(&mut *self.de)?
-            }
-            Some(_) => seed.deserialize(MapSeqValue::new(self.de))?,
-            None => return Err(self.de.peek_error(ErrorCode::EofWhileParsingAlist)),
-        };
-        match self.de.parse_whitespace()? {
-            Some(b')') => {
-                self.de.eat_char();
-                Ok(value)
+        self.skip_value()?;
+        visitor.visit_unit()
+    }
+
+    /// Parses `#t` or `#f`, and the symbols `t` and `nil` as well if the
+    /// options read them as booleans.
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.options.lenient_scalars {
+            let target = Scalar::Bool;
+            return self.deserialize_any(Lenient { visitor, target });
+        }
+        if !self.options.symbol_booleans {
+            return self.deserialize_any(visitor);
+        }
+        match self.parse_whitespace()?.map(|ch| self.fold_case(ch)) {
+            Some(b't') | Some(b'n') => {
+                self.count_atom();
+                self.str_buf.clear();
+                let symbol = match self.read.parse_symbol(&mut self.str_buf, &self.options)? {
+                    Reference::Borrowed(s) | Reference::Copied(s) => s,
+                };
+                let value = match symbol {
+                    "t" => visitor.visit_bool(true),
+                    "nil" => visitor.visit_bool(false),
+                    _ => visitor.visit_newtype_struct(Atom::symbol(symbol)),
+                };
+                value.map_err(|err| err.fix_position(|code| self.error(code)))
             }
-            Some(_) => Err(self.de.peek_error(ErrorCode::TrailingCharacters)),
-            None => Err(self.de.peek_error(ErrorCode::EofWhileParsingAlist)),
+            _ => self.deserialize_any(visitor),
         }
     }
+
+    deserialize_lenient! {
+        deserialize_i8 => Scalar::Int,
+        deserialize_i16 => Scalar::Int,
+        deserialize_i32 => Scalar::Int,
+        deserialize_i64 => Scalar::Int,
+        deserialize_u8 => Scalar::Int,
+        deserialize_u16 => Scalar::Int,
+        deserialize_u32 => Scalar::Int,
+        deserialize_u64 => Scalar::Int,
+        deserialize_f32 => Scalar::Float,
+        deserialize_f64 => Scalar::Float,
+    }
+
+    forward_to_deserialize_any! {
+        char str string unit identifier
+    }
 }
 
-// To be used after consuming the initial open parenthesis of an
-// association list item.
-struct MapKey<'a, R: 'a> {
-    de: &'a mut Deserializer<R>,
+/// A symbol already read to see whether it was `nil`, handed on as the value
+/// of a `Some` or of an alist entry.
+struct SymbolDeserializer {
+    atom: Atom,
+    lenient_scalars: bool,
 }
 
-impl<'de, 'a, R> de::Deserializer<'de> for MapKey<'a, R>
-where
-    R: Read<'de>,
-{
+impl<'de> de::Deserializer<'de> for SymbolDeserializer {
     type Error = Error;
 
-    #[inline]
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        // TODO: this is duplicated from `parse_value`. Find out about
-        // the relationship between symbols and newtype structs.
-        match self.de.parse_whitespace()? {
-            Some(b) => match b {
-                b'"' => {
-                    self.de.eat_char();
-                    self.de.str_buf.clear();
-                    match self.de.read.parse_str(&mut self.de.str_buf)? {
-                        Reference::Borrowed(s) => visitor.visit_borrowed_str(s),
-                        Reference::Copied(s) => visitor.visit_str(s),
-                    }
-                }
-                b'a'..=b'z' | b'A'..=b'Z' => {
-                    self.de.str_buf.clear();
-                    match self.de.read.parse_symbol(&mut self.de.str_buf)? {
-                        Reference::Borrowed(s) => visitor.visit_borrowed_str(s),
-                        Reference::Copied(s) => visitor.visit_str(s),
-                    }
-                }
-                _ => Err(self.de.peek_error(ErrorCode::ExpectedSomeIdent)), // TODO: inaccurate error code
-            },
-            None => Err(self.de.peek_error(ErrorCode::EofWhileParsingAlist)),
+        match self.atom {
+            // A keyword is handed over with its `#:` prefix, as
+            // `parse_value` does.
+            Atom::Keyword(keyword) => visitor.visit_newtype_struct(
+                de::IntoDeserializer::<Error>::into_deserializer(format!("#:{}", keyword)),
+            ),
+            atom => visitor.visit_newtype_struct(atom),
         }
     }
 
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string unit unit_struct seq tuple tuple_struct map
-        bytes byte_buf option newtype_struct enum
-        struct identifier ignored_any
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.lenient_scalars {
+            let target = Scalar::Bool;
+            self.deserialize_any(Lenient { visitor, target })
+        } else {
+            self.deserialize_any(visitor)
+        }
     }
-}
-
-// To be used after consuming the field name (key) of an alist item
-struct MapSeqValue<'a, R: 'a> {
-    de: &'a mut Deserializer<R>,
-}
 
-impl<'a, R: 'a> MapSeqValue<'a, R> {
-    fn new(de: &'a mut Deserializer<R>) -> Self {
-        Self { de }
+    // The symbol `nil` never gets here, so an `Option` holds the symbol.
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_some(self)
     }
-}
 
-impl<'de, 'a, R> de::Deserializer<'de> for MapSeqValue<'a, R>
-where
-    R: Read<'de>,
-{
-    type Error = Error;
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.atom.as_str() == crate::ser::unit_struct_symbol(name) {
+            visitor.visit_unit()
+        } else {
+            Err(de::Error::invalid_value(
+                Unexpected::Str(self.atom.as_str()),
+                &visitor,
+            ))
+        }
+    }
 
-    #[inline]
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_enum<V>(
+        self,
+        _name: &str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_seq(SeqAccess::new(self.de))
+        visitor.visit_enum(de::IntoDeserializer::<Error>::into_deserializer(
+            self.atom.into_string(),
+        ))
     }
 
     forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string unit unit_struct seq tuple tuple_struct map
-        bytes byte_buf option newtype_struct enum
-        struct identifier ignored_any
+        i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+            byte_buf unit newtype_struct seq tuple tuple_struct map struct
+            identifier ignored_any
     }
 }
 
-struct VariantAccess<'a, R: 'a> {
-    de: &'a mut Deserializer<R>,
+/// The kind of value a `Lenient` visitor coerces its input to.
+#[derive(Clone, Copy)]
+enum Scalar {
+    Bool,
+    Int,
+    Float,
 }
 
-impl<'a, R: 'a> VariantAccess<'a, R> {
-    fn new(de: &'a mut Deserializer<R>) -> Self {
-        VariantAccess { de }
+/// Wraps the visitor of a number or boolean to coerce the text of strings
+/// and symbols to it, as `DeserializerOptions::lenient_scalars` says.
+/// Anything it cannot coerce goes to the wrapped visitor as it is.
+struct Lenient<V> {
+    visitor: V,
+    target: Scalar,
+}
+
+impl Scalar {
+    /// The value of the target kind that `text` holds, if any.
+    fn parse(self, text: &str) -> Option<ParsedScalar> {
+        let text = text.trim();
+        match self {
+            Scalar::Bool => match text.to_ascii_lowercase().as_str() {
+                "t" | "true" | "yes" => Some(ParsedScalar::Bool(true)),
+                "nil" | "false" | "no" => Some(ParsedScalar::Bool(false)),
+                _ => None,
+            },
+            Scalar::Int => text
+                .parse()
+                .map(ParsedScalar::I64)
+                .or_else(|_| text.parse().map(ParsedScalar::U64))
+                .ok(),
+            Scalar::Float => match text.parse::<f64>() {
+                Ok(f) if f.is_finite() => Some(ParsedScalar::F64(f)),
+                _ => None,
+            },
+        }
     }
 }
 
-impl<'de, 'a, R: Read<'de> + 'a> de::EnumAccess<'de> for VariantAccess<'a, R> {
-    type Error = Error;
-    type Variant = Self;
+enum ParsedScalar {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+}
 
-    fn variant_seed<V>(self, _seed: V) -> Result<(V::Value, Self)>
+impl ParsedScalar {
+    fn visit<'de, V, E>(self, visitor: V) -> result::Result<V::Value, E>
     where
-        V: de::DeserializeSeed<'de>,
+        V: de::Visitor<'de>,
+        E: de::Error,
     {
-        unimplemented!()
+        match self {
+            ParsedScalar::Bool(b) => visitor.visit_bool(b),
+            ParsedScalar::I64(i) => visitor.visit_i64(i),
+            ParsedScalar::U64(u) => visitor.visit_u64(u),
+            ParsedScalar::F64(f) => visitor.visit_f64(f),
+        }
     }
 }
 
-impl<'de, 'a, R: Read<'de> + 'a> de::VariantAccess<'de> for VariantAccess<'a, R> {
-    type Error = Error;
+macro_rules! forward_lenient_visit {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<E: de::Error>(self, $($arg: $ty),*) -> result::Result<V::Value, E> {
+                self.visitor.$method($($arg),*)
+            }
+        )*
+    };
+}
 
-    fn unit_variant(self) -> Result<()> {
-        de::Deserialize::deserialize(self.de)
+impl<'de, V: de::Visitor<'de>> de::Visitor<'de> for Lenient<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.visitor.expecting(formatter)
     }
 
-    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    forward_lenient_visit! {
+        visit_bool(v: bool);
+        visit_i64(v: i64);
+        visit_i128(v: i128);
+        visit_u64(v: u64);
+        visit_u128(v: u128);
+        visit_f64(v: f64);
+        visit_char(v: char);
+        visit_bytes(v: &[u8]);
+        visit_borrowed_bytes(v: &'de [u8]);
+        visit_byte_buf(v: Vec<u8>);
+        visit_none();
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> result::Result<V::Value, E> {
+        match self.target.parse(v) {
+            Some(parsed) => parsed.visit(self.visitor),
+            None => self.visitor.visit_str(v),
+        }
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> result::Result<V::Value, E> {
+        match self.target.parse(v) {
+            Some(parsed) => parsed.visit(self.visitor),
+            None => self.visitor.visit_borrowed_str(v),
+        }
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> result::Result<V::Value, E> {
+        match self.target.parse(&v) {
+            Some(parsed) => parsed.visit(self.visitor),
+            None => self.visitor.visit_string(v),
+        }
+    }
+
+    /// `#nil` is false.
+    fn visit_unit<E: de::Error>(self) -> result::Result<V::Value, E> {
+        match self.target {
+            Scalar::Bool => self.visitor.visit_bool(false),
+            _ => self.visitor.visit_unit(),
+        }
+    }
+
+    /// Symbols come as newtype structs of their text.
+    fn visit_newtype_struct<D>(self, deserializer: D) -> result::Result<V::Value, D::Error>
     where
-        T: de::DeserializeSeed<'de>,
+        D: de::Deserializer<'de>,
     {
-        seed.deserialize(self.de)
+        deserializer.deserialize_any(self)
     }
 
-    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn visit_some<D>(self, deserializer: D) -> result::Result<V::Value, D::Error>
     where
-        V: de::Visitor<'de>,
+        D: de::Deserializer<'de>,
     {
-        de::Deserializer::deserialize_any(self.de, visitor)
+        self.visitor.visit_some(deserializer)
     }
 
-    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    fn visit_seq<A>(self, seq: A) -> result::Result<V::Value, A::Error>
     where
-        V: de::Visitor<'de>,
+        A: de::SeqAccess<'de>,
+    {
+        self.visitor.visit_seq(seq)
+    }
+
+    fn visit_map<A>(self, map: A) -> result::Result<V::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        self.visitor.visit_map(map)
+    }
+
+    fn visit_enum<A>(self, data: A) -> result::Result<V::Value, A::Error>
+    where
+        A: de::EnumAccess<'de>,
     {
-        de::Deserializer::deserialize_any(self.de, visitor)
+        self.visitor.visit_enum(data)
     }
 }
 
-struct UnitVariantAccess<'a, R: 'a> {
+struct SeqAccess<'a, R: 'a> {
     de: &'a mut Deserializer<R>,
+    index: usize,
+    /// Whether the tail after a `.` has been read, so that the list must
+    /// end.
+    tail: bool,
 }
 
-impl<'a, R: 'a> UnitVariantAccess<'a, R> {
+impl<'a, R: 'a> SeqAccess<'a, R> {
     fn new(de: &'a mut Deserializer<R>) -> Self {
-        UnitVariantAccess { de }
+        SeqAccess {
+            de,
+            index: 0,
+            tail: false,
+        }
     }
 }
 
-impl<'de, 'a, R: Read<'de> + 'a> de::EnumAccess<'de> for UnitVariantAccess<'a, R> {
+impl<'de, 'a, R: Read<'de> + 'a> de::SeqAccess<'de> for SeqAccess<'a, R> {
     type Error = Error;
-    type Variant = Self;
 
-    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    /// Elements are separated by any run of whitespace and comments, so the
+    /// amount or kind of spacing between them is not significant.
+    ///
+    /// The tail of a dotted list, as in `(a b . c)`, is handed over as a map
+    /// of one entry under `PAIR_TOKEN`, which the visitor of `Sexp` turns
+    /// into pairs. Other visitors see an ordinary map.
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
-        V: de::DeserializeSeed<'de>,
+        T: de::DeserializeSeed<'de>,
     {
-        let variant = seed.deserialize(&mut *self.de)?;
-        Ok((variant, self))
+        match self.de.parse_whitespace()? {
+            Some(b) if self.de.is_list_close(b) => Ok(None),
+            Some(_) if self.tail => Err(self.de.peek_error(ErrorCode::TrailingCharacters)),
+            Some(b'.') if self.index > 0 && self.de.peek_dot()? => {
+                self.de.eat_char();
+                self.index += 1;
+                self.tail = true;
+                let tail = DottedTail {
+                    de: &mut *self.de,
+                    key_taken: false,
+                };
+                seed.deserialize(tail)
+                    .map(Some)
+                    .map_err(|err| err.in_path(self.index - 1))
+            }
+            Some(_) => {
+                self.index += 1;
+                seed.deserialize(&mut *self.de)
+                    .map(Some)
+                    .map_err(|err| err.in_path(self.index - 1))
+            }
+            None => Err(self.de.peek_error(ErrorCode::EofWhileParsingList)),
+        }
     }
 }
 
-impl<'de, 'a, R: Read<'de> + 'a> de::VariantAccess<'de> for UnitVariantAccess<'a, R> {
+/// The tail of a dotted list, read as a map whose one entry has the key
+/// `PAIR_TOKEN` and the tail as its value.
+struct DottedTail<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    key_taken: bool,
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::Deserializer<'de> for DottedTail<'a, R> {
     type Error = Error;
 
-    fn unit_variant(self) -> Result<()> {
-        Ok(())
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_map(self)
     }
 
-    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value>
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string unit unit_struct seq
+        tuple tuple_struct map bytes byte_buf option newtype_struct enum struct identifier
+        ignored_any
+    }
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::MapAccess<'de> for DottedTail<'a, R> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
     where
-        T: de::DeserializeSeed<'de>,
+        K: de::DeserializeSeed<'de>,
     {
-        Err(de::Error::invalid_type(
-            Unexpected::UnitVariant,
-            &"newtype variant",
-        ))
+        if self.key_taken {
+            return Ok(None);
+        }
+        self.key_taken = true;
+        seed.deserialize(de::IntoDeserializer::<Error>::into_deserializer(PAIR_TOKEN))
+            .map(Some)
     }
 
-    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
     where
-        V: de::Visitor<'de>,
+        V: de::DeserializeSeed<'de>,
     {
-        Err(de::Error::invalid_type(
-            Unexpected::UnitVariant,
-            &"tuple variant",
-        ))
+        seed.deserialize(&mut *self.de)
     }
+}
 
-    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+/// Reads the elements left in the list being read as a sequence of their
+/// own, for a `Rest`. The list's closing bracket is left for whatever reads
+/// the list.
+struct RestDeserializer<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::Deserializer<'de> for RestDeserializer<'a, R> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(de::Error::invalid_type(
-            Unexpected::UnitVariant,
-            &"struct variant",
-        ))
+        visitor.visit_seq(SeqAccess::new(self.de))
     }
-}
 
-//////////////////////////////////////////////////////////////////////////////
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string unit unit_struct seq
+        tuple tuple_struct map bytes byte_buf option newtype_struct enum struct identifier
+        ignored_any
+    }
+}
 
-/// Iterator that deserializes a stream into multiple Sexp values.
-///
-/// A stream deserializer can be created from any SExp deserializer using the
-/// `Deserializer::into_iter` method.
-///
-/// The data must consist of Sexp lists optionally separated by whitespace. A
-/// null, boolean, number, or string at the top level are all
-/// errors.
+/// Access to the elements of a list that must hold exactly `len` of them.
 ///
-/// ```
-/// use sexpr::{Deserializer, Sexp};
+/// The last element may follow a `.`, so that `(1 . 2)` reads as a pair just
+/// like `(1 2)`.
+struct TupleAccess<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    len: usize,
+    count: usize,
+}
+
+impl<'a, R: 'a> TupleAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>, len: usize) -> Self {
+        TupleAccess { de, len, count: 0 }
+    }
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::SeqAccess<'de> for TupleAccess<'a, R> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.de.parse_whitespace()? {
+            Some(b) if self.de.is_list_close(b) => {
+                if self.count + 1 == self.len {
+                    // Only a `Rest` can be the last element when there is
+                    // nothing left for it.
+                    self.count += 1;
+                    let end = TupleEnd {
+                        de: &mut *self.de,
+                        len: self.len,
+                    };
+                    seed.deserialize(end).map(Some)
+                } else if self.count < self.len {
+                    Err(self
+                        .de
+                        .peek_error(ErrorCode::InvalidLength(self.len, self.count)))
+                } else {
+                    Ok(None)
+                }
+            }
+            Some(b'.') if self.count > 0 && self.de.peek_dot()? => {
+                // The tail of a dotted list is its last element, so the
+                // list holds one more element than has been read.
+                if self.count + 1 != self.len {
+                    return Err(self
+                        .de
+                        .peek_error(ErrorCode::InvalidLength(self.len, self.count + 1)));
+                }
+                self.de.eat_char();
+                self.count += 1;
+                let value = seed
+                    .deserialize(&mut *self.de)
+                    .map_err(|err| err.in_path(self.count - 1))?;
+                match self.de.parse_whitespace()? {
+                    Some(b) if self.de.is_list_close(b) => Ok(Some(value)),
+                    Some(_) => Err(self.de.peek_error(ErrorCode::TrailingCharacters)),
+                    None => Err(self.de.peek_error(ErrorCode::EofWhileParsingList)),
+                }
+            }
+            Some(_) => {
+                self.count += 1;
+                self.de.rest_at = Some(self.de.read.byte_offset());
+                let value = seed.deserialize(&mut *self.de);
+                self.de.rest_at = None;
+                value.map(Some).map_err(|err| err.in_path(self.count - 1))
+            }
+            None => Err(self.de.peek_error(ErrorCode::EofWhileParsingList)),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.count)
+    }
+}
+
+/// The last element of a tuple whose list has already ended, which reads
+/// as an empty `Rest` and as nothing else.
+struct TupleEnd<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    len: usize,
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::Deserializer<'de> for TupleEnd<'a, R> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(self
+            .de
+            .peek_error(ErrorCode::InvalidLength(self.len, self.len - 1)))
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if name == REST_TOKEN {
+            visitor.visit_newtype_struct(RestDeserializer { de: self.de })
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string unit unit_struct seq
+        tuple tuple_struct map bytes byte_buf option enum struct identifier ignored_any
+    }
+}
+
+/// Deserialize an association list (alist) as a map.
+///
+/// An alist has the a shape of `((key1 . v1) (key2 . v2) ...)`. Note
+/// that the keys may be either strings or symbols. When the values
+/// are themselves lists, the dot may be omitted, for example, the
+/// following two expressions are identical:
+///
+/// ```lisp
+/// ((key some values))
+/// ```
+///
+/// ```lisp
+/// ((key . (some values)))
+/// ```
+///
+/// An entry may also be written flat, as `key . value` without brackets of
+/// its own, which is how the compact serializer writes a map:
+/// `("a".1 "b".(1 2))`.
+struct MapAccess<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    /// The byte that opened the current alist entry.
+    entry_open: u8,
+    /// The index of the next alist entry, counting malformed ones.
+    index: usize,
+    /// The key of the current entry, for the path of an error in its value.
+    key: String,
+    /// Whether the `.` before the current entry's value has been read.
+    dotted: bool,
+    /// Whether the current entry is written flat, so that it ends with its
+    /// value.
+    flat: bool,
+    /// The symbol value of the current entry, if it had to be read to see
+    /// whether it was `nil`.
+    symbol: Option<Atom>,
+    /// The keys read so far, if the options deny duplicate keys.
+    keys: HashSet<String>,
+}
+
+impl<'a, R: 'a> MapAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>) -> Self {
+        MapAccess {
+            de,
+            entry_open: b'(',
+            index: 0,
+            key: String::new(),
+            dotted: false,
+            flat: false,
+            symbol: None,
+            keys: HashSet::new(),
+        }
+    }
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> MapAccess<'a, R> {
+    /// Fails on the malformed entry at `position`, or skips the rest of it
+    /// if the options say so.
+    fn malformed_entry(&mut self, position: read::Position, open: Option<u8>) -> Result<()> {
+        if !self.de.options.skip_malformed_entries {
+            let code = ErrorCode::MalformedAlistEntry(self.index);
+            return Err(Error::syntax(code, position.line, position.column));
+        }
+        let open = match open {
+            Some(open) => open,
+            None => return self.de.skip_value(),
+        };
+        loop {
+            match self.de.parse_whitespace()? {
+                Some(b) if self.de.is_list_close(b) => return self.de.end_seq(open),
+                Some(b'.') => self.de.eat_char(),
+                Some(_) => self.de.skip_value()?,
+                None => return Err(self.de.peek_error(ErrorCode::EofWhileParsingAlist)),
+            }
+        }
+    }
+
+    /// Reads the key of an entry into `key`, and the rest of the entry too
+    /// if its value is nil, as in `(port . #nil)`, or `(port . nil)` if the
+    /// options read the symbol `nil` as nil. Returns true if it was.
+    fn skip_nil_entry(&mut self) -> Result<bool> {
+        self.key.clear();
+        let key = MapKey {
+            de: &mut *self.de,
+            text: Some(&mut self.key),
+        };
+        de::Deserializer::deserialize_any(key, de::IgnoredAny)?;
+        self.check_duplicate()?;
+        if self.de.parse_whitespace()? != Some(b'.') {
+            return Ok(false);
+        }
+        self.de.eat_char();
+        self.dotted = true;
+        if self.de.options.nil_symbol && self.de.parse_whitespace()? == Some(b'n') {
+            // The whole symbol has to be read to tell `nil` from `nope`, so
+            // any other one is kept for `next_value_seed`.
+            let atom = self.de.parse_symbol_atom()?;
+            if atom.as_symbol() != Some("nil") {
+                self.symbol = Some(atom);
+                return Ok(false);
+            }
+        } else if !self.de.parse_nil()? {
+            return Ok(false);
+        }
+        self.dotted = false;
+        self.end_entry()?;
+        Ok(true)
+    }
+
+    /// Reads the key of an entry written flat, `key . value`, into `key`,
+    /// along with its `.`. Returns false if no `.` follows the key.
+    fn read_flat_key(&mut self) -> Result<bool> {
+        self.key.clear();
+        let key = MapKey {
+            de: &mut *self.de,
+            text: Some(&mut self.key),
+        };
+        de::Deserializer::deserialize_any(key, de::IgnoredAny)?;
+        if self.de.parse_whitespace()? != Some(b'.') {
+            return Ok(false);
+        }
+        self.de.eat_char();
+        self.check_duplicate()?;
+        self.flat = true;
+        Ok(true)
+    }
+
+    /// Fails if the key just read into `key` was read before and the
+    /// options deny duplicate keys.
+    fn check_duplicate(&mut self) -> Result<()> {
+        if self.de.options.deny_duplicate_keys && !self.keys.insert(self.key.clone()) {
+            return Err(self.de.error(ErrorCode::DuplicateKey(self.key.clone())));
+        }
+        Ok(())
+    }
+
+    fn end_entry(&mut self) -> Result<()> {
+        match self.de.end_seq(self.entry_open) {
+            Ok(()) => Ok(()),
+            Err(ref err) if err.is_eof() => {
+                Err(self.de.peek_error(ErrorCode::EofWhileParsingAlist))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::MapAccess<'de> for MapAccess<'a, R> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        loop {
+            let peek = match self.de.parse_whitespace()? {
+                Some(b) => b,
+                None => return Err(self.de.peek_error(ErrorCode::EofWhileParsingAlist)),
+            };
+            if self.de.is_list_close(peek) {
+                return Ok(None);
+            }
+            let position = self.de.read.peek_position();
+            if self.de.list_close(peek).is_some() {
+                self.de.eat_char();
+                self.entry_open = peek;
+                if !self.de.starts_map_key()? {
+                    self.malformed_entry(position, Some(peek))?;
+                } else if !self.de.options.nil_as_default {
+                    break;
+                } else if !self.skip_nil_entry()? {
+                    // The key has been read, so hand over its text.
+                    self.index += 1;
+                    let key = de::IntoDeserializer::<Error>::into_deserializer(self.key.as_str());
+                    return seed.deserialize(key).map(Some);
+                }
+            } else if self.de.starts_map_key()? {
+                if self.read_flat_key()? {
+                    self.index += 1;
+                    let key = de::IntoDeserializer::<Error>::into_deserializer(self.key.as_str());
+                    return seed.deserialize(key).map(Some);
+                }
+                // The key of the malformed entry has been read already.
+                if !self.de.options.skip_malformed_entries {
+                    let code = ErrorCode::MalformedAlistEntry(self.index);
+                    return Err(Error::syntax(code, position.line, position.column));
+                }
+            } else {
+                self.malformed_entry(position, None)?;
+            }
+            self.index += 1;
+        }
+        self.index += 1;
+        self.key.clear();
+        let key = MapKey {
+            de: &mut *self.de,
+            text: Some(&mut self.key),
+        };
+        let key = seed.deserialize(key)?;
+        self.check_duplicate()?;
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        if self.flat {
+            self.flat = false;
+            return seed
+                .deserialize(&mut *self.de)
+                .map_err(|err| err.in_path(&self.key));
+        }
+        let value = match self.de.parse_whitespace()? {
+            _ if self.symbol.is_some() => {
+                self.dotted = false;
+                let symbol = SymbolDeserializer {
+                    atom: self.symbol.take().unwrap(),
+                    lenient_scalars: self.de.options.lenient_scalars,
+                };
+                seed.deserialize(symbol)
+                    .map_err(|err| err.fix_position(|code| self.de.error(code)))
+            }
+            _ if self.dotted => {
+                self.dotted = false;
+                seed.deserialize(&mut *self.de)
+            }
+            Some(b'.') if self.de.peek_dot()? => {
+                self.de.eat_char();
+                seed.deserialize(&mut *self.de)
+            }
+            Some(_) => seed.deserialize(MapSeqValue::new(self.de)),
+            None => return Err(self.de.peek_error(ErrorCode::EofWhileParsingAlist)),
+        };
+        let value = value.map_err(|err| err.in_path(&self.key))?;
+        self.end_entry()?;
+        Ok(value)
+    }
+}
+
+// To be used after consuming the initial open parenthesis of an
+// association list item.
+struct MapKey<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    /// Where to record the text of the key, if anywhere.
+    text: Option<&'a mut String>,
+}
+
+fn record_key(text: &mut Option<&mut String>, key: &str) {
+    if let Some(ref mut text) = *text {
+        text.push_str(key);
+    }
+}
+
+impl<'de, 'a, R> de::Deserializer<'de> for MapKey<'a, R>
+where
+    R: Read<'de>,
+{
+    type Error = Error;
+
+    #[inline]
+    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        // TODO: this is duplicated from `parse_value`. Find out about
+        // the relationship between symbols and newtype structs.
+        match self.de.parse_whitespace()? {
+            Some(b) => match b {
+                b'"' => {
+                    self.de.count_atom();
+                    self.de.eat_char();
+                    self.de.str_buf.clear();
+                    let key = self.de.read.parse_str(&mut self.de.str_buf)?;
+                    record_key(&mut self.text, &key);
+                    match key {
+                        Reference::Borrowed(s) => visitor.visit_borrowed_str(s),
+                        Reference::Copied(s) => visitor.visit_str(s),
+                    }
+                }
+                b'|' => {
+                    self.de.count_atom();
+                    self.de.eat_char();
+                    self.de.str_buf.clear();
+                    let key = self.de.parse_bar_symbol()?;
+                    record_key(&mut self.text, key);
+                    visitor.visit_str(key)
+                }
+                b'a'..=b'z' | b'A'..=b'Z' => {
+                    self.de.count_atom();
+                    self.de.str_buf.clear();
+                    let key = self
+                        .de
+                        .read
+                        .parse_symbol(&mut self.de.str_buf, &self.de.options)?;
+                    record_key(&mut self.text, &key);
+                    match key {
+                        Reference::Borrowed(s) => visitor.visit_borrowed_str(s),
+                        Reference::Copied(s) => visitor.visit_str(s),
+                    }
+                }
+                b'#' if self.de.starts_datum_label()? => {
+                    self.de.count_atom();
+                    self.de.eat_char();
+                    let first = self.de.next_char_or_null()?;
+                    let key = self.de.parse_datum_label(first)?.into_string();
+                    record_key(&mut self.text, &key);
+                    visitor.visit_string(key)
+                }
+                _ => Err(self.de.peek_error(ErrorCode::ExpectedSomeIdent)), // TODO: inaccurate error code
+            },
+            None => Err(self.de.peek_error(ErrorCode::EofWhileParsingAlist)),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string unit unit_struct seq tuple tuple_struct map
+        bytes byte_buf option newtype_struct enum
+        struct identifier ignored_any
+    }
+}
+
+// To be used after consuming the field name (key) of an alist item
+struct MapSeqValue<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+}
+
+impl<'a, R: 'a> MapSeqValue<'a, R> {
+    fn new(de: &'a mut Deserializer<R>) -> Self {
+        Self { de }
+    }
+}
+
+impl<'de, 'a, R> de::Deserializer<'de> for MapSeqValue<'a, R>
+where
+    R: Read<'de>,
+{
+    type Error = Error;
+
+    #[inline]
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_seq(SeqAccess::new(self.de))
+    }
+
+    /// Reads the rest of the entry as the tuple, so that both `(a 1 2)` and
+    /// `(a 1 . 2)` give `a` the value `(1, 2)`.
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_seq(TupleAccess::new(self.de, len))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string unit unit_struct seq map
+        bytes byte_buf option newtype_struct enum
+        struct identifier ignored_any
+    }
+}
+
+// To be used after consuming the opening parenthesis of a variant with
+// content.
+struct VariantAccess<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    /// Whether the name was followed by `.`, as in `(Circle . 3)`, so the
+    /// content is the single value after it. Otherwise the content is the
+    /// rest of the list, as in `(Circle 3)`.
+    dotted: bool,
+}
+
+impl<'a, R: 'a> VariantAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>) -> Self {
+        VariantAccess { de, dotted: false }
+    }
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::EnumAccess<'de> for VariantAccess<'a, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(MapKey {
+            de: &mut *self.de,
+            text: None,
+        })?;
+        if self.de.parse_whitespace()? == Some(b'.') {
+            self.de.eat_char();
+            self.dotted = true;
+        }
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::VariantAccess<'de> for VariantAccess<'a, R> {
+    type Error = Error;
+
+    /// Accepts `(Empty)`, or `(Empty . #nil)` in the dotted form.
+    fn unit_variant(self) -> Result<()> {
+        if self.dotted {
+            de::Deserialize::deserialize(self.de)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.de)
+    }
+
+    /// Reads `(Rect 1 2)`, or `(Rect . (1 2))` in the dotted form.
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.dotted {
+            de::Deserializer::deserialize_tuple(self.de, len, visitor)
+        } else {
+            visitor.visit_seq(TupleAccess::new(self.de, len))
+        }
+    }
+
+    /// Reads `(Circle (radius . 3))`, or `(Circle . ((radius . 3)))` in the
+    /// dotted form.
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.dotted {
+            de::Deserializer::deserialize_struct(self.de, "", fields, visitor)
+        } else {
+            visitor.visit_map(MapAccess::new(self.de))
+        }
+    }
+}
+
+struct UnitVariantAccess<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+}
+
+impl<'a, R: 'a> UnitVariantAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>) -> Self {
+        UnitVariantAccess { de }
+    }
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::EnumAccess<'de> for UnitVariantAccess<'a, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(MapKey {
+            de: &mut *self.de,
+            text: None,
+        })?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::VariantAccess<'de> for UnitVariantAccess<'a, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::UnitVariant,
+            &"newtype variant",
+        ))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::UnitVariant,
+            &"tuple variant",
+        ))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::UnitVariant,
+            &"struct variant",
+        ))
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+/// Iterator that deserializes a stream into multiple Sexp values.
+///
+/// A stream deserializer can be created from any SExp deserializer using the
+/// `Deserializer::into_iter` method.
+///
+/// The data must consist of Sexp lists optionally separated by whitespace. A
+/// null, boolean, number, or string at the top level are all
+/// errors.
+///
+/// ```
+/// use sexpr::{Deserializer, Sexp};
 ///
 /// fn main() {
 ///     let data = "(a 1) () (1 2 3)";
@@ -964,11 +3084,92 @@ where
     }
 
     /// Returns the number of bytes so far deserialized into a successful `T`.
-    ///
-    /// If a stream deserializer returns an EOF error, new data can be joined to
-    /// `old_data[stream.byte_offset()..]` to try again.
+    ///
+    /// If a stream deserializer returns an EOF error, new data can be joined to
+    /// `old_data[stream.byte_offset()..]` to try again.
+    pub fn byte_offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Deserializes the next value like `next`, together with the `Span` of
+    /// input it was read from.
+    pub fn next_spanned(&mut self) -> Option<Result<(T, Span)>> {
+        if let Err(err) = self.de.parse_whitespace() {
+            return Some(Err(err));
+        }
+        let start = self.de.read.byte_offset();
+        let position = self.de.read.offset_position();
+        self.next().map(|result| {
+            result.map(|value| {
+                let span = Span {
+                    start,
+                    end: self.offset,
+                    line: position.line,
+                    column: position.column,
+                };
+                (value, span)
+            })
+        })
+    }
+
+    /// Turns the stream into an iterator over values together with the
+    /// `Span` each was read from, so that a bad record can be reported by
+    /// where it appears in the input.
+    ///
+    /// ```rust
+    /// use sexpr::Deserializer;
+    ///
+    /// let input = "(1 2)\n(3)";
+    /// let stream = Deserializer::from_str(input).into_iter::<Vec<u32>>();
+    /// let spans: Vec<_> = stream.spanned().map(|r| r.unwrap().1).collect();
+    ///
+    /// assert_eq!(&input[spans[1].start..spans[1].end], "(3)");
+    /// assert_eq!((spans[1].line, spans[1].column), (2, 1));
+    /// ```
+    pub fn spanned(self) -> SpannedStreamDeserializer<'de, R, T> {
+        SpannedStreamDeserializer { stream: self }
+    }
+}
+
+/// The part of the input a value was read from by a `StreamDeserializer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// Byte offset of the first byte of the value.
+    pub start: usize,
+    /// Byte offset just past the last byte of the value.
+    pub end: usize,
+    /// Line of the first byte of the value, starting at 1.
+    pub line: usize,
+    /// Column of the first byte of the value, starting at 1.
+    pub column: usize,
+}
+
+/// Iterator over the values of a `StreamDeserializer` together with their
+/// `Span`s, created by `StreamDeserializer::spanned`.
+pub struct SpannedStreamDeserializer<'de, R, T> {
+    stream: StreamDeserializer<'de, R, T>,
+}
+
+impl<'de, R, T> SpannedStreamDeserializer<'de, R, T>
+where
+    R: read::Read<'de>,
+    T: de::Deserialize<'de>,
+{
+    /// Returns the number of bytes so far deserialized into a successful `T`.
     pub fn byte_offset(&self) -> usize {
-        self.offset
+        self.stream.byte_offset()
+    }
+}
+
+impl<'de, R, T> Iterator for SpannedStreamDeserializer<'de, R, T>
+where
+    R: Read<'de>,
+    T: de::Deserialize<'de>,
+{
+    type Item = Result<(T, Span)>;
+
+    fn next(&mut self) -> Option<Result<(T, Span)>> {
+        self.stream.next_spanned()
     }
 }
 
@@ -988,7 +3189,7 @@ where
                 self.offset = self.de.read.byte_offset();
                 None
             }
-            Ok(Some(b'(')) => {
+            Ok(Some(b)) if self.de.list_close(b).is_some() => {
                 self.offset = self.de.read.byte_offset();
                 let result = de::Deserialize::deserialize(&mut self.de);
                 if result.is_ok() {
@@ -1009,12 +3210,17 @@ where
     R: Read<'de>,
     T: de::Deserialize<'de>,
 {
-    let mut de = Deserializer::new(read);
-    let value = de::Deserialize::deserialize(&mut de)?;
+    #[cfg(feature = "trace")]
+    let _span = tracing::debug_span!("parse", ty = std::any::type_name::<T>()).entered();
 
-    // Make sure the whole stream has been consumed.
-    de.end()?;
-    Ok(value)
+    let mut de = Deserializer::new(read);
+    let result = de::Deserialize::deserialize(&mut de).and_then(|value| {
+        // Make sure the whole stream has been consumed.
+        de.end()?;
+        Ok(value)
+    });
+    trace_error!(result, "parse");
+    result
 }
 
 /// Deserialize an instance of type `T` from an IO stream of S-expressions.
@@ -1046,104 +3252,599 @@ where
 ///     // Open the file in read-only mode.
 ///     let file = File::open(path)?;
 ///
-///     // Read the Sexp contents of the file as an instance of `User`.
-///     let u = sexpr::from_reader(file)?;
+///     // Read the Sexp contents of the file as an instance of `User`.
+///     let u = sexpr::from_reader(file)?;
+///
+///     // Return the `User`.
+///     Ok(u)
+/// }
+///
+/// fn main() {
+/// # }
+/// # fn fake_main() {
+///     let u = read_user_from_file("test.scm").unwrap();
+///     println!("{:#?}", u);
+/// }
+/// ```
+pub fn from_reader<R, T>(rdr: R) -> Result<T>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    from_trait(read::IoRead::new(rdr))
+}
+
+/// Finds the first list whose head is the symbol `head` in an IO stream, as
+/// `Deserializer::find_form` does, without parsing the rest of the stream.
+/// Bytes are read from `rdr` one at a time, so a `File` should be wrapped in
+/// a `BufReader`.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let netlist = BufReader::new(File::open("board.net")?);
+/// if let Some(module) = sexpr::de::find_form(netlist, "module")? {
+///     println!("{}", module);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn find_form<R>(rdr: R, head: &str) -> Result<Option<Sexp>>
+where
+    R: io::Read,
+{
+    Deserializer::new(read::IoRead::new(rdr)).find_form(head)
+}
+
+/// Deserialize an instance of type `T` from an IO stream of gzip-compressed
+/// S-expression text, as `from_reader` does for uncompressed text. To read a
+/// stream of values, use `Deserializer::from_reader_gzip`. Requires the
+/// `gzip` feature.
+///
+/// ```no_run
+/// use std::fs::File;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let samples: Vec<(String, f64)> = sexpr::de::from_reader_gzip(File::open("samples.sexp.gz")?)?;
+/// # drop(samples);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Fails in the same cases as `from_reader`, and if the input is not valid
+/// gzip data.
+#[cfg(feature = "gzip")]
+pub fn from_reader_gzip<R, T>(rdr: R) -> Result<T>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    from_trait(read::IoRead::new(io::BufReader::new(
+        flate2::read::MultiGzDecoder::new(rdr),
+    )))
+}
+
+/// Deserialize an instance of type `T` from an IO stream of zstd-compressed
+/// S-expression text, as `from_reader` does for uncompressed text. To read a
+/// stream of values, use `Deserializer::from_reader_zstd`. Requires the
+/// `zstd` feature.
+///
+/// # Errors
+///
+/// Fails in the same cases as `from_reader`, and if the input is not valid
+/// zstd data.
+#[cfg(feature = "zstd")]
+pub fn from_reader_zstd<R, T>(rdr: R) -> Result<T>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    let decoder = zstd::Decoder::new(rdr).map_err(Error::io)?;
+    from_trait(read::IoRead::new(io::BufReader::new(decoder)))
+}
+
+/// Deserialize an instance of type `T` from bytes of an S-expression.
+///
+/// # Errors
+///
+/// This conversion can fail if the structure of the input does not match the
+/// structure expected by `T`, for example if `T` is a struct type but the input
+/// contains something other than a S-expression "map". It can also fail if the
+/// structure is correct but `T`'s implementation of `Deserialize` decides that
+/// something is wrong with the data, for example required struct fields are
+/// missing from the S-expression or some number is too big to fit in the expected
+/// primitive type.
+///
+/// ```
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize, Debug)]
+/// struct User {
+///     fingerprint: String,
+///     location: String,
+/// }
+///
+/// fn main() {
+///     // The type of `s` is `&[u8]`
+///     let s = b"(
+///                 (fingerprint . \"0xF9BA143B95FF6D82\")
+///                 (location . \"Menlo Park, CA\")
+///               )";
+///
+///     let u: User = sexpr::from_slice(s).unwrap();
+///     println!("{:#?}", u);
+/// }
+/// ```
+pub fn from_slice<'a, T>(v: &'a [u8]) -> Result<T>
+where
+    T: de::Deserialize<'a>,
+{
+    from_trait(read::SliceRead::new(v))
+}
+
+/// Deserialize an instance of type `T` from a string of S-expressions.
+///
+/// # Errors
+///
+/// This conversion can fail if the structure of the input does not match the
+/// structure expected by `T`, for example if `T` is a struct type but the input
+/// contains something other than a S-expression "map". It can also fail if the
+/// structure is correct but `T`'s implementation of `Deserialize` decides that
+/// something is wrong with the data, for example required struct fields are
+/// missing from the S-expression or some number is too big to fit in the expected
+/// primitive type.
+///
+/// ```
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize, Debug)]
+/// struct User {
+///     fingerprint: String,
+///     location: String,
+/// }
+///
+/// fn main() {
+///     // The type of `s` is `&str`
+///     let s = "(
+///                (fingerprint . \"0xF9BA143B95FF6D82\")
+///                (location . \"Menlo Park, CA\")
+///              )";
+///
+///     let u: User = sexpr::from_str(s).unwrap();
+///     println!("{:#?}", u);
+/// }
+/// ```
+pub fn from_str<'a, T>(s: &'a str) -> Result<T>
+where
+    T: de::Deserialize<'a>,
+{
+    from_trait(read::StrRead::new(s))
+}
+
+/// Deserialize an instance of type `T` from the S-expression at the start of
+/// a string, returning it together with the rest of the string.
+///
+/// Unlike `from_str` this does not fail when something follows the value,
+/// so S-expressions can be read out of larger text such as a REPL line or a
+/// protocol frame. The remainder starts right after the value, including
+/// any whitespace there.
+///
+/// ```
+/// let (v, rest): (Vec<u32>, &str) = sexpr::from_str_prefix("(1 2) ; done").unwrap();
+/// assert_eq!(v, [1, 2]);
+/// assert_eq!(rest, " ; done");
+/// ```
+///
+/// # Errors
+///
+/// This conversion can fail for the same reasons as `from_str`, except for
+/// trailing characters.
+pub fn from_str_prefix<'a, T>(s: &'a str) -> Result<(T, &'a str)>
+where
+    T: de::Deserialize<'a>,
+{
+    let mut de = Deserializer::new(read::StrRead::new(s));
+    let value = de::Deserialize::deserialize(&mut de)?;
+    // Every value ends on an ASCII byte or at the end of the input, so the
+    // offset is at a character boundary.
+    let rest = &s[de.read.byte_offset()..];
+    Ok((value, rest))
+}
+
+/// Deserialize an instance of type `T` from a string of S-expression text,
+/// returning it together with counts of what was parsed.
+///
+/// The counts are kept only for this call, so `from_str` pays nothing for
+/// them. See `ParseStats` for what is counted.
+///
+/// ```
+/// let (v, stats): (Vec<u32>, _) = sexpr::from_str_with_stats("(1 2 3)").unwrap();
+/// assert_eq!(v, [1, 2, 3]);
+/// assert_eq!((stats.atoms, stats.max_depth), (3, 1));
+/// ```
+///
+/// # Errors
+///
+/// This conversion can fail for the same reasons as `from_str`.
+pub fn from_str_with_stats<'a, T>(s: &'a str) -> Result<(T, ParseStats)>
+where
+    T: de::Deserialize<'a>,
+{
+    let mut de = Deserializer::new(read::StrRead::new(s)).with_stats(true);
+    let value = de::Deserialize::deserialize(&mut de)?;
+    de.end()?;
+    let stats = de.stats().unwrap();
+    Ok((value, stats))
+}
+
+/// Parses a string into a `SpannedSexp`, which records where in the string
+/// each value, down to the elements of nested lists, was read.
+///
+/// Tools such as linters and compilers can use the spans to report a
+/// problem at the exact place it appears. `SpannedSexp::into_sexp` gives
+/// the plain value `from_str` would have returned.
+///
+/// ```rust
+/// let input = "(let ((x 1))\n  (bad-call x))";
+/// let form = sexpr::from_str_spanned(input).unwrap();
+///
+/// let offset = input.find("bad-call").unwrap();
+/// let span = form.find(offset).unwrap().span;
+/// assert_eq!((span.line, span.column), (2, 4));
+/// ```
+pub fn from_str_spanned(s: &str) -> Result<SpannedSexp> {
+    let mut de = Deserializer::new(read::StrRead::new(s));
+    let value = de.parse_spanned()?;
+    de.end()?;
+    Ok(value)
+}
+
+/// Parses a string holding any number of top-level forms into a
+/// `SexpDocument`, which keeps the comments between the forms along with
+/// them.
+///
+/// ```rust
+/// let doc = sexpr::from_str_document("; first\n(a 1)\n(b 2) ; last").unwrap();
+/// assert_eq!(doc.0.len(), 4);
+/// assert_eq!(doc.forms().last().unwrap().to_string(), "(b 2)");
+/// ```
+///
+/// # Errors
+///
+/// Fails if any of the forms is not a well formed S-expression, with the
+/// position of the error in the whole string.
+pub fn from_str_document(s: &str) -> Result<SexpDocument> {
+    let mut de = Deserializer::new(read::StrRead::new(s));
+    let mut items = Vec::new();
+    loop {
+        let start = de.read.byte_offset();
+        let next = de.parse_whitespace()?;
+        push_comments(&s[start..de.read.byte_offset()], &mut items);
+        if next.is_none() {
+            return Ok(SexpDocument(items));
+        }
+        items.push(DocumentItem::Form(de::Deserialize::deserialize(&mut de)?));
+    }
+}
+
+/// Appends the comments in `gap`, text between top-level forms that holds
+/// only whitespace and comments, to `items`.
+fn push_comments(gap: &str, items: &mut Vec<DocumentItem>) {
+    let mut rest = gap;
+    while let Some(start) = rest.find(';') {
+        let comment = &rest[start + 1..];
+        let end = comment.find(['\n', '\r']).unwrap_or(comment.len());
+        items.push(DocumentItem::Comment(comment[..end].to_owned()));
+        rest = &comment[end..];
+    }
+}
+
+/// Parses `s` as exactly one value, with nothing before or after it, for
+/// the `FromStr` impls of `Atom` and `Number`.
+pub(crate) fn from_token(s: &str) -> Result<Sexp> {
+    let mut de = Deserializer::from_str(s);
+    if de.parse_whitespace()?.is_some() && de.read.byte_offset() > 0 {
+        return Err(de.peek_error(ErrorCode::ExpectedSomeValue));
+    }
+    let value = de::Deserialize::deserialize(&mut de)?;
+    if de.read.byte_offset() < s.len() {
+        return Err(de.peek_error(ErrorCode::TrailingCharacters));
+    }
+    Ok(value)
+}
+
+/// Checks that `s` is well formed, reporting every syntax error in it rather
+/// than only the first.
+///
+/// The whole input is lexed and parsed as `from_str` would, but nothing is
+/// built from the forms, so a large file is checked much faster than it is
+/// read into `Sexp`s. After an error, checking starts over at the next line
+/// that begins with an opening bracket.
+///
+/// ```rust
+/// assert!(sexpr::validate_syntax("(a 1)\n(b \"two\")").is_ok());
+///
+/// let errors = sexpr::validate_syntax("(a 1..2)\n(b 2)\n(c))\n").unwrap_err();
+/// assert_eq!(errors.len(), 2);
+/// assert_eq!(errors[0].line(), 1);
+/// assert_eq!(errors[1].line(), 3);
+/// ```
+pub fn validate_syntax(s: &str) -> result::Result<(), Vec<Error>> {
+    Deserializer::from_str(s).validate_syntax()
+}
+
+/// Checks that the input of an IO stream is well formed, reporting every
+/// syntax error in it rather than only the first. See `validate_syntax`.
+///
+/// The content of the IO stream is read through and thrown away as it is
+/// checked. As with `from_reader`, wrapping it in a `BufReader` makes reading
+/// faster.
+pub fn validate_syntax_reader<R>(rdr: R) -> result::Result<(), Vec<Error>>
+where
+    R: io::Read,
+{
+    Deserializer::from_reader(rdr).validate_syntax()
+}
+
+/// Parses a value in full and throws it away, for `validate_syntax`.
+/// `IgnoredAny` would skip the elements of a list without parsing them.
+struct Validate;
+
+impl<'de> de::Deserialize<'de> for Validate {
+    fn deserialize<D>(deserializer: D) -> result::Result<Validate, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(Validate)
+    }
+}
+
+macro_rules! validate_scalars {
+    ($($method:ident($ty:ty);)*) => {
+        $(
+            fn $method<E: de::Error>(self, _: $ty) -> result::Result<Validate, E> {
+                Ok(Validate)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Visitor<'de> for Validate {
+    type Value = Validate;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    validate_scalars! {
+        visit_bool(bool);
+        visit_i64(i64);
+        visit_u64(u64);
+        visit_i128(i128);
+        visit_u128(u128);
+        visit_f64(f64);
+        visit_char(char);
+        visit_str(&str);
+        visit_bytes(&[u8]);
+    }
+
+    fn visit_unit<E: de::Error>(self) -> result::Result<Validate, E> {
+        Ok(Validate)
+    }
+
+    fn visit_none<E: de::Error>(self) -> result::Result<Validate, E> {
+        Ok(Validate)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> result::Result<Validate, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(Validate)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> result::Result<Validate, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(Validate)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> result::Result<Validate, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        while seq.next_element::<Validate>()?.is_some() {}
+        Ok(Validate)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> result::Result<Validate, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        while map.next_entry::<Validate, Validate>()?.is_some() {}
+        Ok(Validate)
+    }
+}
+
+/// Splits `s` into the source text of each of its top-level values, without
+/// parsing them, so that a large file can be handed out in pieces to be
+/// parsed in parallel or only in part.
+///
+/// Each slice is exactly the text of one value, with the whitespace and
+/// comments between values left out. Brackets inside strings, symbols
+/// between bars and comments are not counted.
 ///
-///     // Return the `User`.
-///     Ok(u)
-/// }
+/// ```rust
+/// let input = "(a \")\") ; (not a form\n(b (c)) 42";
+/// let forms: Vec<&str> = sexpr::split_top_level(input).collect();
+/// assert_eq!(forms, ["(a \")\")", "(b (c))", "42"]);
+/// ```
 ///
-/// fn main() {
+/// If the text is not well formed, the last slice holds the rest of it from
+/// the start of the value that is not, so that no input is lost and parsing
+/// that slice reports the error.
+pub fn split_top_level(s: &str) -> SplitTopLevel<'_> {
+    SplitTopLevel {
+        input: s,
+        de: Deserializer::from_str(s),
+        done: false,
+    }
+}
+
+/// Iterator over the source text of each top-level value in a string,
+/// created by `split_top_level`.
+pub struct SplitTopLevel<'a> {
+    input: &'a str,
+    de: Deserializer<read::StrRead<'a>>,
+    done: bool,
+}
+
+impl<'a> SplitTopLevel<'a> {
+    /// The number of bytes of the input split off so far, including the
+    /// whitespace and comments after the last value.
+    pub fn byte_offset(&self) -> usize {
+        self.de.read.byte_offset()
+    }
+}
+
+impl<'a> Iterator for SplitTopLevel<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.done {
+            return None;
+        }
+        let start = match self.de.parse_whitespace() {
+            Ok(Some(_)) => self.de.read.byte_offset(),
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(_) => {
+                self.done = true;
+                return Some(&self.input[self.de.read.byte_offset()..]);
+            }
+        };
+        match self.de.ignore_value() {
+            Ok(()) => Some(&self.input[start..self.de.read.byte_offset()]),
+            Err(_) => {
+                self.done = true;
+                Some(&self.input[start..])
+            }
+        }
+    }
+}
+
+/// Deserialize an instance of type `T` from a memory-mapped file of
+/// S-expressions. Strings and symbols may borrow from the map. Requires the
+/// `memmap2` feature.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use memmap2::Mmap;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let file = File::open("design.sexp")?;
+/// // The file must not be changed by anyone while it is mapped.
+/// let map = unsafe { Mmap::map(&file)? };
+/// let cells: Vec<(&str, u32)> = sexpr::de::from_mmap(&map)?;
+/// # drop(cells);
+/// # Ok(())
 /// # }
-/// # fn fake_main() {
-///     let u = read_user_from_file("test.scm").unwrap();
-///     println!("{:#?}", u);
-/// }
 /// ```
-pub fn from_reader<R, T>(rdr: R) -> Result<T>
+///
+/// # Errors
+///
+/// Fails in the same cases as `from_slice`.
+#[cfg(feature = "memmap2")]
+pub fn from_mmap<'a, T>(map: &'a memmap2::Mmap) -> Result<T>
 where
-    R: io::Read,
-    T: de::DeserializeOwned,
+    T: de::Deserialize<'a>,
 {
-    from_trait(read::IoRead::new(rdr))
+    from_trait(read::MmapRead::new(map))
 }
 
-/// Deserialize an instance of type `T` from bytes of an S-expression.
-///
-/// # Errors
-///
-/// This conversion can fail if the structure of the input does not match the
-/// structure expected by `T`, for example if `T` is a struct type but the input
-/// contains something other than a S-expression "map". It can also fail if the
-/// structure is correct but `T`'s implementation of `Deserialize` decides that
-/// something is wrong with the data, for example required struct fields are
-/// missing from the S-expression or some number is too big to fit in the expected
-/// primitive type.
+/// Deserialize an instance of type `T` from a string of S-expressions like
+/// `from_str`, using `scratch` as the buffer for unescaping strings.
 ///
 /// ```
-/// use serde_derive::Deserialize;
+/// use sexpr::de::{from_str_buffered, Scratch};
 ///
-/// #[derive(Deserialize, Debug)]
-/// struct User {
-///     fingerprint: String,
-///     location: String,
+/// let mut scratch = Scratch::new();
+/// for line in &["(\"a\\n\" 1)", "(\"b\\t\" 2)"] {
+///     let (s, n): (String, u32) = from_str_buffered(line, &mut scratch).unwrap();
+///     assert_eq!(s.len(), 2);
+///     assert!(n > 0);
 /// }
+/// ```
 ///
-/// fn main() {
-///     // The type of `s` is `&[u8]`
-///     let s = b"(
-///                 (fingerprint . \"0xF9BA143B95FF6D82\")
-///                 (location . \"Menlo Park, CA\")
-///               )";
+/// # Errors
 ///
-///     let u: User = sexpr::from_slice(s).unwrap();
-///     println!("{:#?}", u);
-/// }
-/// ```
-pub fn from_slice<'a, T>(v: &'a [u8]) -> Result<T>
+/// Fails in the same cases as `from_str`.
+pub fn from_str_buffered<'a, T>(s: &'a str, scratch: &mut Scratch) -> Result<T>
 where
     T: de::Deserialize<'a>,
 {
-    from_trait(read::SliceRead::new(v))
+    let str_buf = mem::take(&mut scratch.buf);
+    let mut de = Deserializer::with_str_buf(
+        read::StrRead::new(s),
+        DeserializerOptions::default(),
+        str_buf,
+    );
+    let value = de::Deserialize::deserialize(&mut de).and_then(|value| de.end().map(|()| value));
+    scratch.buf = de.str_buf;
+    value
 }
 
-/// Deserialize an instance of type `T` from a string of S-expressions.
+/// Deserialize every top-level value in a byte slice as a `T`, parsing the
+/// values in parallel on the rayon thread pool.
+///
+/// The slice is first scanned once to find where each value starts and
+/// ends; the values are then parsed independently. Results are returned in
+/// input order. Requires the `rayon` feature.
 ///
 /// # Errors
 ///
-/// This conversion can fail if the structure of the input does not match the
-/// structure expected by `T`, for example if `T` is a struct type but the input
-/// contains something other than a S-expression "map". It can also fail if the
-/// structure is correct but `T`'s implementation of `Deserialize` decides that
-/// something is wrong with the data, for example required struct fields are
-/// missing from the S-expression or some number is too big to fit in the expected
-/// primitive type.
+/// Returns the error of the first value, in input order, that fails to
+/// parse, with its line and column counted from the start of the slice.
 ///
 /// ```
-/// use serde_derive::Deserialize;
-///
-/// #[derive(Deserialize, Debug)]
-/// struct User {
-///     fingerprint: String,
-///     location: String,
-/// }
-///
-/// fn main() {
-///     // The type of `s` is `&str`
-///     let s = "(
-///                (fingerprint . \"0xF9BA143B95FF6D82\")
-///                (location . \"Menlo Park, CA\")
-///              )";
-///
-///     let u: User = sexpr::from_str(s).unwrap();
-///     println!("{:#?}", u);
-/// }
+/// let forms: Vec<Vec<u32>> = sexpr::de::par_parse_many(b"(1 2) (3)\n()").unwrap();
+/// assert_eq!(forms, vec![vec![1, 2], vec![3], vec![]]);
 /// ```
-pub fn from_str<'a, T>(s: &'a str) -> Result<T>
+#[cfg(feature = "rayon")]
+pub fn par_parse_many<'a, T>(v: &'a [u8]) -> Result<Vec<T>>
 where
-    T: de::Deserialize<'a>,
+    T: de::Deserialize<'a> + Send,
 {
-    from_trait(read::StrRead::new(s))
+    use rayon::prelude::*;
+
+    split_forms(v)?
+        .into_par_iter()
+        .map(|(start, end, position)| {
+            from_slice(&v[start..end])
+                .map_err(|err| err.shift_position(position.line, position.column))
+        })
+        .collect()
+}
+
+/// Finds the byte range and starting position of each top-level value.
+#[cfg(feature = "rayon")]
+fn split_forms(v: &[u8]) -> Result<Vec<(usize, usize, read::Position)>> {
+    let mut de = Deserializer::from_slice(v);
+    let mut forms = Vec::new();
+    while de.parse_whitespace()?.is_some() {
+        let start = de.read.byte_offset();
+        let position = de.read.offset_position();
+        de.ignore_value()?;
+        forms.push((start, de.read.byte_offset(), position));
+    }
+    Ok(forms)
 }
 
 #[cfg(test)]
@@ -1183,4 +3884,518 @@ mod tests {
             }
         );
     }
+
+    fn with_options<'a, T>(s: &'a str, options: super::DeserializerOptions) -> crate::Result<T>
+    where
+        T: serde::Deserialize<'a>,
+    {
+        let mut de = super::Deserializer::from_str_with_options(s, options);
+        let value = serde::Deserialize::deserialize(&mut de)?;
+        de.end()?;
+        Ok(value)
+    }
+
+    #[test]
+    fn test_square_brackets() {
+        let options = super::DeserializerOptions {
+            square_brackets: true,
+            ..Default::default()
+        };
+        let v: Vec<u32> = with_options("[1 2 3]", options.clone()).unwrap();
+        assert_eq!(v, vec![1, 2, 3]);
+
+        let user: User = with_options(
+            "[(fingerprint . \"0xF9BA143B95FF6D82\") [location . \"Menlo Park, CA\"]]",
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(user.location, "Menlo Park, CA");
+
+        assert!(with_options::<Vec<u32>>("[1 2 3)", options).is_err());
+        assert!(super::from_str::<Vec<u32>>("[1 2 3]").is_err());
+    }
+
+    #[test]
+    fn test_hex_escapes() {
+        let options = super::DeserializerOptions {
+            hex_escapes: true,
+            ..Default::default()
+        };
+        let v: Vec<String> = with_options("(#61626364# #6869#)", options.clone()).unwrap();
+        assert_eq!(v, vec!["abcd", "hi"]);
+        let b: Vec<bool> = with_options("(#f #t)", options.clone()).unwrap();
+        assert_eq!(b, vec![false, true]);
+        assert!(with_options::<String>("#686#", options).is_err());
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let options = super::DeserializerOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let user: User = with_options(
+            "((FINGERPRINT . \"0xF9BA143B95FF6D82\") (Location . \"Menlo Park, CA\"))",
+            options.clone(),
+        )
+        .unwrap();
+        assert_eq!(user.fingerprint, "0xF9BA143B95FF6D82");
+        let b: Vec<bool> = with_options("(#T #F)", options).unwrap();
+        assert_eq!(b, vec![true, false]);
+    }
+
+    #[test]
+    fn test_tuple_arity() {
+        let t: (i32, i32, i32) = super::from_str("(1 2 3)").unwrap();
+        assert_eq!(t, (1, 2, 3));
+        let a: [u8; 3] = super::from_str("( 1 2 3 )").unwrap();
+        assert_eq!(a, [1, 2, 3]);
+
+        let err = super::from_str::<(i32, i32, i32)>("(1 2)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected 3 elements, found 2 at line 1 column 5"
+        );
+        let err = super::from_str::<[u8; 3]>("(1 2 3 4 5)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected 3 elements, found 5 at line 1 column 7"
+        );
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Point(i64, i64);
+        let p: Point = super::from_str("(-1 2)").unwrap();
+        assert_eq!(p, Point(-1, 2));
+        assert!(super::from_str::<Point>("(-1)").is_err());
+    }
+
+    #[test]
+    fn test_list_spacing() {
+        let inputs = [
+            "(1 2 3)",
+            "( 1  2\n3 )",
+            "(1\t2\r\n3)",
+            "(\n\n1\n\n2\n\n3\n\n)",
+            "(1 2 3 )",
+            "(  1 2 3)",
+            "(1 ; one\n 2 ;two\n3)",
+            "; leading comment\n(1 2 3) ; trailing comment",
+        ];
+        for input in &inputs {
+            let v: Vec<u64> = super::from_str(input).unwrap();
+            assert_eq!(v, vec![1, 2, 3], "input: {:?}", input);
+        }
+
+        let nested: Vec<Vec<u64>> = super::from_str("( (1)\t( 2 3 ) () )").unwrap();
+        assert_eq!(nested, vec![vec![1], vec![2, 3], vec![]]);
+    }
+
+    #[test]
+    fn test_lossless_integers() {
+        let lossless = super::DeserializerOptions {
+            lossless_integers: true,
+            ..super::DeserializerOptions::default()
+        };
+
+        let v: f64 = super::from_str("-9223372036854775809").unwrap();
+        assert_eq!(v, -9223372036854775809.0);
+
+        let v: i64 = with_options("-9223372036854775808", lossless.clone()).unwrap();
+        assert_eq!(v, i64::MIN);
+        let v: u64 = with_options("18446744073709551615", lossless.clone()).unwrap();
+        assert_eq!(v, u64::MAX);
+
+        let err = with_options::<f64>("-9223372036854775809", lossless.clone()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "integer `-9223372036854775809` out of range at line 1 column 20"
+        );
+        let err = with_options::<Vec<f64>>("(184467440737095516150 1)", lossless).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "integer `184467440737095516150` out of range at line 1 column 22"
+        );
+    }
+
+    #[test]
+    fn test_stream_spans() {
+        let input = "(1)\n  (2 3)\t(4\n5)";
+        let check = |spans: Vec<super::Span>| {
+            let found: Vec<_> = spans
+                .iter()
+                .map(|span| (&input[span.start..span.end], span.line, span.column))
+                .collect();
+            assert_eq!(
+                found,
+                vec![("(1)", 1, 1), ("(2 3)", 2, 3), ("(4\n5)", 2, 9)]
+            );
+        };
+
+        let stream = super::Deserializer::from_str(input).into_iter::<Vec<u32>>();
+        check(stream.spanned().map(|r| r.unwrap().1).collect());
+
+        let stream = super::Deserializer::from_reader(input.as_bytes()).into_iter::<Vec<u32>>();
+        check(stream.spanned().map(|r| r.unwrap().1).collect());
+    }
+
+    #[test]
+    fn test_incremental_parser() {
+        use super::{IncrementalParser, Parsed};
+        use crate::Sexp;
+
+        let mut parser = IncrementalParser::new();
+        let mut values = Vec::new();
+        let input = "(a \"x)\" ; c)\n 1) (b)\n(c (d";
+        for chunk in input.as_bytes().chunks(3) {
+            parser.feed(chunk);
+            while let Parsed::Value(value) = parser.next_value().unwrap() {
+                values.push(value);
+            }
+        }
+        let expected: Vec<Sexp> = vec![
+            super::from_str("(a \"x)\" 1)").unwrap(),
+            super::from_str("(b)").unwrap(),
+        ];
+        assert_eq!(values, expected);
+        assert!(parser.finish().is_err());
+
+        parser.feed(b"))");
+        let value = parser.next_value().unwrap();
+        assert_eq!(value, Parsed::Value(super::from_str("(c (d))").unwrap()));
+        assert!(parser.finish().is_ok());
+
+        let mut parser = IncrementalParser::new();
+        parser.feed(b"(1)\n  (2 #q)");
+        assert!(parser.next_value().is_ok());
+        let err = parser.next_value().unwrap_err();
+        assert_eq!((err.line(), err.column()), (2, 8));
+
+        let mut parser = IncrementalParser::new();
+        parser.feed(b"(|a) \\|\"| #:|;|");
+        assert_eq!(parser.next_value().unwrap(), Parsed::NeedMoreData);
+        parser.feed(b")");
+        let value = parser.next_value().unwrap();
+        assert_eq!(
+            value,
+            Parsed::Value(super::from_str("(|a) \\|\"| #:|;|)").unwrap())
+        );
+
+        // Atoms can stand at the top level too, and give the same values
+        // however the input is split up.
+        let input = "42 foo \"bar\"(a . b)#t |x y|#:k #x\"FF\" #u8(1 2) -1.5\u{a0}z\n; end\nlast";
+        let expected: Vec<Sexp> = super::split_top_level(input)
+            .map(|text| super::from_str(text).unwrap())
+            .collect();
+        assert_eq!(expected.len(), 12);
+        for size in 1..=input.len() {
+            let mut parser = IncrementalParser::new();
+            let mut values = Vec::new();
+            for chunk in input.as_bytes().chunks(size) {
+                parser.feed(chunk);
+                while let Parsed::Value(value) = parser.next_value().unwrap() {
+                    values.push(value);
+                }
+            }
+            values.extend(parser.finish().unwrap());
+            assert_eq!(values, expected, "chunks of {}", size);
+        }
+
+        let mut parser = IncrementalParser::new();
+        parser.feed(b"12");
+        assert_eq!(parser.next_value().unwrap(), Parsed::NeedMoreData);
+        parser.feed(b"3)");
+        assert_eq!(parser.next_value().unwrap(), Parsed::Value(Sexp::from(123)));
+        let err = parser.next_value().unwrap_err();
+        assert_eq!((err.line(), err.column()), (1, 4));
+        let mut parser = IncrementalParser::new();
+        parser.feed(b"\"open");
+        assert_eq!(parser.next_value().unwrap(), Parsed::NeedMoreData);
+        assert!(parser.finish().unwrap_err().is_eof());
+    }
+
+    #[test]
+    fn test_ignored_fields() {
+        let s = "((junk . (1 \"a)\\\" b\" (sym\"bol #t) -2.5 [x] ; c)\n ()))
+                  (fingerprint . \"0xF9BA143B95FF6D82\")
+                  (more . #nil)
+                  (location . \"Menlo Park, CA\"))";
+        let user: User = super::from_str(s).unwrap();
+        assert_eq!(user.location, "Menlo Park, CA");
+
+        let user: User = super::from_reader(s.as_bytes()).unwrap();
+        assert_eq!(user.location, "Menlo Park, CA");
+
+        let ignored = super::from_str::<serde::de::IgnoredAny>("(1 (2)");
+        assert!(ignored.unwrap_err().is_eof());
+        let ignored = super::from_reader::<_, serde::de::IgnoredAny>(&b"(1 \"2)"[..]);
+        assert!(ignored.unwrap_err().is_eof());
+        assert!(super::from_str::<serde::de::IgnoredAny>(")").is_err());
+
+        let options = super::DeserializerOptions {
+            square_brackets: true,
+            ..super::DeserializerOptions::default()
+        };
+        let err = with_options::<serde::de::IgnoredAny>("([a) b]", options).unwrap_err();
+        assert_eq!(err.to_string(), "mismatched bracket at line 1 column 4");
+    }
+
+    #[test]
+    fn test_raw_sexp() {
+        use crate::RawSexp;
+
+        #[derive(Deserialize)]
+        struct Document {
+            header: u32,
+            body: RawSexp,
+        }
+
+        let s = "((body .  (a \"b c\" (d . 1)) ) (header . 7))";
+        let doc: Document = super::from_str(s).unwrap();
+        assert_eq!(doc.header, 7);
+        assert_eq!(doc.body.get(), "(a \"b c\" (d . 1))");
+
+        let doc: Document = super::from_reader(s.as_bytes()).unwrap();
+        assert_eq!(doc.body.get(), "(a \"b c\" (d . 1))");
+
+        let raw: Vec<RawSexp> = super::from_str("(sym \"str\" 12)").unwrap();
+        let raw: Vec<&str> = raw.iter().map(RawSexp::get).collect();
+        assert_eq!(raw, vec!["sym", "\"str\"", "12"]);
+
+        let raw = RawSexp::from_string("(x \"y\")".to_owned()).unwrap();
+        assert_eq!(crate::to_string(&raw).unwrap(), "(x \"y\")");
+        assert_eq!(
+            crate::to_value(&raw).unwrap(),
+            super::from_str::<crate::Sexp>("(x \"y\")").unwrap()
+        );
+        assert!(RawSexp::from_string("(x".to_owned()).is_err());
+        assert!(RawSexp::from_string("x y".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_skip_preamble() {
+        let options = super::DeserializerOptions {
+            skip_bom: true,
+            skip_shebang: true,
+            ..super::DeserializerOptions::default()
+        };
+        let from_reader = |s: &str| -> crate::Result<Vec<u32>> {
+            let mut de =
+                super::Deserializer::from_reader_with_options(s.as_bytes(), options.clone());
+            let value = serde::Deserialize::deserialize(&mut de)?;
+            de.end()?;
+            Ok(value)
+        };
+
+        for s in &[
+            "(1 2)",
+            "\u{feff}(1 2)",
+            "#!/usr/bin/env scheme\n(1 2)",
+            "\u{feff}#!/usr/bin/env scheme\n  ; comment\n(1 2)",
+        ] {
+            assert_eq!(
+                with_options::<Vec<u32>>(s, options.clone()).unwrap(),
+                vec![1, 2]
+            );
+            assert_eq!(from_reader(s).unwrap(), vec![1, 2]);
+            let plain = with_options::<Vec<u32>>(s, Default::default());
+            assert_eq!(plain.is_ok(), *s == "(1 2)");
+        }
+
+        // A leading `#` that does not start a `#!` line is still a value.
+        assert!(with_options::<bool>("#t", options.clone()).unwrap());
+        let mut de = super::Deserializer::from_reader_with_options(&b"#f"[..], options.clone());
+        assert!(!<bool as serde::Deserialize>::deserialize(&mut de).unwrap());
+
+        let ignored = with_options::<serde::de::IgnoredAny>("\u{feff}#!x\n(a)", options.clone());
+        assert!(ignored.is_ok());
+        assert!(from_reader("\u{feff}").is_err());
+        assert!(from_reader("\u{ff}(1)").is_err());
+    }
+
+    #[test]
+    fn test_from_str_buffered() {
+        let mut scratch = super::Scratch::new();
+        let v: Vec<String> = super::from_str_buffered("(\"a\\nb\" \"c\")", &mut scratch).unwrap();
+        assert_eq!(v, vec!["a\nb", "c"]);
+        let capacity = scratch.buf.capacity();
+        assert!(capacity > 0);
+
+        let v: String = super::from_str_buffered("\"d\\te\"", &mut scratch).unwrap();
+        assert_eq!(v, "d\te");
+        assert_eq!(scratch.buf.capacity(), capacity);
+
+        assert!(super::from_str_buffered::<String>("\"x\" y", &mut scratch).is_err());
+        assert_eq!(scratch.buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_from_str_prefix() {
+        let (v, rest): (String, _) = super::from_str_prefix("\"é\"(next)").unwrap();
+        assert_eq!((v.as_str(), rest), ("é", "(next)"));
+        let (n, rest): (u8, _) = super::from_str_prefix("42").unwrap();
+        assert_eq!((n, rest), (42, ""));
+        let (b, rest): (bool, _) = super::from_str_prefix("  #t\n#f").unwrap();
+        assert_eq!((b, rest), (true, "\n#f"));
+        assert!(super::from_str_prefix::<(u8,)>("(1").is_err());
+    }
+
+    #[cfg(feature = "memmap2")]
+    #[test]
+    fn test_from_mmap() {
+        use std::fs::{self, File};
+        use std::io::Write;
+
+        let path = std::env::temp_dir().join(format!("sexpr-mmap-{}.sexp", std::process::id()));
+        File::create(&path)
+            .unwrap()
+            .write_all(b"((\"nand\" 2) (\"inv\" 1))")
+            .unwrap();
+        let map = unsafe { memmap2::Mmap::map(&File::open(&path).unwrap()).unwrap() };
+
+        let cells: Vec<(&str, u32)> = super::from_mmap(&map).unwrap();
+        assert_eq!(cells, vec![("nand", 2), ("inv", 1)]);
+
+        let mut de = super::Deserializer::from_mmap(&map);
+        let err = <(u32,) as serde::Deserialize>::deserialize(&mut de).unwrap_err();
+        assert_eq!(err.line(), 1);
+
+        drop(map);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        let value = vec![("reading".to_owned(), 1.5), ("reading".to_owned(), -2.0)];
+        let mut gz = Vec::new();
+        crate::ser::to_writer_gzip(&mut gz, &value, 6).unwrap();
+        assert_eq!(&gz[..2], b"\x1f\x8b");
+        let back: Vec<(String, f64)> = super::from_reader_gzip(&gz[..]).unwrap();
+        assert_eq!(back, value);
+
+        // Appended members read as one stream.
+        let mut gz = Vec::new();
+        crate::ser::to_writer_gzip(&mut gz, &[1, 2], 6).unwrap();
+        crate::ser::to_writer_gzip(&mut gz, &[3], 1).unwrap();
+        let stream: Vec<Vec<u32>> = super::Deserializer::from_reader_gzip(&gz[..])
+            .into_iter()
+            .collect::<crate::Result<_>>()
+            .unwrap();
+        assert_eq!(stream, vec![vec![1, 2], vec![3]]);
+
+        let err = super::from_reader_gzip::<_, crate::Sexp>(&b"(1 2)"[..]).unwrap_err();
+        assert!(err.is_io());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        let value = vec![("reading".to_owned(), 1.5), ("reading".to_owned(), -2.0)];
+        let mut zst = Vec::new();
+        crate::ser::to_writer_zstd(&mut zst, &value, 3).unwrap();
+        let back: Vec<(String, f64)> = super::from_reader_zstd(&zst[..]).unwrap();
+        assert_eq!(back, value);
+
+        let mut de = super::Deserializer::from_reader_zstd(&zst[..]).unwrap();
+        let back: Vec<(String, f64)> = serde::Deserialize::deserialize(&mut de).unwrap();
+        de.end().unwrap();
+        assert_eq!(back, value);
+
+        assert!(super::from_reader_zstd::<_, crate::Sexp>(&b"(1 2)"[..]).is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_parse_many() {
+        let input = b"(1 2)\n(\"a\" ; )\n)\n  (3)";
+        let err = super::par_parse_many::<Vec<u32>>(input).unwrap_err();
+        assert_eq!((err.line(), err.column()), (2, 4));
+
+        let forms: Vec<Vec<u32>> = super::par_parse_many(b" (1 2) ; x\n(3)").unwrap();
+        assert_eq!(forms, vec![vec![1, 2], vec![3]]);
+        assert!(super::par_parse_many::<Vec<u32>>(b"(1 (2)").is_err());
+    }
+
+    #[test]
+    fn test_special_floats() {
+        let v: Vec<f64> = super::from_str("(+inf.0 -inf.0 +nan.0 -1)").unwrap();
+        assert_eq!(v[0], f64::INFINITY);
+        assert_eq!(v[1], f64::NEG_INFINITY);
+        assert!(v[2].is_nan());
+        assert_eq!(v[3], -1.0);
+
+        assert!(super::from_str::<f64>("+inf").is_err());
+        assert!(super::from_str::<f64>("+1").is_err());
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn test_trace_events() {
+        use std::fmt::{self, Write as _};
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        // Writes each span and event it sees as a line of text.
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        struct Line<'a>(&'a mut String);
+
+        impl Visit for Line<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                write!(self.0, " {}={:?}", field.name(), value).unwrap();
+            }
+        }
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut line = format!("span {}", span.metadata().name());
+                span.record(&mut Line(&mut line));
+                let mut lines = self.0.lock().unwrap();
+                lines.push(line);
+                Id::from_u64(lines.len() as u64)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut line = String::from("event");
+                event.record(&mut Line(&mut line));
+                self.0.lock().unwrap().push(line);
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            super::from_str::<crate::Sexp>("(1 (2))").unwrap();
+            super::from_str::<u8>("(").unwrap_err();
+            crate::ser::to_writer(Vec::new(), &[1, 2]).unwrap();
+        });
+        let lines = recorder.0.lock().unwrap();
+        assert_eq!(
+            *lines,
+            [
+                "span parse ty=\"sexpr::sexp::Sexp\"",
+                "event message=list start depth=1",
+                "event message=list start depth=2",
+                "span parse ty=\"u8\"",
+                "event message=list start depth=1",
+                "event message=parse failed: invalid type: sequence, expected u8 at line 1 \
+                 column 1 line=1 column=1 kind=Custom",
+                "span serialize ty=\"[i32; 2]\"",
+            ]
+        );
+    }
 }
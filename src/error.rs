@@ -56,7 +56,23 @@ impl Error {
     /// - `Category::Eof` - unexpected end of the input data
     pub fn classify(&self) -> Category {
         match self.err.code {
-            ErrorCode::Message(_) => Category::Data,
+            ErrorCode::Message(_)
+            | ErrorCode::InvalidType(..)
+            | ErrorCode::InvalidValue(..)
+            | ErrorCode::InvalidLength(_, _)
+            | ErrorCode::IncludeCycle(_)
+            | ErrorCode::InvalidInclude
+            | ErrorCode::MissingVariable(_)
+            | ErrorCode::InvalidEnvForm
+            | ErrorCode::ExpectedAlist
+            | ErrorCode::ExpectedProperList
+            | ErrorCode::InvalidPattern(_)
+            | ErrorCode::StepLimitExceeded(_)
+            | ErrorCode::InvalidRecordFrame(_)
+            | ErrorCode::ChecksumMismatch(_)
+            | ErrorCode::InvalidSymbol(_)
+            | ErrorCode::MalformedAlistEntry(_)
+            | ErrorCode::DuplicateKey(_) => Category::Data,
             ErrorCode::Io(_) => Category::Io,
             ErrorCode::EofWhileParsingList
             | ErrorCode::EofWhileParsingAlist
@@ -72,11 +88,17 @@ impl Error {
             | ErrorCode::InvalidEscape
             | ErrorCode::InvalidNumber
             | ErrorCode::NumberOutOfRange
+            | ErrorCode::IntegerOutOfRange(_)
             | ErrorCode::InvalidUnicodeCodePoint
             | ErrorCode::KeyMustBeAString
+            | ErrorCode::FloatMustBeFinite
             | ErrorCode::LoneLeadingSurrogateInHexEscape
             | ErrorCode::TrailingCharacters
+            | ErrorCode::MismatchedBracket
             | ErrorCode::UnexpectedEndOfHexEscape
+            | ErrorCode::InvalidBase64
+            | ErrorCode::UnknownDatumLabel
+            | ErrorCode::CommentNotAllowed
             | ErrorCode::RecursionLimitExceeded => Category::Syntax,
         }
     }
@@ -110,6 +132,130 @@ impl Error {
     pub fn is_eof(&self) -> bool {
         self.classify() == Category::Eof
     }
+
+    /// What went wrong, as a value that can be matched on rather than the
+    /// message in `Display`.
+    pub fn kind(&self) -> ErrorKind {
+        match self.err.code {
+            ErrorCode::Message(..) | ErrorCode::InvalidType(..) | ErrorCode::InvalidValue(..) => {
+                ErrorKind::Custom
+            }
+            ErrorCode::Io(..) => ErrorKind::Io,
+            ErrorCode::EofWhileParsingList => ErrorKind::EofWhileParsingList,
+            ErrorCode::EofWhileParsingAlist => ErrorKind::EofWhileParsingAlist,
+            ErrorCode::EofWhileParsingString => ErrorKind::EofWhileParsingString,
+            ErrorCode::EofWhileParsingValue => ErrorKind::EofWhileParsingValue,
+            ErrorCode::ExpectedPairDot => ErrorKind::ExpectedPairDot,
+            ErrorCode::ExpectedListEltOrEnd => ErrorKind::ExpectedListEltOrEnd,
+            ErrorCode::ExpectedPairOrEnd => ErrorKind::ExpectedPairOrEnd,
+            ErrorCode::ExpectedList => ErrorKind::ExpectedList,
+            ErrorCode::ExpectedSomeIdent => ErrorKind::ExpectedSomeIdent,
+            ErrorCode::ExpectedSomeValue => ErrorKind::ExpectedSomeValue,
+            ErrorCode::ExpectedSomeString => ErrorKind::ExpectedSomeString,
+            ErrorCode::InvalidEscape => ErrorKind::InvalidEscape,
+            ErrorCode::InvalidNumber => ErrorKind::InvalidNumber,
+            ErrorCode::NumberOutOfRange => ErrorKind::NumberOutOfRange,
+            ErrorCode::IntegerOutOfRange(..) => ErrorKind::IntegerOutOfRange,
+            ErrorCode::InvalidUnicodeCodePoint => ErrorKind::InvalidUnicodeCodePoint,
+            ErrorCode::KeyMustBeAString => ErrorKind::KeyMustBeAString,
+            ErrorCode::FloatMustBeFinite => ErrorKind::FloatMustBeFinite,
+            ErrorCode::LoneLeadingSurrogateInHexEscape => {
+                ErrorKind::LoneLeadingSurrogateInHexEscape
+            }
+            ErrorCode::TrailingCharacters => ErrorKind::TrailingCharacters,
+            ErrorCode::MismatchedBracket => ErrorKind::MismatchedBracket,
+            ErrorCode::InvalidLength(..) => ErrorKind::InvalidLength,
+            ErrorCode::UnexpectedEndOfHexEscape => ErrorKind::UnexpectedEndOfHexEscape,
+            ErrorCode::InvalidBase64 => ErrorKind::InvalidBase64,
+            ErrorCode::UnknownDatumLabel => ErrorKind::UnknownDatumLabel,
+            ErrorCode::RecursionLimitExceeded => ErrorKind::RecursionLimitExceeded,
+            ErrorCode::IncludeCycle(..) => ErrorKind::IncludeCycle,
+            ErrorCode::InvalidInclude => ErrorKind::InvalidInclude,
+            ErrorCode::MissingVariable(..) => ErrorKind::MissingVariable,
+            ErrorCode::InvalidEnvForm => ErrorKind::InvalidEnvForm,
+            ErrorCode::ExpectedAlist => ErrorKind::ExpectedAlist,
+            ErrorCode::ExpectedProperList => ErrorKind::ExpectedProperList,
+            ErrorCode::InvalidPattern(..) => ErrorKind::InvalidPattern,
+            ErrorCode::StepLimitExceeded(..) => ErrorKind::StepLimitExceeded,
+            ErrorCode::InvalidRecordFrame(..) => ErrorKind::InvalidRecordFrame,
+            ErrorCode::ChecksumMismatch(..) => ErrorKind::ChecksumMismatch,
+            ErrorCode::InvalidSymbol(..) => ErrorKind::InvalidSymbol,
+            ErrorCode::MalformedAlistEntry(..) => ErrorKind::MalformedAlistEntry,
+            ErrorCode::CommentNotAllowed => ErrorKind::CommentNotAllowed,
+            ErrorCode::DuplicateKey(..) => ErrorKind::DuplicateKey,
+        }
+    }
+
+    /// What a `Deserialize` implementation expected when it was given a
+    /// value of the wrong type or an invalid value, such as `u32` or `one of
+    /// "red", "green"`. `None` for any other error.
+    ///
+    /// Together with `found`, `path`, `line` and `column` this describes a
+    /// mismatch between the input and the Rust type it was read as, for
+    /// callers that report it as data rather than as the message of
+    /// `Display`:
+    ///
+    /// ```rust
+    /// use serde_derive::Deserialize;
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct Server {
+    ///     port: u32,
+    /// }
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct Config {
+    ///     servers: Vec<Server>,
+    /// }
+    ///
+    /// let err = sexpr::from_str::<Config>(
+    ///     "((servers ((port . 80)) ((port . \"abc\"))))",
+    /// )
+    /// .unwrap_err();
+    /// assert_eq!(err.expected(), Some("u32"));
+    /// assert_eq!(err.found(), Some("string \"abc\""));
+    /// assert_eq!(err.path().as_deref(), Some("servers.1.port"));
+    /// assert_eq!((err.line(), err.column()), (1, 38));
+    /// ```
+    pub fn expected(&self) -> Option<&str> {
+        match self.err.code {
+            ErrorCode::InvalidType(_, ref expected) | ErrorCode::InvalidValue(_, ref expected) => {
+                Some(expected)
+            }
+            _ => None,
+        }
+    }
+
+    /// A summary of the value a `Deserialize` implementation was given when
+    /// it expected another type or value, such as `string "abc"` or
+    /// `integer `7``. `None` for any other error. See `expected`.
+    pub fn found(&self) -> Option<&str> {
+        match self.err.code {
+            ErrorCode::InvalidType(ref found, _) | ErrorCode::InvalidValue(ref found, _) => {
+                Some(found)
+            }
+            _ => None,
+        }
+    }
+
+    /// Where in the value being read the error was found, as the keys of
+    /// the alists and the indexes of the lists leading to it separated by
+    /// `.`, such as `servers.1.port`. `None` if the error was found outside
+    /// any list, or did not come from reading text.
+    pub fn path(&self) -> Option<String> {
+        if self.err.path.is_empty() {
+            return None;
+        }
+        let segments: Vec<&str> = self.err.path.iter().rev().map(String::as_str).collect();
+        Some(segments.join("."))
+    }
+
+    // Records that the error was found under the key or index `segment` of
+    // an enclosing list.
+    pub(crate) fn in_path<T: Display>(mut self, segment: T) -> Self {
+        self.err.path.push(segment.to_string());
+        self
+    }
 }
 
 /// Categorizes the cause of a `sexpr::Error`.
@@ -135,6 +281,157 @@ pub enum Category {
     Eof,
 }
 
+/// What went wrong, for callers that handle some errors differently from
+/// others.
+///
+/// More kinds may be added, so a `match` on the kind needs a wildcard arm.
+///
+/// ```rust
+/// use sexpr::error::ErrorKind;
+/// use sexpr::Sexp;
+///
+/// let err = sexpr::from_str::<Sexp>("(1 2").unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::EofWhileParsingList);
+///
+/// let advice = match err.kind() {
+///     kind if err.is_eof() => format!("the file ends early ({:?})", kind),
+///     ErrorKind::InvalidNumber => "check the numbers".to_owned(),
+///     _ => err.to_string(),
+/// };
+/// assert_eq!(advice, "the file ends early (EofWhileParsingList)");
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A message from a `Serialize` or `Deserialize` implementation, such as
+    /// a value of the wrong type or a missing struct field.
+    Custom,
+
+    /// Reading or writing the underlying stream failed.
+    Io,
+
+    /// The input ended inside a list.
+    EofWhileParsingList,
+
+    /// The input ended inside an alist.
+    EofWhileParsingAlist,
+
+    /// The input ended inside a string.
+    EofWhileParsingString,
+
+    /// The input ended where a value was expected.
+    EofWhileParsingValue,
+
+    /// Expected the `.` of a pair.
+    ExpectedPairDot,
+
+    /// Expected another list element or the end of the list.
+    ExpectedListEltOrEnd,
+
+    /// Expected the `.` of a pair or the end of the list.
+    ExpectedPairOrEnd,
+
+    /// Expected a `(` to start a list.
+    ExpectedList,
+
+    /// Expected `#t`, `#f` or `#nil`.
+    ExpectedSomeIdent,
+
+    /// Expected the start of a value.
+    ExpectedSomeValue,
+
+    /// Expected a string, symbol or keyword.
+    ExpectedSomeString,
+
+    /// A string held an invalid escape.
+    InvalidEscape,
+
+    /// A number was malformed.
+    InvalidNumber,
+
+    /// A number was too big for its type.
+    NumberOutOfRange,
+
+    /// An integer fit neither a `u64` nor an `i64`.
+    IntegerOutOfRange,
+
+    /// An escape named an invalid unicode code point.
+    InvalidUnicodeCodePoint,
+
+    /// A map key could not be written as a string.
+    KeyMustBeAString,
+
+    /// A float to be written was NaN or infinite.
+    FloatMustBeFinite,
+
+    /// An escape held a lone leading surrogate.
+    LoneLeadingSurrogateInHexEscape,
+
+    /// More input followed the value.
+    TrailingCharacters,
+
+    /// A list was closed by a different kind of bracket than opened it.
+    MismatchedBracket,
+
+    /// A list had a different number of elements than expected.
+    InvalidLength,
+
+    /// A hex escape ended early.
+    UnexpectedEndOfHexEscape,
+
+    /// Bytes written between bars were not valid base64.
+    InvalidBase64,
+
+    /// A datum label was referenced before it was defined.
+    UnknownDatumLabel,
+
+    /// Values were nested deeper than the recursion limit.
+    RecursionLimitExceeded,
+
+    /// A file included itself, directly or through other files.
+    IncludeCycle,
+
+    /// An include form did not name exactly one file.
+    InvalidInclude,
+
+    /// A variable to be substituted had no value.
+    MissingVariable,
+
+    /// An env form did not name exactly one variable.
+    InvalidEnvForm,
+
+    /// Expected a list of pairs.
+    ExpectedAlist,
+
+    /// Expected a list that does not end in a dotted tail.
+    ExpectedProperList,
+
+    /// A pattern was malformed.
+    InvalidPattern,
+
+    /// Rewriting did not reach a fixed point within the step limit.
+    StepLimitExceeded,
+
+    /// A journal record was not framed by its length and checksum.
+    InvalidRecordFrame,
+
+    /// A journal record did not match its checksum.
+    ChecksumMismatch,
+
+    /// A symbol or keyword could not be written without bars.
+    InvalidSymbol,
+
+    /// An entry of an alist read as a struct or map was not a list headed
+    /// by a key.
+    MalformedAlistEntry,
+
+    /// A comment was found where the options do not allow one.
+    CommentNotAllowed,
+
+    /// An alist read as a struct or map repeated a key.
+    DuplicateKey,
+}
+
 impl From<Error> for io::Error {
     /// Convert a `sexpr::Error` into an `io::Error`.
     ///
@@ -181,6 +478,8 @@ struct ErrorImpl {
     code: ErrorCode,
     line: usize,
     column: usize,
+    /// The segments of the path to the error, innermost first.
+    path: Vec<String>,
 }
 
 // Not public API. Should be pub(crate).
@@ -190,6 +489,14 @@ pub enum ErrorCode {
     /// Catchall for syntax error messages
     Message(String),
 
+    /// A value of the wrong type for a `Deserialize` implementation. Holds
+    /// what was found and what was expected.
+    InvalidType(String, String),
+
+    /// A value of the right type that a `Deserialize` implementation did
+    /// not accept. Holds what was found and what was expected.
+    InvalidValue(String, String),
+
     /// Some IO error occurred while serializing or deserializing.
     Io(io::Error),
 
@@ -235,23 +542,94 @@ pub enum ErrorCode {
     /// Number is bigger than the maximum value of its type.
     NumberOutOfRange,
 
+    /// Integer literal fits neither a `u64` nor an `i64`.
+    IntegerOutOfRange(String),
+
     /// Invalid unicode code point.
     InvalidUnicodeCodePoint,
 
     /// Object key is not a string.
     KeyMustBeAString,
 
+    /// Float to be serialized is NaN or infinite.
+    FloatMustBeFinite,
+
     /// Lone leading surrogate in hex escape.
     LoneLeadingSurrogateInHexEscape,
 
     /// S-expression has non-whitespace trailing characters after the value.
     TrailingCharacters,
 
+    /// A list was closed by a different kind of bracket than opened it.
+    MismatchedBracket,
+
+    /// A list had a different number of elements than expected. Holds the
+    /// expected and the actual number of elements.
+    InvalidLength(usize, usize),
+
     /// Unexpected end of hex excape.
     UnexpectedEndOfHexEscape,
 
+    /// Bytes between bars are not valid base64.
+    InvalidBase64,
+
+    /// A `#N#` reference to a datum label that no `#N=` has defined.
+    UnknownDatumLabel,
+
     /// Encountered nesting of S-expression maps and arrays more than 128 layers deep.
     RecursionLimitExceeded,
+
+    /// A file included itself, directly or through other files.
+    IncludeCycle(String),
+
+    /// An include form did not hold exactly one string naming the file.
+    InvalidInclude,
+
+    /// A variable to be substituted has no value.
+    MissingVariable(String),
+
+    /// An env form did not hold exactly one string naming the variable.
+    InvalidEnvForm,
+
+    /// Expected a list of pairs whose cars are atoms.
+    ExpectedAlist,
+
+    /// Expected a list, or a chain of pairs ending in nil or a list, rather
+    /// than an improper list or a value that is not a list.
+    ExpectedProperList,
+
+    /// A pattern is malformed, for example a list has more than one segment
+    /// variable.
+    InvalidPattern(String),
+
+    /// Rewriting did not reach a fixed point within the step limit.
+    StepLimitExceeded(usize),
+
+    /// A journal record was not framed by its length, its checksum and a
+    /// newline. Holds the byte offset of the record.
+    InvalidRecordFrame(u64),
+
+    /// A journal record that is not the last one did not match its
+    /// checksum. Holds the byte offset of the record.
+    ChecksumMismatch(u64),
+
+    /// A symbol or keyword name that would have to be written between bars
+    /// to read back, where bars were not allowed. Holds the name.
+    InvalidSymbol(String),
+
+    /// An entry of an alist read as a struct or map was not a list headed
+    /// by a key, such as `b` or `()` in `((a . 1) b ())`. Holds the index of the
+    /// entry.
+    MalformedAlistEntry(usize),
+
+    /// A comment where `DeserializerOptions::reject_comments` does not allow
+    /// one.
+    CommentNotAllowed,
+
+    /// A key repeated in an alist read as a struct or map, where
+    /// `DeserializerOptions::deny_duplicate_keys` does not allow it. Holds
+    /// the key.
+    DuplicateKey(String),
 }
 
 impl Error {
@@ -259,22 +637,70 @@ impl Error {
     #[doc(hidden)]
     pub fn syntax(code: ErrorCode, line: usize, column: usize) -> Self {
         Error {
-            err: Box::new(ErrorImpl { code, line, column }),
+            err: Box::new(ErrorImpl {
+                code,
+                line,
+                column,
+                path: Vec::new(),
+            }),
         }
     }
 
-    // Not public API. Should be pub(crate).
-    #[doc(hidden)]
+    /// Makes an error for a message at a position in the input, for custom
+    /// readers and formatters that find a problem of their own. Its kind is
+    /// `ErrorKind::Custom`.
+    ///
+    /// Lines and columns are one-based. Pass 0 for both if the error has no
+    /// position.
+    ///
+    /// ```rust
+    /// use sexpr::error::ErrorKind;
+    /// use sexpr::Error;
+    ///
+    /// let err = Error::custom_at("unexpected tab", 3, 7);
+    /// assert_eq!(err.kind(), ErrorKind::Custom);
+    /// assert_eq!((err.line(), err.column()), (3, 7));
+    /// assert_eq!(err.to_string(), "unexpected tab at line 3 column 7");
+    /// ```
+    pub fn custom_at<T: Display>(msg: T, line: usize, column: usize) -> Self {
+        Error::syntax(ErrorCode::Message(msg.to_string()), line, column)
+    }
+
+    /// Wraps a failure of the underlying stream, for custom readers and
+    /// formatters. Its kind is `ErrorKind::Io` and it has no position.
+    ///
+    /// ```rust
+    /// use std::io;
+    /// use sexpr::Error;
+    ///
+    /// let err = Error::io(io::Error::new(io::ErrorKind::BrokenPipe, "closed"));
+    /// assert!(err.is_io());
+    /// ```
     pub fn io(error: io::Error) -> Self {
         Error {
             err: Box::new(ErrorImpl {
                 code: ErrorCode::Io(error),
                 line: 0,
                 column: 0,
+                path: Vec::new(),
             }),
         }
     }
 
+    // Not public API. Should be pub(crate).
+    #[doc(hidden)]
+    pub fn shift_position(mut self, line: usize, column: usize) -> Self {
+        // Positions of errors raised while parsing a slice of a larger input
+        // start over at line 1, column 1 for the first byte of the slice.
+        if self.err.line != 0 {
+            if self.err.line == 1 {
+                self.err.column += column - 1;
+            }
+            self.err.line += line - 1;
+        }
+        self
+    }
+
     // Not public API. Should be pub(crate).
     #[doc(hidden)]
     pub fn fix_position<F>(self, f: F) -> Self
@@ -282,7 +708,10 @@ impl Error {
         F: FnOnce(ErrorCode) -> Error,
     {
         if self.err.line == 0 {
-            f(self.err.code)
+            let ErrorImpl { code, path, .. } = *self.err;
+            let mut fixed = f(code);
+            fixed.err.path = path;
+            fixed
         } else {
             self
         }
@@ -293,6 +722,12 @@ impl Display for ErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ErrorCode::Message(ref msg) => f.write_str(msg),
+            ErrorCode::InvalidType(ref found, ref expected) => {
+                write!(f, "invalid type: {}, expected {}", found, expected)
+            }
+            ErrorCode::InvalidValue(ref found, ref expected) => {
+                write!(f, "invalid value: {}, expected {}", found, expected)
+            }
             ErrorCode::Io(ref err) => Display::fmt(err, f),
             ErrorCode::EofWhileParsingList => f.write_str("EOF while parsing a list"),
             ErrorCode::EofWhileParsingAlist => f.write_str("EOF while parsing an alist"),
@@ -308,14 +743,48 @@ impl Display for ErrorCode {
             ErrorCode::InvalidEscape => f.write_str("invalid escape"),
             ErrorCode::InvalidNumber => f.write_str("invalid number"),
             ErrorCode::NumberOutOfRange => f.write_str("number out of range"),
+            ErrorCode::IntegerOutOfRange(ref literal) => {
+                write!(f, "integer `{}` out of range", literal)
+            }
             ErrorCode::InvalidUnicodeCodePoint => f.write_str("invalid unicode code point"),
             ErrorCode::KeyMustBeAString => f.write_str("key must be a string"),
+            ErrorCode::FloatMustBeFinite => f.write_str("float must be finite"),
             ErrorCode::LoneLeadingSurrogateInHexEscape => {
                 f.write_str("lone leading surrogate in hex escape")
             }
             ErrorCode::TrailingCharacters => f.write_str("trailing characters"),
+            ErrorCode::MismatchedBracket => f.write_str("mismatched bracket"),
+            ErrorCode::InvalidLength(expected, found) => {
+                write!(f, "expected {} elements, found {}", expected, found)
+            }
             ErrorCode::UnexpectedEndOfHexEscape => f.write_str("unexpected end of hex escape"),
+            ErrorCode::InvalidBase64 => f.write_str("invalid base64"),
+            ErrorCode::UnknownDatumLabel => f.write_str("unknown datum label"),
             ErrorCode::RecursionLimitExceeded => f.write_str("recursion limit exceeded"),
+            ErrorCode::IncludeCycle(ref file) => write!(f, "`{}` includes itself", file),
+            ErrorCode::InvalidInclude => f.write_str("include form must name one file"),
+            ErrorCode::MissingVariable(ref name) => write!(f, "variable `{}` is not set", name),
+            ErrorCode::InvalidEnvForm => f.write_str("env form must name one variable"),
+            ErrorCode::ExpectedAlist => f.write_str("expected an alist"),
+            ErrorCode::ExpectedProperList => f.write_str("expected a proper list"),
+            ErrorCode::InvalidPattern(ref msg) => write!(f, "invalid pattern: {}", msg),
+            ErrorCode::StepLimitExceeded(steps) => {
+                write!(f, "rewriting did not finish within {} steps", steps)
+            }
+            ErrorCode::InvalidRecordFrame(offset) => {
+                write!(f, "invalid journal record at byte {}", offset)
+            }
+            ErrorCode::ChecksumMismatch(offset) => {
+                write!(f, "checksum mismatch in journal record at byte {}", offset)
+            }
+            ErrorCode::InvalidSymbol(ref name) => {
+                write!(f, "`{}` is not a valid bare symbol", name)
+            }
+            ErrorCode::MalformedAlistEntry(index) => {
+                write!(f, "alist entry {} is not a (key . value) pair", index)
+            }
+            ErrorCode::CommentNotAllowed => f.write_str("comments are not allowed"),
+            ErrorCode::DuplicateKey(ref key) => write!(f, "duplicate key `{}`", key),
         }
     }
 }
@@ -369,24 +838,22 @@ impl Debug for Error {
 
 impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Error {
-        Error {
-            err: Box::new(ErrorImpl {
-                code: ErrorCode::Message(msg.to_string()),
-                line: 0,
-                column: 0,
-            }),
-        }
+        Error::syntax(ErrorCode::Message(msg.to_string()), 0, 0)
+    }
+
+    fn invalid_type(unexp: de::Unexpected, exp: &dyn de::Expected) -> Error {
+        let code = ErrorCode::InvalidType(unexp.to_string(), exp.to_string());
+        Error::syntax(code, 0, 0)
+    }
+
+    fn invalid_value(unexp: de::Unexpected, exp: &dyn de::Expected) -> Error {
+        let code = ErrorCode::InvalidValue(unexp.to_string(), exp.to_string());
+        Error::syntax(code, 0, 0)
     }
 }
 
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Error {
-        Error {
-            err: Box::new(ErrorImpl {
-                code: ErrorCode::Message(msg.to_string()),
-                line: 0,
-                column: 0,
-            }),
-        }
+        Error::syntax(ErrorCode::Message(msg.to_string()), 0, 0)
     }
 }
@@ -0,0 +1,189 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Push-based parsing of S-expressions that arrive in pieces.
+
+use serde::de::Deserialize;
+
+use crate::de::{Deserializer, DeserializerOptions};
+use crate::error::{Error, ErrorCode, Result};
+use crate::read::{Scanner, Step};
+use crate::sexp::Sexp;
+
+/// What `IncrementalParser::next_value` found in the bytes fed so far.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Parsed {
+    /// The bytes fed so far do not hold another complete value.
+    NeedMoreData,
+
+    /// A complete top-level value.
+    Value(Sexp),
+}
+
+/// A parser that is fed a stream of top-level values chunk by chunk, as the
+/// chunks arrive from a socket or pipe.
+///
+/// Bytes are scanned once, as they are fed, to find where each top-level
+/// value ends; a value is only parsed once all of it has arrived. This
+/// avoids the quadratic cost of parsing the whole buffer again after every
+/// chunk. A list or string ends with its closing bracket or quote, but an
+/// atom such as `42` or `foo` only ends with the next delimiter, or with the
+/// input at `finish`.
+///
+/// ```rust
+/// use sexpr::de::{IncrementalParser, Parsed};
+/// use sexpr::Sexp;
+///
+/// let mut parser = IncrementalParser::new();
+/// parser.feed(b"(1 2");
+/// assert_eq!(parser.next_value().unwrap(), Parsed::NeedMoreData);
+///
+/// parser.feed(b")\n4");
+/// assert_eq!(
+///     parser.next_value().unwrap(),
+///     Parsed::Value(Sexp::List(vec![Sexp::from(1), Sexp::from(2)]))
+/// );
+/// assert_eq!(parser.next_value().unwrap(), Parsed::NeedMoreData);
+///
+/// parser.feed(b"2 ");
+/// assert_eq!(parser.next_value().unwrap(), Parsed::Value(Sexp::from(42)));
+/// ```
+///
+/// Once `next_value` has returned an error the parser should be discarded.
+pub struct IncrementalParser {
+    options: DeserializerOptions,
+    buf: Vec<u8>,
+    /// Index in `buf` of the next byte to scan.
+    scan: usize,
+    /// Index in `buf` of the first byte of the value being scanned, if the
+    /// first byte has arrived.
+    start: Option<usize>,
+    scanner: Scanner,
+    /// Line and column of the next byte to scan.
+    line: usize,
+    column: usize,
+    /// Line and column of the first byte of the value being scanned.
+    start_line: usize,
+    start_column: usize,
+}
+
+impl IncrementalParser {
+    /// Creates a parser with the default `DeserializerOptions`.
+    pub fn new() -> Self {
+        IncrementalParser::with_options(DeserializerOptions::default())
+    }
+
+    /// Creates a parser that parses each value with the given options.
+    pub fn with_options(options: DeserializerOptions) -> Self {
+        IncrementalParser {
+            options,
+            buf: Vec::new(),
+            scan: 0,
+            start: None,
+            scanner: Scanner::new(),
+            line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+        }
+    }
+
+    /// Appends the next chunk of input.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Returns the next complete value in the input fed so far, or
+    /// `Parsed::NeedMoreData` if there is none yet.
+    pub fn next_value(&mut self) -> Result<Parsed> {
+        while self.scan < self.buf.len() {
+            let rest = &self.buf[self.scan..];
+            if self.scanner.needs_more(rest, &self.options) {
+                break;
+            }
+            let step = self.scanner.step(rest, &self.options);
+            if step == Step::EndBefore {
+                return self.parse_value().map(Parsed::Value);
+            }
+
+            let (line, column) = (self.line, self.column);
+            if self.buf[self.scan] == b'\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+            self.scan += 1;
+
+            match step {
+                Step::Start | Step::Unmatched => {
+                    self.start = Some(self.scan - 1);
+                    self.start_line = line;
+                    self.start_column = column;
+                    if step == Step::Unmatched {
+                        // Parsing the stray bracket reports it.
+                        return self.parse_value().map(Parsed::Value);
+                    }
+                }
+                Step::End => return self.parse_value().map(Parsed::Value),
+                Step::Inside | Step::EndBefore => {}
+            }
+        }
+
+        self.compact();
+        Ok(Parsed::NeedMoreData)
+    }
+
+    /// Checks that the input ended between values rather than in the middle
+    /// of one. Call this once the stream has closed. Returns the last value
+    /// if it was an atom that nothing followed, such as the `42` of `(a) 42`.
+    pub fn finish(&mut self) -> Result<Option<Sexp>> {
+        if self.start.is_none() {
+            return Ok(None);
+        }
+        if !self.scanner.in_top_level_atom() {
+            let code = if self.scanner.depth() > 0 {
+                ErrorCode::EofWhileParsingList
+            } else {
+                ErrorCode::EofWhileParsingString
+            };
+            return Err(Error::syntax(code, self.line, self.column));
+        }
+        self.scan = self.buf.len();
+        self.scanner = Scanner::new();
+        self.parse_value().map(Some)
+    }
+
+    /// Parses the complete value that ends just before `self.scan`.
+    fn parse_value(&mut self) -> Result<Sexp> {
+        let start = self.start.take().unwrap_or(0);
+        let slice = &self.buf[start..self.scan];
+        let mut de = Deserializer::from_slice_with_options(slice, self.options.clone());
+        let value = Sexp::deserialize(&mut de).and_then(|value| de.end().map(|()| value));
+        self.compact();
+        value.map_err(|err| err.shift_position(self.start_line, self.start_column))
+    }
+
+    /// Drops the bytes before the value being scanned once they make up at
+    /// least half of the buffer, so that a long value arriving in many small
+    /// chunks is not copied once per chunk.
+    fn compact(&mut self) {
+        let consumed = self.start.unwrap_or(self.scan);
+        if consumed > 0 && consumed >= self.buf.len() / 2 {
+            self.buf.drain(..consumed);
+            self.scan -= consumed;
+            self.start = self.start.map(|start| start - consumed);
+        }
+    }
+}
+
+impl Default for IncrementalParser {
+    fn default() -> Self {
+        IncrementalParser::new()
+    }
+}
@@ -25,6 +25,10 @@ pub struct LineColIterator<I> {
     /// computation of the current line, column, and byte offset while only
     /// updating one of the counters in `next()` in the common case.
     start_of_line: usize,
+
+    /// Whether the last character was a CR, so that an LF right after it
+    /// ends the same line.
+    after_cr: bool,
 }
 
 impl<I> LineColIterator<I>
@@ -37,6 +41,7 @@ where
             line: 1,
             col: 0,
             start_of_line: 0,
+            after_cr: false,
         }
     }
 
@@ -60,13 +65,21 @@ where
     type Item = io::Result<u8>;
 
     fn next(&mut self) -> Option<io::Result<u8>> {
+        let after_cr = self.after_cr;
+        self.after_cr = false;
         match self.iter.next() {
             None => None,
-            Some(Ok(b'\n')) => {
+            // The LF of a CR LF pair, whose line the CR already ended.
+            Some(Ok(b'\n')) if after_cr => {
+                self.start_of_line += 1;
+                Some(Ok(b'\n'))
+            }
+            Some(Ok(c @ b'\n')) | Some(Ok(c @ b'\r')) => {
                 self.start_of_line += self.col + 1;
                 self.line += 1;
                 self.col = 0;
-                Some(Ok(b'\n'))
+                self.after_cr = c == b'\r';
+                Some(Ok(c))
             }
             Some(Ok(c)) => {
                 self.col += 1;
@@ -0,0 +1,289 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Append-only log files of S-expression records.
+//!
+//! A `JournalWriter` appends each record on a line of its own, framed by the
+//! length of its text in bytes and the CRC-32 of the text in hex:
+//!
+//! ```text
+//! 7 c9f74b67 (1 "a")
+//! ```
+//!
+//! A `JournalReader` checks each record against its frame. If the program
+//! writing the log died partway through a record, the record is cut short or
+//! fails its checksum. Such a torn record at the end of the log is not an
+//! error: reading stops before it, and `valid_len` tells how much of the log
+//! to keep before appending to it again. Damage anywhere else is an error.
+//!
+//! ```rust
+//! use sexpr::journal::{JournalReader, JournalWriter};
+//!
+//! let mut journal = JournalWriter::new(Vec::new());
+//! journal.append(&(1, "a")).unwrap();
+//! journal.append(&(2, "b")).unwrap();
+//! let mut log = journal.into_inner();
+//!
+//! // The writer dies in the middle of the third record.
+//! log.extend_from_slice(b"7 1f2e");
+//!
+//! let mut reader = JournalReader::new(&log[..]);
+//! assert_eq!(reader.read_record::<(u8, String)>().unwrap(), Some((1, "a".to_owned())));
+//! assert_eq!(reader.read_record::<(u8, String)>().unwrap(), Some((2, "b".to_owned())));
+//! assert_eq!(reader.read_record::<(u8, String)>().unwrap(), None);
+//! assert!(reader.is_torn());
+//! assert_eq!(reader.valid_len(), log.len() as u64 - 6);
+//! ```
+
+use std::io::{self, BufRead, Read};
+
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+use crate::error::{Error, ErrorCode, Result};
+
+/// Appends records to a journal.
+///
+/// Each record is handed to the underlying writer in one `write_all` call,
+/// so a writer that is a `File` opened for appending only ever tears the
+/// last record. Nothing is synced to disk by the journal; call
+/// `File::sync_data` through `get_ref` when a record must be durable.
+pub struct JournalWriter<W> {
+    writer: W,
+    frame: Vec<u8>,
+}
+
+impl<W: io::Write> JournalWriter<W> {
+    /// Creates a journal that appends to `writer`.
+    pub fn new(writer: W) -> Self {
+        JournalWriter {
+            writer,
+            frame: Vec::new(),
+        }
+    }
+
+    /// Serializes `value` and appends it as a record.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `value` cannot be serialized, in which case nothing is
+    /// written, or if the record cannot be written.
+    pub fn append<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let text = crate::ser::to_vec(value)?;
+        self.frame.clear();
+        self.frame
+            .extend_from_slice(format!("{} {:08x} ", text.len(), crc32(&text)).as_bytes());
+        self.frame.extend_from_slice(&text);
+        self.frame.push(b'\n');
+        self.writer.write_all(&self.frame).map_err(Error::io)
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().map_err(Error::io)
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Unwraps the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the records of a journal in the order they were appended.
+pub struct JournalReader<R> {
+    reader: R,
+    /// The length of the records read intact so far.
+    offset: u64,
+    torn: bool,
+    text: Vec<u8>,
+}
+
+/// How reading the frame of a record ended.
+enum Frame {
+    /// The record's text is in `text`.
+    Record,
+    /// The journal ended cleanly.
+    End,
+    /// The journal ended in a torn record.
+    Torn,
+}
+
+impl<R: BufRead> JournalReader<R> {
+    /// Creates a reader of the journal in `reader`, which is read from the
+    /// start of the first record. A `File` should be wrapped in a
+    /// `BufReader`.
+    pub fn new(reader: R) -> Self {
+        JournalReader {
+            reader,
+            offset: 0,
+            torn: false,
+            text: Vec::new(),
+        }
+    }
+
+    /// Reads the next record as a `T`. Returns `None` at the end of the
+    /// journal, or at a torn record that ends it, after which `is_torn`
+    /// returns true.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a record is framed wrongly, or fails its checksum but is
+    /// followed by more of the journal. Fails if an intact record cannot be
+    /// deserialized as a `T`; the following records can still be read.
+    pub fn read_record<T>(&mut self) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        match self.read_frame()? {
+            Frame::Record => crate::de::from_slice(&self.text).map(Some),
+            Frame::End => Ok(None),
+            Frame::Torn => {
+                self.torn = true;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Returns true if the journal ended in a torn record.
+    pub fn is_torn(&self) -> bool {
+        self.torn
+    }
+
+    /// The length in bytes of the records read so far, not counting a torn
+    /// record. Truncating a torn journal to this length before appending to
+    /// it again drops the torn record.
+    pub fn valid_len(&self) -> u64 {
+        self.offset
+    }
+
+    /// Unwraps the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_frame(&mut self) -> Result<Frame> {
+        if self.torn || self.at_end()? {
+            return Ok(Frame::End);
+        }
+
+        let mut header = Vec::new();
+        // A `u64` length has at most 20 digits.
+        let len = match self.read_field(&mut header, 20)? {
+            Some(field) => field,
+            None => return Ok(Frame::Torn),
+        };
+        let len = len.parse::<u64>().map_err(|_| self.frame_error())?;
+        let crc = match self.read_field(&mut header, 8)? {
+            Some(field) if field.len() == 8 => field,
+            Some(_) => return Err(self.frame_error()),
+            None => return Ok(Frame::Torn),
+        };
+        let crc = u32::from_str_radix(crc, 16).map_err(|_| self.frame_error())?;
+
+        self.text.clear();
+        let read = (&mut self.reader)
+            .take(len)
+            .read_to_end(&mut self.text)
+            .map_err(Error::io)?;
+        if (read as u64) < len {
+            return Ok(Frame::Torn);
+        }
+        let mut newline = [0];
+        if self.reader.read(&mut newline).map_err(Error::io)? == 0 {
+            return Ok(Frame::Torn);
+        }
+        if newline[0] != b'\n' {
+            return Err(self.frame_error());
+        }
+
+        if crc32(&self.text) != crc {
+            return if self.at_end()? {
+                Ok(Frame::Torn)
+            } else {
+                Err(Error::syntax(
+                    ErrorCode::ChecksumMismatch(self.offset),
+                    0,
+                    0,
+                ))
+            };
+        }
+        self.offset += header.len() as u64 + len + 1;
+        Ok(Frame::Record)
+    }
+
+    /// Reads a header field of up to `max` bytes and the space after it onto
+    /// the end of `header`, returning the field. Returns `None` if the
+    /// journal ends first.
+    fn read_field<'a>(&mut self, header: &'a mut Vec<u8>, max: usize) -> Result<Option<&'a str>> {
+        let start = header.len();
+        (&mut self.reader)
+            .take(max as u64 + 1)
+            .read_until(b' ', header)
+            .map_err(Error::io)?;
+        let field = &header[start..];
+        match field.split_last() {
+            // Both fields are hex digits if the frame is valid, which also
+            // keeps out the sign that `parse` would accept.
+            Some((b' ', field)) if !field.is_empty() && field.iter().all(u8::is_ascii_hexdigit) => {
+                Ok(std::str::from_utf8(field).ok())
+            }
+            _ if field.len() <= max && self.at_end()? => Ok(None),
+            _ => Err(self.frame_error()),
+        }
+    }
+
+    fn at_end(&mut self) -> Result<bool> {
+        Ok(self.reader.fill_buf().map_err(Error::io)?.is_empty())
+    }
+
+    fn frame_error(&self) -> Error {
+        Error::syntax(ErrorCode::InvalidRecordFrame(self.offset), 0, 0)
+    }
+}
+
+/// The CRC-32 of `bytes`, as used by gzip and zlib.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &b| {
+        CRC32_TABLE[((crc ^ u32::from(b)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
@@ -212,24 +212,88 @@
 //! #     print_an_address().unwrap();
 //! # }
 //! ```
+//!
+//! # Optional features
+//!
+//! - `trace`: emit [`tracing`](https://docs.rs/tracing) spans around each
+//!   top-level parse and serialize call, trace events as lists start, and
+//!   debug events with the position and kind of each error.
+//! - `rayon`: parse many inputs in parallel.
+//! - `memmap2`: parse a memory-mapped file.
+//! - `gzip`, `zstd`: read and write compressed S-expression text with
+//!   `de::from_reader_gzip`, `ser::to_writer_zstd` and the like.
+//! - `config`: `config::SexpFormat`, which reads S-expression files as a
+//!   source for the [`config`](https://docs.rs/config) crate.
+//! - `capi`: a C interface in the `capi` module, for bindings in other
+//!   languages, with a generated `include/sexpr.h` header.
+//! - `cli`: build `sexp`, a command line tool that formats, validates and
+//!   queries S-expression files and converts them to and from JSON.
+//! - `arbitrary_precision`: keep the text of number literals that no `u64`,
+//!   `i64` or `f64` holds exactly, such as big integers and long decimals,
+//!   in `Number`, so that they print as they were read.
+#[doc(inline)]
+pub use self::bytes::{Base64Bytes, HexBytes};
+#[doc(inline)]
+pub use self::complete::{is_complete, Completeness};
 #[doc(inline)]
-pub use self::de::{from_reader, from_slice, from_str, Deserializer, StreamDeserializer};
+pub use self::de::{
+    from_reader, from_slice, from_str, from_str_document, from_str_prefix, from_str_spanned,
+    from_str_with_stats, split_top_level, validate_syntax, validate_syntax_reader, Deserializer,
+    ParseStats, StreamDeserializer,
+};
 #[doc(inline)]
 pub use self::error::{Error, Result};
 #[doc(inline)]
-pub use self::sexp::{from_value, to_value, Number, Sexp};
+pub use self::project::{project, Projected};
+#[doc(inline)]
+pub use self::raw::RawSexp;
+#[doc(inline)]
+pub use self::reformat::{reformat, FormatStyle};
 #[doc(inline)]
-pub use crate::ser::{to_string, Serializer};
+pub use self::rest::Rest;
+#[doc(inline)]
+pub use self::sexp::{
+    from_alist, from_value, gensym, to_alist, to_value, to_value_with_options, Number, Sexp,
+    ToValueOptions,
+};
+#[doc(inline)]
+pub use self::text_eq::{text_eq, text_eq_with_options, TextEqOptions};
+#[doc(inline)]
+pub use crate::ser::{to_string, to_string_document, Serializer};
+
+// Values and errors are commonly handed between threads, so fail to compile
+// if any of them stops being `Send + Sync`.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Sexp>();
+    assert_send_sync::<sexp::Atom>();
+    assert_send_sync::<Number>();
+    assert_send_sync::<Error>();
+};
 
 #[macro_use]
 mod macros;
 
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "config")]
+pub mod config;
 pub mod de;
 pub mod error;
+pub mod journal;
+pub mod loader;
 pub mod ser;
 pub mod sexp;
 
 mod atom;
+mod bytes;
+mod complete;
+mod incremental;
 mod iter;
 mod number;
+mod project;
+mod raw;
 mod read;
+mod reformat;
+mod rest;
+mod text_eq;
@@ -0,0 +1,515 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Loading S-expression files that include other files.
+//!
+//! A `Loader` parses a file and replaces every include form in it, by default
+//! `(include "other.scm")`, with the top-level values of the named file. The
+//! files are found through a `Resolver`, so they can come from the file
+//! system, from memory or from anywhere else. A `Substitution` can then fill
+//! in placeholders for environment variables in the loaded values.
+//!
+//! ```rust
+//! use sexpr::loader::{Loader, MemoryResolver};
+//!
+//! let mut files = MemoryResolver::new();
+//! files.insert("main.scm", "((name \"app\") (include \"ports.scm\"))");
+//! files.insert("ports.scm", "(port 80) (port 443)");
+//!
+//! let forms = Loader::new(files).load("main.scm").unwrap();
+//! assert_eq!(
+//!     sexpr::to_string(&forms).unwrap(),
+//!     "(((name \"app\") (port 80) (port 443)))"
+//! );
+//! ```
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::result;
+
+use crate::atom::Atom;
+use crate::de::Deserializer;
+use crate::error::{Error, ErrorCode};
+use crate::sexp::Sexp;
+
+/// Finds and reads the files named by include forms.
+pub trait Resolver {
+    /// Returns the key of the file that `name` refers to when it is included
+    /// from the file with key `from`, or loaded directly if `from` is `None`.
+    ///
+    /// Two names refer to the same file exactly when they resolve to the same
+    /// key, which is how include cycles are detected.
+    fn resolve(&self, name: &str, from: Option<&str>) -> io::Result<String>;
+
+    /// Returns the contents of the file with the given key.
+    fn load(&self, key: &str) -> io::Result<String>;
+}
+
+/// Resolves includes to files on disk. A relative name is looked up next to
+/// the file that includes it, or in the base directory for the file loaded
+/// first.
+#[derive(Clone, Debug, Default)]
+pub struct FileResolver {
+    base: PathBuf,
+}
+
+impl FileResolver {
+    /// Creates a resolver whose base directory is the current directory.
+    pub fn new() -> Self {
+        FileResolver::default()
+    }
+
+    /// Sets the directory relative names of the file loaded first are looked
+    /// up in.
+    pub fn with_base_dir<P: Into<PathBuf>>(mut self, base: P) -> Self {
+        self.base = base.into();
+        self
+    }
+}
+
+impl Resolver for FileResolver {
+    fn resolve(&self, name: &str, from: Option<&str>) -> io::Result<String> {
+        let dir = match from {
+            Some(from) => Path::new(from).parent().unwrap_or_else(|| Path::new("")),
+            None => &self.base,
+        };
+        let path = fs::canonicalize(dir.join(name))?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    fn load(&self, key: &str) -> io::Result<String> {
+        fs::read_to_string(key)
+    }
+}
+
+/// Resolves includes to files held in memory, by their exact name.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryResolver {
+    files: HashMap<String, String>,
+}
+
+impl MemoryResolver {
+    /// Creates a resolver holding no files.
+    pub fn new() -> Self {
+        MemoryResolver::default()
+    }
+
+    /// Adds a file, replacing any earlier file with the same name.
+    pub fn insert<N, C>(&mut self, name: N, contents: C)
+    where
+        N: Into<String>,
+        C: Into<String>,
+    {
+        self.files.insert(name.into(), contents.into());
+    }
+}
+
+impl Resolver for MemoryResolver {
+    fn resolve(&self, name: &str, _from: Option<&str>) -> io::Result<String> {
+        if self.files.contains_key(name) {
+            Ok(name.to_owned())
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotFound, "no such file"))
+        }
+    }
+
+    fn load(&self, key: &str) -> io::Result<String> {
+        self.files
+            .get(key)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file"))
+    }
+}
+
+/// Parses files and splices the files they include into them.
+pub struct Loader<R> {
+    resolver: R,
+    include_form: String,
+}
+
+impl<R: Resolver> Loader<R> {
+    /// Creates a loader that finds files through `resolver` and recognizes
+    /// `(include "name")` forms.
+    pub fn new(resolver: R) -> Self {
+        Loader {
+            resolver,
+            include_form: String::from("include"),
+        }
+    }
+
+    /// Sets the symbol that starts an include form, in place of `include`.
+    pub fn with_include_form<S: Into<String>>(mut self, symbol: S) -> Self {
+        self.include_form = symbol.into();
+        self
+    }
+
+    /// Loads the file `name` and returns its top-level values, with every
+    /// include form replaced by the top-level values of the file it names.
+    ///
+    /// Every file is read as a stream of top-level lists. An include form can
+    /// appear at the top level or as an element of any list, and must hold a
+    /// single string, the name of the file to include.
+    pub fn load(&self, name: &str) -> result::Result<Vec<Sexp>, LoadError> {
+        let mut chain = Vec::new();
+        let key = self
+            .resolver
+            .resolve(name, None)
+            .map_err(|err| LoadError::new(cannot_include(name, err), &chain))?;
+        self.load_file(key, &mut chain)
+    }
+
+    /// Loads the file with key `key`. `chain` holds the keys of the files
+    /// that include it, outermost first.
+    fn load_file(
+        &self,
+        key: String,
+        chain: &mut Vec<String>,
+    ) -> result::Result<Vec<Sexp>, LoadError> {
+        chain.push(key);
+        let result = self.parse_file(chain);
+        chain.pop();
+        result
+    }
+
+    fn parse_file(&self, chain: &mut Vec<String>) -> result::Result<Vec<Sexp>, LoadError> {
+        let key = &chain[chain.len() - 1];
+        let contents = self
+            .resolver
+            .load(key)
+            .map_err(|err| LoadError::new(Error::io(err), chain))?;
+        let forms = Deserializer::from_str(&contents)
+            .into_iter::<Sexp>()
+            .collect::<crate::Result<Vec<Sexp>>>()
+            .map_err(|err| LoadError::new(err, chain))?;
+
+        let mut expanded = Vec::with_capacity(forms.len());
+        for form in forms {
+            self.expand(form, chain, &mut expanded)?;
+        }
+        Ok(expanded)
+    }
+
+    /// Pushes `form` onto `out`, with its include forms expanded. An include
+    /// form itself expands to any number of values.
+    fn expand(
+        &self,
+        form: Sexp,
+        chain: &mut Vec<String>,
+        out: &mut Vec<Sexp>,
+    ) -> result::Result<(), LoadError> {
+        let elems = match form {
+            Sexp::List(elems) => elems,
+            other => {
+                out.push(other);
+                return Ok(());
+            }
+        };
+
+        if let Some(name) = self.include_target(&elems) {
+            let name = name.map_err(|err| LoadError::new(err, chain))?;
+            let from = &chain[chain.len() - 1];
+            let key = self
+                .resolver
+                .resolve(name, Some(from))
+                .map_err(|err| LoadError::new(cannot_include(name, err), chain))?;
+            if chain.contains(&key) {
+                return Err(LoadError::new(
+                    Error::syntax(ErrorCode::IncludeCycle(key), 0, 0),
+                    chain,
+                ));
+            }
+            out.extend(self.load_file(key, chain)?);
+            return Ok(());
+        }
+
+        let mut expanded = Vec::with_capacity(elems.len());
+        for elem in elems {
+            self.expand(elem, chain, &mut expanded)?;
+        }
+        out.push(Sexp::List(expanded));
+        Ok(())
+    }
+
+    /// Returns the name of the file to include if `elems` are the elements
+    /// of an include form.
+    fn include_target<'a>(&self, elems: &'a [Sexp]) -> Option<crate::Result<&'a str>> {
+        match elems.first() {
+            Some(Sexp::Atom(head)) if head.as_symbol() == Some(self.include_form.as_str()) => {}
+            _ => return None,
+        }
+        let name = match elems {
+            [_, Sexp::Atom(name)] => name.as_string(),
+            _ => None,
+        };
+        Some(name.ok_or_else(|| Error::syntax(ErrorCode::InvalidInclude, 0, 0)))
+    }
+}
+
+fn cannot_include(name: &str, err: io::Error) -> Error {
+    Error::io(io::Error::new(
+        err.kind(),
+        format!("cannot load `{}`: {}", name, err),
+    ))
+}
+
+/// An error raised while loading a file or one of the files it includes.
+#[derive(Debug)]
+pub struct LoadError {
+    error: Error,
+    chain: Vec<String>,
+}
+
+impl LoadError {
+    fn new(error: Error, chain: &[String]) -> Self {
+        LoadError {
+            error,
+            chain: chain.to_vec(),
+        }
+    }
+
+    /// The underlying error. Its line and column, if any, are those in the
+    /// last file of the include chain.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// The keys of the files being loaded when the error was raised, from the
+    /// file loaded first to the one the error was found in. Empty if the
+    /// first file could not be found.
+    pub fn include_chain(&self) -> &[String] {
+        &self.chain
+    }
+
+    /// Consumes the `LoadError` and returns the underlying error.
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.error, f)?;
+        let mut files = self.chain.iter().rev();
+        if let Some(file) = files.next() {
+            write!(f, " in `{}`", file)?;
+        }
+        for file in files {
+            write!(f, ", included from `{}`", file)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Looks up the values of variables for a `Substitution`.
+///
+/// Implemented for maps from names to values and for closures, and by
+/// `EnvVariables` for the environment of the process.
+pub trait Variables {
+    /// Returns the value of the variable `name`, or `None` if it has none.
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+impl Variables for HashMap<String, String> {
+    fn get(&self, name: &str) -> Option<String> {
+        HashMap::get(self, name).cloned()
+    }
+}
+
+impl<F> Variables for F
+where
+    F: Fn(&str) -> Option<String>,
+{
+    fn get(&self, name: &str) -> Option<String> {
+        self(name)
+    }
+}
+
+/// Looks variables up in the environment of the process.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EnvVariables;
+
+impl Variables for EnvVariables {
+    fn get(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+/// What a `Substitution` does with a variable that has no value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingVariables {
+    /// Fail with an error.
+    #[default]
+    Error,
+
+    /// Substitute an empty string.
+    Empty,
+
+    /// Leave the placeholder as it was written.
+    Keep,
+}
+
+/// A pass over parsed values that fills in placeholders with the values of
+/// variables.
+///
+/// Two kinds of placeholder are replaced:
+///
+/// - an env form, by default `(env "NAME")`, which becomes a string holding
+///   the value of the variable;
+/// - `${NAME}` anywhere in the text of a symbol, keyword or string, which is
+///   replaced by the value in place.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use sexpr::loader::Substitution;
+///
+/// let mut vars = HashMap::new();
+/// vars.insert("HOME".to_owned(), "/home/me".to_owned());
+///
+/// let config = sexpr::from_str("((home (env \"HOME\")) (log \"${HOME}/log\"))").unwrap();
+/// let config = Substitution::new(vars).apply(config).unwrap();
+/// assert_eq!(
+///     sexpr::to_string(&config).unwrap(),
+///     "((home \"/home/me\") (log \"/home/me/log\"))"
+/// );
+/// ```
+pub struct Substitution<V> {
+    variables: V,
+    missing: MissingVariables,
+    env_form: String,
+}
+
+impl<V: Variables> Substitution<V> {
+    /// Creates a pass that looks variables up in `variables` and fails on a
+    /// variable with no value.
+    pub fn new(variables: V) -> Self {
+        Substitution {
+            variables,
+            missing: MissingVariables::Error,
+            env_form: String::from("env"),
+        }
+    }
+
+    /// Sets what the pass does with a variable that has no value.
+    pub fn with_missing_variables(mut self, missing: MissingVariables) -> Self {
+        self.missing = missing;
+        self
+    }
+
+    /// Sets the symbol that starts an env form, in place of `env`.
+    pub fn with_env_form<S: Into<String>>(mut self, symbol: S) -> Self {
+        self.env_form = symbol.into();
+        self
+    }
+
+    /// Returns `sexp` with every placeholder in it replaced.
+    pub fn apply(&self, sexp: Sexp) -> crate::Result<Sexp> {
+        match sexp {
+            Sexp::Atom(atom) => self.apply_atom(atom).map(Sexp::Atom),
+            Sexp::List(elems) => {
+                if let Some(name) = self.env_target(&elems) {
+                    let name = name?;
+                    return Ok(match self.lookup(name)? {
+                        Some(value) => Sexp::Atom(Atom::string(value)),
+                        None => Sexp::List(elems),
+                    });
+                }
+                elems
+                    .into_iter()
+                    .map(|elem| self.apply(elem))
+                    .collect::<crate::Result<_>>()
+                    .map(Sexp::List)
+            }
+            Sexp::Pair(car, cdr) => {
+                let cell = |cell: Option<Box<Sexp>>| -> crate::Result<Option<Box<Sexp>>> {
+                    match cell {
+                        Some(sexp) => Ok(Some(Box::new(self.apply(*sexp)?))),
+                        None => Ok(None),
+                    }
+                };
+                Ok(Sexp::Pair(cell(car)?, cell(cdr)?))
+            }
+            other => Ok(other),
+        }
+    }
+
+    /// Applies the pass to each of `forms`, as returned by `Loader::load`.
+    pub fn apply_all(&self, forms: Vec<Sexp>) -> crate::Result<Vec<Sexp>> {
+        forms.into_iter().map(|form| self.apply(form)).collect()
+    }
+
+    fn apply_atom(&self, atom: Atom) -> crate::Result<Atom> {
+        if !atom.as_str().contains("${") {
+            return Ok(atom);
+        }
+        let text = self.interpolate(atom.as_str())?;
+        Ok(match atom {
+            Atom::Symbol(_) => Atom::symbol(text),
+            Atom::Keyword(_) => Atom::keyword(text),
+            Atom::String(_) => Atom::string(text),
+        })
+    }
+
+    /// Replaces every `${NAME}` in `text`. A `${` without a closing `}` is
+    /// left as it is.
+    fn interpolate(&self, mut text: &str) -> crate::Result<String> {
+        let mut out = String::with_capacity(text.len());
+        while let Some(start) = text.find("${") {
+            let end = match text[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            out.push_str(&text[..start]);
+            match self.lookup(&text[start + 2..end])? {
+                Some(value) => out.push_str(&value),
+                None => out.push_str(&text[start..=end]),
+            }
+            text = &text[end + 1..];
+        }
+        out.push_str(text);
+        Ok(out)
+    }
+
+    /// Returns the value to substitute for `name`, or `None` to keep the
+    /// placeholder.
+    fn lookup(&self, name: &str) -> crate::Result<Option<String>> {
+        match (self.variables.get(name), self.missing) {
+            (Some(value), _) => Ok(Some(value)),
+            (None, MissingVariables::Error) => Err(Error::syntax(
+                ErrorCode::MissingVariable(name.to_owned()),
+                0,
+                0,
+            )),
+            (None, MissingVariables::Empty) => Ok(Some(String::new())),
+            (None, MissingVariables::Keep) => Ok(None),
+        }
+    }
+
+    /// Returns the name of the variable if `elems` are the elements of an
+    /// env form.
+    fn env_target<'a>(&self, elems: &'a [Sexp]) -> Option<crate::Result<&'a str>> {
+        match elems.first() {
+            Some(Sexp::Atom(head)) if head.as_symbol() == Some(self.env_form.as_str()) => {}
+            _ => return None,
+        }
+        let name = match elems {
+            [_, Sexp::Atom(name)] => name.as_string(),
+            _ => None,
+        };
+        Some(name.ok_or_else(|| Error::syntax(ErrorCode::InvalidEnvForm, 0, 0)))
+    }
+}
@@ -26,3 +26,97 @@ macro_rules! sexp {
         $crate::from_str(stringify!($t)).unwrap();
     };
 }
+
+/// Construct a `sexpr::Sexp::List` from a comma separated list of expressions.
+///
+/// Each element may be anything that converts into a `Sexp`.
+///
+/// ```rust
+/// # use sexpr::{list, Sexp};
+/// #
+/// # fn main() {
+/// let width = 10;
+/// let value: Sexp = list![1, "rectangle", width * 2, list![true, false]];
+/// assert_eq!(value.to_string(), "(1 rectangle 20 (#t #f))");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! list {
+    ($($elem:expr),* $(,)?) => {
+        $crate::Sexp::List(vec![$($crate::Sexp::from($elem)),*])
+    };
+}
+
+/// Construct an association list `sexpr::Sexp` from `key => value` entries.
+///
+/// Keys are any expression that converts into an `Atom`, or `sym name` to use
+/// the identifier `name` as a symbol. Values may be anything that converts
+/// into a `Sexp`.
+///
+/// ```rust
+/// # use sexpr::{alist, Sexp};
+/// #
+/// # fn main() {
+/// let code = 200;
+/// let value: Sexp = alist! {
+///     "code" => code,
+///     sym success => true,
+/// };
+/// assert_eq!(
+///     value,
+///     Sexp::List(vec![
+///         Sexp::new_entry("code", 200),
+///         Sexp::new_entry("success", true),
+///     ])
+/// );
+/// # }
+/// ```
+#[macro_export]
+macro_rules! alist {
+    (@entries [$($out:expr),*]) => {
+        $crate::Sexp::List(vec![$($out),*])
+    };
+    (@entries [$($out:expr),*] sym $key:ident => $value:expr $(, $($rest:tt)*)?) => {
+        $crate::alist!(@entries [$($out,)* $crate::Sexp::new_entry(
+            $crate::sexp::Atom::new_symbol(String::from(stringify!($key))),
+            $value
+        )] $($($rest)*)?)
+    };
+    (@entries [$($out:expr),*] $key:expr => $value:expr $(, $($rest:tt)*)?) => {
+        $crate::alist!(@entries [$($out,)* $crate::Sexp::new_entry($key, $value)] $($($rest)*)?)
+    };
+    ($($entries:tt)*) => {
+        $crate::alist!(@entries [] $($entries)*)
+    };
+}
+
+// Emits a `tracing` event when the `trace` feature is enabled, and nothing
+// otherwise, so call sites need no `cfg` of their own.
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "trace")]
+        {
+            tracing::$level!($($arg)+);
+        }
+    };
+}
+
+// Emits a `tracing` event for the error in `result`, if it holds one, when
+// the `trace` feature is enabled.
+macro_rules! trace_error {
+    ($result:expr, $what:expr) => {
+        #[cfg(feature = "trace")]
+        {
+            if let Err(ref err) = $result {
+                tracing::debug!(
+                    line = err.line(),
+                    column = err.column(),
+                    kind = ?err.kind(),
+                    "{} failed: {}",
+                    $what,
+                    err
+                );
+            }
+        }
+    };
+}
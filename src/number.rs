@@ -2,14 +2,30 @@
 use crate::error::Error;
 use num_traits::NumCast;
 use serde::de::{self, Unexpected, Visitor};
+#[cfg(feature = "arbitrary_precision")]
+use serde::de::{DeserializeSeed, IntoDeserializer, MapAccess};
 use serde::{forward_to_deserialize_any, Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
 use std::fmt::{self, Debug, Display};
 use std::i64;
 
+/// Name of the single key in the map a number's literal text passes through
+/// serde as, and of the newtype struct it is serialized as.
+pub(crate) const NUMBER_TOKEN: &str = "$sexpr::private::Number";
+
 /// Represents a Sexp number, whether integer or floating point.
+///
+/// With the `arbitrary_precision` feature, a number read from a literal such
+/// as `123456789012345678901234567890` or `0.10000000000000000001`, which no
+/// `u64`, `i64` or `f64` holds exactly, keeps the literal's text and is
+/// printed as it was written. The accessors still return the nearest value.
 #[derive(Clone, PartialEq)]
 pub struct Number {
     n: N,
+    /// The text the number was read from, if printing `n` does not give it
+    /// back.
+    #[cfg(feature = "arbitrary_precision")]
+    literal: Option<Box<str>>,
 }
 
 // "N" is a prefix of "NegInt"... this is a false positive.
@@ -24,6 +40,15 @@ enum N {
 }
 
 impl Number {
+    #[inline]
+    const fn new(n: N) -> Number {
+        Number {
+            n,
+            #[cfg(feature = "arbitrary_precision")]
+            literal: None,
+        }
+    }
+
     #[inline]
     pub fn is_i64(&self) -> bool {
         match self.n {
@@ -79,15 +104,184 @@ impl Number {
     #[inline]
     pub fn from_f64(f: f64) -> Option<Number> {
         if f.is_finite() {
-            Some(Number { n: N::Float(f) })
+            Some(Number::new(N::Float(f)))
         } else {
             None
         }
     }
+
+    /// Creates a `Number` from a `u64`. Unlike `Number::from`, this can be
+    /// used to initialize constants and statics.
+    #[inline]
+    pub const fn from_u64(u: u64) -> Number {
+        Number::new(N::PosInt(u))
+    }
+
+    /// Creates a `Number` from an `i64`. Unlike `Number::from`, this can be
+    /// used to initialize constants and statics.
+    #[inline]
+    pub const fn from_i64(i: i64) -> Number {
+        if i < 0 {
+            Number::new(N::NegInt(i))
+        } else {
+            Number::new(N::PosInt(i as u64))
+        }
+    }
+
+    /// Converts an `i128` to a `Number`. Returns `None` if the value fits
+    /// neither a `u64` nor an `i64`.
+    #[inline]
+    pub fn from_i128(i: i128) -> Option<Number> {
+        if i < 0 {
+            NumCast::from(i).map(|i| Number::new(N::NegInt(i)))
+        } else {
+            NumCast::from(i).map(|u| Number::new(N::PosInt(u)))
+        }
+    }
+
+    /// Returns the value as an `i128` if it is an integer. Every integer
+    /// `Number` fits.
+    #[inline]
+    pub fn as_i128(&self) -> Option<i128> {
+        match self.n {
+            N::PosInt(n) => Some(n.into()),
+            N::NegInt(n) => Some(n.into()),
+            N::Float(_) => None,
+        }
+    }
+
+    /// Returns true if the two numbers have the same numeric value, whether
+    /// they are stored as integers or floats. Unlike `==`, which also
+    /// compares how the numbers are stored, this treats `1` and `1.0` as
+    /// equal.
+    ///
+    /// ```rust
+    /// # use sexpr::Number;
+    /// let int = Number::from(1);
+    /// let float = Number::from_f64(1.0).unwrap();
+    /// assert!(int != float);
+    /// assert!(int.eq_numeric(&float));
+    /// ```
+    #[inline]
+    pub fn eq_numeric(&self, other: &Number) -> bool {
+        self.cmp_numeric(other) == Ordering::Equal
+    }
+
+    /// Creates a number from the text of a numeric literal such as `-12` or
+    /// `3.14159265358979323846264338327950288`. The text is kept, and printed
+    /// in place of the value, if the value would print differently. Returns
+    /// `None` if `text` is not a literal the parser reads as a finite number.
+    ///
+    /// ```rust
+    /// # use sexpr::Number;
+    /// let big = Number::from_literal("123456789012345678901234567890").unwrap();
+    /// assert_eq!(big.to_string(), "123456789012345678901234567890");
+    /// assert_eq!(big.as_f64(), Some(1.2345678901234568e29));
+    ///
+    /// assert_eq!(Number::from_literal("12").unwrap().as_literal(), None);
+    /// assert!(Number::from_literal("1e5").is_none());
+    /// ```
+    #[cfg(feature = "arbitrary_precision")]
+    pub fn from_literal(text: &str) -> Option<Number> {
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        let (int, frac) = match digits.find('.') {
+            Some(dot) => (&digits[..dot], Some(&digits[dot + 1..])),
+            None => (digits, None),
+        };
+        let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        // There can be only one leading '0', as for the parser.
+        if !all_digits(int) || (int.len() > 1 && int.starts_with('0')) {
+            return None;
+        }
+        if !frac.is_none_or(all_digits) {
+            return None;
+        }
+
+        let mut number = match (frac, text.parse(), text.parse()) {
+            (None, Ok(u), _) => Number::from_u64(u),
+            (None, _, Ok(i)) => Number::from_i64(i),
+            _ => Number::from_f64(text.parse().ok()?)?,
+        };
+        if number.canonical_text() != text {
+            number.literal = Some(text.into());
+        }
+        Some(number)
+    }
+
+    /// Returns the literal text the number was read from, if the value
+    /// alone would not print as it.
+    #[cfg(feature = "arbitrary_precision")]
+    #[inline]
+    pub fn as_literal(&self) -> Option<&str> {
+        self.literal.as_deref()
+    }
+
+    /// The text the serializer writes for the value.
+    #[cfg(feature = "arbitrary_precision")]
+    fn canonical_text(&self) -> String {
+        match self.n {
+            N::Float(f) => {
+                let mut buf = Vec::new();
+                let _ = dtoa::write(&mut buf, f);
+                String::from_utf8(buf).unwrap_or_default()
+            }
+            _ => self.to_string(),
+        }
+    }
+
+    /// Orders two numbers by their exact values, without converting an
+    /// integer to a float and losing precision on the way.
+    fn cmp_numeric(&self, other: &Number) -> Ordering {
+        match (self.n, other.n) {
+            (N::Float(a), N::Float(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            (N::Float(f), _) => cmp_float_int(f, other.as_i128().unwrap()),
+            (_, N::Float(f)) => cmp_float_int(f, self.as_i128().unwrap()).reverse(),
+            _ => self.as_i128().cmp(&other.as_i128()),
+        }
+    }
+}
+
+/// Compares a finite float with an integer exactly.
+fn cmp_float_int(f: f64, i: i128) -> Ordering {
+    // Every integer a `Number` holds lies within (-2^64, 2^64).
+    const BOUND: f64 = 18_446_744_073_709_551_616.0;
+    if f >= BOUND {
+        return Ordering::Greater;
+    }
+    if f <= -BOUND {
+        return Ordering::Less;
+    }
+    let whole = f.trunc();
+    match (whole as i128).cmp(&i) {
+        Ordering::Equal => f.partial_cmp(&whole).unwrap_or(Ordering::Equal),
+        ord => ord,
+    }
+}
+
+/// Numbers are ordered by their numeric value across integers and floats, so
+/// `-1 < 0.5 < 1`. Comparison is exact: a large integer is not rounded to a
+/// float before it is compared.
+///
+/// An integer and a float with the same value, such as `1` and `1.0`, are
+/// unordered and `partial_cmp` returns `None`, in keeping with `==` treating
+/// them as different. Use `eq_numeric` to compare values alone.
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match self.cmp_numeric(other) {
+            Ordering::Equal if self != other => None,
+            ord => Some(ord),
+        }
+    }
 }
 
 impl fmt::Display for Number {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        #[cfg(feature = "arbitrary_precision")]
+        {
+            if let Some(ref literal) = self.literal {
+                return formatter.write_str(literal);
+            }
+        }
         match self.n {
             N::PosInt(i) => Display::fmt(&i, formatter),
             N::NegInt(i) => Display::fmt(&i, formatter),
@@ -108,6 +302,14 @@ impl Serialize for Number {
     where
         S: Serializer,
     {
+        // The S-expression serializer writes the literal as it is; other
+        // serializers see it as a string.
+        #[cfg(feature = "arbitrary_precision")]
+        {
+            if let Some(ref literal) = self.literal {
+                return serializer.serialize_newtype_struct(NUMBER_TOKEN, &**literal);
+            }
+        }
         match self.n {
             N::PosInt(i) => serializer.serialize_u64(i),
             N::NegInt(i) => serializer.serialize_i64(i),
@@ -148,6 +350,14 @@ impl<'de> Deserialize<'de> for Number {
             {
                 Number::from_f64(value).ok_or_else(|| de::Error::custom("not a Sexp number"))
             }
+
+            #[cfg(feature = "arbitrary_precision")]
+            fn visit_map<A>(self, map: A) -> Result<Number, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                Number::from_literal_map(map)
+            }
         }
 
         deserializer.deserialize_any(NumberVisitor)
@@ -198,6 +408,70 @@ impl<'de, 'a> Deserializer<'de> for &'a Number {
     }
 }
 
+/// Hands the literal text of a number to a visitor as a map of one entry,
+/// keyed by `NUMBER_TOKEN`, that the visitors of `Number` and `Sexp` turn
+/// back into a `Number`. Other visitors see an ordinary map.
+#[cfg(feature = "arbitrary_precision")]
+pub(crate) struct LiteralAccess {
+    literal: Option<String>,
+    key_taken: bool,
+}
+
+#[cfg(feature = "arbitrary_precision")]
+impl LiteralAccess {
+    pub(crate) fn new(literal: String) -> Self {
+        LiteralAccess {
+            literal: Some(literal),
+            key_taken: false,
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary_precision")]
+impl<'de> MapAccess<'de> for LiteralAccess {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.key_taken {
+            return Ok(None);
+        }
+        self.key_taken = true;
+        seed.deserialize(NUMBER_TOKEN.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let literal = self.literal.take().unwrap_or_default();
+        seed.deserialize(literal.into_deserializer())
+    }
+}
+
+#[cfg(feature = "arbitrary_precision")]
+impl Number {
+    /// Reads the number `LiteralAccess` hands over.
+    pub(crate) fn from_literal_map<'de, A>(mut map: A) -> Result<Number, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        match map.next_key::<String>()? {
+            Some(ref key) if key == NUMBER_TOKEN => {}
+            _ => return Err(de::Error::invalid_type(Unexpected::Map, &"a number")),
+        }
+        Number::from_literal_value(map.next_value()?)
+    }
+
+    /// Reads the value of the map entry keyed by `NUMBER_TOKEN`.
+    pub(crate) fn from_literal_value<E: de::Error>(literal: String) -> Result<Number, E> {
+        Number::from_literal(&literal)
+            .ok_or_else(|| de::Error::invalid_value(Unexpected::Str(&literal), &"a number"))
+    }
+}
+
 macro_rules! from_signed {
     ($($signed_ty:ident)*) => {
         $(
@@ -207,9 +481,9 @@ macro_rules! from_signed {
                 #[inline]
                 fn from(i: $signed_ty) -> Self {
                     if i < 0 {
-                        Number { n: N::NegInt(i as i64) }
+                        Number::new(N::NegInt(i as i64))
                     } else {
-                        Number { n: N::PosInt(i as u64) }
+                        Number::new(N::PosInt(i as u64))
                     }
                 }
             }
@@ -225,7 +499,7 @@ macro_rules! from_unsigned {
                 #[allow(clippy::cast_lossless)]
                 #[inline]
                 fn from(u: $unsigned_ty) -> Self {
-                    Number { n: N::PosInt(u as u64) }
+                    Number::new(N::PosInt(u as u64))
                 }
             }
         )*
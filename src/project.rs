@@ -0,0 +1,451 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Keeping only selected keys of a value, as when redacting secrets from a
+//! configuration before it is logged.
+//!
+//! A path is a key, or keys separated by `.` to select a key of the value
+//! under another, as in `server.port`. A key selected by itself is kept
+//! with all of its value; a key only named as the start of longer paths is
+//! kept with its value projected in turn. Every other key is left out.
+
+use std::fmt::Display;
+
+use serde::ser::{
+    Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant, Serializer,
+};
+
+use crate::sexp::Sexp;
+
+/// Returns the parts of an association list that `paths` select.
+///
+/// The entries are read as `alist_get` reads them: pairs `(key . value)`
+/// and lists `(key value ...)` with an atom as their key. A list entry is an
+/// association list itself when projected further, so `server.port` keeps
+/// `(server (port 80))` out of `(server (host "a") (port 80))`. A head atom,
+/// as in `(package (name "web"))`, is kept; other elements that are not
+/// entries are left out, and so is anything but a list when a path reaches
+/// into it, which leaves `()`.
+///
+/// ```rust
+/// use sexpr::Sexp;
+///
+/// let config: Sexp = sexpr::from_str(
+///     "((name \"web\") (version 2) (db (host \"db1\") (password \"hunter2\")))",
+/// )
+/// .unwrap();
+/// let shown = sexpr::project(&config, &["name", "db.host"]);
+/// assert_eq!(shown.to_string(), "((name \"web\") (db (host \"db1\")))");
+/// ```
+pub fn project(sexp: &Sexp, paths: &[&str]) -> Sexp {
+    let elems = match *sexp {
+        Sexp::List(ref elems) => elems,
+        _ => return Sexp::List(Vec::new()),
+    };
+    let (head, entries) = match elems.first() {
+        Some(head @ &Sexp::Atom(_)) => (Some(head), &elems[1..]),
+        _ => (None, &elems[..]),
+    };
+
+    let mut projected: Vec<Sexp> = head.into_iter().cloned().collect();
+    for entry in entries {
+        let key = match *entry {
+            Sexp::Pair(Some(ref car), _) => &**car,
+            Sexp::List(ref kv) if kv.len() >= 2 => &kv[0],
+            _ => continue,
+        };
+        let key = match *key {
+            Sexp::Atom(ref atom) => atom.as_str(),
+            _ => continue,
+        };
+        match select(paths, key) {
+            Selection::Drop => {}
+            Selection::Whole => projected.push(entry.clone()),
+            Selection::Paths(rest) => projected.push(match entry.as_pair() {
+                Some((car, cdr)) => Sexp::pair(car.clone(), project(cdr, &rest)),
+                None => project(entry, &rest),
+            }),
+        }
+    }
+    Sexp::List(projected)
+}
+
+/// A value that serializes as only the keys of its structs and maps that
+/// `paths` select, as `project` selects them. Serialize it with `to_writer`
+/// or any other serializer to leave the other fields out of the output
+/// without building a `Sexp` first.
+///
+/// ```rust
+/// use serde_derive::Serialize;
+/// use sexpr::Projected;
+///
+/// #[derive(Serialize)]
+/// struct Db {
+///     host: String,
+///     password: String,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Config {
+///     name: String,
+///     db: Db,
+/// }
+///
+/// let config = Config {
+///     name: "web".to_owned(),
+///     db: Db {
+///         host: "db1".to_owned(),
+///         password: "hunter2".to_owned(),
+///     },
+/// };
+/// let shown = sexpr::to_string(&Projected::new(&config, &["name", "db.host"])).unwrap();
+/// assert_eq!(shown, "(\"name\".\"web\" \"db\".(\"host\".\"db1\"))");
+/// ```
+pub struct Projected<'a, T: ?Sized> {
+    value: &'a T,
+    paths: &'a [&'a str],
+}
+
+impl<'a, T: ?Sized> Projected<'a, T> {
+    /// Wraps `value` so that it serializes as only what `paths` select.
+    pub fn new(value: &'a T, paths: &'a [&'a str]) -> Self {
+        Projected { value, paths }
+    }
+}
+
+impl<'a, T> Serialize for Projected<'a, T>
+where
+    T: ?Sized + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value.serialize(ProjectSerializer {
+            inner: serializer,
+            paths: self.paths,
+        })
+    }
+}
+
+/// Which part of an entry the paths select.
+enum Selection<'p> {
+    Drop,
+    Whole,
+    /// The entry's value projected by the rest of these paths.
+    Paths(Vec<&'p str>),
+}
+
+fn select<'p>(paths: &[&'p str], key: &str) -> Selection<'p> {
+    let mut rest = Vec::new();
+    for path in paths {
+        if *path == key {
+            return Selection::Whole;
+        }
+        if let Some(sub) = path.strip_prefix(key).and_then(|sub| sub.strip_prefix('.')) {
+            rest.push(sub);
+        }
+    }
+    if rest.is_empty() {
+        Selection::Drop
+    } else {
+        Selection::Paths(rest)
+    }
+}
+
+/// Serializes a value through `inner`, leaving out the fields of its
+/// outermost struct or map that `paths` do not select. Values that are
+/// neither, apart from the wrappers an `Option` or newtype puts around one,
+/// serialize as an empty sequence.
+struct ProjectSerializer<'p, S> {
+    inner: S,
+    paths: &'p [&'p str],
+}
+
+impl<'p, S: Serializer> ProjectSerializer<'p, S> {
+    fn empty(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_seq(Some(0))?.end()
+    }
+}
+
+macro_rules! serialize_empty {
+    ($($method:ident($($arg:ty),*);)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<S::Ok, S::Error> {
+                self.empty()
+            }
+        )*
+    };
+}
+
+impl<'p, S: Serializer> Serializer for ProjectSerializer<'p, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Discard<S>;
+    type SerializeTuple = Discard<S>;
+    type SerializeTupleStruct = Discard<S>;
+    type SerializeTupleVariant = Discard<S>;
+    type SerializeMap = ProjectMap<'p, S::SerializeMap>;
+    type SerializeStruct = ProjectStruct<'p, S::SerializeStruct>;
+    type SerializeStructVariant = Discard<S>;
+
+    serialize_empty! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.empty()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Discard<S>, S::Error> {
+        Ok(Discard(self.inner))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Discard<S>, S::Error> {
+        Ok(Discard(self.inner))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Discard<S>, S::Error> {
+        Ok(Discard(self.inner))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Discard<S>, S::Error> {
+        Ok(Discard(self.inner))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        // Entries off the selected paths are dropped, so the original
+        // length is no longer known up front.
+        Ok(ProjectMap {
+            inner: self.inner.serialize_map(None)?,
+            paths: self.paths,
+            value: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        Ok(ProjectStruct {
+            inner: self.inner.serialize_struct(name, len)?,
+            paths: self.paths,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Discard<S>, S::Error> {
+        Ok(Discard(self.inner))
+    }
+
+    fn collect_str<T>(self, _value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Display,
+    {
+        self.empty()
+    }
+}
+
+/// Ignores the elements of a value that has no keys, and serializes an empty
+/// sequence through the serializer it holds in its place.
+struct Discard<S>(S);
+
+impl<S: Serializer> Discard<S> {
+    fn end_empty(self) -> Result<S::Ok, S::Error> {
+        self.0.serialize_seq(Some(0))?.end()
+    }
+}
+
+macro_rules! impl_discard {
+    ($($trait:ident :: $method:ident($($key:ty)?);)*) => {
+        $(
+            impl<S: Serializer> $trait for Discard<S> {
+                type Ok = S::Ok;
+                type Error = S::Error;
+
+                fn $method<T>(&mut self, $(_: $key,)? _: &T) -> Result<(), S::Error>
+                where
+                    T: ?Sized + Serialize,
+                {
+                    Ok(())
+                }
+
+                fn end(self) -> Result<S::Ok, S::Error> {
+                    self.end_empty()
+                }
+            }
+        )*
+    };
+}
+
+impl_discard! {
+    SerializeSeq::serialize_element();
+    SerializeTuple::serialize_element();
+    SerializeTupleStruct::serialize_field();
+    SerializeTupleVariant::serialize_field();
+    SerializeStructVariant::serialize_field(&'static str);
+}
+
+/// Serializes the value of an entry as `select` says to.
+struct Entry<'a, 'p, T: ?Sized> {
+    value: &'a T,
+    paths: &'a [&'p str],
+}
+
+impl<'a, 'p, T> Serialize for Entry<'a, 'p, T>
+where
+    T: ?Sized + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value.serialize(ProjectSerializer {
+            inner: serializer,
+            paths: self.paths,
+        })
+    }
+}
+
+struct ProjectStruct<'p, S> {
+    inner: S,
+    paths: &'p [&'p str],
+}
+
+impl<'p, S: SerializeStruct> SerializeStruct for ProjectStruct<'p, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match select(self.paths, key) {
+            Selection::Drop => self.inner.skip_field(key),
+            Selection::Whole => self.inner.serialize_field(key, value),
+            Selection::Paths(rest) => self.inner.serialize_field(
+                key,
+                &Entry {
+                    value,
+                    paths: &rest,
+                },
+            ),
+        }
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.inner.end()
+    }
+}
+
+struct ProjectMap<'p, S> {
+    inner: S,
+    paths: &'p [&'p str],
+    /// What the paths select of the value after the key just serialized,
+    /// or `None` if the key was left out.
+    value: Option<Selection<'p>>,
+}
+
+impl<'p, S: SerializeMap> SerializeMap for ProjectMap<'p, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let text = match crate::sexp::to_value(key) {
+            Ok(Sexp::Atom(atom)) => atom.into_string(),
+            Ok(Sexp::Number(n)) => n.to_string(),
+            _ => String::new(),
+        };
+        self.value = match select(self.paths, &text) {
+            Selection::Drop => None,
+            selection => {
+                self.inner.serialize_key(key)?;
+                Some(selection)
+            }
+        };
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match self.value.take() {
+            None | Some(Selection::Drop) => Ok(()),
+            Some(Selection::Whole) => self.inner.serialize_value(value),
+            Some(Selection::Paths(rest)) => self.inner.serialize_value(&Entry {
+                value,
+                paths: &rest,
+            }),
+        }
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.inner.end()
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Unparsed S-expression text captured from the input.
+
+use std::fmt::{self, Debug, Display};
+
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::error::Result;
+
+/// Name of the newtype struct a `RawSexp` passes through serde as. The
+/// S-expression deserializer hands over the text of the value instead of
+/// parsing it, and the serializer writes the text out unchanged.
+pub(crate) const RAW_TOKEN: &str = "$sexpr::private::RawSexp";
+
+/// The text of a single S-expression value, kept as it appeared in the input
+/// instead of being parsed.
+///
+/// A field of this type is skipped over quickly by the deserializer, which
+/// only checks where the value ends. This is useful for large documents of
+/// which only a part is needed now, or for passing a subtree through
+/// unchanged. The text can be deserialized later with `sexpr::from_str`.
+///
+/// ```rust
+/// use serde_derive::Deserialize;
+/// use sexpr::RawSexp;
+///
+/// #[derive(Deserialize)]
+/// struct Document {
+///     version: u32,
+///     body: RawSexp,
+/// }
+///
+/// let doc: Document = sexpr::from_str("((version . 2) (body . (big (tree) \"x\")))").unwrap();
+/// assert_eq!(doc.version, 2);
+/// assert_eq!(doc.body.get(), "(big (tree) \"x\")");
+/// ```
+///
+/// `RawSexp` can only be deserialized from S-expression text, not from a
+/// `Sexp` value.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RawSexp {
+    sexp: String,
+}
+
+impl RawSexp {
+    /// Checks that `sexp` holds exactly one S-expression value and wraps it.
+    pub fn from_string(sexp: String) -> Result<Self> {
+        {
+            let mut de = crate::Deserializer::from_str(&sexp);
+            IgnoredAny::deserialize(&mut de)?;
+            de.end()?;
+        }
+        Ok(RawSexp { sexp })
+    }
+
+    /// Returns the text of the value.
+    pub fn get(&self) -> &str {
+        &self.sexp
+    }
+
+    /// Consumes the `RawSexp` and returns the text of the value.
+    pub fn into_string(self) -> String {
+        self.sexp
+    }
+}
+
+impl Debug for RawSexp {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_tuple("RawSexp")
+            .field(&format_args!("{}", self.sexp))
+            .finish()
+    }
+}
+
+impl Display for RawSexp {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.sexp)
+    }
+}
+
+impl Serialize for RawSexp {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_TOKEN, &self.sexp)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawSexp {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<RawSexp, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawSexpVisitor;
+
+        impl<'de> Visitor<'de> for RawSexpVisitor {
+            type Value = RawSexp;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("any valid S-expression")
+            }
+
+            fn visit_string<E>(self, sexp: String) -> std::result::Result<RawSexp, E>
+            where
+                E: de::Error,
+            {
+                Ok(RawSexp { sexp })
+            }
+
+            fn visit_str<E>(self, sexp: &str) -> std::result::Result<RawSexp, E>
+            where
+                E: de::Error,
+            {
+                self.visit_string(sexp.to_owned())
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(RAW_TOKEN, RawSexpVisitor)
+    }
+}
@@ -7,26 +7,125 @@
 // except according to those terms.
 
 use std::ops::Deref;
-use std::{char, cmp, io, str};
+use std::{char, cmp, io, result, str};
 
+use serde::de;
+
+use crate::de::DeserializerOptions;
 use crate::iter::LineColIterator;
 
 use super::error::{Error, ErrorCode, Result};
 
-/// Trait used by the deserializer for iterating over input. This is manually
-/// "specialized" for iterating over &[u8]. Once feature(specialization) is
-/// stable we can use actual specialization.
+/// A source of input bytes for the `Deserializer`.
 ///
-/// This trait is sealed and cannot be implemented for types outside of
-/// `serde_json`.
-pub trait Read<'de>: private::Sealed {
-    #[doc(hidden)]
+/// The crate implements this for `IoRead`, `SliceRead` and `StrRead`, which
+/// `Deserializer::from_reader`, `from_slice` and `from_str` use. Other
+/// sources, such as a memory-mapped file or a ring buffer, can implement it
+/// and be passed to `Deserializer::new`.
+///
+/// Only the methods that move through the input, look up to three bytes
+/// ahead and report positions have to be written. The others have provided
+/// implementations built on `next` and `peek`, which copy every string into
+/// the scratch space; a source that holds its whole input in memory can
+/// override `parse_str`, `parse_str_raw` and `parse_symbol` to return
+/// `Reference::Borrowed` slices of it instead.
+///
+/// # Stability
+///
+/// This trait follows the deserializer's needs more closely than the rest of
+/// the API. Methods added in a minor release come with provided
+/// implementations, so existing sources keep compiling, but a new required
+/// method, such as one looking further ahead, is a breaking change and waits
+/// for the next major version. The provided implementations may be replaced
+/// by faster ones that still call only the required methods.
+///
+/// ```
+/// use std::io;
+/// use serde::Deserialize;
+/// use sexpr::de::{Deserializer, Position, Read};
+///
+/// /// Reads the bytes of a `VecDeque`, as filled by a ring buffer.
+/// struct DequeRead {
+///     bytes: std::collections::VecDeque<u8>,
+///     offset: usize,
+///     line: usize,
+///     column: usize,
+/// }
+///
+/// impl<'de> Read<'de> for DequeRead {
+///     fn next(&mut self) -> io::Result<Option<u8>> {
+///         let ch = self.bytes.pop_front();
+///         if let Some(ch) = ch {
+///             self.offset += 1;
+///             if ch == b'\n' {
+///                 self.line += 1;
+///                 self.column = 0;
+///             } else {
+///                 self.column += 1;
+///             }
+///         }
+///         Ok(ch)
+///     }
+///
+///     fn peek(&mut self) -> io::Result<Option<u8>> {
+///         Ok(self.bytes.front().cloned())
+///     }
+///
+///     fn peek_second(&mut self) -> io::Result<Option<u8>> {
+///         Ok(self.bytes.get(1).cloned())
+///     }
+///
+///     fn peek_third(&mut self) -> io::Result<Option<u8>> {
+///         Ok(self.bytes.get(2).cloned())
+///     }
+///
+///     fn discard(&mut self) {
+///         let _ = self.next();
+///     }
+///
+///     fn position(&self) -> Position {
+///         Position { line: self.line, column: self.column }
+///     }
+///
+///     fn peek_position(&self) -> Position {
+///         Position { line: self.line, column: self.column + 1 }
+///     }
+///
+///     fn byte_offset(&self) -> usize {
+///         self.offset
+///     }
+/// }
+///
+/// let read = DequeRead {
+///     bytes: b"(1 \"two\" three)".iter().cloned().collect(),
+///     offset: 0,
+///     line: 1,
+///     column: 0,
+/// };
+/// let mut de = Deserializer::new(read);
+/// let value = sexpr::Sexp::deserialize(&mut de).unwrap();
+/// de.end().unwrap();
+/// assert_eq!(sexpr::to_string(&value).unwrap(), "(1 \"two\" three)");
+/// ```
+pub trait Read<'de> {
+    /// Consumes and returns the next byte, or `None` at the end of the input.
     fn next(&mut self) -> io::Result<Option<u8>>;
-    #[doc(hidden)]
+
+    /// Returns the next byte without consuming it, or `None` at the end of
+    /// the input.
     fn peek(&mut self) -> io::Result<Option<u8>>;
 
+    /// Returns the byte after the one `peek` returns, consuming neither, or
+    /// `None` at the end of the input. Used to tell `()` from `(a)` and
+    /// `#t` from `#(`.
+    fn peek_second(&mut self) -> io::Result<Option<u8>>;
+
+    /// Returns the byte after the one `peek_second` returns, consuming
+    /// nothing, or `None` at the end of the input. Used to recognize Unicode
+    /// whitespace that takes three bytes, such as U+2028.
+    fn peek_third(&mut self) -> io::Result<Option<u8>>;
+
     /// Only valid after a call to peek(). Discards the peeked byte.
-    #[doc(hidden)]
     fn discard(&mut self);
 
     /// Position of the most recent call to next().
@@ -36,7 +135,6 @@ pub trait Read<'de>: private::Sealed {
     /// actually peek() because we don't always know.
     ///
     /// Only called in case of an error, so performance is not important.
-    #[doc(hidden)]
     fn position(&self) -> Position;
 
     /// Position of the most recent call to peek().
@@ -46,22 +144,32 @@ pub trait Read<'de>: private::Sealed {
     /// actually next() because we don't always know.
     ///
     /// Only called in case of an error, so performance is not important.
-    #[doc(hidden)]
     fn peek_position(&self) -> Position;
 
     /// Offset from the beginning of the input to the next byte that would be
     /// returned by next() or peek().
-    #[doc(hidden)]
     fn byte_offset(&self) -> usize;
 
+    /// Position of the next byte that would be returned by next() or peek(),
+    /// with a column starting at 1.
+    ///
+    /// Called once per value when a stream is iterated with spans, so this
+    /// should not rescan the input from the beginning every time. The
+    /// provided implementation assumes no byte has been peeked.
+    fn offset_position(&mut self) -> Position {
+        let pos = self.position();
+        Position {
+            line: pos.line,
+            column: pos.column + 1,
+        }
+    }
+
     /// Assumes the previous byte was a quotation mark. Parses a JSON-escaped
     /// string until the next quotation mark using the given scratch space if
     /// necessary. The scratch space is initially empty.
-    #[doc(hidden)]
-    fn parse_str<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>>;
-
-    /// Parses an unescaped string until the next whitespace or list close..
-    fn parse_symbol<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>>;
+    fn parse_str<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>> {
+        parse_str_bytes(self, scratch, true, as_str).map(Reference::Copied)
+    }
 
     /// Assumes the previous byte was a quotation mark. Parses a JSON-escaped
     /// string until the next quotation mark using the given scratch space if
@@ -69,20 +177,179 @@ pub trait Read<'de>: private::Sealed {
     ///
     /// This function returns the raw bytes in the string with escape sequences
     /// expanded but without performing unicode validation.
-    #[doc(hidden)]
     fn parse_str_raw<'s>(
         &'s mut self,
         scratch: &'s mut Vec<u8>,
-    ) -> Result<Reference<'de, 's, [u8]>>;
+    ) -> Result<Reference<'de, 's, [u8]>> {
+        parse_str_bytes(self, scratch, false, |_, bytes| Ok(bytes)).map(Reference::Copied)
+    }
+
+    /// Parses an unescaped string until the next whitespace or list close,
+    /// which is left unconsumed. The scratch space is initially empty.
+    ///
+    /// Which bytes close a list, and whether the symbol is folded to lower
+    /// case, is decided by `options`.
+    fn parse_symbol<'s>(
+        &'s mut self,
+        scratch: &'s mut Vec<u8>,
+        options: &DeserializerOptions,
+    ) -> Result<Reference<'de, 's, str>> {
+        parse_symbol_bytes(self, scratch, options, as_str).map(Reference::Copied)
+    }
+
+    /// Assumes the previous byte was a quotation mark. Skips to just past the
+    /// closing quotation mark without decoding escapes or checking UTF-8.
+    #[doc(hidden)]
+    fn ignore_str(&mut self) -> Result<()> {
+        loop {
+            match next_or_eof(self)? {
+                b'"' => return Ok(()),
+                b'\\' => {
+                    next_or_eof(self)?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Skips over one value and any whitespace or comments before it. Brackets
+    /// are matched and strings and comments skipped, but nothing is decoded
+    /// and atoms are only scanned for where they end.
+    #[doc(hidden)]
+    fn ignore_value(&mut self, options: &DeserializerOptions) -> Result<()> {
+        let mut depth = 0usize;
+        let mut opens = Vec::new();
+        loop {
+            let space = peek_whitespace_len(self, options)?;
+            if space > 0 {
+                for _ in 0..space {
+                    self.discard();
+                    self.peek().map_err(Error::io)?;
+                }
+                continue;
+            }
+            let ch = match self.next().map_err(Error::io)? {
+                Some(ch) => ch,
+                None => return error(self, eof_while_ignoring(depth)),
+            };
+            match ch {
+                b';' if options.reject_comments => {
+                    return error(self, ErrorCode::CommentNotAllowed);
+                }
+                b';' => {
+                    while let Some(ch) = self.next().map_err(Error::io)? {
+                        if ch == b'\n' || ch == b'\r' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                b'"' => self.ignore_str()?,
+                b'|' => ignore_bar_symbol(self)?,
+                b'#' if self.peek().map_err(Error::io)? == Some(b'x') => {
+                    // Hex digits in a string, as in `#x"DEADBEEF"`.
+                    self.discard();
+                    if self.next().map_err(Error::io)? != Some(b'"') {
+                        return error(self, ErrorCode::ExpectedSomeString);
+                    }
+                    self.ignore_str()?;
+                }
+                b'#' if self.peek().map_err(Error::io)? == Some(b'u') => {
+                    // The `#u8` of a bytevector, whose list is skipped next.
+                    self.discard();
+                    if self.next().map_err(Error::io)? != Some(b'8') {
+                        return error(self, ErrorCode::ExpectedSomeIdent);
+                    }
+                    continue;
+                }
+                _ if opens_list(ch, options) => open_list(ch, options, &mut depth, &mut opens),
+                _ if closes_list(ch, options) => {
+                    if let Err(code) = close_list(ch, options, &mut depth, &mut opens) {
+                        return error(self, code);
+                    }
+                }
+                _ => {
+                    let symbol = ch.is_ascii_alphabetic();
+                    while let Some(ch) = self.peek().map_err(Error::io)? {
+                        if ends_atom(ch, symbol, options)
+                            || (ch >= 0x80 && peek_whitespace_len(self, options)? > 0)
+                        {
+                            break;
+                        }
+                        self.discard();
+                    }
+                    if !symbol && self.peek().map_err(Error::io)? == Some(b'|') {
+                        // A keyword such as `#:|a b|`.
+                        self.discard();
+                        ignore_bar_symbol(self)?;
+                    }
+                }
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Skips a UTF-8 byte order mark and a `#!` line at the start of the
+    /// input, as far as `options` asks for them to be skipped.
+    ///
+    /// Telling a `#!` line apart from a value such as `#t` takes two bytes of
+    /// lookahead, so the provided implementation only skips the byte order
+    /// mark.
+    #[doc(hidden)]
+    fn skip_preamble(&mut self, options: &DeserializerOptions) -> Result<()> {
+        if options.skip_bom {
+            skip_bom(self)?;
+        }
+        Ok(())
+    }
+
+    /// Starts recording the bytes consumed from here on, for `RawSexp`.
+    ///
+    /// The provided implementation records nothing, so `RawSexp` cannot be
+    /// deserialized from a source that does not override this and
+    /// `end_raw_buffering`.
+    #[doc(hidden)]
+    fn begin_raw_buffering(&mut self) {}
+
+    /// Stops recording and returns the bytes consumed since
+    /// `begin_raw_buffering`, which must be valid UTF-8.
+    #[doc(hidden)]
+    fn end_raw_buffering(&mut self) -> Result<String> {
+        Err(de::Error::custom(
+            "this input source cannot capture a RawSexp",
+        ))
+    }
+
+    /// Records that an escape sequence in a string has been decoded. The
+    /// provided implementation does nothing, for a source that does not
+    /// report `ParseStats`.
+    fn count_escape(&mut self) {}
+
+    /// The number of escape sequences decoded so far, for `ParseStats`. The
+    /// provided implementation counts nothing and returns 0.
+    fn escapes(&self) -> usize {
+        0
+    }
 }
 
+/// A line and column in the input. Lines start at 1; the column of the
+/// first byte of a line is 1, and 0 stands for the start of the line before
+/// any byte has been read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Position {
+    /// The line number, starting at 1.
     pub line: usize,
+    /// The column number within the line.
     pub column: usize,
 }
 
+/// A string or byte string parsed by a `Read` source.
 pub enum Reference<'b, 'c, T: ?Sized + 'static> {
+    /// Borrowed from the input for the `'de` lifetime of the deserializer.
     Borrowed(&'b T),
+    /// Copied into the scratch space, valid only until it is next used.
     Copied(&'c T),
 }
 
@@ -105,6 +372,13 @@ where
     iter: LineColIterator<io::Bytes<R>>,
     /// Temporary storage of peeked byte.
     ch: Option<u8>,
+    /// Bytes read from `iter` that come after `ch`, in order, put back when
+    /// looking more than one byte ahead.
+    pushback: Vec<u8>,
+    /// Bytes consumed since `begin_raw_buffering`, if it has been called.
+    raw_buffer: Option<Vec<u8>>,
+    /// Escape sequences decoded so far.
+    escapes: usize,
 }
 
 /// JSON input source that reads from a slice of bytes.
@@ -115,6 +389,13 @@ pub struct SliceRead<'a> {
     slice: &'a [u8],
     /// Index of the *next* byte that will be returned by next() or peek().
     index: usize,
+    /// Index and position last computed by offset_position(), so that the
+    /// next call only has to scan the bytes after it.
+    position_cache: (usize, Position),
+    /// Index at which `begin_raw_buffering` was last called.
+    raw_buffering_start_index: usize,
+    /// Escape sequences decoded so far.
+    escapes: usize,
 }
 
 /// JSON input source that reads from a UTF-8 string.
@@ -124,9 +405,14 @@ pub struct StrRead<'a> {
     delegate: SliceRead<'a>,
 }
 
-// Prevent users from implementing the Read trait.
-mod private {
-    pub trait Sealed {}
+/// S-expression input source that reads from a memory-mapped file.
+///
+/// Strings and symbols are borrowed straight out of the mapping, as with
+/// `SliceRead`, so a large file is never copied into memory as a whole.
+/// Requires the `memmap2` feature.
+#[cfg(feature = "memmap2")]
+pub struct MmapRead<'a> {
+    delegate: SliceRead<'a>,
 }
 
 //////////////////////////////////////////////////////////////////////////////
@@ -140,61 +426,39 @@ where
         IoRead {
             iter: LineColIterator::new(reader.bytes()),
             ch: None,
+            pushback: Vec::new(),
+            raw_buffer: None,
+            escapes: 0,
         }
     }
 }
 
-impl<R> private::Sealed for IoRead<R> where R: io::Read {}
-
 impl<R> IoRead<R>
 where
     R: io::Read,
 {
-    fn parse_str_bytes<'s, T, F>(
-        &'s mut self,
-        scratch: &'s mut Vec<u8>,
-        validate: bool,
-        result: F,
-    ) -> Result<T>
-    where
-        T: 's,
-        F: FnOnce(&'s Self, &'s [u8]) -> Result<T>,
-    {
-        loop {
-            let ch = next_or_eof(self)?;
-            if !ESCAPE[ch as usize] {
-                scratch.push(ch);
-                continue;
-            }
-            match ch {
-                b'"' => {
-                    return result(self, scratch);
-                }
-                b'\\' => {
-                    parse_escape(self, scratch)?;
-                }
-                _ => {
-                    if validate {
-                        return error(self, ErrorCode::InvalidUnicodeCodePoint);
-                    }
-                    scratch.push(ch);
-                }
+    /// Returns the byte `i` places after the peeked one, reading it into
+    /// `pushback` if need be.
+    fn peek_ahead(&mut self, i: usize) -> io::Result<Option<u8>> {
+        if self.peek()?.is_none() {
+            return Ok(None);
+        }
+        while self.pushback.len() <= i {
+            match self.iter.next() {
+                Some(Err(err)) => return Err(err),
+                Some(Ok(ch)) => self.pushback.push(ch),
+                None => return Ok(None),
             }
         }
+        Ok(Some(self.pushback[i]))
     }
 
-    fn parse_symbol_bytes<'s, T, F>(&'s mut self, scratch: &'s mut Vec<u8>, result: F) -> Result<T>
-    where
-        T: 's,
-        F: FnOnce(&'s Self, &'s [u8]) -> Result<T>,
-    {
-        loop {
-            match self.next().map_err(Error::io)? {
-                Some(b' ') | Some(b'\n') | Some(b'\t') | Some(b'\r') | Some(b')') | None => {
-                    return result(self, scratch);
-                }
-                Some(ch) => scratch.push(ch),
-            }
+    #[inline]
+    fn next_byte(&mut self) -> Option<io::Result<u8>> {
+        if self.pushback.is_empty() {
+            self.iter.next()
+        } else {
+            Some(Ok(self.pushback.remove(0)))
         }
     }
 }
@@ -205,21 +469,25 @@ where
 {
     #[inline]
     fn next(&mut self) -> io::Result<Option<u8>> {
-        match self.ch.take() {
-            Some(ch) => Ok(Some(ch)),
-            None => match self.iter.next() {
-                Some(Err(err)) => Err(err),
-                Some(Ok(ch)) => Ok(Some(ch)),
-                None => Ok(None),
+        let ch = match self.ch.take() {
+            Some(ch) => ch,
+            None => match self.next_byte() {
+                Some(Err(err)) => return Err(err),
+                Some(Ok(ch)) => ch,
+                None => return Ok(None),
             },
+        };
+        if let Some(ref mut buf) = self.raw_buffer {
+            buf.push(ch);
         }
+        Ok(Some(ch))
     }
 
     #[inline]
     fn peek(&mut self) -> io::Result<Option<u8>> {
         match self.ch {
             Some(ch) => Ok(Some(ch)),
-            None => match self.iter.next() {
+            None => match self.next_byte() {
                 Some(Err(err)) => Err(err),
                 Some(Ok(ch)) => {
                     self.ch = Some(ch);
@@ -232,7 +500,11 @@ where
 
     #[inline]
     fn discard(&mut self) {
-        self.ch = None;
+        if let Some(ch) = self.ch.take() {
+            if let Some(ref mut buf) = self.raw_buffer {
+                buf.push(ch);
+            }
+        }
     }
 
     fn position(&self) -> Position {
@@ -249,28 +521,61 @@ where
     }
 
     fn byte_offset(&self) -> usize {
+        self.iter.byte_offset() - self.ch.iter().count() - self.pushback.len()
+    }
+
+    fn offset_position(&mut self) -> Position {
         match self.ch {
-            Some(_) => self.iter.byte_offset() - 1,
-            None => self.iter.byte_offset(),
+            // The peeked byte has already been counted by the LineColIterator.
+            Some(_) => self.position(),
+            None => Position {
+                line: self.iter.line(),
+                column: self.iter.col() + 1,
+            },
         }
     }
 
-    fn parse_str<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>> {
-        self.parse_str_bytes(scratch, true, as_str)
-            .map(Reference::Copied)
+    fn skip_preamble(&mut self, options: &DeserializerOptions) -> Result<()> {
+        if options.skip_bom {
+            skip_bom(self)?;
+        }
+        if options.skip_shebang && self.peek().map_err(Error::io)? == Some(b'#') {
+            self.discard();
+            match self.peek().map_err(Error::io)? {
+                Some(b'!') => skip_line(self)?,
+                // Not a `#!` line after all, so hand both bytes back.
+                next => {
+                    self.ch = Some(b'#');
+                    self.pushback.splice(0..0, next);
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn parse_str_raw<'s>(
-        &'s mut self,
-        scratch: &'s mut Vec<u8>,
-    ) -> Result<Reference<'de, 's, [u8]>> {
-        self.parse_str_bytes(scratch, false, |_, bytes| Ok(bytes))
-            .map(Reference::Copied)
+    fn begin_raw_buffering(&mut self) {
+        self.raw_buffer = Some(Vec::new());
+    }
+
+    fn end_raw_buffering(&mut self) -> Result<String> {
+        let buf = self.raw_buffer.take().unwrap_or_default();
+        String::from_utf8(buf).or_else(|_| error(self, ErrorCode::InvalidUnicodeCodePoint))
     }
 
-    fn parse_symbol<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>> {
-        self.parse_symbol_bytes(scratch, as_str)
-            .map(Reference::Copied)
+    fn peek_second(&mut self) -> io::Result<Option<u8>> {
+        self.peek_ahead(0)
+    }
+
+    fn peek_third(&mut self) -> io::Result<Option<u8>> {
+        self.peek_ahead(1)
+    }
+
+    fn count_escape(&mut self) {
+        self.escapes += 1;
+    }
+
+    fn escapes(&self) -> usize {
+        self.escapes
     }
 }
 
@@ -279,28 +584,83 @@ where
 impl<'a> SliceRead<'a> {
     /// Create a JSON input source to read from a slice of bytes.
     pub fn new(slice: &'a [u8]) -> Self {
-        SliceRead { slice, index: 0 }
+        SliceRead {
+            slice,
+            index: 0,
+            position_cache: (0, Position { line: 1, column: 0 }),
+            raw_buffering_start_index: 0,
+            escapes: 0,
+        }
+    }
+
+    /// The line and column of the byte at `offset` in the slice, with a
+    /// column starting at 1, as in a `Span`. An offset past the end is taken
+    /// to be the end.
+    ///
+    /// Reading keeps no count of lines, so this scans the slice up to
+    /// `offset`. Tools that map many offsets, such as the starts of spans,
+    /// should visit them in order and keep their own running count.
+    ///
+    /// ```
+    /// use sexpr::de::{Position, SliceRead};
+    ///
+    /// let read = SliceRead::new(b"(a\r\n  b)");
+    /// assert_eq!(read.position_of(1), Position { line: 1, column: 2 });
+    /// assert_eq!(read.position_of(6), Position { line: 2, column: 3 });
+    /// ```
+    pub fn position_of(&self, offset: usize) -> Position {
+        let offset = cmp::min(offset, self.slice.len());
+        if offset > 0 && self.slice[offset - 1..].starts_with(b"\r\n") {
+            // The LF of a CR LF pair is on the line the CR ends.
+            let cr = self.position_of(offset - 1);
+            return Position {
+                line: cr.line,
+                column: cr.column + 1,
+            };
+        }
+        let pos = self.position_of_index(offset);
+        Position {
+            line: pos.line,
+            column: pos.column + 1,
+        }
     }
 
     fn position_of_index(&self, i: usize) -> Position {
-        let mut pos = Position { line: 1, column: 0 };
-        for ch in &self.slice[..i] {
-            match *ch {
-                b'\n' => {
-                    pos.line += 1;
-                    pos.column = 0;
-                }
-                _ => {
-                    pos.column += 1;
+        self.position_from(0, Position { line: 1, column: 0 }, i)
+    }
+
+    /// Advances `pos`, the position of index `from`, to index `i`.
+    ///
+    /// A line ends at LF, at CR, or at a CR LF pair, which ends only one.
+    /// Only line breaks are counted on the way; the column is measured back
+    /// from `i` to the last of them.
+    fn position_from(&self, from: usize, pos: Position, i: usize) -> Position {
+        let bytes = &self.slice[from..i];
+        let is_break = |&b: &u8| b == b'\n' || b == b'\r';
+        let last_break = match bytes.iter().rposition(is_break) {
+            Some(last) => last,
+            None => {
+                return Position {
+                    line: pos.line,
+                    column: pos.column + bytes.len(),
                 }
             }
+        };
+        let breaks = bytes.iter().filter(|b| is_break(b)).count();
+        let mut crlfs = bytes.windows(2).filter(|pair| *pair == b"\r\n").count();
+        if from > 0 && self.slice[from - 1] == b'\r' && bytes.first() == Some(&b'\n') {
+            crlfs += 1;
+        }
+        Position {
+            line: pos.line + breaks - crlfs,
+            column: bytes.len() - last_break - 1,
         }
-        pos
     }
 
     fn parse_symbol_bytes<'s, T: ?Sized, F>(
         &'s mut self,
         scratch: &'s mut Vec<u8>,
+        options: &DeserializerOptions,
         result: F,
     ) -> Result<Reference<'a, 's, T>>
     where
@@ -310,23 +670,29 @@ impl<'a> SliceRead<'a> {
         // Index of the first byte not yet copied into the scratch space.
         let start = self.index;
 
-        loop {
-            match self.slice[self.index] {
-                b' ' | b'\n' | b'\t' | b'\r' | b')' => {
-                    if scratch.is_empty() {
-                        // Fast path: return a slice of the raw JSON without any
-                        // copying.
-                        let borrowed = &self.slice[start..self.index];
-                        return result(self, borrowed).map(Reference::Borrowed);
-                    } else {
-                        scratch.extend_from_slice(&self.slice[start..self.index]);
-                        // "as &[u8]" is required for rustc 1.8.0
-                        let copied = scratch as &[u8];
-                        return result(self, copied).map(Reference::Copied);
-                    }
-                }
-                _ => self.index += 1,
-            }
+        while self.index < self.slice.len()
+            && !is_symbol_delimiter(self.slice[self.index], options)
+            && (self.slice[self.index] < 0x80
+                || whitespace_len(&self.slice[self.index..], options) == 0)
+        {
+            self.index += 1;
+        }
+
+        let symbol = &self.slice[start..self.index];
+        if options.case_insensitive && symbol.iter().any(u8::is_ascii_uppercase) {
+            // Folding the case means the symbol can no longer be borrowed
+            // from the input.
+            scratch.extend(symbol.iter().map(u8::to_ascii_lowercase));
+            let copied = scratch as &[u8];
+            result(self, copied).map(Reference::Copied)
+        } else if scratch.is_empty() {
+            // Fast path: return a slice of the raw input without any copying.
+            result(self, symbol).map(Reference::Borrowed)
+        } else {
+            scratch.extend_from_slice(symbol);
+            // "as &[u8]" is required for rustc 1.8.0
+            let copied = scratch as &[u8];
+            result(self, copied).map(Reference::Copied)
         }
     }
 
@@ -386,8 +752,6 @@ impl<'a> SliceRead<'a> {
     }
 }
 
-impl<'a> private::Sealed for SliceRead<'a> {}
-
 impl<'a> Read<'a> for SliceRead<'a> {
     #[inline]
     fn next(&mut self) -> io::Result<Option<u8>> {
@@ -432,12 +796,161 @@ impl<'a> Read<'a> for SliceRead<'a> {
         self.index
     }
 
+    fn offset_position(&mut self) -> Position {
+        let index = cmp::min(self.slice.len(), self.index);
+        let (from, pos) = match self.position_cache {
+            (from, pos) if from <= index => (from, pos),
+            _ => (0, Position { line: 1, column: 0 }),
+        };
+        let pos = self.position_from(from, pos, index);
+        self.position_cache = (index, pos);
+        Position {
+            line: pos.line,
+            column: pos.column + 1,
+        }
+    }
+
+    fn ignore_str(&mut self) -> Result<()> {
+        loop {
+            match self.slice[self.index..]
+                .iter()
+                .position(|&ch| ch == b'"' || ch == b'\\')
+            {
+                Some(i) => {
+                    self.index += i + 1;
+                    if self.slice[self.index - 1] == b'"' {
+                        return Ok(());
+                    }
+                    if self.index == self.slice.len() {
+                        return error(self, ErrorCode::EofWhileParsingString);
+                    }
+                    self.index += 1;
+                }
+                None => {
+                    self.index = self.slice.len();
+                    return error(self, ErrorCode::EofWhileParsingString);
+                }
+            }
+        }
+    }
+
+    fn ignore_value(&mut self, options: &DeserializerOptions) -> Result<()> {
+        let mut depth = 0usize;
+        let mut opens = Vec::new();
+        loop {
+            let space = whitespace_len(&self.slice[self.index..], options);
+            if space > 0 {
+                self.index += space;
+                continue;
+            }
+            let ch = match self.slice.get(self.index) {
+                Some(&ch) => ch,
+                None => return error(self, eof_while_ignoring(depth)),
+            };
+            self.index += 1;
+            match ch {
+                b';' if options.reject_comments => {
+                    return error(self, ErrorCode::CommentNotAllowed);
+                }
+                b';' => {
+                    self.index = match self.slice[self.index..]
+                        .iter()
+                        .position(|&ch| ch == b'\n' || ch == b'\r')
+                    {
+                        Some(i) => self.index + i + 1,
+                        None => self.slice.len(),
+                    };
+                    continue;
+                }
+                b'"' => self.ignore_str()?,
+                b'|' => ignore_bar_symbol(self)?,
+                // Hex digits in a string, as in `#x"DEADBEEF"`.
+                b'#' if self.slice[self.index..].starts_with(b"x\"") => {
+                    self.index += 2;
+                    self.ignore_str()?;
+                }
+                // The `#u8` of a bytevector, whose list is skipped next.
+                b'#' if self.slice[self.index..].starts_with(b"u8") => {
+                    self.index += 2;
+                    continue;
+                }
+                _ if opens_list(ch, options) => open_list(ch, options, &mut depth, &mut opens),
+                _ if closes_list(ch, options) => {
+                    if let Err(code) = close_list(ch, options, &mut depth, &mut opens) {
+                        return error(self, code);
+                    }
+                }
+                _ => {
+                    let symbol = ch.is_ascii_alphabetic();
+                    let rest = &self.slice[self.index..];
+                    self.index = match (0..rest.len()).position(|i| {
+                        ends_atom(rest[i], symbol, options)
+                            || (rest[i] >= 0x80 && whitespace_len(&rest[i..], options) > 0)
+                    }) {
+                        Some(i) => self.index + i,
+                        None => self.slice.len(),
+                    };
+                    if !symbol && self.slice.get(self.index) == Some(&b'|') {
+                        // A keyword such as `#:|a b|`.
+                        self.index += 1;
+                        ignore_bar_symbol(self)?;
+                    }
+                }
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn skip_preamble(&mut self, options: &DeserializerOptions) -> Result<()> {
+        if options.skip_bom && self.slice[self.index..].starts_with(BOM) {
+            self.index += BOM.len();
+        }
+        if options.skip_shebang && self.slice[self.index..].starts_with(b"#!") {
+            self.index = match self.slice[self.index..].iter().position(|&ch| ch == b'\n') {
+                Some(i) => self.index + i + 1,
+                None => self.slice.len(),
+            };
+        }
+        Ok(())
+    }
+
+    fn begin_raw_buffering(&mut self) {
+        self.raw_buffering_start_index = self.index;
+    }
+
+    fn end_raw_buffering(&mut self) -> Result<String> {
+        let raw = &self.slice[self.raw_buffering_start_index..self.index];
+        as_str(self, raw).map(String::from)
+    }
+
+    fn peek_second(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.slice.get(self.index + 1).cloned())
+    }
+
+    fn peek_third(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.slice.get(self.index + 2).cloned())
+    }
+
+    fn count_escape(&mut self) {
+        self.escapes += 1;
+    }
+
+    fn escapes(&self) -> usize {
+        self.escapes
+    }
+
     fn parse_str<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'a, 's, str>> {
         self.parse_str_bytes(scratch, true, as_str)
     }
 
-    fn parse_symbol<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'a, 's, str>> {
-        self.parse_symbol_bytes(scratch, as_str)
+    fn parse_symbol<'s>(
+        &'s mut self,
+        scratch: &'s mut Vec<u8>,
+        options: &DeserializerOptions,
+    ) -> Result<Reference<'a, 's, str>> {
+        self.parse_symbol_bytes(scratch, options, as_str)
     }
 
     fn parse_str_raw<'s>(
@@ -457,9 +970,13 @@ impl<'a> StrRead<'a> {
             delegate: SliceRead::new(s.as_bytes()),
         }
     }
-}
 
-impl<'a> private::Sealed for StrRead<'a> {}
+    /// The line and column of the byte at `offset` in the string. See
+    /// `SliceRead::position_of`.
+    pub fn position_of(&self, offset: usize) -> Position {
+        self.delegate.position_of(offset)
+    }
+}
 
 impl<'a> Read<'a> for StrRead<'a> {
     #[inline]
@@ -489,6 +1006,46 @@ impl<'a> Read<'a> for StrRead<'a> {
         self.delegate.byte_offset()
     }
 
+    fn offset_position(&mut self) -> Position {
+        self.delegate.offset_position()
+    }
+
+    fn ignore_str(&mut self) -> Result<()> {
+        self.delegate.ignore_str()
+    }
+
+    fn ignore_value(&mut self, options: &DeserializerOptions) -> Result<()> {
+        self.delegate.ignore_value(options)
+    }
+
+    fn skip_preamble(&mut self, options: &DeserializerOptions) -> Result<()> {
+        self.delegate.skip_preamble(options)
+    }
+
+    fn begin_raw_buffering(&mut self) {
+        self.delegate.begin_raw_buffering();
+    }
+
+    fn end_raw_buffering(&mut self) -> Result<String> {
+        self.delegate.end_raw_buffering()
+    }
+
+    fn peek_second(&mut self) -> io::Result<Option<u8>> {
+        self.delegate.peek_second()
+    }
+
+    fn peek_third(&mut self) -> io::Result<Option<u8>> {
+        self.delegate.peek_third()
+    }
+
+    fn count_escape(&mut self) {
+        self.delegate.count_escape();
+    }
+
+    fn escapes(&self) -> usize {
+        self.delegate.escapes()
+    }
+
     fn parse_str<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'a, 's, str>> {
         self.delegate.parse_str_bytes(scratch, true, |_, bytes| {
             // The input is assumed to be valid UTF-8 and the \u-escapes are
@@ -497,12 +1054,127 @@ impl<'a> Read<'a> for StrRead<'a> {
         })
     }
 
-    fn parse_symbol<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'a, 's, str>> {
-        self.delegate.parse_symbol_bytes(scratch, |_, bytes| {
-            // The input is assumed to be valid UTF-8 and the \u-escapes are
-            // checked along the way, so don't need to check here.
-            Ok(unsafe { str::from_utf8_unchecked(bytes) })
-        })
+    fn parse_symbol<'s>(
+        &'s mut self,
+        scratch: &'s mut Vec<u8>,
+        options: &DeserializerOptions,
+    ) -> Result<Reference<'a, 's, str>> {
+        self.delegate
+            .parse_symbol_bytes(scratch, options, |_, bytes| {
+                // The input is assumed to be valid UTF-8 and the \u-escapes are
+                // checked along the way, so don't need to check here.
+                Ok(unsafe { str::from_utf8_unchecked(bytes) })
+            })
+    }
+
+    fn parse_str_raw<'s>(
+        &'s mut self,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'a, 's, [u8]>> {
+        self.delegate.parse_str_raw(scratch)
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+#[cfg(feature = "memmap2")]
+impl<'a> MmapRead<'a> {
+    /// Create an S-expression input source to read from a memory map.
+    ///
+    /// The map is borrowed for as long as the values deserialized from it, so
+    /// that they can borrow from it in turn.
+    pub fn new(map: &'a memmap2::Mmap) -> Self {
+        MmapRead {
+            delegate: SliceRead::new(map),
+        }
+    }
+
+    /// The line and column of the byte at `offset` in the map. See
+    /// `SliceRead::position_of`.
+    pub fn position_of(&self, offset: usize) -> Position {
+        self.delegate.position_of(offset)
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl<'a> Read<'a> for MmapRead<'a> {
+    #[inline]
+    fn next(&mut self) -> io::Result<Option<u8>> {
+        self.delegate.next()
+    }
+
+    #[inline]
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        self.delegate.peek()
+    }
+
+    #[inline]
+    fn discard(&mut self) {
+        self.delegate.discard();
+    }
+
+    fn position(&self) -> Position {
+        self.delegate.position()
+    }
+
+    fn peek_position(&self) -> Position {
+        self.delegate.peek_position()
+    }
+
+    fn byte_offset(&self) -> usize {
+        self.delegate.byte_offset()
+    }
+
+    fn offset_position(&mut self) -> Position {
+        self.delegate.offset_position()
+    }
+
+    fn ignore_str(&mut self) -> Result<()> {
+        self.delegate.ignore_str()
+    }
+
+    fn ignore_value(&mut self, options: &DeserializerOptions) -> Result<()> {
+        self.delegate.ignore_value(options)
+    }
+
+    fn skip_preamble(&mut self, options: &DeserializerOptions) -> Result<()> {
+        self.delegate.skip_preamble(options)
+    }
+
+    fn begin_raw_buffering(&mut self) {
+        self.delegate.begin_raw_buffering();
+    }
+
+    fn end_raw_buffering(&mut self) -> Result<String> {
+        self.delegate.end_raw_buffering()
+    }
+
+    fn peek_second(&mut self) -> io::Result<Option<u8>> {
+        self.delegate.peek_second()
+    }
+
+    fn peek_third(&mut self) -> io::Result<Option<u8>> {
+        self.delegate.peek_third()
+    }
+
+    fn count_escape(&mut self) {
+        self.delegate.count_escape();
+    }
+
+    fn escapes(&self) -> usize {
+        self.delegate.escapes()
+    }
+
+    fn parse_str<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'a, 's, str>> {
+        self.delegate.parse_str(scratch)
+    }
+
+    fn parse_symbol<'s>(
+        &'s mut self,
+        scratch: &'s mut Vec<u8>,
+        options: &DeserializerOptions,
+    ) -> Result<Reference<'a, 's, str>> {
+        self.delegate.parse_symbol(scratch, options)
     }
 
     fn parse_str_raw<'s>(
@@ -543,25 +1215,453 @@ static ESCAPE: [bool; 256] = [
     O,  O,  O,  O,  O,  O,  O,  O,  O,  O,  O,  O,  O,  O,  O,  O, // F
 ];
 
-fn next_or_eof<'de, R: Read<'de>>(read: &mut R) -> Result<u8> {
+/// Returns true if `ch` ends a symbol: whitespace, or a byte that closes a
+/// list under the given options. Whitespace outside ASCII is told apart by
+/// `whitespace_len`.
+pub(crate) fn is_symbol_delimiter(ch: u8, options: &DeserializerOptions) -> bool {
+    match ch {
+        b' ' | b'\n' | b'\t' | b'\r' | b')' => true,
+        // Vertical tab and form feed.
+        b'\x0B' | b'\x0C' => !options.strict_whitespace,
+        b']' => options.square_brackets,
+        _ => false,
+    }
+}
+
+/// Returns the length in bytes of the whitespace character that `bytes`
+/// start with, or 0 if they do not start with whitespace. Besides space,
+/// tab, CR and LF, this is vertical tab, form feed and the other characters
+/// Unicode calls whitespace, such as U+00A0 and U+2028, unless
+/// `strict_whitespace` is set.
+pub(crate) fn whitespace_len(bytes: &[u8], options: &DeserializerOptions) -> usize {
+    match *bytes {
+        [b' ', ..] | [b'\n', ..] | [b'\t', ..] | [b'\r', ..] => 1,
+        _ if options.strict_whitespace => 0,
+        [b'\x0B', ..] | [b'\x0C', ..] => 1,
+        // U+0085 and U+00A0.
+        [0xC2, 0x85, ..] | [0xC2, 0xA0, ..] => 2,
+        // U+1680.
+        [0xE1, 0x9A, 0x80, ..] => 3,
+        // U+2000 to U+200A, U+2028, U+2029 and U+202F.
+        [0xE2, 0x80, 0x80..=0x8A, ..] | [0xE2, 0x80, 0xA8, ..] | [0xE2, 0x80, 0xA9, ..] => 3,
+        [0xE2, 0x80, 0xAF, ..] => 3,
+        // U+205F.
+        [0xE2, 0x81, 0x9F, ..] => 3,
+        // U+3000.
+        [0xE3, 0x80, 0x80, ..] => 3,
+        _ => 0,
+    }
+}
+
+/// Returns the length in bytes of the whitespace character at the next byte
+/// of `read`, consuming nothing, as `whitespace_len` measures it.
+pub(crate) fn peek_whitespace_len<'de, R: ?Sized + Read<'de>>(
+    read: &mut R,
+    options: &DeserializerOptions,
+) -> Result<usize> {
+    let first = match read.peek().map_err(Error::io)? {
+        Some(first) => first,
+        None => return Ok(0),
+    };
+    if first < 0x80 {
+        return Ok(whitespace_len(&[first], options));
+    }
+    if options.strict_whitespace || !matches!(first, 0xC2 | 0xE1..=0xE3) {
+        return Ok(0);
+    }
+    let second = read.peek_second().map_err(Error::io)?.unwrap_or(0);
+    let third = if first == 0xC2 {
+        0
+    } else {
+        read.peek_third().map_err(Error::io)?.unwrap_or(0)
+    };
+    Ok(whitespace_len(&[first, second, third], options))
+}
+
+fn opens_list(ch: u8, options: &DeserializerOptions) -> bool {
+    ch == b'(' || (ch == b'[' && options.square_brackets)
+}
+
+fn closes_list(ch: u8, options: &DeserializerOptions) -> bool {
+    ch == b')' || (ch == b']' && options.square_brackets)
+}
+
+/// Returns true if `ch` is the first byte after an atom being ignored. A
+/// symbol ends only where `parse_symbol` would end it; other atoms also end
+/// where a list, string, comment or symbol between bars begins.
+fn ends_atom(ch: u8, symbol: bool, options: &DeserializerOptions) -> bool {
+    is_symbol_delimiter(ch, options)
+        || (!symbol && (ch == b'"' || ch == b';' || ch == b'|' || opens_list(ch, options)))
+}
+
+/// Skips the rest of a symbol written between bars, after the opening bar.
+fn ignore_bar_symbol<'de, R: ?Sized + Read<'de>>(read: &mut R) -> Result<()> {
+    loop {
+        match next_or_eof(read)? {
+            b'|' => return Ok(()),
+            b'\\' => {
+                next_or_eof(read)?;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Records a list opened by `ch` while ignoring a value. The kind of bracket
+/// is only remembered when there is more than one kind to match up.
+fn open_list(ch: u8, options: &DeserializerOptions, depth: &mut usize, opens: &mut Vec<u8>) {
+    *depth += 1;
+    if options.square_brackets {
+        opens.push(ch);
+    }
+}
+
+/// Closes the innermost list opened while ignoring a value.
+fn close_list(
+    ch: u8,
+    options: &DeserializerOptions,
+    depth: &mut usize,
+    opens: &mut Vec<u8>,
+) -> result::Result<(), ErrorCode> {
+    if *depth == 0 {
+        return Err(ErrorCode::ExpectedSomeValue);
+    }
+    if options.square_brackets {
+        match (opens.pop(), ch) {
+            (Some(b'('), b')') | (Some(b'['), b']') => {}
+            _ => return Err(ErrorCode::MismatchedBracket),
+        }
+    }
+    *depth -= 1;
+    Ok(())
+}
+
+fn eof_while_ignoring(depth: usize) -> ErrorCode {
+    if depth == 0 {
+        ErrorCode::EofWhileParsingValue
+    } else {
+        ErrorCode::EofWhileParsingList
+    }
+}
+
+/// What one byte fed to a `Scanner` did to the value around it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Step {
+    /// The byte is inside a value, or between values.
+    Inside,
+    /// The byte starts a top-level value.
+    Start,
+    /// The byte ends a top-level value, such as its closing bracket.
+    End,
+    /// A top-level atom ended just before the byte, which was not taken.
+    /// Feed it again to go on.
+    EndBefore,
+    /// The byte closes a list that was never opened.
+    Unmatched,
+}
+
+/// Where a token being scanned stands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    /// Whitespace, or nothing yet.
+    Space,
+    Comment,
+    /// A string, and whether the last byte was an unescaped backslash.
+    Str(bool),
+    /// A symbol between bars, and whether the last byte was an unescaped
+    /// backslash.
+    Bars(bool),
+    /// Any other atom, and whether it is a symbol, which begins with a
+    /// letter.
+    Atom(bool),
+}
+
+/// Finds where values begin and end in text that arrives a byte at a time,
+/// without decoding them. Brackets are counted, and strings, symbols between
+/// bars, comments and atoms are passed over the way `Read::ignore_value`
+/// skips them. Used by `is_complete` and `IncrementalParser`.
+pub(crate) struct Scanner {
+    token: Token,
+    /// Lists opened and not yet closed.
+    depth: usize,
+    /// The first bytes of the atom being scanned, to tell `#x"..."` and
+    /// `#u8(...)` apart from an atom followed by a string or a list.
+    prefix: [u8; 3],
+    prefix_len: usize,
+    /// Bytes left of a multi-byte whitespace character.
+    skip: usize,
+}
+
+impl Scanner {
+    pub(crate) fn new() -> Self {
+        Scanner {
+            token: Token::Space,
+            depth: 0,
+            prefix: [0; 3],
+            prefix_len: 0,
+            skip: 0,
+        }
+    }
+
+    /// The number of lists opened and not yet closed.
+    pub(crate) fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Whether the text so far ends inside a string or a symbol between
+    /// bars.
+    pub(crate) fn in_string(&self) -> bool {
+        matches!(self.token, Token::Str(_) | Token::Bars(_))
+    }
+
+    /// Whether the text so far ends inside an atom that is not in a list,
+    /// which only the next delimiter or the end of the input will end.
+    pub(crate) fn in_top_level_atom(&self) -> bool {
+        self.depth == 0 && matches!(self.token, Token::Atom(_))
+    }
+
+    /// Whether `bytes`, the rest of the text so far, start with part of a
+    /// character that might be whitespace, so that more text is needed to
+    /// tell where a token ends.
+    pub(crate) fn needs_more(&self, bytes: &[u8], options: &DeserializerOptions) -> bool {
+        let needed = match bytes.first() {
+            Some(0xC2) => 2,
+            Some(0xE1..=0xE3) => 3,
+            _ => return false,
+        };
+        self.skip == 0
+            && !options.strict_whitespace
+            && matches!(self.token, Token::Space | Token::Atom(_))
+            && bytes.len() < needed
+    }
+
+    /// Feeds the first byte of `bytes`, the rest of the text so far.
+    pub(crate) fn step(&mut self, bytes: &[u8], options: &DeserializerOptions) -> Step {
+        let ch = bytes[0];
+        if self.skip > 0 {
+            self.skip -= 1;
+            return Step::Inside;
+        }
+        match self.token {
+            Token::Comment => {
+                if ch == b'\n' || ch == b'\r' {
+                    self.token = Token::Space;
+                }
+                Step::Inside
+            }
+            Token::Str(escape) | Token::Bars(escape) => {
+                let close = if let Token::Str(_) = self.token {
+                    b'"'
+                } else {
+                    b'|'
+                };
+                if escape {
+                    self.token = self.with_escape(false);
+                } else if ch == b'\\' {
+                    self.token = self.with_escape(true);
+                } else if ch == close {
+                    self.token = Token::Space;
+                    if self.depth == 0 {
+                        return Step::End;
+                    }
+                }
+                Step::Inside
+            }
+            Token::Atom(symbol) => {
+                if !ends_atom(ch, symbol, options) && whitespace_len(bytes, options) == 0 {
+                    if self.prefix_len < self.prefix.len() {
+                        self.prefix[self.prefix_len] = ch;
+                        self.prefix_len += 1;
+                    }
+                    return Step::Inside;
+                }
+                // A string, list or symbol between bars that carries on the
+                // same value, as in `#x"FF"`, `#u8(1 2)` and `#:|a b|`.
+                let prefix = &self.prefix[..self.prefix_len];
+                if (ch == b'"' && prefix == b"#x")
+                    || (opens_list(ch, options) && prefix == b"#u8")
+                    || (ch == b'|' && !symbol)
+                {
+                    self.token = Token::Space;
+                    self.step(bytes, options);
+                    return Step::Inside;
+                }
+                self.token = Token::Space;
+                if self.depth == 0 {
+                    Step::EndBefore
+                } else {
+                    self.step(bytes, options)
+                }
+            }
+            Token::Space => {
+                let space = whitespace_len(bytes, options);
+                if space > 0 {
+                    self.skip = space - 1;
+                    return Step::Inside;
+                }
+                let top_level = self.depth == 0;
+                match ch {
+                    b';' => {
+                        self.token = Token::Comment;
+                        return Step::Inside;
+                    }
+                    b'"' => self.token = Token::Str(false),
+                    b'|' => self.token = Token::Bars(false),
+                    _ if opens_list(ch, options) => self.depth += 1,
+                    _ if closes_list(ch, options) => {
+                        if top_level {
+                            return Step::Unmatched;
+                        }
+                        self.depth -= 1;
+                        return if self.depth == 0 {
+                            Step::End
+                        } else {
+                            Step::Inside
+                        };
+                    }
+                    _ => {
+                        self.token = Token::Atom(ch.is_ascii_alphabetic());
+                        self.prefix[0] = ch;
+                        self.prefix_len = 1;
+                    }
+                }
+                if top_level {
+                    Step::Start
+                } else {
+                    Step::Inside
+                }
+            }
+        }
+    }
+
+    fn with_escape(&self, escape: bool) -> Token {
+        match self.token {
+            Token::Str(_) => Token::Str(escape),
+            _ => Token::Bars(escape),
+        }
+    }
+}
+
+fn fold_case(ch: u8, options: &DeserializerOptions) -> u8 {
+    if options.case_insensitive {
+        ch.to_ascii_lowercase()
+    } else {
+        ch
+    }
+}
+
+/// Parses a string byte by byte into the scratch space, for sources that
+/// cannot lend out slices of their input.
+fn parse_str_bytes<'de, 's, R, T, F>(
+    read: &'s mut R,
+    scratch: &'s mut Vec<u8>,
+    validate: bool,
+    result: F,
+) -> Result<T>
+where
+    R: ?Sized + Read<'de>,
+    T: 's,
+    F: FnOnce(&'s R, &'s [u8]) -> Result<T>,
+{
+    loop {
+        let ch = next_or_eof(read)?;
+        if !ESCAPE[ch as usize] {
+            scratch.push(ch);
+            continue;
+        }
+        match ch {
+            b'"' => {
+                return result(read, scratch);
+            }
+            b'\\' => {
+                parse_escape(read, scratch)?;
+            }
+            _ => {
+                if validate {
+                    return error(read, ErrorCode::InvalidUnicodeCodePoint);
+                }
+                scratch.push(ch);
+            }
+        }
+    }
+}
+
+/// Parses a symbol byte by byte into the scratch space, for sources that
+/// cannot lend out slices of their input.
+fn parse_symbol_bytes<'de, 's, R, T, F>(
+    read: &'s mut R,
+    scratch: &'s mut Vec<u8>,
+    options: &DeserializerOptions,
+    result: F,
+) -> Result<T>
+where
+    R: ?Sized + Read<'de>,
+    T: 's,
+    F: FnOnce(&'s R, &'s [u8]) -> Result<T>,
+{
+    loop {
+        // The delimiter is left in place so that the caller can see the
+        // end of the enclosing list.
+        match read.peek().map_err(Error::io)? {
+            Some(ch)
+                if !is_symbol_delimiter(ch, options)
+                    && (ch < 0x80 || peek_whitespace_len(read, options)? == 0) =>
+            {
+                read.discard();
+                scratch.push(fold_case(ch, options));
+            }
+            _ => return result(read, scratch),
+        }
+    }
+}
+
+/// The UTF-8 encoding of U+FEFF, which some editors put at the start of a
+/// file.
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+fn skip_bom<'de, R: ?Sized + Read<'de>>(read: &mut R) -> Result<()> {
+    if read.peek().map_err(Error::io)? != Some(BOM[0]) {
+        return Ok(());
+    }
+    read.discard();
+    for &expected in &BOM[1..] {
+        if read.next().map_err(Error::io)? != Some(expected) {
+            return error(read, ErrorCode::InvalidUnicodeCodePoint);
+        }
+    }
+    Ok(())
+}
+
+/// Consumes everything up to and including the next newline.
+fn skip_line<'de, R: ?Sized + Read<'de>>(read: &mut R) -> Result<()> {
+    while let Some(ch) = read.next().map_err(Error::io)? {
+        if ch == b'\n' {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn next_or_eof<'de, R: ?Sized + Read<'de>>(read: &mut R) -> Result<u8> {
     match read.next().map_err(Error::io)? {
         Some(b) => Ok(b),
         None => error(read, ErrorCode::EofWhileParsingString),
     }
 }
 
-fn error<'de, R: Read<'de>, T>(read: &R, reason: ErrorCode) -> Result<T> {
+fn error<'de, R: ?Sized + Read<'de>, T>(read: &R, reason: ErrorCode) -> Result<T> {
     let pos = read.position();
     Err(Error::syntax(reason, pos.line, pos.column))
 }
 
-fn as_str<'de, 's, R: Read<'de>>(read: &R, slice: &'s [u8]) -> Result<&'s str> {
+fn as_str<'de, 's, R: ?Sized + Read<'de>>(read: &R, slice: &'s [u8]) -> Result<&'s str> {
     str::from_utf8(slice).or_else(|_| error(read, ErrorCode::InvalidUnicodeCodePoint))
 }
 
 /// Parses a JSON escape sequence and appends it into the scratch space. Assumes
 /// the previous byte read was a backslash.
-fn parse_escape<'de, R: Read<'de>>(read: &mut R, scratch: &mut Vec<u8>) -> Result<()> {
+fn parse_escape<'de, R: ?Sized + Read<'de>>(read: &mut R, scratch: &mut Vec<u8>) -> Result<()> {
+    read.count_escape();
     let ch = next_or_eof(read)?;
 
     match ch {
@@ -619,6 +1719,13 @@ fn parse_escape<'de, R: Read<'de>>(read: &mut R, scratch: &mut Vec<u8>) -> Resul
             buf.push(c);
             scratch.extend(buf.bytes());
         }
+        // An R7RS hex escape such as `\x3bb;`, as `NonAsciiChars::EscapeHex`
+        // writes.
+        b'x' => {
+            let c = decode_r7rs_hex_escape(read)?;
+            let mut buf = [0; 4];
+            scratch.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
         _ => {
             return error(read, ErrorCode::InvalidEscape);
         }
@@ -627,7 +1734,27 @@ fn parse_escape<'de, R: Read<'de>>(read: &mut R, scratch: &mut Vec<u8>) -> Resul
     Ok(())
 }
 
-fn decode_hex_escape<'de, R: Read<'de>>(read: &mut R) -> Result<u16> {
+/// Reads the hex digits and `;` of an R7RS escape such as `\x3bb;`. Assumes
+/// the `\x` was read.
+fn decode_r7rs_hex_escape<'de, R: ?Sized + Read<'de>>(read: &mut R) -> Result<char> {
+    let mut n = 0u32;
+    let mut digits = 0;
+    loop {
+        let digit = match next_or_eof(read)? {
+            b';' if digits > 0 => break,
+            c => (c as char).to_digit(16),
+        };
+        match digit {
+            // Six digits hold any code point.
+            Some(digit) if digits < 6 => n = n * 16 + digit,
+            _ => return error(read, ErrorCode::InvalidEscape),
+        }
+        digits += 1;
+    }
+    char::from_u32(n).map_or_else(|| error(read, ErrorCode::InvalidUnicodeCodePoint), Ok)
+}
+
+fn decode_hex_escape<'de, R: ?Sized + Read<'de>>(read: &mut R) -> Result<u16> {
     let mut n = 0;
     for _ in 0..4 {
         n = match next_or_eof(read)? {
@@ -0,0 +1,438 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Re-printing S-expression text in a consistent layout.
+//!
+//! Unlike the deserializer this reads text without interpreting it: every
+//! atom is kept exactly as written, and comments and blank lines are kept
+//! where they were. Only the whitespace between elements changes.
+
+use crate::error::{Error, ErrorCode, Result};
+
+/// How `reformat` lays out S-expression text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatStyle {
+    indent: usize,
+    max_width: usize,
+    max_blank_lines: usize,
+}
+
+impl FormatStyle {
+    /// Creates the default style: lists indented by two spaces, lines up to
+    /// 80 columns, and at most one blank line kept in a row.
+    pub fn new() -> Self {
+        FormatStyle {
+            indent: 2,
+            max_width: 80,
+            max_blank_lines: 1,
+        }
+    }
+
+    /// Sets the number of spaces the elements of a list that does not fit on
+    /// one line are indented by, relative to its opening bracket.
+    pub fn with_indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Sets the column a list has to end within to be written on one line.
+    pub fn with_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Sets how many blank lines in a row are kept. Longer runs of blank
+    /// lines are shortened to this many.
+    pub fn with_max_blank_lines(mut self, max_blank_lines: usize) -> Self {
+        self.max_blank_lines = max_blank_lines;
+        self
+    }
+}
+
+impl Default for FormatStyle {
+    fn default() -> Self {
+        FormatStyle::new()
+    }
+}
+
+/// Re-prints S-expression text in the given style, keeping comments.
+///
+/// A list is written on one line if it fits within the maximum width and
+/// holds no comments. Otherwise a list of atoms is filled, as many to a line
+/// as fit, and any other list has one element to a line, except that an
+/// atom at its head and the element after it stay on the first line, as in
+/// `(define (f x)`. The closing bracket follows the last element. Atoms,
+/// including strings and prefixes such as `'` or `#`, are copied exactly as
+/// written, so the output reads back as the same values.
+///
+/// ```rust
+/// use sexpr::FormatStyle;
+///
+/// let input = "(define (square x)   ; squares x\n(* x x))\n\n\n(square 3)";
+/// let output = sexpr::reformat(input, &FormatStyle::new().with_max_width(20)).unwrap();
+/// assert_eq!(
+///     output,
+///     "(define (square x) ; squares x\n  (* x x))\n\n(square 3)\n"
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Fails if a bracket, string or symbol between bars is left open, or if
+/// brackets do not match.
+pub fn reformat(input: &str, style: &FormatStyle) -> Result<String> {
+    let mut parser = Parser { input, index: 0 };
+    let nodes = parser.parse_items(None, 0)?;
+
+    let mut printer = Printer {
+        style,
+        out: String::with_capacity(input.len()),
+        column: 0,
+    };
+    printer.print_lines(&nodes, 0, true);
+    if !printer.out.is_empty() {
+        printer.out.push('\n');
+    }
+    Ok(printer.out)
+}
+
+/// The same limit the deserializer puts on nesting.
+const MAX_DEPTH: usize = 128;
+
+struct Node<'a> {
+    /// Blank lines between this node and the one before it.
+    blank_lines: usize,
+    kind: Kind<'a>,
+}
+
+enum Kind<'a> {
+    Atom(&'a str),
+    /// A `;` comment, without the line break ending it. A trailing comment
+    /// was on the same line as the text before it.
+    Comment {
+        text: &'a str,
+        trailing: bool,
+    },
+    /// A list with the text glued to its opening bracket, such as `'` or
+    /// `#`.
+    List {
+        prefix: &'a str,
+        open: char,
+        close: char,
+        children: Vec<Node<'a>>,
+    },
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    index: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.index).cloned()
+    }
+
+    /// Parses the elements up to the closing bracket `close`, or to the end
+    /// of the input at the top level.
+    fn parse_items(&mut self, close: Option<u8>, depth: usize) -> Result<Vec<Node<'a>>> {
+        let mut nodes = Vec::new();
+        loop {
+            let mut newlines = 0usize;
+            while let Some(ch) = self.peek() {
+                match ch {
+                    b'\n' => newlines += 1,
+                    b' ' | b'\t' | b'\r' => {}
+                    _ => break,
+                }
+                self.index += 1;
+            }
+            let blank_lines = if nodes.is_empty() && close.is_none() {
+                0
+            } else {
+                newlines.saturating_sub(1)
+            };
+
+            let ch = match self.peek() {
+                Some(ch) => ch,
+                None if close.is_some() => return Err(self.error(ErrorCode::EofWhileParsingList)),
+                None => return Ok(nodes),
+            };
+            let kind = match ch {
+                b';' => {
+                    let start = self.index;
+                    let end = match self.input[start..].find('\n') {
+                        Some(len) => start + len,
+                        None => self.input.len(),
+                    };
+                    self.index = end;
+                    Kind::Comment {
+                        text: self.input[start..end].trim_end(),
+                        trailing: newlines == 0 && (!nodes.is_empty() || close.is_some()),
+                    }
+                }
+                b')' | b']' => {
+                    if Some(ch) == close {
+                        self.index += 1;
+                        return Ok(nodes);
+                    }
+                    return Err(self.error(match close {
+                        Some(_) => ErrorCode::MismatchedBracket,
+                        None => ErrorCode::ExpectedSomeValue,
+                    }));
+                }
+                _ => self.parse_element(depth)?,
+            };
+            nodes.push(Node { blank_lines, kind });
+        }
+    }
+
+    /// Parses an atom, or a list together with any prefix glued to it.
+    fn parse_element(&mut self, depth: usize) -> Result<Kind<'a>> {
+        let start = self.index;
+        loop {
+            match self.peek() {
+                None | Some(b' ') | Some(b'\n') | Some(b'\t') | Some(b'\r') | Some(b';')
+                | Some(b')') | Some(b']') => break,
+                Some(open @ b'(') | Some(open @ b'[') => {
+                    if depth + 1 >= MAX_DEPTH {
+                        return Err(self.error(ErrorCode::RecursionLimitExceeded));
+                    }
+                    let prefix = &self.input[start..self.index];
+                    let close = if open == b'(' { b')' } else { b']' };
+                    self.index += 1;
+                    let children = self.parse_items(Some(close), depth + 1)?;
+                    return Ok(Kind::List {
+                        prefix,
+                        open: open as char,
+                        close: close as char,
+                        children,
+                    });
+                }
+                Some(quote @ b'"') | Some(quote @ b'|') => self.skip_quoted(quote)?,
+                Some(_) => self.index += 1,
+            }
+        }
+        Ok(Kind::Atom(&self.input[start..self.index]))
+    }
+
+    /// Skips a string or a symbol between bars, from its opening quote.
+    fn skip_quoted(&mut self, quote: u8) -> Result<()> {
+        self.index += 1;
+        loop {
+            match self.peek() {
+                None => return Err(self.error(ErrorCode::EofWhileParsingString)),
+                Some(b'\\') => self.index += 2,
+                Some(ch) => {
+                    self.index += 1;
+                    if ch == quote {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    fn error(&self, code: ErrorCode) -> Error {
+        let before = &self.input.as_bytes()[..self.index.min(self.input.len())];
+        let line = 1 + before.iter().filter(|&&ch| ch == b'\n').count();
+        let column = match before.iter().rposition(|&ch| ch == b'\n') {
+            Some(newline) => before.len() - newline,
+            None => before.len() + 1,
+        };
+        Error::syntax(code, line, column)
+    }
+}
+
+struct Printer<'s> {
+    style: &'s FormatStyle,
+    out: String,
+    /// Column of the end of `out`, counted in characters.
+    column: usize,
+}
+
+impl<'s> Printer<'s> {
+    fn push(&mut self, text: &str) {
+        match text.rfind('\n') {
+            Some(newline) => self.column = text[newline + 1..].chars().count(),
+            None => self.column += text.chars().count(),
+        }
+        self.out.push_str(text);
+    }
+
+    fn newline(&mut self, blank_lines: usize, indent: usize) {
+        for _ in 0..1 + blank_lines.min(self.style.max_blank_lines) {
+            self.out.push('\n');
+        }
+        self.out.extend((0..indent).map(|_| ' '));
+        self.column = indent;
+    }
+
+    /// Whether a node written on one line still ends within the maximum
+    /// width when it starts `gap` columns after the current one.
+    fn fits(&self, node: &Node, gap: usize) -> bool {
+        match flat_width(node, self.style.max_blank_lines > 0) {
+            Some(width) => self.column + gap + width <= self.style.max_width,
+            None => false,
+        }
+    }
+
+    /// Prints nodes that each start a line at column `indent`, except for
+    /// trailing comments, which stay on the line before, and the first node
+    /// if `continue_line` is set.
+    fn print_lines(&mut self, nodes: &[Node], indent: usize, continue_line: bool) {
+        for (i, node) in nodes.iter().enumerate() {
+            match node.kind {
+                Kind::Comment {
+                    trailing: true,
+                    text,
+                } => {
+                    self.push(" ");
+                    self.push(text);
+                }
+                _ if i == 0 && continue_line => self.print(node),
+                _ => {
+                    self.newline(node.blank_lines, indent);
+                    self.print(node);
+                }
+            }
+        }
+    }
+
+    fn print(&mut self, node: &Node) {
+        match node.kind {
+            Kind::Atom(text) | Kind::Comment { text, .. } => self.push(text),
+            Kind::List {
+                prefix,
+                open,
+                close,
+                ref children,
+            } => {
+                if self.fits(node, 0) {
+                    self.print_flat(node);
+                    return;
+                }
+                self.push(prefix);
+                let open_column = self.column;
+                let indent = open_column + self.style.indent;
+                self.out.push(open);
+                self.column += 1;
+                if children.iter().all(is_flat_atom) {
+                    self.print_filled(children, indent);
+                } else {
+                    self.print_broken(children, indent);
+                }
+                if let Some(&Node {
+                    kind: Kind::Comment { .. },
+                    ..
+                }) = children.last()
+                {
+                    // The comment runs to the end of its line.
+                    self.newline(0, open_column);
+                }
+                self.out.push(close);
+                self.column += 1;
+            }
+        }
+    }
+
+    /// Prints the elements of a list of atoms as many to a line as fit.
+    fn print_filled(&mut self, children: &[Node], indent: usize) {
+        for (i, child) in children.iter().enumerate() {
+            if i > 0 {
+                if !self.starts_line(child) && self.fits(child, 1) {
+                    self.push(" ");
+                } else {
+                    self.newline(child.blank_lines, indent);
+                }
+            }
+            self.print(child);
+        }
+    }
+
+    /// Prints the elements of a list one to a line. An atom at the head of
+    /// the list stays on the line of the opening bracket, and so does the
+    /// element after it if it fits, as in `(define (f x)`.
+    fn print_broken(&mut self, children: &[Node], indent: usize) {
+        let mut rest = children;
+        if let Some(head) = children.first().filter(|head| !self.starts_line(head)) {
+            self.print(head);
+            rest = &children[1..];
+            if let (Kind::Atom(_), Some(second)) = (&head.kind, rest.first()) {
+                if !self.starts_line(second) && self.fits(second, 1) {
+                    self.push(" ");
+                    self.print_flat(second);
+                    rest = &rest[1..];
+                }
+            }
+        }
+        self.print_lines(rest, indent, false);
+    }
+
+    /// Whether a node goes on a line of its own even where it could follow the
+    /// text before it.
+    fn starts_line(&self, node: &Node) -> bool {
+        (node.blank_lines > 0 && self.style.max_blank_lines > 0)
+            || matches!(node.kind, Kind::Comment { .. })
+    }
+
+    fn print_flat(&mut self, node: &Node) {
+        match node.kind {
+            Kind::Atom(text) | Kind::Comment { text, .. } => self.push(text),
+            Kind::List {
+                prefix,
+                open,
+                close,
+                ref children,
+            } => {
+                self.push(prefix);
+                self.out.push(open);
+                self.column += 1;
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        self.push(" ");
+                    }
+                    self.print_flat(child);
+                }
+                self.out.push(close);
+                self.column += 1;
+            }
+        }
+    }
+}
+
+fn is_flat_atom(node: &Node) -> bool {
+    match node.kind {
+        Kind::Atom(text) => !text.contains('\n'),
+        _ => false,
+    }
+}
+
+/// The width of a node written on one line, or `None` if it cannot be
+/// written on one line because it holds a comment, a line break or, if they
+/// are kept, a blank line.
+fn flat_width(node: &Node, keep_blank_lines: bool) -> Option<usize> {
+    match node.kind {
+        Kind::Atom(text) if !text.contains('\n') => Some(text.chars().count()),
+        Kind::Atom(_) | Kind::Comment { .. } => None,
+        Kind::List {
+            prefix,
+            ref children,
+            ..
+        } => {
+            let mut width = prefix.chars().count() + 2 + children.len().saturating_sub(1);
+            for child in children {
+                if keep_blank_lines && child.blank_lines > 0 {
+                    return None;
+                }
+                width += flat_width(child, keep_blank_lines)?;
+            }
+            Some(width)
+        }
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A wrapper that stands for the rest of the elements of a list.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+/// Name of the newtype struct a `Rest` passes through serde as. The
+/// S-expression serializer and deserializer splice its elements into the
+/// enclosing list rather than nesting them in a list of their own.
+pub(crate) const REST_TOKEN: &str = "$sexpr::private::Rest";
+
+/// The elements of a list left after the ones before it, for reading forms
+/// such as `(define x 1)` whose head says how to read what follows.
+///
+/// As the last field of a tuple or tuple struct, a `Rest` takes every
+/// element of the list that the fields before it leave, so `(f 1 2 3)` reads
+/// as `(Symbol, Rest<Sexp>)` with three elements in the `Rest`, and `(f)`
+/// with none. Writing splices the elements back into the list the same way.
+///
+/// ```rust
+/// use serde_derive::{Deserialize, Serialize};
+/// use sexpr::sexp::Symbol;
+/// use sexpr::{Rest, Sexp};
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// struct Call(Symbol, Rest<Sexp>);
+///
+/// let call: Call = sexpr::from_str("(add 1 (mul 2 3))").unwrap();
+/// assert_eq!(call.0.as_str(), "add");
+/// assert_eq!(call.1.len(), 2);
+/// assert_eq!(call.1[1].to_string(), "(mul 2 3)");
+/// assert_eq!(sexpr::to_string(&call).unwrap(), "(add 1 (mul 2 3))");
+/// ```
+///
+/// Outside of a list, and with serializers and deserializers of other
+/// formats, a `Rest` is an ordinary sequence. A struct with named fields is
+/// read from an alist rather than by position, so a form headed by a symbol
+/// is read as a tuple struct.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Rest<T>(pub Vec<T>);
+
+impl<T> From<Vec<T>> for Rest<T> {
+    fn from(elems: Vec<T>) -> Self {
+        Rest(elems)
+    }
+}
+
+impl<T> From<Rest<T>> for Vec<T> {
+    fn from(rest: Rest<T>) -> Self {
+        rest.0
+    }
+}
+
+impl<T> Deref for Rest<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Rest<T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.0
+    }
+}
+
+impl<T: Serialize> Serialize for Rest<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(REST_TOKEN, &self.0)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Rest<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(REST_TOKEN, RestVisitor(PhantomData))
+    }
+}
+
+struct RestVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for RestVisitor<T> {
+    type Value = Rest<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("the rest of a list")
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Rest<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::deserialize(deserializer).map(Rest)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Rest<T>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut elems = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(elem) = seq.next_element()? {
+            elems.push(elem);
+        }
+        Ok(Rest(elems))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Rest<T>, E> {
+        Ok(Rest(Vec::new()))
+    }
+}
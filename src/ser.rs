@@ -8,21 +8,158 @@
 
 //! Serialize a Rust data structure into S-expression data.
 
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt;
 use std::io;
+use std::mem;
 use std::num::FpCategory;
 use std::str;
+use std::sync::Arc;
 
 use super::error::{Error, ErrorCode, Result};
+use crate::atom::{KEYWORD_TOKEN, SYMBOL_TOKEN};
+use crate::bytes::{BASE64_BYTES_TOKEN, HEX_BYTES_TOKEN};
+use crate::number::NUMBER_TOKEN;
+use crate::raw::RAW_TOKEN;
+use crate::rest::REST_TOKEN;
+use crate::sexp::{DocumentItem, SexpDocument, PAIR_TOKEN};
 use serde::ser::{self, Impossible};
 
 use dtoa;
 use itoa;
 
 /// A structure for serializing Rust values into S-expression.
+///
+/// Struct fields are written in the order they are declared, and map entries
+/// in the order the map iterates over them, so a `BTreeMap` comes out sorted
+/// while a `HashMap` comes out in an arbitrary order that can change from run
+/// to run. `with_sort_keys` sorts map entries for output that is stable
+/// whatever the map type.
 pub struct Serializer<W, F = CompactFormatter> {
-    writer: W,
+    writer: Sink<W>,
     formatter: F,
+    non_finite_floats: NonFiniteFloats,
+    /// Overrides the formatter's `FloatStyle`, if set.
+    float_style: Option<FloatStyle>,
+    always_decimal_point: bool,
+    non_ascii_chars: NonAsciiChars,
+    sort_keys: bool,
+    unit_symbols: bool,
+    nil_style: NilStyle,
+    bytes_style: BytesStyle,
+    hex_style: HexStyle,
+    datum_labels: Option<DatumLabels>,
+    symbol_case: SymbolCase,
+    invalid_symbols: InvalidSymbols,
+    keyword_style: KeywordStyle,
+    depth: usize,
+    /// The index of the element being written in the innermost list, where a
+    /// `Rest` written as that element splices its elements in.
+    rest_index: Option<usize>,
+    /// The index a `Rest` starts at, for the sequence inside it.
+    splice_at: Option<usize>,
+    /// How many elements the `Rest` just written spliced into its list.
+    spliced: Option<usize>,
+    /// Whether the list about to be written is the car and cdr of a pair.
+    dotted: bool,
+}
+
+/// The atoms a serializer has written, for `with_datum_labels`. An atom is
+/// remembered by its written text, so a symbol and a string of the same
+/// name are told apart.
+struct DatumLabels {
+    min_len: usize,
+    atoms: HashMap<Vec<u8>, Option<u64>>,
+    next: u64,
+}
+
+enum DatumLabel {
+    /// Write the atom as it is.
+    None,
+    /// Write `#N=` before the atom.
+    Define(u64),
+    /// Write `#N#` instead of the atom.
+    Refer(u64),
+}
+
+impl DatumLabels {
+    fn label(&mut self, token: &[u8]) -> DatumLabel {
+        if token.len() < self.min_len {
+            return DatumLabel::None;
+        }
+        match self.atoms.get_mut(token) {
+            // Labeled the first time it repeats, since most long atoms in a
+            // stream of records are written once or many times.
+            Some(label @ None) => {
+                let n = self.next;
+                self.next += 1;
+                *label = Some(n);
+                DatumLabel::Define(n)
+            }
+            Some(Some(n)) => DatumLabel::Refer(*n),
+            None => {
+                self.atoms.insert(token.to_vec(), None);
+                DatumLabel::None
+            }
+        }
+    }
+}
+
+/// The serializer's writer, which can instead collect what is written into
+/// buffers, so that map entries can be sorted before they are written.
+struct Sink<W> {
+    inner: W,
+    captures: Vec<Vec<u8>>,
+    /// What the formatter wrote before a list element, held back until the
+    /// element writes something, so that an empty `Rest` leaves no gap.
+    pending: Vec<u8>,
+}
+
+impl<W: io::Write> Sink<W> {
+    fn write_pending(&mut self) -> io::Result<()> {
+        let result = match self.captures.last_mut() {
+            Some(capture) => {
+                capture.extend_from_slice(&self.pending);
+                Ok(())
+            }
+            None => self.inner.write_all(&self.pending),
+        };
+        self.pending.clear();
+        result
+    }
+}
+
+impl<W: io::Write> io::Write for Sink<W> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.pending.is_empty() {
+            self.write_pending()?;
+        }
+        match self.captures.last_mut() {
+            Some(capture) => capture.write(buf),
+            None => self.inner.write(buf),
+        }
+    }
+
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.write_pending()?;
+        }
+        match self.captures.last_mut() {
+            Some(capture) => capture.write_all(buf),
+            None => self.inner.write_all(buf),
+        }
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        match self.captures.last_mut() {
+            Some(_) => Ok(()),
+            None => self.inner.flush(),
+        }
+    }
 }
 
 impl<W> Serializer<W>
@@ -56,14 +193,699 @@ where
     /// specified.
     #[inline]
     pub fn with_formatter(writer: W, formatter: F) -> Self {
-        Serializer { writer, formatter }
+        Serializer {
+            writer: Sink {
+                inner: writer,
+                captures: Vec::new(),
+                pending: Vec::new(),
+            },
+            formatter,
+            non_finite_floats: NonFiniteFloats::Error,
+            float_style: None,
+            always_decimal_point: false,
+            non_ascii_chars: NonAsciiChars::Write,
+            sort_keys: false,
+            unit_symbols: false,
+            nil_style: NilStyle::Hash,
+            bytes_style: BytesStyle::Hex,
+            hex_style: HexStyle::Prefixed,
+            datum_labels: None,
+            symbol_case: SymbolCase::Preserve,
+            invalid_symbols: InvalidSymbols::Quote,
+            keyword_style: KeywordStyle::Hash,
+            depth: 0,
+            rest_index: None,
+            splice_at: None,
+            spliced: None,
+            dotted: false,
+        }
+    }
+
+    /// Sets what the serializer does with floating point values that are NaN
+    /// or infinite. By default it fails with an error.
+    #[inline]
+    pub fn with_non_finite_floats(mut self, non_finite_floats: NonFiniteFloats) -> Self {
+        self.non_finite_floats = non_finite_floats;
+        self
+    }
+
+    /// Writes floats in the given style, whatever the formatter's
+    /// `float_style` says, so that compact output can use one too.
+    ///
+    /// ```rust
+    /// use serde::Serialize;
+    /// use sexpr::ser::FloatStyle;
+    /// use sexpr::Serializer;
+    ///
+    /// let mut ser = Serializer::new(Vec::new()).with_float_style(FloatStyle::Trimmed(3));
+    /// (1.25, 2.0, 0.1234).serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.into_inner(), b"(1.25 2 0.123)");
+    /// ```
+    #[inline]
+    pub fn with_float_style(mut self, float_style: FloatStyle) -> Self {
+        self.float_style = Some(float_style);
+        self
+    }
+
+    /// Makes every finite float carry a decimal point, so that no reader
+    /// takes it for an exact integer.
+    ///
+    /// Whether a float has one otherwise depends on its `FloatStyle`.
+    /// `Shortest` writes an integral value such as `3.0` with a point, but
+    /// switches to exponent form without one for very large or small
+    /// magnitudes, as in `1e300` and `1e-7`. `Trimmed` and `Fixed(0)` write
+    /// integral values as bare integers like `3`, and `Scientific` writes
+    /// `3e0`. With this set, `.0` is added to the digits before any
+    /// exponent: `3.0`, `1.0e300`, `3.0e0`.
+    ///
+    /// ```rust
+    /// use serde::Serialize;
+    /// use sexpr::ser::{FloatStyle, PrettyFormatter};
+    /// use sexpr::Serializer;
+    ///
+    /// let formatter = PrettyFormatter::new().with_float_style(FloatStyle::Trimmed(2));
+    /// let mut ser = Serializer::with_formatter(Vec::new(), formatter).with_always_decimal_point(true);
+    /// (2.0, 2.5, 1e300).serialize(&mut ser).unwrap();
+    /// let text = String::from_utf8(ser.into_inner()).unwrap();
+    /// assert!(text.starts_with("(\n  2.0\n  2.5\n  1"));
+    /// assert!(text.ends_with(".0\n)"));
+    /// ```
+    #[inline]
+    pub fn with_always_decimal_point(mut self, always_decimal_point: bool) -> Self {
+        self.always_decimal_point = always_decimal_point;
+        self
+    }
+
+    /// Sets how the serializer writes characters outside ASCII in strings.
+    /// By default they are written as UTF-8.
+    ///
+    /// ```rust
+    /// # use serde::Serialize;
+    /// use sexpr::ser::{NonAsciiChars, Serializer};
+    ///
+    /// let mut ser = Serializer::new(Vec::new()).with_non_ascii_chars(NonAsciiChars::EscapeHex);
+    /// "λx".serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.into_inner(), b"\"\\x3bb;x\"");
+    /// ```
+    #[inline]
+    pub fn with_non_ascii_chars(mut self, non_ascii_chars: NonAsciiChars) -> Self {
+        self.non_ascii_chars = non_ascii_chars;
+        self
+    }
+
+    /// Sets whether the entries of a map are written sorted by the text of
+    /// their keys, rather than in the order the map gives them. By default
+    /// they are not sorted. Struct fields keep their declared order either
+    /// way.
+    ///
+    /// ```rust
+    /// # use serde::Serialize;
+    /// use std::collections::HashMap;
+    /// use sexpr::Serializer;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("b", 2);
+    /// map.insert("c", 3);
+    /// map.insert("a", 1);
+    ///
+    /// let mut ser = Serializer::new(Vec::new()).with_sort_keys(true);
+    /// map.serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.into_inner(), b"(\"a\".1 \"b\".2 \"c\".3)");
+    /// ```
+    #[inline]
+    pub fn with_sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Sets whether unit structs and unit variants are written as bare
+    /// symbols, as Lisp data usually names such constants. A unit struct is
+    /// written as its name in lower case with words split by `-`, and a unit
+    /// variant as its name unchanged. By default a unit struct is written as
+    /// `#nil` and a unit variant as a string.
+    ///
+    /// ```rust
+    /// use serde::Serialize as _;
+    /// use serde_derive::Serialize;
+    /// use sexpr::Serializer;
+    ///
+    /// #[derive(Serialize)]
+    /// struct EndMarker;
+    ///
+    /// #[derive(Serialize)]
+    /// enum Animal {
+    ///     Dog,
+    /// }
+    ///
+    /// let mut ser = Serializer::new(Vec::new()).with_unit_symbols(true);
+    /// (EndMarker, Animal::Dog).serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.into_inner(), b"(end-marker Dog)");
+    /// ```
+    #[inline]
+    pub fn with_unit_symbols(mut self, unit_symbols: bool) -> Self {
+        self.unit_symbols = unit_symbols;
+        self
+    }
+
+    /// Sets the token written for nil: a unit, a `None` or a `Sexp::Nil`. By
+    /// default it is `#nil`, which reads back as nil with any options. The
+    /// other styles suit Lisp readers, and read back as nil with the
+    /// matching `DeserializerOptions`.
+    ///
+    /// ```rust
+    /// # use serde::Serialize;
+    /// use sexpr::ser::{NilStyle, Serializer};
+    ///
+    /// let mut ser = Serializer::new(Vec::new()).with_nil_style(NilStyle::EmptyList);
+    /// (1, None::<u8>).serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.into_inner(), b"(1 ())");
+    /// ```
+    #[inline]
+    pub fn with_nil_style(mut self, nil_style: NilStyle) -> Self {
+        self.nil_style = nil_style;
+        self
+    }
+
+    /// Sets how byte strings, such as a `serde_bytes::ByteBuf`, are written.
+    /// By default they are written in hex, which reads back into a `Sexp` as
+    /// `Sexp::Bytes`. Whatever the style, a byte string type reads back from
+    /// any of them.
+    ///
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_bytes::Bytes;
+    /// use sexpr::ser::{BytesStyle, Serializer};
+    ///
+    /// let mut ser = Serializer::new(Vec::new()).with_bytes_style(BytesStyle::Base64);
+    /// Bytes::new(b"hello").serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.into_inner(), b"|aGVsbG8=|");
+    /// ```
+    #[inline]
+    pub fn with_bytes_style(mut self, bytes_style: BytesStyle) -> Self {
+        self.bytes_style = bytes_style;
+        self
+    }
+
+    /// Sets how bytes written in hex, by `BytesStyle::Hex` or a `HexBytes`,
+    /// are delimited. By default they are written `#x"DEADBEEF"`, which reads
+    /// back with the default `DeserializerOptions`.
+    ///
+    /// ```rust
+    /// # use serde::Serialize;
+    /// use sexpr::ser::{HexStyle, Serializer};
+    /// use sexpr::HexBytes;
+    ///
+    /// let mut ser = Serializer::new(Vec::new()).with_hex_style(HexStyle::Hash);
+    /// HexBytes(vec![0xde, 0xad]).serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.into_inner(), b"#DEAD#");
+    /// ```
+    #[inline]
+    pub fn with_hex_style(mut self, hex_style: HexStyle) -> Self {
+        self.hex_style = hex_style;
+        self
+    }
+
+    /// Sets whether symbols, keywords and strings written at least
+    /// `min_len` bytes long are shared through datum labels when they
+    /// repeat, which shrinks a stream of records that repeat the same keys.
+    /// The second time such an atom is written it is defined as a label, as
+    /// in `#0=temperature`, and from then on written as a reference to it,
+    /// `#0#`. Labels carry over from one value to the next written by the
+    /// same serializer. By default no labels are written.
+    ///
+    /// The serializer remembers every atom long enough to label, so the
+    /// length should be set high enough to pass over unique values such as
+    /// ids. Entries of a map written with `with_sort_keys` are never
+    /// labeled. The output reads back with
+    /// `DeserializerOptions::datum_labels`.
+    ///
+    /// ```rust
+    /// # use serde::Serialize;
+    /// use sexpr::Serializer;
+    ///
+    /// let mut ser = Serializer::new(Vec::new()).with_datum_labels(Some(8));
+    /// for reading in &[("temperature", 20), ("temperature", 21), ("temperature", 19)] {
+    ///     reading.serialize(&mut ser).unwrap();
+    /// }
+    /// assert_eq!(
+    ///     ser.into_inner(),
+    ///     b"(\"temperature\" 20)(#0=\"temperature\" 21)(#0# 19)"
+    /// );
+    /// ```
+    #[inline]
+    pub fn with_datum_labels(mut self, min_len: Option<usize>) -> Self {
+        self.datum_labels = min_len.map(|min_len| DatumLabels {
+            min_len,
+            atoms: HashMap::new(),
+            next: 0,
+        });
+        self
+    }
+
+    /// Sets the case that the letters of symbols and keywords are written
+    /// in. By default they are written as they are spelled. A Common Lisp
+    /// reader upcases the symbols it reads, so that `Dog` and `DOG` are the
+    /// same symbol; upcasing output suits such readers, and so does
+    /// downcasing for readers that preserve case. Strings are never changed.
+    /// Symbols written in either case read back as their lower case
+    /// spelling with `DeserializerOptions::case_insensitive`.
+    ///
+    /// ```rust
+    /// use serde::Serialize as _;
+    /// use serde_derive::Serialize;
+    /// use sexpr::ser::{Serializer, SymbolCase};
+    /// use sexpr::sexp::Atom;
+    ///
+    /// #[derive(Serialize)]
+    /// enum Op {
+    ///     Load,
+    /// }
+    ///
+    /// let mut ser = Serializer::new(Vec::new())
+    ///     .with_unit_symbols(true)
+    ///     .with_symbol_case(SymbolCase::Upcase);
+    /// (Op::Load, Atom::symbol("reg-a"), Atom::keyword("wide"), "text")
+    ///     .serialize(&mut ser)
+    ///     .unwrap();
+    /// assert_eq!(ser.into_inner(), b"(LOAD REG-A #:WIDE \"text\")");
+    /// ```
+    #[inline]
+    pub fn with_symbol_case(mut self, symbol_case: SymbolCase) -> Self {
+        self.symbol_case = symbol_case;
+        self
+    }
+
+    /// Sets what the serializer does with a symbol or keyword that cannot
+    /// be written bare, such as one holding a space or a `)`. By default it
+    /// is quoted between bars, as in `|has space)|`, which reads back as the
+    /// same symbol. Rejecting it instead catches names that were never meant
+    /// to be symbols, such as unchecked input spliced into a program.
+    ///
+    /// ```rust
+    /// # use serde::Serialize;
+    /// use sexpr::ser::{InvalidSymbols, Serializer};
+    /// use sexpr::sexp::Atom;
+    ///
+    /// let mut ser = Serializer::new(Vec::new()).with_invalid_symbols(InvalidSymbols::Reject);
+    /// let err = Atom::symbol("x) (rm").serialize(&mut ser).unwrap_err();
+    /// assert_eq!(err.to_string(), "`x) (rm` is not a valid bare symbol");
+    /// ```
+    #[inline]
+    pub fn with_invalid_symbols(mut self, invalid_symbols: InvalidSymbols) -> Self {
+        self.invalid_symbols = invalid_symbols;
+        self
+    }
+
+    /// Sets how keywords are written. By default they are written `#:name`;
+    /// `KeywordStyle::TrailingColon` writes `name:` for data read by DSSSL
+    /// and the Schemes that follow it.
+    ///
+    /// ```rust
+    /// # use serde::Serialize;
+    /// use sexpr::ser::{KeywordStyle, Serializer};
+    /// use sexpr::sexp::Atom;
+    ///
+    /// let mut ser = Serializer::new(Vec::new()).with_keyword_style(KeywordStyle::TrailingColon);
+    /// (Atom::keyword("width"), 80, Atom::symbol("label:"))
+    ///     .serialize(&mut ser)
+    ///     .unwrap();
+    /// assert_eq!(ser.into_inner(), b"(width: 80 |label:|)");
+    /// ```
+    #[inline]
+    pub fn with_keyword_style(mut self, keyword_style: KeywordStyle) -> Self {
+        self.keyword_style = keyword_style;
+        self
     }
 
     /// Unwrap the `Writer` from the `Serializer`.
     #[inline]
     pub fn into_inner(self) -> W {
-        self.writer
+        self.writer.inner
+    }
+
+    /// Returns a reference to the underlying writer.
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        &self.writer.inner
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    ///
+    /// A `Serializer` can write any number of values one after another, so
+    /// one serializer and its buffer can be reused across many messages,
+    /// clearing the buffer in between:
+    ///
+    /// ```rust
+    /// use serde::Serialize;
+    /// use sexpr::Serializer;
+    ///
+    /// let mut ser = Serializer::new(Vec::new());
+    /// for n in 0..3 {
+    ///     ser.get_mut().clear();
+    ///     (n, "msg").serialize(&mut ser).unwrap();
+    ///     assert_eq!(ser.get_ref(), format!("({} \"msg\")", n).as_bytes());
+    /// }
+    /// ```
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer.inner
+    }
+
+    fn serialize_non_finite(&mut self, value: f64) -> Result<()> {
+        match self.non_finite_floats {
+            NonFiniteFloats::Error => Err(Error::syntax(ErrorCode::FloatMustBeFinite, 0, 0)),
+            NonFiniteFloats::WriteNil => self.write_nil(),
+            NonFiniteFloats::WriteSpecialTokens => self
+                .formatter
+                .write_non_finite(&mut self.writer, value)
+                .map_err(Error::io),
+        }
+    }
+
+    // Writes a finite float with `write`, adding `.0` before any exponent if
+    // `always_decimal_point` is set and the formatter wrote no point.
+    fn write_finite_float(
+        &mut self,
+        write: impl FnOnce(&mut F, &mut dyn io::Write) -> io::Result<()>,
+    ) -> Result<()> {
+        if !self.always_decimal_point {
+            return write(&mut self.formatter, &mut self.writer).map_err(Error::io);
+        }
+        let mut text = Vec::new();
+        write(&mut self.formatter, &mut text).map_err(Error::io)?;
+        if !text.contains(&b'.') {
+            let digits_end = text
+                .iter()
+                .position(|&b| b == b'e' || b == b'E')
+                .unwrap_or(text.len());
+            text.splice(digits_end..digits_end, b".0".iter().cloned());
+        }
+        io::Write::write_all(&mut self.writer, &text).map_err(Error::io)
+    }
+
+    fn serialize_byte_list(&mut self, value: &[u8]) -> Result<()> {
+        use serde::ser::SerializeSeq;
+        let mut seq = ser::Serializer::serialize_seq(&mut *self, Some(value.len()))?;
+        for byte in value {
+            seq.serialize_element(byte)?;
+        }
+        seq.end()
+    }
+
+    fn write_nil(&mut self) -> Result<()> {
+        match self.nil_style {
+            NilStyle::Hash => self
+                .formatter
+                .write_null(&mut self.writer)
+                .map_err(Error::io),
+            NilStyle::EmptyList => io::Write::write_all(&mut self.writer, b"()").map_err(Error::io),
+            // Written bare, where `write_symbol` would put bars around it.
+            NilStyle::Symbol => {
+                let nil = self.symbol_case.apply("nil");
+                self.formatter
+                    .write_raw_fragment(&mut self.writer, &nil)
+                    .map_err(Error::io)
+            }
+        }
+    }
+
+    // Writes a symbol in the case `symbol_case` asks for, failing if it
+    // needs bars and `invalid_symbols` rejects those.
+    fn write_symbol(&mut self, symbol: &str) -> Result<()> {
+        let symbol = self.symbol_case.apply(symbol);
+        if self.invalid_symbols == InvalidSymbols::Reject && symbol_needs_bars(&symbol) {
+            return Err(Error::syntax(
+                ErrorCode::InvalidSymbol(symbol.into_owned()),
+                0,
+                0,
+            ));
+        }
+        self.formatter
+            .write_symbol(&mut self.writer, &symbol)
+            .map_err(Error::io)
     }
+
+    fn begin_list(&mut self, is_alist: bool) -> Result<()> {
+        let cx = ListContext::new(self.depth, is_alist, 0);
+        self.depth += 1;
+        self.rest_index = None;
+        if is_alist {
+            self.formatter.begin_object_in(&mut self.writer, cx)
+        } else {
+            self.formatter.begin_array_in(&mut self.writer, cx)
+        }
+        .map_err(Error::io)
+    }
+
+    fn end_list(&mut self, is_alist: bool, len: usize) -> Result<()> {
+        self.depth -= 1;
+        let cx = ListContext::new(self.depth, is_alist, len);
+        if is_alist {
+            self.formatter.end_object_in(&mut self.writer, cx)
+        } else {
+            self.formatter.end_array_in(&mut self.writer, cx)
+        }
+        .map_err(Error::io)
+    }
+
+    // Ends a list whose elements were written on one line.
+    fn end_inline_list(&mut self, len: usize) -> Result<()> {
+        self.depth -= 1;
+        let cx = ListContext::new(self.depth, false, len);
+        self.formatter
+            .end_inline_array_in(&mut self.writer, cx)
+            .map_err(Error::io)
+    }
+
+    // The context of an element of the innermost open list.
+    fn element_context(&self, is_alist: bool, index: usize) -> ListContext {
+        ListContext::new(self.depth - 1, is_alist, index)
+    }
+
+    // Starts an alist. If `sort_keys` is set its entries are collected and
+    // written sorted when it ends.
+    fn begin_map(&mut self, sort_keys: bool) -> Result<Compound<'_, W, F>> {
+        self.begin_list(true)?;
+        Ok(Compound {
+            ser: self,
+            len: 0,
+            sorted: if sort_keys { Some(Vec::new()) } else { None },
+            held: None,
+            splice: None,
+            dotted: false,
+        })
+    }
+
+    // Runs `serialize` with its output collected into a buffer rather than
+    // written.
+    fn capture(&mut self, serialize: impl FnOnce(&mut Self) -> Result<()>) -> Result<Vec<u8>> {
+        if !self.writer.pending.is_empty() {
+            self.writer.write_pending().map_err(Error::io)?;
+        }
+        self.writer.captures.push(Vec::new());
+        let result = serialize(self);
+        let captured = self.writer.captures.pop().unwrap();
+        result.map(|()| captured)
+    }
+
+    // Runs `write` to write one symbol, keyword or string, labeling it if
+    // `with_datum_labels` is on and it repeats.
+    fn write_atom(&mut self, write: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        // Entries collected to be sorted are reordered, which could move a
+        // reference ahead of its definition.
+        if self.datum_labels.is_none() || !self.writer.captures.is_empty() {
+            return write(self);
+        }
+        let token = self.capture(write)?;
+        let label = self.datum_labels.as_mut().unwrap().label(&token);
+        let writer = &mut self.writer;
+        match label {
+            DatumLabel::None => io::Write::write_all(writer, &token),
+            DatumLabel::Define(n) => io::Write::write_all(writer, format!("#{}=", n).as_bytes())
+                .and_then(|()| io::Write::write_all(writer, &token)),
+            DatumLabel::Refer(n) => io::Write::write_all(writer, format!("#{}#", n).as_bytes()),
+        }
+        .map_err(Error::io)
+    }
+
+    // Writes the `(variant .` that starts an externally tagged enum variant.
+    fn begin_variant(&mut self, variant: &'static str) -> Result<()> {
+        self.begin_list(true)?;
+        let cx = self.element_context(true, 0);
+        self.formatter
+            .begin_object_key_in(&mut self.writer, cx)
+            .map_err(Error::io)?;
+        ser::Serializer::serialize_str(&mut *self, variant)?;
+        self.formatter
+            .end_object_key_in(&mut self.writer, cx)
+            .map_err(Error::io)?;
+        self.formatter
+            .begin_object_value_in(&mut self.writer, cx)
+            .map_err(Error::io)
+    }
+
+    // Writes the `)` that ends an externally tagged enum variant.
+    fn end_variant(&mut self) -> Result<()> {
+        let cx = self.element_context(true, 0);
+        self.formatter
+            .end_object_value_in(&mut self.writer, cx)
+            .map_err(Error::io)?;
+        self.end_list(true, 1)
+    }
+}
+
+/// The symbol `with_unit_symbols` writes for a unit struct: its name in lower
+/// case, with a `-` wherever a capital letter follows a lower case letter or
+/// digit, so `EndMarker` becomes `end-marker`.
+pub(crate) fn unit_struct_symbol(name: &str) -> String {
+    let mut symbol = String::with_capacity(name.len() + 4);
+    let mut prev_lower = false;
+    for ch in name.chars() {
+        if ch.is_uppercase() && prev_lower {
+            symbol.push('-');
+        }
+        prev_lower = ch.is_lowercase() || ch.is_ascii_digit();
+        symbol.extend(ch.to_lowercase());
+    }
+    symbol
+}
+
+/// What a `Serializer` does with floating point values that are NaN or
+/// infinite, which have no plain numeric S-expression form.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonFiniteFloats {
+    /// Fail with an error.
+    #[default]
+    Error,
+
+    /// Write nil, in the serializer's `NilStyle`, in place of the value. The
+    /// value does not read back as a float.
+    WriteNil,
+
+    /// Write one of the tokens `+nan.0`, `+inf.0` or `-inf.0`, which the
+    /// deserializer reads back as the same float.
+    WriteSpecialTokens,
+}
+
+/// The token a `Serializer` writes for nil. Lisps traditionally write nil
+/// as the empty list, but then `None` and `Some(vec![])` look the same, so
+/// the default is `#nil`, which is never a list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NilStyle {
+    /// Write `#nil`.
+    #[default]
+    Hash,
+
+    /// Write `()`, which reads back as nil with
+    /// `DeserializerOptions::empty_list_as_nil`.
+    EmptyList,
+
+    /// Write the symbol `nil`, which reads back as nil with
+    /// `DeserializerOptions::nil_symbol`.
+    Symbol,
+}
+
+/// How a `Serializer` writes byte strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BytesStyle {
+    /// Write a list of integers, as in `(104 105)`.
+    List,
+
+    /// Write a bytevector, as in `#u8(104 105)`.
+    ByteVector,
+
+    /// Write hex digit pairs, as in `#x"6869"`, or between `#` characters
+    /// as in `#6869#` if the serializer's `HexStyle` says so.
+    #[default]
+    Hex,
+
+    /// Write standard base64 between bars, as in `|aGk=|`. These read back
+    /// into a `Sexp` as `Sexp::Bytes` only with
+    /// `DeserializerOptions::base64_bars`, and as symbols otherwise.
+    Base64,
+}
+
+/// How a `Serializer` writes keywords.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeywordStyle {
+    /// Write `#:name`.
+    #[default]
+    Hash,
+
+    /// Write `name:`, which reads back as a keyword with
+    /// `DeserializerOptions::trailing_colon_keywords`. A keyword that needs
+    /// bars is written `#:|a b|` instead, and a symbol ending in `:` is
+    /// written between bars so that it reads back as a symbol.
+    TrailingColon,
+}
+
+/// What a `Serializer` does with a symbol that cannot be written bare.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InvalidSymbols {
+    /// Write it between bars, as in `|a b|`.
+    #[default]
+    Quote,
+
+    /// Fail with an `InvalidSymbol` error.
+    Reject,
+}
+
+/// The case a `Serializer` writes the letters of symbols in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymbolCase {
+    /// Write symbols as they are spelled.
+    #[default]
+    Preserve,
+
+    /// Write ASCII letters in upper case, as Common Lisp prints symbols.
+    Upcase,
+
+    /// Write ASCII letters in lower case.
+    Downcase,
+}
+
+impl SymbolCase {
+    fn apply(self, symbol: &str) -> Cow<'_, str> {
+        match self {
+            SymbolCase::Preserve => Cow::Borrowed(symbol),
+            SymbolCase::Upcase => Cow::Owned(symbol.to_ascii_uppercase()),
+            SymbolCase::Downcase => Cow::Owned(symbol.to_ascii_lowercase()),
+        }
+    }
+}
+
+/// How a `Serializer` delimits bytes written in hex.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HexStyle {
+    /// Write the digits between `#` characters, as in `#DEADBEEF#`, which
+    /// is Rivest's transport syntax. These read back only with
+    /// `DeserializerOptions::hex_escapes`, or into a byte type such as
+    /// `serde_bytes::ByteBuf`.
+    Hash,
+
+    /// Write the digits as a string after `#x`, as in `#x"DEADBEEF"`, which
+    /// reads back as bytes whatever the options.
+    #[default]
+    Prefixed,
+}
+
+/// How a `Serializer` writes characters outside ASCII in strings. Escaping
+/// them keeps the output readable through channels that only pass ASCII.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonAsciiChars {
+    /// Write the characters as UTF-8.
+    #[default]
+    Write,
+
+    /// Write each character as `\uXXXX`, using a surrogate pair for
+    /// characters outside the Basic Multilingual Plane. The deserializer
+    /// reads these escapes back.
+    EscapeUnicode,
+
+    /// Write each character as an R7RS hex escape such as `\x3bb;`, which
+    /// Scheme readers understand. The deserializer reads these escapes back
+    /// too.
+    EscapeHex,
 }
 
 impl<'a, W, F> ser::Serializer for &'a mut Serializer<W, F>
@@ -158,14 +980,14 @@ where
     fn serialize_f32(self, value: f32) -> Result<()> {
         match value.classify() {
             FpCategory::Nan | FpCategory::Infinite => {
-                self.formatter
-                    .write_null(&mut self.writer)
-                    .map_err(Error::io)?;
+                self.serialize_non_finite(f64::from(value))?;
             }
             _ => {
-                self.formatter
-                    .write_f32(&mut self.writer, value)
-                    .map_err(Error::io)?;
+                let style = self.float_style;
+                self.write_finite_float(|formatter, writer| match style {
+                    Some(style) => write_float(writer, value, style),
+                    None => formatter.write_f32(writer, value),
+                })?;
             }
         }
         Ok(())
@@ -175,14 +997,14 @@ where
     fn serialize_f64(self, value: f64) -> Result<()> {
         match value.classify() {
             FpCategory::Nan | FpCategory::Infinite => {
-                self.formatter
-                    .write_null(&mut self.writer)
-                    .map_err(Error::io)?;
+                self.serialize_non_finite(value)?;
             }
             _ => {
-                self.formatter
-                    .write_f64(&mut self.writer, value)
-                    .map_err(Error::io)?;
+                let style = self.float_style;
+                self.write_finite_float(|formatter, writer| match style {
+                    Some(style) => write_float(writer, value, style),
+                    None => formatter.write_f64(writer, value),
+                })?;
             }
         }
         Ok(())
@@ -190,37 +1012,66 @@ where
 
     #[inline]
     fn serialize_char(self, value: char) -> Result<()> {
-        format_escaped_char(&mut self.writer, &mut self.formatter, value).map_err(Error::io)?;
+        format_escaped_char(
+            &mut self.writer,
+            &mut self.formatter,
+            self.non_ascii_chars,
+            value,
+        )
+        .map_err(Error::io)?;
         Ok(())
     }
 
     #[inline]
     fn serialize_str(self, value: &str) -> Result<()> {
-        format_escaped_str(&mut self.writer, &mut self.formatter, value).map_err(Error::io)?;
-        Ok(())
+        self.write_atom(|ser| {
+            format_escaped_str(
+                &mut ser.writer,
+                &mut ser.formatter,
+                ser.non_ascii_chars,
+                value,
+            )
+            .map_err(Error::io)
+        })
     }
 
     #[inline]
     fn serialize_bytes(self, value: &[u8]) -> Result<()> {
-        use serde::ser::SerializeSeq;
-        let mut seq = self.serialize_seq(Some(value.len()))?;
-        for byte in value {
-            seq.serialize_element(byte)?;
-        }
-        seq.end()
+        let text = match self.bytes_style {
+            BytesStyle::List => return self.serialize_byte_list(value),
+            BytesStyle::ByteVector => {
+                io::Write::write_all(&mut self.writer, b"#u8").map_err(Error::io)?;
+                return self.serialize_byte_list(value);
+            }
+            BytesStyle::Hex => match self.hex_style {
+                // There are no empty hex strings, so the empty string stands
+                // in.
+                HexStyle::Hash if value.is_empty() => return self.serialize_str(""),
+                // Digits after `#` would read as a datum label.
+                HexStyle::Hash if self.datum_labels.is_none() || value[0] >= 0xa0 => {
+                    format!("#{}#", crate::bytes::encode_hex(value))
+                }
+                HexStyle::Hash | HexStyle::Prefixed => {
+                    format!("#x\"{}\"", crate::bytes::encode_hex(value))
+                }
+            },
+            BytesStyle::Base64 => format!("|{}|", crate::bytes::encode_base64(value)),
+        };
+        io::Write::write_all(&mut self.writer, text.as_bytes()).map_err(Error::io)
     }
 
     #[inline]
     fn serialize_unit(self) -> Result<()> {
-        self.formatter
-            .write_null(&mut self.writer)
-            .map_err(Error::io)?;
-        Ok(())
+        self.write_nil()
     }
 
     #[inline]
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        self.serialize_unit()
+    fn serialize_unit_struct(self, name: &'static str) -> Result<()> {
+        if self.unit_symbols {
+            self.write_symbol(&unit_struct_symbol(name))
+        } else {
+            self.serialize_unit()
+        }
     }
 
     #[inline]
@@ -230,19 +1081,55 @@ where
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<()> {
-        self.serialize_str(variant)
+        if self.unit_symbols {
+            self.write_symbol(variant)
+        } else {
+            self.serialize_str(variant)
+        }
     }
 
-    /// Serialize newtypes without an object wrapper.
+    /// Serialize newtypes without an object wrapper. The contents of the
+    /// `Symbol` newtype used by `Atom` are written as a bare symbol, and the
+    /// text of a `RawSexp` is written unchanged.
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        self.formatter
-            .write_bare_string(&mut self.writer, value)
-            .map_err(Error::io)?;
-        Ok(())
+        if name == SYMBOL_TOKEN || name == KEYWORD_TOKEN || name == RAW_TOKEN {
+            value.serialize(SymbolSerializer {
+                ser: self,
+                raw: name == RAW_TOKEN,
+                keyword: name == KEYWORD_TOKEN,
+            })
+        } else if name == HEX_BYTES_TOKEN || name == BASE64_BYTES_TOKEN {
+            // The bytes inside take the encoding of their wrapper.
+            let bytes_style = self.bytes_style;
+            self.bytes_style = if name == HEX_BYTES_TOKEN {
+                BytesStyle::Hex
+            } else {
+                BytesStyle::Base64
+            };
+            let result = value.serialize(&mut *self);
+            self.bytes_style = bytes_style;
+            result
+        } else if name == REST_TOKEN {
+            // Outside of a list the elements are a list of their own.
+            self.splice_at = self.rest_index.take();
+            value.serialize(self)
+        } else if name == PAIR_TOKEN {
+            self.dotted = true;
+            value.serialize(self)
+        } else if name == NUMBER_TOKEN {
+            // The literal text of a `Number` is written as it is.
+            value.serialize(SymbolSerializer {
+                ser: self,
+                raw: true,
+                keyword: false,
+            })
+        } else {
+            value.serialize(self)
+        }
     }
 
     #[inline]
@@ -256,27 +1143,9 @@ where
     where
         T: ser::Serialize,
     {
-        self.formatter
-            .begin_object(&mut self.writer)
-            .map_err(Error::io)?;
-        self.formatter
-            .begin_object_key(&mut self.writer, true)
-            .map_err(Error::io)?;
-        self.serialize_str(variant)?;
-        self.formatter
-            .end_object_key(&mut self.writer)
-            .map_err(Error::io)?;
-        self.formatter
-            .begin_object_value(&mut self.writer)
-            .map_err(Error::io)?;
+        self.begin_variant(variant)?;
         value.serialize(&mut *self)?;
-        self.formatter
-            .end_object_value(&mut self.writer)
-            .map_err(Error::io)?;
-        self.formatter
-            .end_object(&mut self.writer)
-            .map_err(Error::io)?;
-        Ok(())
+        self.end_variant()
     }
 
     #[inline]
@@ -292,28 +1161,33 @@ where
         value.serialize(self)
     }
 
+    /// Starts a list. The list is closed by `end`, whatever the length hint
+    /// says, so an empty list is written `()` and a wrong hint does no harm.
     #[inline]
-    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
-        if len == Some(0) {
-            self.formatter
-                .begin_array(&mut self.writer)
-                .map_err(Error::io)?;
-            self.formatter
-                .end_array(&mut self.writer)
-                .map_err(Error::io)?;
-            Ok(Compound {
-                ser: self,
-                state: State::Empty,
-            })
-        } else {
-            self.formatter
-                .begin_array(&mut self.writer)
-                .map_err(Error::io)?;
-            Ok(Compound {
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        let dotted = mem::replace(&mut self.dotted, false);
+        if let Some(start) = self.splice_at.take() {
+            return Ok(Compound {
                 ser: self,
-                state: State::First,
-            })
+                len: 0,
+                sorted: None,
+                held: None,
+                splice: Some(start),
+                dotted: false,
+            });
         }
+        self.begin_list(false)?;
+        // Held elements are written later than they are serialized, which
+        // could move a datum label's reference ahead of its definition.
+        let hold = self.formatter.inline_atoms().is_some() && self.datum_labels.is_none();
+        Ok(Compound {
+            ser: self,
+            len: 0,
+            sorted: None,
+            held: if hold { Some(Vec::new()) } else { None },
+            splice: None,
+            dotted,
+        })
     }
 
     #[inline]
@@ -338,49 +1212,19 @@ where
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        self.formatter
-            .begin_object(&mut self.writer)
-            .map_err(Error::io)?;
-        self.formatter
-            .begin_object_key(&mut self.writer, true)
-            .map_err(Error::io)?;
-        self.serialize_str(variant)?;
-        self.formatter
-            .end_object_key(&mut self.writer)
-            .map_err(Error::io)?;
-        self.formatter
-            .begin_object_value(&mut self.writer)
-            .map_err(Error::io)?;
+        self.begin_variant(variant)?;
         self.serialize_seq(Some(len))
     }
 
     #[inline]
-    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-        if len == Some(0) {
-            self.formatter
-                .begin_object(&mut self.writer)
-                .map_err(Error::io)?;
-            self.formatter
-                .end_object(&mut self.writer)
-                .map_err(Error::io)?;
-            Ok(Compound {
-                ser: self,
-                state: State::Empty,
-            })
-        } else {
-            self.formatter
-                .begin_object(&mut self.writer)
-                .map_err(Error::io)?;
-            Ok(Compound {
-                ser: self,
-                state: State::First,
-            })
-        }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        let sort_keys = self.sort_keys;
+        self.begin_map(sort_keys)
     }
 
     #[inline]
-    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-        self.serialize_map(Some(len))
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        self.begin_map(false)
     }
 
     #[inline]
@@ -389,22 +1233,10 @@ where
         _name: &'static str,
         _variant_index: u32,
         variant: &'static str,
-        len: usize,
+        _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.formatter
-            .begin_object(&mut self.writer)
-            .map_err(Error::io)?;
-        self.formatter
-            .begin_object_key(&mut self.writer, true)
-            .map_err(Error::io)?;
-        self.serialize_str(variant)?;
-        self.formatter
-            .end_object_key(&mut self.writer)
-            .map_err(Error::io)?;
-        self.formatter
-            .begin_object_value(&mut self.writer)
-            .map_err(Error::io)?;
-        self.serialize_map(Some(len))
+        self.begin_variant(variant)?;
+        self.begin_map(false)
     }
 
     fn collect_str<T: ?Sized>(self, value: &T) -> Result<Self::Ok>
@@ -416,6 +1248,7 @@ where
         struct Adapter<'ser, W: 'ser, F: 'ser> {
             writer: &'ser mut W,
             formatter: &'ser mut F,
+            non_ascii_chars: NonAsciiChars,
             error: Option<io::Error>,
         }
 
@@ -426,7 +1259,12 @@ where
         {
             fn write_str(&mut self, s: &str) -> fmt::Result {
                 assert!(self.error.is_none());
-                match format_escaped_str_contents(self.writer, self.formatter, s) {
+                match format_escaped_str_contents(
+                    self.writer,
+                    self.formatter,
+                    self.non_ascii_chars,
+                    s,
+                ) {
                     Ok(()) => Ok(()),
                     Err(err) => {
                         self.error = Some(err);
@@ -443,6 +1281,7 @@ where
             let mut adapter = Adapter {
                 writer: &mut self.writer,
                 formatter: &mut self.formatter,
+                non_ascii_chars: self.non_ascii_chars,
                 error: None,
             };
             match write!(adapter, "{}", value) {
@@ -459,18 +1298,44 @@ where
     }
 }
 
-#[doc(hidden)]
-#[derive(Eq, PartialEq)]
-pub enum State {
-    Empty,
-    First,
-    Rest,
-}
-
 #[doc(hidden)]
 pub struct Compound<'a, W: 'a, F: 'a> {
     ser: &'a mut Serializer<W, F>,
-    state: State,
+    /// The number of elements or entries written so far.
+    len: usize,
+    /// The written keys and values of a map whose entries are sorted before
+    /// they are written.
+    sorted: Option<Vec<(Vec<u8>, Vec<u8>)>>,
+    /// The elements of a list that may still be written on one line, held
+    /// back until the list ends or turns out not to fit.
+    held: Option<Vec<HeldElement>>,
+    /// For the elements of a `Rest`, the index in the enclosing list they
+    /// start at. They are written into that list, which they neither begin
+    /// nor end.
+    splice: Option<usize>,
+    /// For the car and cdr of a pair, whether the `.` between them is still
+    /// to be written.
+    dotted: bool,
+}
+
+/// The `.` between the car and cdr of a pair.
+struct Dot;
+
+impl ser::Serialize for Dot {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_TOKEN, ".")
+    }
+}
+
+/// An element of a list held back by `Compound`: its text, and what the
+/// formatter wrote before and after it to lay it out on a line of its own.
+struct HeldElement {
+    before: Vec<u8>,
+    text: Vec<u8>,
+    after: Vec<u8>,
 }
 
 impl<'a, W, F> ser::SerializeSeq for Compound<'a, W, F>
@@ -486,50 +1351,178 @@ where
     where
         T: ser::Serialize,
     {
+        if let Some(start) = self.splice {
+            return self.splice_element(value, start);
+        }
+        if self.dotted && self.len == 1 {
+            // The dot is laid out as an element of its own.
+            self.dotted = false;
+            self.serialize_element(&Dot)?;
+        }
+        let cx = self.ser.element_context(false, self.len);
+        if self.held.is_some() {
+            return self.hold_element(value, cx);
+        }
         self.ser
             .formatter
-            .begin_array_value(&mut self.ser.writer, self.state == State::First)
+            .begin_array_value_in(&mut self.ser.writer.pending, cx)
             .map_err(Error::io)?;
-        self.state = State::Rest;
+        self.len += 1;
+        self.ser.rest_index = Some(self.len - 1);
         value.serialize(&mut *self.ser)?;
+        self.ser.rest_index = None;
+        let cx = match self.ser.spliced.take() {
+            None => cx,
+            Some(0) => {
+                self.ser.writer.pending.clear();
+                self.len -= 1;
+                return Ok(());
+            }
+            Some(n) => {
+                self.len += n - 1;
+                self.ser.element_context(false, self.len - 1)
+            }
+        };
         self.ser
             .formatter
-            .end_array_value(&mut self.ser.writer)
+            .end_array_value_in(&mut self.ser.writer, cx)
             .map_err(Error::io)?;
         Ok(())
     }
 
     #[inline]
-    fn end(self) -> Result<()> {
-        match self.state {
-            State::Empty => {}
-            _ => self
-                .ser
-                .formatter
-                .end_array(&mut self.ser.writer)
-                .map_err(Error::io)?,
+    fn end(mut self) -> Result<()> {
+        if self.splice.is_some() {
+            self.ser.spliced = Some(self.len);
+            return Ok(());
         }
-        Ok(())
+        self.end_array()
     }
 }
 
-impl<'a, W, F> ser::SerializeTuple for Compound<'a, W, F>
+impl<'a, W, F> Compound<'a, W, F>
 where
     W: io::Write,
     F: Formatter,
 {
-    type Ok = ();
-    type Error = Error;
+    // Writes an element of a `Rest` into the enclosing list. The list wrote
+    // what goes before the first element, and writes what goes after the
+    // last once it knows how many there were.
+    fn splice_element<T>(&mut self, value: &T, start: usize) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        if self.len > 0 {
+            let before = self.ser.element_context(false, start + self.len - 1);
+            let cx = self.ser.element_context(false, start + self.len);
+            let ser = &mut *self.ser;
+            ser.formatter
+                .end_array_value_in(&mut ser.writer, before)
+                .and_then(|()| ser.formatter.begin_array_value_in(&mut ser.writer, cx))
+                .map_err(Error::io)?;
+        }
+        self.len += 1;
+        value.serialize(&mut *self.ser)
+    }
 
-    #[inline]
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    // Serializes an element of a list that may still be written on one line,
+    // and holds it back. Once an element is not a short atom, or there are
+    // more than `inline_atoms` allows, the held elements are written as
+    // usual.
+    fn hold_element<T>(&mut self, value: &T, cx: ListContext) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        ser::SerializeSeq::serialize_element(self, value)
+        let mut before = Vec::new();
+        self.ser
+            .formatter
+            .begin_array_value_in(&mut before, cx)
+            .map_err(Error::io)?;
+        self.len += 1;
+        self.ser.rest_index = Some(self.len - 1);
+        let text = self.ser.capture(|ser| value.serialize(ser))?;
+        self.ser.rest_index = None;
+        let cx = match self.ser.spliced.take() {
+            None => cx,
+            Some(0) => {
+                self.len -= 1;
+                return Ok(());
+            }
+            Some(n) => {
+                self.len += n - 1;
+                self.ser.element_context(false, self.len - 1)
+            }
+        };
+        let mut after = Vec::new();
+        self.ser
+            .formatter
+            .end_array_value_in(&mut after, cx)
+            .map_err(Error::io)?;
+
+        let (max_atoms, max_atom_len) = self.ser.formatter.inline_atoms().unwrap_or((0, 0));
+        let is_short_atom = text.len() <= max_atom_len
+            && !text.contains(&b'\n')
+            && (&text[..] == b"()" || !matches!(text.first(), Some(b'(') | Some(b'[')));
+        let held = self.held.get_or_insert_with(Vec::new);
+        held.push(HeldElement {
+            before,
+            text,
+            after,
+        });
+        if !is_short_atom || held.len() > max_atoms {
+            self.write_held()?;
+        }
+        Ok(())
     }
 
-    #[inline]
+    // Writes the held elements each laid out as the formatter asked, and
+    // holds no more.
+    fn write_held(&mut self) -> Result<()> {
+        let writer = &mut self.ser.writer;
+        for element in self.held.take().unwrap_or_default() {
+            io::Write::write_all(writer, &element.before)
+                .and_then(|()| io::Write::write_all(writer, &element.text))
+                .and_then(|()| io::Write::write_all(writer, &element.after))
+                .map_err(Error::io)?;
+        }
+        Ok(())
+    }
+
+    // Ends a list, writing it on one line if all of its elements were held.
+    fn end_array(&mut self) -> Result<()> {
+        match self.held.take() {
+            Some(ref held) if !held.is_empty() => {
+                let writer = &mut self.ser.writer;
+                for (i, element) in held.iter().enumerate() {
+                    if i > 0 {
+                        io::Write::write_all(writer, b" ").map_err(Error::io)?;
+                    }
+                    io::Write::write_all(writer, &element.text).map_err(Error::io)?;
+                }
+                self.ser.end_inline_list(self.len)
+            }
+            _ => self.ser.end_list(false, self.len),
+        }
+    }
+}
+
+impl<'a, W, F> ser::SerializeTuple for Compound<'a, W, F>
+where
+    W: io::Write,
+    F: Formatter,
+{
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    where
+        T: ser::Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    #[inline]
     fn end(self) -> Result<()> {
         ser::SerializeSeq::end(self)
     }
@@ -574,24 +1567,9 @@ where
     }
 
     #[inline]
-    fn end(self) -> Result<()> {
-        match self.state {
-            State::Empty => {}
-            _ => self
-                .ser
-                .formatter
-                .end_array(&mut self.ser.writer)
-                .map_err(Error::io)?,
-        }
-        self.ser
-            .formatter
-            .end_object_value(&mut self.ser.writer)
-            .map_err(Error::io)?;
-        self.ser
-            .formatter
-            .end_object(&mut self.ser.writer)
-            .map_err(Error::io)?;
-        Ok(())
+    fn end(mut self) -> Result<()> {
+        self.end_array()?;
+        self.ser.end_variant()
     }
 }
 
@@ -608,17 +1586,25 @@ where
     where
         T: ser::Serialize,
     {
+        if let Some(ref mut sorted) = self.sorted {
+            let key = self
+                .ser
+                .capture(|ser| key.serialize(MapKeySerializer { ser }))?;
+            sorted.push((key, Vec::new()));
+            return Ok(());
+        }
+
+        let cx = self.ser.element_context(true, self.len);
         self.ser
             .formatter
-            .begin_object_key(&mut self.ser.writer, self.state == State::First)
+            .begin_object_key_in(&mut self.ser.writer, cx)
             .map_err(Error::io)?;
-        self.state = State::Rest;
 
         key.serialize(MapKeySerializer { ser: self.ser })?;
 
         self.ser
             .formatter
-            .end_object_key(&mut self.ser.writer)
+            .end_object_key_in(&mut self.ser.writer, cx)
             .map_err(Error::io)?;
         Ok(())
     }
@@ -628,29 +1614,51 @@ where
     where
         T: ser::Serialize,
     {
+        if let Some(ref mut sorted) = self.sorted {
+            let value = self.ser.capture(|ser| value.serialize(ser))?;
+            let entry = sorted
+                .last_mut()
+                .expect("serialize_value called before serialize_key");
+            entry.1 = value;
+            return Ok(());
+        }
+
+        let cx = self.ser.element_context(true, self.len);
         self.ser
             .formatter
-            .begin_object_value(&mut self.ser.writer)
+            .begin_object_value_in(&mut self.ser.writer, cx)
             .map_err(Error::io)?;
+        self.len += 1;
         value.serialize(&mut *self.ser)?;
         self.ser
             .formatter
-            .end_object_value(&mut self.ser.writer)
+            .end_object_value_in(&mut self.ser.writer, cx)
             .map_err(Error::io)?;
         Ok(())
     }
 
     #[inline]
-    fn end(self) -> Result<()> {
-        match self.state {
-            State::Empty => {}
-            _ => self
-                .ser
-                .formatter
-                .end_object(&mut self.ser.writer)
-                .map_err(Error::io)?,
+    fn end(mut self) -> Result<()> {
+        if let Some(mut sorted) = self.sorted.take() {
+            // Only the key and value themselves were collected, so the layout
+            // around each entry is written here, in the sorted order.
+            sorted.sort_by(|a, b| a.0.cmp(&b.0));
+            for (key, value) in sorted {
+                let cx = self.ser.element_context(true, self.len);
+                let writer = &mut self.ser.writer;
+                let formatter = &mut self.ser.formatter;
+                formatter
+                    .begin_object_key_in(writer, cx)
+                    .and_then(|()| io::Write::write_all(writer, &key))
+                    .and_then(|()| formatter.end_object_key_in(writer, cx))
+                    .and_then(|()| formatter.begin_object_value_in(writer, cx))
+                    .and_then(|()| io::Write::write_all(writer, &value))
+                    .and_then(|()| formatter.end_object_value_in(writer, cx))
+                    .map_err(Error::io)?;
+                self.len += 1;
+            }
         }
-        Ok(())
+        self.ser.end_list(true, self.len)
     }
 }
 
@@ -695,23 +1703,8 @@ where
 
     #[inline]
     fn end(self) -> Result<()> {
-        match self.state {
-            State::Empty => {}
-            _ => self
-                .ser
-                .formatter
-                .end_object(&mut self.ser.writer)
-                .map_err(Error::io)?,
-        }
-        self.ser
-            .formatter
-            .end_object_value(&mut self.ser.writer)
-            .map_err(Error::io)?;
-        self.ser
-            .formatter
-            .end_object(&mut self.ser.writer)
-            .map_err(Error::io)?;
-        Ok(())
+        self.ser.end_list(true, self.len)?;
+        self.ser.end_variant()
     }
 }
 
@@ -747,11 +1740,19 @@ where
     }
 
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        value.serialize(self)
+        if name == SYMBOL_TOKEN || name == KEYWORD_TOKEN {
+            value.serialize(SymbolSerializer {
+                ser: self.ser,
+                raw: false,
+                keyword: name == KEYWORD_TOKEN,
+            })
+        } else {
+            value.serialize(self)
+        }
     }
 
     type SerializeSeq = Impossible<(), Error>;
@@ -987,6 +1988,215 @@ where
     }
 }
 
+/// Serializes the contents of the `Symbol` newtype, or of a `RawSexp` when
+/// `raw` is set. Strings are written as bare symbols or as raw text; anything
+/// else is serialized as it would be outside of the newtype.
+struct SymbolSerializer<'a, W: 'a, F: 'a> {
+    ser: &'a mut Serializer<W, F>,
+    raw: bool,
+    keyword: bool,
+}
+
+impl<'a, W, F> ser::Serializer for SymbolSerializer<'a, W, F>
+where
+    W: io::Write,
+    F: Formatter,
+{
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, W, F>;
+    type SerializeTuple = Compound<'a, W, F>;
+    type SerializeTupleStruct = Compound<'a, W, F>;
+    type SerializeTupleVariant = Compound<'a, W, F>;
+    type SerializeMap = Compound<'a, W, F>;
+    type SerializeStruct = Compound<'a, W, F>;
+    type SerializeStructVariant = Compound<'a, W, F>;
+
+    #[inline]
+    fn serialize_str(self, value: &str) -> Result<()> {
+        if self.raw {
+            let ser = self.ser;
+            return ser
+                .formatter
+                .write_raw_fragment(&mut ser.writer, value)
+                .map_err(Error::io);
+        }
+        let keyword = self.keyword;
+        let trailing_colon =
+            self.ser.keyword_style == KeywordStyle::TrailingColon && !symbol_needs_bars(value);
+        self.ser.write_atom(|ser| {
+            let (prefix, suffix) = match (keyword, trailing_colon) {
+                (true, true) => ("", ":"),
+                (true, false) => ("#:", ""),
+                // Bare, the symbol would read back as a keyword.
+                (false, true) if value.ends_with(':') => ("|", "|"),
+                (false, _) => ("", ""),
+            };
+            if !prefix.is_empty() {
+                ser.formatter
+                    .write_raw_fragment(&mut ser.writer, prefix)
+                    .map_err(Error::io)?;
+            }
+            ser.write_symbol(value)?;
+            if !suffix.is_empty() {
+                ser.formatter
+                    .write_raw_fragment(&mut ser.writer, suffix)
+                    .map_err(Error::io)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn serialize_bool(self, value: bool) -> Result<()> {
+        self.ser.serialize_bool(value)
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<()> {
+        self.ser.serialize_i8(value)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<()> {
+        self.ser.serialize_i16(value)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<()> {
+        self.ser.serialize_i32(value)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<()> {
+        self.ser.serialize_i64(value)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<()> {
+        self.ser.serialize_u8(value)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<()> {
+        self.ser.serialize_u16(value)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<()> {
+        self.ser.serialize_u32(value)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<()> {
+        self.ser.serialize_u64(value)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<()> {
+        self.ser.serialize_f32(value)
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<()> {
+        self.ser.serialize_f64(value)
+    }
+
+    fn serialize_char(self, value: char) -> Result<()> {
+        self.ser.serialize_char(value)
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<()> {
+        self.ser.serialize_bytes(value)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.ser.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<()> {
+        self.ser.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.ser
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.ser.serialize_newtype_struct(name, value)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.ser
+            .serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.ser.serialize_none()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.ser.serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.ser.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.ser.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.ser
+            .serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.ser.serialize_map(len)
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.ser.serialize_struct(name, len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.ser
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+}
+
 /// Represents a character escape code in a type-safe manner.
 pub enum CharEscape {
     /// An escaped quote `"`
@@ -1011,6 +2221,21 @@ pub enum CharEscape {
 }
 
 impl CharEscape {
+    /// The character this escape stands for.
+    fn unescaped(&self) -> char {
+        match *self {
+            CharEscape::Quote => '"',
+            CharEscape::ReverseSolidus => '\\',
+            CharEscape::Solidus => '/',
+            CharEscape::Backspace => '\u{8}',
+            CharEscape::FormFeed => '\u{c}',
+            CharEscape::LineFeed => '\n',
+            CharEscape::CarriageReturn => '\r',
+            CharEscape::Tab => '\t',
+            CharEscape::AsciiControl(byte) => byte as char,
+        }
+    }
+
     #[inline]
     fn from_escape_table(escape: u8, byte: u8) -> CharEscape {
         match escape {
@@ -1027,8 +2252,55 @@ impl CharEscape {
     }
 }
 
+/// Where a `Formatter` callback is in the output: which list it belongs to
+/// and how far into that list it is.
+///
+/// Maps, structs and enum variants are written as association lists, and
+/// their callbacks are the `*_object*` ones; sequences and tuples use the
+/// `*_array*` ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ListContext {
+    depth: usize,
+    is_alist: bool,
+    index: usize,
+}
+
+impl ListContext {
+    fn new(depth: usize, is_alist: bool, index: usize) -> Self {
+        ListContext {
+            depth,
+            is_alist,
+            index,
+        }
+    }
+
+    /// The number of lists enclosing this one. A top-level list has depth 0.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Whether the list is an association list of key/value entries.
+    #[inline]
+    pub fn is_alist(&self) -> bool {
+        self.is_alist
+    }
+
+    /// The index of the current element of the list. When a list begins it is
+    /// 0, and when it ends it is the number of elements written.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
 /// This trait abstracts away serializing the S-expression control characters, which allows the user to
 /// optionally pretty print the S-expression output.
+///
+/// The `Serializer` calls the `*_in` methods, which receive a `ListContext`.
+/// By default they forward to the method of the same name without the
+/// suffix, so a formatter only needs to override the `*_in` methods when it
+/// lays out output by nesting level or position.
 pub trait Formatter {
     /// Writes a `null` value to the specified writer.
     #[inline]
@@ -1126,37 +2398,84 @@ pub trait Formatter {
         itoa::write(writer, value).map(|_| ())
     }
 
-    /// Writes a floating point value like `-31.26e+12` to the specified writer.
+    /// The style in which `write_f32` and `write_f64` write floating point
+    /// values.
+    #[inline]
+    fn float_style(&self) -> FloatStyle {
+        FloatStyle::Shortest
+    }
+
+    /// Writes a floating point value like `-31.26e+12` to the specified
+    /// writer, in the style given by `float_style`.
     #[inline]
     fn write_f32<W: ?Sized>(&mut self, writer: &mut W, value: f32) -> io::Result<()>
     where
         W: io::Write,
     {
-        dtoa::write(writer, value).map(|_| ())
+        write_float(writer, value, self.float_style())
     }
 
-    /// Writes a floating point value like `-31.26e+12` to the specified writer.
+    /// Writes a floating point value like `-31.26e+12` to the specified
+    /// writer, in the style given by `float_style`.
     #[inline]
     fn write_f64<W: ?Sized>(&mut self, writer: &mut W, value: f64) -> io::Result<()>
     where
         W: io::Write,
     {
-        dtoa::write(writer, value).map(|_| ())
+        write_float(writer, value, self.float_style())
     }
 
-    /// Write a string without any enclosing quotes
+    /// Writes a NaN or infinite floating point value as one of the tokens
+    /// `+nan.0`, `+inf.0` or `-inf.0` to the specified writer.
     #[inline]
-    fn write_bare_string<W: ?Sized, T: ?Sized>(
-        &mut self,
-        writer: &mut W,
-        value: &T,
-    ) -> io::Result<()>
+    fn write_non_finite<W>(&mut self, writer: &mut W, value: f64) -> io::Result<()>
     where
-        W: io::Write,
-        T: ser::Serialize,
+        W: ?Sized + io::Write,
+    {
+        if value.is_nan() {
+            writer.write_all(b"+nan.0")
+        } else if value > 0.0 {
+            writer.write_all(b"+inf.0")
+        } else {
+            writer.write_all(b"-inf.0")
+        }
+    }
+
+    /// Writes a symbol, without any enclosing quotes, to the specified
+    /// writer. A symbol that would not read back as the same symbol when
+    /// written bare, such as one that is empty, does not start with a letter,
+    /// contains a delimiter or is `nil`, is written between bars as `|a b|`,
+    /// with `|` and `\` escaped by a backslash.
+    #[inline]
+    fn write_symbol<W>(&mut self, writer: &mut W, value: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if !symbol_needs_bars(value) {
+            return writer.write_all(value.as_bytes());
+        }
+        writer.write_all(b"|")?;
+        let bytes = value.as_bytes();
+        let mut start = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            if byte == b'|' || byte == b'\\' {
+                writer.write_all(&bytes[start..i])?;
+                writer.write_all(b"\\")?;
+                start = i;
+            }
+        }
+        writer.write_all(&bytes[start..])?;
+        writer.write_all(b"|")
+    }
+
+    /// Writes a piece of S-expression text, such as the contents of a
+    /// `RawSexp`, to the specified writer unchanged.
+    #[inline]
+    fn write_raw_fragment<W>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
     {
-        let n = to_string(value).unwrap();
-        writer.write_all(&n[1..n.len() - 1].as_bytes())
+        writer.write_all(fragment.as_bytes())
     }
 
     /// Called before each series of `write_string_fragment` and
@@ -1333,20 +2652,171 @@ pub trait Formatter {
     {
         Ok(())
     }
-}
 
-/// This structure compacts a S-expression value with no extra whitespace.
-#[derive(Clone, Debug)]
-pub struct CompactFormatter;
+    /// Called before every array, with the context of that array. Calls
+    /// `begin_array` by default.
+    #[inline]
+    fn begin_array_in<W>(&mut self, writer: &mut W, _cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.begin_array(writer)
+    }
 
-impl Formatter for CompactFormatter {}
+    /// Called after every array, with the context of that array. Calls
+    /// `end_array` by default.
+    #[inline]
+    fn end_array_in<W>(&mut self, writer: &mut W, _cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.end_array(writer)
+    }
+
+    /// Called before every array value, with the context of that value.
+    /// Calls `begin_array_value` by default.
+    #[inline]
+    fn begin_array_value_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.begin_array_value(writer, cx.index() == 0)
+    }
+
+    /// Called after every array value, with the context of that value.
+    /// Calls `end_array_value` by default.
+    #[inline]
+    fn end_array_value_in<W>(&mut self, writer: &mut W, _cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.end_array_value(writer)
+    }
+
+    /// Called before every object, with the context of that object. Calls
+    /// `begin_object` by default.
+    #[inline]
+    fn begin_object_in<W>(&mut self, writer: &mut W, _cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.begin_object(writer)
+    }
+
+    /// Called after every object, with the context of that object. Calls
+    /// `end_object` by default.
+    #[inline]
+    fn end_object_in<W>(&mut self, writer: &mut W, _cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.end_object(writer)
+    }
+
+    /// Called before every object key, with the context of its entry. Calls
+    /// `begin_object_key` by default.
+    #[inline]
+    fn begin_object_key_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.begin_object_key(writer, cx.index() == 0)
+    }
+
+    /// Called after every object key, with the context of its entry. Calls
+    /// `end_object_key` by default.
+    #[inline]
+    fn end_object_key_in<W>(&mut self, writer: &mut W, _cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.end_object_key(writer)
+    }
+
+    /// Called before every object value, with the context of its entry.
+    /// Calls `begin_object_value` by default.
+    #[inline]
+    fn begin_object_value_in<W>(&mut self, writer: &mut W, _cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.begin_object_value(writer)
+    }
+
+    /// Called after every object value, with the context of its entry. Calls
+    /// `end_object_value` by default.
+    #[inline]
+    fn end_object_value_in<W>(&mut self, writer: &mut W, _cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.end_object_value(writer)
+    }
+
+    /// The lists of atoms to write on one line, as the most atoms such a list
+    /// may hold and the most bytes any of them may take. The elements of
+    /// such a list are written separated by single spaces, and what the
+    /// `*_array_value_in` calls for them write is dropped. Returns `None` by
+    /// default, which lays out every element of every array with those
+    /// calls.
+    #[inline]
+    fn inline_atoms(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Called instead of `end_array_in` after an array written on one line,
+    /// as `inline_atoms` asks. Calls `end_array_in` by default.
+    #[inline]
+    fn end_inline_array_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.end_array_in(writer, cx)
+    }
+}
+
+/// The ways in which a `Formatter` can write floating point numbers. Set one
+/// with `PrettyFormatter::with_float_style`, or for any formatter with
+/// `Serializer::with_float_style`.
+///
+/// Readers differ in what they accept: some require a decimal point in every
+/// float, some a fixed number of decimals, and KiCad expects at most six
+/// decimals with trailing zeros removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FloatStyle {
+    /// The shortest representation that reads back as the same value, such
+    /// as `1.0`, `0.1` or `1e100`.
+    #[default]
+    Shortest,
+
+    /// Exactly this many digits after the decimal point: `Fixed(3)` writes
+    /// `1.5` as `1.500`.
+    Fixed(usize),
+
+    /// At most this many digits after the decimal point, with trailing zeros
+    /// and a trailing decimal point removed: `Trimmed(6)` writes `1.5` as
+    /// `1.5` and `2.0` as `2`.
+    Trimmed(usize),
+
+    /// Scientific notation with the shortest mantissa, such as `1.5e3`.
+    Scientific,
+}
+
+/// This structure compacts a S-expression value with no extra whitespace.
+#[derive(Clone, Debug)]
+pub struct CompactFormatter;
+
+impl Formatter for CompactFormatter {}
 
 /// This structure pretty prints a S-expression value to make it human readable.
 #[derive(Clone, Debug)]
 pub struct PrettyFormatter<'a> {
     current_indent: usize,
     has_value: bool,
-    indent: &'a [u8],
+    indent: Cow<'a, [u8]>,
+    float_style: FloatStyle,
+    inline_atoms: Option<(usize, usize)>,
+    trailing_newline: bool,
 }
 
 impl<'a> PrettyFormatter<'a> {
@@ -1356,13 +2826,94 @@ impl<'a> PrettyFormatter<'a> {
     }
 
     /// Construct a pretty printer formatter that uses the `indent` string for indentation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `indent` holds anything but spaces and tabs, which would
+    /// change what the output reads as.
     pub fn with_indent(indent: &'a [u8]) -> Self {
+        PrettyFormatter::from_indent(Cow::Borrowed(indent))
+    }
+
+    /// Construct a pretty printer formatter that indents with one tab per
+    /// level.
+    pub fn tabs() -> Self {
+        PrettyFormatter::with_indent(b"\t")
+    }
+
+    /// Construct a pretty printer formatter that indents with `n` spaces per
+    /// level.
+    pub fn spaces(n: usize) -> Self {
+        PrettyFormatter::from_indent(Cow::Owned(vec![b' '; n]))
+    }
+
+    fn from_indent(indent: Cow<'a, [u8]>) -> Self {
+        assert!(
+            indent.iter().all(|&b| b == b' ' || b == b'\t'),
+            "indent must be spaces and tabs only"
+        );
         PrettyFormatter {
             current_indent: 0,
             has_value: false,
             indent,
+            float_style: FloatStyle::Shortest,
+            inline_atoms: None,
+            trailing_newline: false,
         }
     }
+
+    /// Write floating point numbers in the given style rather than the
+    /// shortest round-trip representation.
+    pub fn with_float_style(mut self, float_style: FloatStyle) -> Self {
+        self.float_style = float_style;
+        self
+    }
+
+    /// Write a list of at most `max_atoms` atoms, none longer than
+    /// `max_atom_len` bytes, on one line, as `(1 2 3)`, rather than each
+    /// atom on a line of its own. Only lists of atoms are kept together: a
+    /// list holding another list is laid out element by element.
+    ///
+    /// ```rust
+    /// use serde::Serialize;
+    /// use sexpr::ser::PrettyFormatter;
+    /// use sexpr::Serializer;
+    ///
+    /// let formatter = PrettyFormatter::new().with_inline_atoms(8, 16);
+    /// let mut ser = Serializer::with_formatter(Vec::new(), formatter);
+    /// vec![vec![1, 2, 3], vec![4]].serialize(&mut ser).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(ser.into_inner()).unwrap(),
+    ///     "(\n  (1 2 3)\n  (4)\n)"
+    /// );
+    /// ```
+    ///
+    /// Lists are laid out element by element whatever this says when the
+    /// serializer writes datum labels.
+    pub fn with_inline_atoms(mut self, max_atoms: usize, max_atom_len: usize) -> Self {
+        self.inline_atoms = Some((max_atoms, max_atom_len));
+        self
+    }
+
+    /// End the output with a newline after the outermost list or map, as
+    /// text files conventionally end. A value that is a single atom is
+    /// written without one.
+    pub fn with_trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    // Writes the trailing newline if the list or map just closed was the
+    // outermost.
+    fn end_outermost<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.trailing_newline && self.current_indent == 0 {
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
 }
 
 impl<'a> Default for PrettyFormatter<'a> {
@@ -1372,6 +2923,26 @@ impl<'a> Default for PrettyFormatter<'a> {
 }
 
 impl<'a> Formatter for PrettyFormatter<'a> {
+    #[inline]
+    fn float_style(&self) -> FloatStyle {
+        self.float_style
+    }
+
+    #[inline]
+    fn inline_atoms(&self) -> Option<(usize, usize)> {
+        self.inline_atoms
+    }
+
+    #[inline]
+    fn end_inline_array_in<W>(&mut self, writer: &mut W, _cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.current_indent -= 1;
+        writer.write_all(b")")?;
+        self.end_outermost(writer)
+    }
+
     #[inline]
     fn begin_array<W: ?Sized>(&mut self, writer: &mut W) -> io::Result<()>
     where
@@ -1391,10 +2962,11 @@ impl<'a> Formatter for PrettyFormatter<'a> {
 
         if self.has_value {
             writer.write_all(b"\n")?;
-            indent(writer, self.current_indent, self.indent)?;
+            indent(writer, self.current_indent, &self.indent)?;
         }
 
-        writer.write_all(b")")
+        writer.write_all(b")")?;
+        self.end_outermost(writer)
     }
 
     #[inline]
@@ -1403,7 +2975,7 @@ impl<'a> Formatter for PrettyFormatter<'a> {
         W: io::Write,
     {
         writer.write_all(b"\n")?;
-        indent(writer, self.current_indent, self.indent)?;
+        indent(writer, self.current_indent, &self.indent)?;
         Ok(())
     }
 
@@ -1435,10 +3007,11 @@ impl<'a> Formatter for PrettyFormatter<'a> {
 
         if self.has_value {
             writer.write_all(b"\n")?;
-            indent(writer, self.current_indent, self.indent)?;
+            indent(writer, self.current_indent, &self.indent)?;
         }
 
-        writer.write_all(b"}")
+        writer.write_all(b"}")?;
+        self.end_outermost(writer)
     }
 
     #[inline]
@@ -1451,7 +3024,7 @@ impl<'a> Formatter for PrettyFormatter<'a> {
         } else {
             writer.write_all(b",\n")?;
         }
-        indent(writer, self.current_indent, self.indent)
+        indent(writer, self.current_indent, &self.indent)
     }
 
     #[inline]
@@ -1472,9 +3045,360 @@ impl<'a> Formatter for PrettyFormatter<'a> {
     }
 }
 
+/// A formatter that writes the string values of chosen keys as `"***"`, so
+/// that a request or configuration can be logged whole without leaking its
+/// passwords and tokens. Everything else is written by the wrapped
+/// formatter.
+///
+/// A key is the key of a map or struct entry, or the atom at the head of a
+/// list, as in the entry `(password "hunter2")` of a `Sexp` alist. It is
+/// chosen by name with `with_key`, or by any test of its name with
+/// `with_key_matching`, such as a regex match. Every string under a chosen
+/// key is replaced, including those in lists and alists nested in its value;
+/// numbers, symbols and the keys themselves are written as they are.
+///
+/// ```rust
+/// use serde_derive::Serialize;
+/// use sexpr::ser::{CompactFormatter, Redacting};
+/// use sexpr::Serializer;
+///
+/// #[derive(Serialize)]
+/// struct Login {
+///     user: String,
+///     password: String,
+///     api_token: String,
+/// }
+///
+/// let login = Login {
+///     user: "ada".to_owned(),
+///     password: "hunter2".to_owned(),
+///     api_token: "abc123".to_owned(),
+/// };
+/// let formatter = Redacting::new(CompactFormatter)
+///     .with_key("password")
+///     .with_key_matching(|key| key.ends_with("_token"));
+/// let mut ser = Serializer::with_formatter(Vec::new(), formatter);
+/// serde::Serialize::serialize(&login, &mut ser).unwrap();
+/// assert_eq!(
+///     String::from_utf8(ser.into_inner()).unwrap(),
+///     r#"("user"."ada" "password"."***" "api_token"."***")"#
+/// );
+/// ```
+///
+/// Keys are recognized through the `*_object_key*` callbacks. A serializer
+/// `with_sort_keys` writes the entries of a map before it sorts them, outside
+/// those callbacks, so its maps are not redacted; use a `BTreeMap` for
+/// sorted output instead.
+#[derive(Clone)]
+pub struct Redacting<F> {
+    inner: F,
+    keys: Vec<String>,
+    matchers: Vec<KeyMatcher>,
+    /// The text of the key or list head being written, while it is written.
+    key: Option<String>,
+    /// The last key written.
+    last_key: String,
+    /// The depth of the alist entry or list with a chosen key, while the
+    /// rest of it is written.
+    redacting: Option<usize>,
+    /// Whether the fragments of the string being written are left out.
+    in_redacted_string: bool,
+}
+
+type KeyMatcher = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+impl<F: Formatter> Redacting<F> {
+    /// Wraps `inner`, redacting no keys until some are chosen.
+    pub fn new(inner: F) -> Self {
+        Redacting {
+            inner,
+            keys: Vec::new(),
+            matchers: Vec::new(),
+            key: None,
+            last_key: String::new(),
+            redacting: None,
+            in_redacted_string: false,
+        }
+    }
+
+    /// Redacts the values of keys named exactly `key`.
+    pub fn with_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.keys.push(key.into());
+        self
+    }
+
+    /// Redacts the values of keys whose names `matches` returns true for.
+    pub fn with_key_matching<M>(mut self, matches: M) -> Self
+    where
+        M: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.matchers.push(Arc::new(matches));
+        self
+    }
+
+    /// Unwraps the wrapped formatter.
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+
+    fn is_chosen(&self, key: &str) -> bool {
+        self.keys.iter().any(|k| k == key) || self.matchers.iter().any(|matches| matches(key))
+    }
+}
+
+impl<F: fmt::Debug> fmt::Debug for Redacting<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Redacting")
+            .field("inner", &self.inner)
+            .field("keys", &self.keys)
+            .field("matchers", &self.matchers.len())
+            .finish()
+    }
+}
+
+macro_rules! forward_to_inner {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            #[inline]
+            fn $method<W>(&mut self, writer: &mut W $(, $arg: $ty)*) -> io::Result<()>
+            where
+                W: ?Sized + io::Write,
+            {
+                self.inner.$method(writer $(, $arg)*)
+            }
+        )*
+    };
+}
+
+impl<F: Formatter> Formatter for Redacting<F> {
+    forward_to_inner! {
+        write_null();
+        write_bool(value: bool);
+        write_i8(value: i8);
+        write_i16(value: i16);
+        write_i32(value: i32);
+        write_i64(value: i64);
+        write_u8(value: u8);
+        write_u16(value: u16);
+        write_u32(value: u32);
+        write_u64(value: u64);
+        write_f32(value: f32);
+        write_f64(value: f64);
+        write_non_finite(value: f64);
+        begin_array();
+        end_array();
+        begin_array_value(first: bool);
+        end_array_value();
+        begin_object();
+        end_object();
+        begin_object_key(first: bool);
+        end_object_key();
+        begin_object_value();
+        end_object_value();
+        end_object_in(cx: ListContext);
+    }
+
+    #[inline]
+    fn float_style(&self) -> FloatStyle {
+        self.inner.float_style()
+    }
+
+    #[inline]
+    fn inline_atoms(&self) -> Option<(usize, usize)> {
+        self.inner.inline_atoms()
+    }
+
+    fn write_symbol<W>(&mut self, writer: &mut W, value: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if let Some(ref mut key) = self.key {
+            key.push_str(value);
+        }
+        self.inner.write_symbol(writer, value)
+    }
+
+    fn write_raw_fragment<W>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.in_redacted_string {
+            return Ok(());
+        }
+        self.inner.write_raw_fragment(writer, fragment)
+    }
+
+    fn begin_string<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.begin_string(writer)?;
+        if self.key.is_none() && self.redacting.is_some() {
+            self.in_redacted_string = true;
+            self.inner.write_string_fragment(writer, "***")?;
+        }
+        Ok(())
+    }
+
+    fn end_string<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.in_redacted_string = false;
+        self.inner.end_string(writer)
+    }
+
+    fn write_string_fragment<W>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.in_redacted_string {
+            return Ok(());
+        }
+        if let Some(ref mut key) = self.key {
+            key.push_str(fragment);
+        }
+        self.inner.write_string_fragment(writer, fragment)
+    }
+
+    fn write_char_escape<W>(&mut self, writer: &mut W, char_escape: CharEscape) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.in_redacted_string {
+            return Ok(());
+        }
+        if let Some(ref mut key) = self.key {
+            key.push(char_escape.unescaped());
+        }
+        self.inner.write_char_escape(writer, char_escape)
+    }
+
+    fn begin_array_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        // A list at the head of a list is not a key.
+        self.key = None;
+        self.inner.begin_array_in(writer, cx)
+    }
+
+    fn end_array_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.redacting == Some(cx.depth()) {
+            self.redacting = None;
+        }
+        self.inner.end_array_in(writer, cx)
+    }
+
+    fn end_inline_array_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.redacting == Some(cx.depth()) {
+            self.redacting = None;
+        }
+        self.inner.end_inline_array_in(writer, cx)
+    }
+
+    fn begin_array_value_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if cx.index() == 0 && self.redacting.is_none() {
+            self.key = Some(String::new());
+        }
+        self.inner.begin_array_value_in(writer, cx)
+    }
+
+    fn end_array_value_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if cx.index() == 0 {
+            if let Some(head) = self.key.take() {
+                if self.is_chosen(&head) {
+                    self.redacting = Some(cx.depth());
+                }
+            }
+        }
+        self.inner.end_array_value_in(writer, cx)
+    }
+
+    fn begin_object_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.key = None;
+        self.inner.begin_object_in(writer, cx)
+    }
+
+    fn begin_object_key_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.key = Some(String::new());
+        self.inner.begin_object_key_in(writer, cx)
+    }
+
+    fn end_object_key_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.last_key = self.key.take().unwrap_or_default();
+        self.inner.end_object_key_in(writer, cx)
+    }
+
+    fn begin_object_value_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.redacting.is_none() && self.is_chosen(&self.last_key) {
+            self.redacting = Some(cx.depth());
+        }
+        self.inner.begin_object_value_in(writer, cx)
+    }
+
+    fn end_object_value_in<W>(&mut self, writer: &mut W, cx: ListContext) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.redacting == Some(cx.depth()) {
+            self.redacting = None;
+        }
+        self.inner.end_object_value_in(writer, cx)
+    }
+}
+
+fn write_float<W, T>(writer: &mut W, value: T, style: FloatStyle) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+    T: dtoa::Floating + fmt::Display + fmt::LowerExp,
+{
+    match style {
+        FloatStyle::Shortest => dtoa::write(writer, value).map(|_| ()),
+        FloatStyle::Fixed(precision) => write!(writer, "{:.*}", precision, value),
+        FloatStyle::Trimmed(precision) => {
+            let fixed = format!("{:.*}", precision, value);
+            let trimmed = if fixed.contains('.') {
+                fixed.trim_end_matches('0').trim_end_matches('.')
+            } else {
+                &fixed
+            };
+            // Rounding a small negative number can leave a bare `-0`.
+            let trimmed = if trimmed == "-0" { "0" } else { trimmed };
+            writer.write_all(trimmed.as_bytes())
+        }
+        FloatStyle::Scientific => write!(writer, "{:e}", value),
+    }
+}
+
 fn format_escaped_str<W: ?Sized, F: ?Sized>(
     writer: &mut W,
     formatter: &mut F,
+    non_ascii_chars: NonAsciiChars,
     value: &str,
 ) -> io::Result<()>
 where
@@ -1482,7 +3406,7 @@ where
     F: Formatter,
 {
     formatter.begin_string(writer)?;
-    format_escaped_str_contents(writer, formatter, value)?;
+    format_escaped_str_contents(writer, formatter, non_ascii_chars, value)?;
     formatter.end_string(writer)?;
     Ok(())
 }
@@ -1490,6 +3414,7 @@ where
 fn format_escaped_str_contents<W: ?Sized, F: ?Sized>(
     writer: &mut W,
     formatter: &mut F,
+    non_ascii_chars: NonAsciiChars,
     value: &str,
 ) -> io::Result<()>
 where
@@ -1499,10 +3424,13 @@ where
     let bytes = value.as_bytes();
 
     let mut start = 0;
+    let mut i = 0;
 
-    for (i, &byte) in bytes.iter().enumerate() {
+    while i < bytes.len() {
+        let byte = bytes[i];
         let escape = ESCAPE[byte as usize];
-        if escape == 0 {
+        if escape == 0 && (byte < 0x80 || non_ascii_chars == NonAsciiChars::Write) {
+            i += 1;
             continue;
         }
 
@@ -1510,10 +3438,18 @@ where
             formatter.write_string_fragment(writer, &value[start..i])?;
         }
 
-        let char_escape = CharEscape::from_escape_table(escape, byte);
-        formatter.write_char_escape(writer, char_escape)?;
+        if escape != 0 {
+            let char_escape = CharEscape::from_escape_table(escape, byte);
+            formatter.write_char_escape(writer, char_escape)?;
+            i += 1;
+        } else {
+            // `i` is at the first byte of a multi-byte character.
+            let ch = value[i..].chars().next().unwrap();
+            write_non_ascii_escape(writer, formatter, non_ascii_chars, ch)?;
+            i += ch.len_utf8();
+        }
 
-        start = i + 1;
+        start = i;
     }
 
     if start != bytes.len() {
@@ -1523,6 +3459,51 @@ where
     Ok(())
 }
 
+/// Whether a symbol has to be written between bars to read back as itself.
+/// The deserializer starts a bare symbol at a letter and ends it at
+/// whitespace or a closing bracket, where whitespace includes all of what
+/// Unicode calls whitespace unless `strict_whitespace` is set; anything that
+/// could open another value or a comment is also kept out of bare symbols.
+/// A bare `nil`, in any case, reads back as nil where an option is expected
+/// or with `DeserializerOptions::nil_symbol`, so it is barred too.
+pub(crate) fn symbol_needs_bars(symbol: &str) -> bool {
+    match symbol.as_bytes().first() {
+        Some(first) if first.is_ascii_alphabetic() => {
+            symbol.eq_ignore_ascii_case("nil")
+                || symbol.chars().any(|ch| {
+                    ch.is_whitespace()
+                        || matches!(ch, '(' | ')' | '[' | ']' | '"' | ';' | '|' | '\\')
+                })
+        }
+        _ => true,
+    }
+}
+
+fn write_non_ascii_escape<W, F>(
+    writer: &mut W,
+    formatter: &mut F,
+    non_ascii_chars: NonAsciiChars,
+    ch: char,
+) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+    F: ?Sized + Formatter,
+{
+    let escaped = match non_ascii_chars {
+        NonAsciiChars::Write => unreachable!(),
+        NonAsciiChars::EscapeUnicode => {
+            let mut units = [0; 2];
+            let mut escaped = String::new();
+            for unit in ch.encode_utf16(&mut units) {
+                escaped.push_str(&format!("\\u{:04x}", unit));
+            }
+            escaped
+        }
+        NonAsciiChars::EscapeHex => format!("\\x{:x};", ch as u32),
+    };
+    formatter.write_raw_fragment(writer, &escaped)
+}
+
 const BB: u8 = b'b'; // \x08
 const TT: u8 = b't'; // \x09
 const NN: u8 = b'n'; // \x0A
@@ -1559,6 +3540,7 @@ static ESCAPE: [u8; 256] = [
 fn format_escaped_char<W: ?Sized, F: ?Sized>(
     wr: &mut W,
     formatter: &mut F,
+    non_ascii_chars: NonAsciiChars,
     value: char,
 ) -> io::Result<()>
 where
@@ -1574,7 +3556,7 @@ where
     // the method `char::encode_utf8`.
     // See https://github.com/serde-rs/json/issues/270.
     let slice = unsafe { str::from_utf8_unchecked(&buf[0..value.len_utf8()]) };
-    format_escaped_str(wr, formatter, slice)
+    format_escaped_str(wr, formatter, non_ascii_chars, slice)
 }
 
 /// Serialize the given data structure as S-expression into the IO stream.
@@ -1589,9 +3571,13 @@ where
     W: io::Write,
     T: ser::Serialize,
 {
+    #[cfg(feature = "trace")]
+    let _span = tracing::debug_span!("serialize", ty = std::any::type_name::<T>()).entered();
+
     let mut ser = Serializer::new(writer);
-    value.serialize(&mut ser)?;
-    Ok(())
+    let result = value.serialize(&mut ser);
+    trace_error!(result, "serialize");
+    result
 }
 
 /// Serialize the given data structure as pretty-printed S-expression into the IO
@@ -1607,11 +3593,82 @@ where
     W: io::Write,
     T: ser::Serialize,
 {
+    #[cfg(feature = "trace")]
+    let _span = tracing::debug_span!("serialize", ty = std::any::type_name::<T>()).entered();
+
     let mut ser = Serializer::pretty(writer);
-    value.serialize(&mut ser)?;
+    let result = value.serialize(&mut ser);
+    trace_error!(result, "serialize");
+    result
+}
+
+/// Serialize the given data structure as gzip-compressed S-expression text
+/// into the IO stream, at a compression `level` from 0 (none) to 9 (best).
+/// To write a stream of values, hand a `flate2::write::GzEncoder` to a
+/// `Serializer`. Requires the `gzip` feature.
+///
+/// ```no_run
+/// use std::fs::File;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let samples = vec![("cpu", 0.25), ("cpu", 0.5)];
+/// sexpr::ser::to_writer_gzip(File::create("samples.sexp.gz")?, &samples, 6)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Fails in the same cases as `to_writer`, and if the compressed data
+/// cannot be written.
+#[cfg(feature = "gzip")]
+pub fn to_writer_gzip<W, T: ?Sized>(writer: W, value: &T, level: u32) -> Result<()>
+where
+    W: io::Write,
+    T: ser::Serialize,
+{
+    let encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::new(level));
+    let encoder = to_buffered_writer(encoder, value)?;
+    encoder.finish().map_err(Error::io)?;
     Ok(())
 }
 
+/// Serialize the given data structure as zstd-compressed S-expression text
+/// into the IO stream, at a compression `level` from 1 to 22, or 0 for
+/// zstd's default. To write a stream of values, hand a `zstd::Encoder` to a
+/// `Serializer`. Requires the `zstd` feature.
+///
+/// # Errors
+///
+/// Fails in the same cases as `to_writer`, and if the compressed data
+/// cannot be written.
+#[cfg(feature = "zstd")]
+pub fn to_writer_zstd<W, T: ?Sized>(writer: W, value: &T, level: i32) -> Result<()>
+where
+    W: io::Write,
+    T: ser::Serialize,
+{
+    let encoder = zstd::Encoder::new(writer, level).map_err(Error::io)?;
+    let encoder = to_buffered_writer(encoder, value)?;
+    encoder.finish().map_err(Error::io)?;
+    Ok(())
+}
+
+/// Serializes `value` into `writer` through a buffer, so that a compressor
+/// is not handed each token on its own, and returns `writer`.
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn to_buffered_writer<W, T: ?Sized>(writer: W, value: &T) -> Result<W>
+where
+    W: io::Write,
+    T: ser::Serialize,
+{
+    let mut buffered = io::BufWriter::new(writer);
+    to_writer(&mut buffered, value)?;
+    buffered
+        .into_inner()
+        .map_err(|err| Error::io(err.into_error()))
+}
+
 /// Serialize the given data structure as a S-expression byte vector.
 ///
 /// # Errors
@@ -1644,6 +3701,61 @@ where
     Ok(writer)
 }
 
+/// Serialize the given data structure as S-expression, appending it to the
+/// end of `vec`.
+///
+/// If serialization fails, `vec` is left as it was.
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+#[inline]
+pub fn to_vec_into<T>(vec: &mut Vec<u8>, value: &T) -> Result<()>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let len = vec.len();
+    let result = to_writer(&mut *vec, value);
+    if result.is_err() {
+        vec.truncate(len);
+    }
+    result
+}
+
+/// Serialize the given data structure as S-expression, appending it to the
+/// end of `string`.
+///
+/// If serialization fails, `string` is left as it was.
+///
+/// ```rust
+/// let mut out = String::new();
+/// for n in 1..4 {
+///     sexpr::ser::to_string_into(&mut out, &vec![n; n]).unwrap();
+///     out.push('\n');
+/// }
+/// assert_eq!(out, "(1)\n(2 2)\n(3 3 3)\n");
+/// ```
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+#[inline]
+pub fn to_string_into<T>(string: &mut String, value: &T) -> Result<()>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut vec = mem::take(string).into_bytes();
+    let result = to_vec_into(&mut vec, value);
+    *string = unsafe {
+        // We do not emit invalid UTF-8, and on failure `vec` is truncated
+        // back to the original string.
+        String::from_utf8_unchecked(vec)
+    };
+    result
+}
+
 /// Serialize the given data structure as a String of S-expression.
 ///
 /// # Errors
@@ -1682,6 +3794,216 @@ where
     Ok(string)
 }
 
+/// Writes the forms and comments of a document into the IO stream, each form
+/// on a line of its own and each comment on its own line before the form it
+/// came before.
+///
+/// # Errors
+///
+/// Fails if writing fails.
+pub fn to_writer_document<W>(mut writer: W, document: &SexpDocument) -> Result<()>
+where
+    W: io::Write,
+{
+    for item in &document.0 {
+        match *item {
+            DocumentItem::Form(ref form) => {
+                to_writer(&mut writer, form)?;
+                writer.write_all(b"\n").map_err(Error::io)?;
+            }
+            DocumentItem::Comment(ref text) => {
+                // An empty comment still takes a line.
+                for line in text.lines().chain(text.is_empty().then_some("")) {
+                    writer
+                        .write_all(b";")
+                        .and_then(|()| writer.write_all(line.as_bytes()))
+                        .and_then(|()| writer.write_all(b"\n"))
+                        .map_err(Error::io)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the forms and comments of a document as a String, laid out as by
+/// `to_writer_document`. Reading the String back with `from_str_document`
+/// gives the same document.
+///
+/// # Errors
+///
+/// Fails for the same reasons as `to_writer_document`.
+pub fn to_string_document(document: &SexpDocument) -> Result<String> {
+    let mut vec = Vec::new();
+    to_writer_document(&mut vec, document)?;
+    let string = unsafe {
+        // We do not emit invalid UTF-8.
+        String::from_utf8_unchecked(vec)
+    };
+    Ok(string)
+}
+
+/// Serialize the given data structure as a String of S-expression that
+/// contains only ASCII. Characters outside ASCII in strings are written as
+/// `\uXXXX` escapes, which `from_str` reads back.
+///
+/// ```rust
+/// let s = sexpr::ser::to_string_ascii(&("café", "😀")).unwrap();
+/// assert_eq!(s, r#"("caf\u00e9" "\ud83d\ude00")"#);
+/// let back: (String, String) = sexpr::from_str(&s).unwrap();
+/// assert_eq!(back, ("café".to_owned(), "😀".to_owned()));
+/// ```
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+#[inline]
+pub fn to_string_ascii<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut writer = Vec::with_capacity(128);
+    {
+        let mut ser =
+            Serializer::new(&mut writer).with_non_ascii_chars(NonAsciiChars::EscapeUnicode);
+        value.serialize(&mut ser)?;
+    }
+    let string = unsafe {
+        // Only ASCII is written.
+        String::from_utf8_unchecked(writer)
+    };
+    Ok(string)
+}
+
+/// The kinds of text that `to_string_escaped_for` and `EscapingWriter` can
+/// escape S-expression text for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscapeTarget {
+    /// The contents of a JSON string, between its `"` quotes. Quotes,
+    /// backslashes and control characters are escaped.
+    JsonString,
+
+    /// The contents of a shell word between `'` quotes. Each `'` is written
+    /// `'\''`, which closes the quotes, adds an escaped quote and opens them
+    /// again.
+    ShellSingleQuoted,
+
+    /// HTML text or the value of a quoted attribute. `&`, `<`, `>`, `"` and
+    /// `'` are written as character references.
+    Html,
+}
+
+/// A writer that escapes everything written through it for `target` before
+/// handing it on, so that S-expression text can be written straight into a
+/// JSON string, a shell command or an HTML page. The quotes around the
+/// escaped text are left to the caller.
+///
+/// ```rust
+/// use sexpr::ser::{EscapeTarget, EscapingWriter};
+///
+/// let mut out = b"{\"form\": \"".to_vec();
+/// sexpr::ser::to_writer(EscapingWriter::new(&mut out, EscapeTarget::JsonString), &("a\nb",)).unwrap();
+/// out.extend_from_slice(b"\"}");
+/// assert_eq!(out, br#"{"form": "(\"a\\nb\")"}"#);
+/// ```
+#[derive(Debug)]
+pub struct EscapingWriter<W> {
+    writer: W,
+    target: EscapeTarget,
+}
+
+impl<W: io::Write> EscapingWriter<W> {
+    /// Creates a writer that escapes for `target` and writes to `writer`.
+    pub fn new(writer: W, target: EscapeTarget) -> Self {
+        EscapingWriter { writer, target }
+    }
+
+    /// Unwraps the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_escape(&mut self, byte: u8) -> io::Result<()> {
+        match (self.target, byte) {
+            (EscapeTarget::JsonString, b'"') => self.writer.write_all(b"\\\""),
+            (EscapeTarget::JsonString, b'\\') => self.writer.write_all(b"\\\\"),
+            (EscapeTarget::JsonString, b'\n') => self.writer.write_all(b"\\n"),
+            (EscapeTarget::JsonString, b'\r') => self.writer.write_all(b"\\r"),
+            (EscapeTarget::JsonString, b'\t') => self.writer.write_all(b"\\t"),
+            (EscapeTarget::JsonString, _) => write!(self.writer, "\\u{:04x}", byte),
+            (EscapeTarget::ShellSingleQuoted, _) => self.writer.write_all(b"'\\''"),
+            (EscapeTarget::Html, b'&') => self.writer.write_all(b"&amp;"),
+            (EscapeTarget::Html, b'<') => self.writer.write_all(b"&lt;"),
+            (EscapeTarget::Html, b'>') => self.writer.write_all(b"&gt;"),
+            (EscapeTarget::Html, b'"') => self.writer.write_all(b"&quot;"),
+            (EscapeTarget::Html, _) => self.writer.write_all(b"&#39;"),
+        }
+    }
+}
+
+impl EscapeTarget {
+    fn needs_escape(self, byte: u8) -> bool {
+        match self {
+            EscapeTarget::JsonString => {
+                byte == b'"' || byte == b'\\' || byte < 0x20 || byte == 0x7f
+            }
+            EscapeTarget::ShellSingleQuoted => byte == b'\'',
+            EscapeTarget::Html => matches!(byte, b'&' | b'<' | b'>' | b'"' | b'\''),
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for EscapingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut start = 0;
+        for (i, &byte) in buf.iter().enumerate() {
+            if self.target.needs_escape(byte) {
+                self.writer.write_all(&buf[start..i])?;
+                self.write_escape(byte)?;
+                start = i + 1;
+            }
+        }
+        self.writer.write_all(&buf[start..])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Serialize the given data structure as a String of S-expression text
+/// escaped to be placed inside the quotes of a JSON string or a shell word,
+/// or into HTML, as `EscapingWriter` escapes it.
+///
+/// ```rust
+/// use sexpr::ser::{to_string_escaped_for, EscapeTarget};
+///
+/// let form = ("it's", "<b>");
+/// let shell = to_string_escaped_for(&form, EscapeTarget::ShellSingleQuoted).unwrap();
+/// assert_eq!(format!("sexp fmt '{}'", shell), r#"sexp fmt '("it'\''s" "<b>")'"#);
+/// let html = to_string_escaped_for(&form, EscapeTarget::Html).unwrap();
+/// assert_eq!(html, "(&quot;it&#39;s&quot; &quot;&lt;b&gt;&quot;)");
+/// ```
+///
+/// # Errors
+///
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to
+/// fail, or if `T` contains a map with non-string keys.
+pub fn to_string_escaped_for<T>(value: &T, target: EscapeTarget) -> Result<String>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut writer = EscapingWriter::new(Vec::with_capacity(128), target);
+    to_writer(&mut writer, value)?;
+    let string = unsafe {
+        // Only whole UTF-8 characters are written, and escapes are ASCII.
+        String::from_utf8_unchecked(writer.into_inner())
+    };
+    Ok(string)
+}
+
 fn indent<W: ?Sized>(wr: &mut W, n: usize, s: &[u8]) -> io::Result<()>
 where
     W: io::Write,
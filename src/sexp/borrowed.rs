@@ -0,0 +1,117 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An S-expression value that borrows all of its parts.
+
+use serde::ser::{Serialize, Serializer};
+
+use crate::atom::{Atom, KEYWORD_TOKEN, SYMBOL_TOKEN};
+use crate::bytes::{ByteSlice, HEX_BYTES_TOKEN};
+use crate::number::Number;
+use crate::sexp::Sexp;
+
+/// An S-expression value built from borrowed strings and slices, so that it
+/// can be written as a constant or a `static`.
+///
+/// Building a `SexpRef` copies no strings, which suits fixed messages sent
+/// over and over on a hot path. Whether serializing it allocates depends on
+/// the serializer and writer it is handed to.
+///
+/// ```rust
+/// use sexpr::sexp::SexpRef;
+/// use sexpr::Number;
+///
+/// static PING: SexpRef<'static> = SexpRef::List(&[
+///     SexpRef::Symbol("ping"),
+///     SexpRef::Number(Number::from_u64(1)),
+///     SexpRef::String("hello"),
+/// ]);
+///
+/// assert_eq!(sexpr::to_string(&PING).unwrap(), "(ping 1 \"hello\")");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum SexpRef<'a> {
+    /// Represents a S-expression nil value.
+    Nil,
+
+    /// Represents a S-expression symbol.
+    Symbol(&'a str),
+
+    /// Represents a S-expression keyword, without its `#:` prefix.
+    Keyword(&'a str),
+
+    /// Represents a S-expression string.
+    String(&'a str),
+
+    /// Represents a S-expression number, whether integer or floating point.
+    Number(Number),
+
+    /// Represents a S-expression boolean.
+    Boolean(bool),
+
+    /// Represents a S-expression byte string.
+    Bytes(&'a [u8]),
+
+    /// Represents a S-expression cons-pair.
+    Pair(&'a SexpRef<'a>, &'a SexpRef<'a>),
+
+    /// Represents a S-expression list.
+    List(&'a [SexpRef<'a>]),
+}
+
+impl<'a> SexpRef<'a> {
+    /// Copies the value into a `Sexp`, which owns all of its parts.
+    pub fn to_sexp(&self) -> Sexp {
+        match *self {
+            SexpRef::Nil => Sexp::Nil,
+            SexpRef::Symbol(s) => Sexp::Atom(Atom::symbol(s)),
+            SexpRef::Keyword(s) => Sexp::Atom(Atom::keyword(s)),
+            SexpRef::String(s) => Sexp::Atom(Atom::string(s)),
+            SexpRef::Number(ref n) => Sexp::Number(n.clone()),
+            SexpRef::Boolean(b) => Sexp::Boolean(b),
+            SexpRef::Bytes(bytes) => Sexp::Bytes(bytes.to_vec()),
+            SexpRef::Pair(car, cdr) => Sexp::pair(car.to_sexp(), cdr.to_sexp()),
+            SexpRef::List(elems) => Sexp::List(elems.iter().map(SexpRef::to_sexp).collect()),
+        }
+    }
+}
+
+impl<'a> From<SexpRef<'a>> for Sexp {
+    fn from(sexp: SexpRef<'a>) -> Self {
+        sexp.to_sexp()
+    }
+}
+
+impl<'a, 'b> From<&'b SexpRef<'a>> for Sexp {
+    fn from(sexp: &'b SexpRef<'a>) -> Self {
+        sexp.to_sexp()
+    }
+}
+
+impl<'a> Serialize for SexpRef<'a> {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            SexpRef::Nil => serializer.serialize_unit(),
+            SexpRef::Symbol(s) => serializer.serialize_newtype_struct(SYMBOL_TOKEN, s),
+            SexpRef::Keyword(s) => serializer.serialize_newtype_struct(KEYWORD_TOKEN, s),
+            SexpRef::String(s) => serializer.serialize_str(s),
+            SexpRef::Number(ref n) => n.serialize(serializer),
+            SexpRef::Boolean(b) => serializer.serialize_bool(b),
+            SexpRef::Bytes(bytes) => {
+                serializer.serialize_newtype_struct(HEX_BYTES_TOKEN, &ByteSlice(bytes))
+            }
+            SexpRef::Pair(_, _) => self.to_sexp().serialize(serializer),
+            SexpRef::List(elems) => elems.serialize(serializer),
+        }
+    }
+}
@@ -13,13 +13,19 @@ use std::slice;
 use std::str;
 use std::vec;
 
-use serde::de::{Deserialize, DeserializeSeed, MapAccess, SeqAccess, Visitor};
-use serde::{self, forward_to_deserialize_any};
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{
+    Deserialize, DeserializeSeed, EnumAccess, Expected, IntoDeserializer, MapAccess, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+};
+use serde::{self, Deserializer as _};
 
-use crate::atom::Atom;
-use crate::error::Error;
+use crate::atom::{Atom, KEYWORD_TOKEN, SYMBOL_TOKEN};
+use crate::error::{Error, ErrorCode};
 use crate::number::Number;
-use crate::sexp::Sexp;
+#[cfg(feature = "arbitrary_precision")]
+use crate::number::NUMBER_TOKEN;
+use crate::sexp::{Sexp, PAIR_TOKEN};
 
 impl<'de> Deserialize<'de> for Sexp {
     #[inline]
@@ -27,102 +33,174 @@ impl<'de> Deserialize<'de> for Sexp {
     where
         D: serde::Deserializer<'de>,
     {
-        struct ValueVisitor;
+        deserializer.deserialize_any(ValueVisitor { tail: None })
+    }
+}
 
-        impl<'de> Visitor<'de> for ValueVisitor {
-            type Value = Sexp;
+/// Builds a `Sexp` from whatever a deserializer hands over. Reading an
+/// element of a list, it notes in `tail` whether that was the tail after a
+/// `.`.
+struct ValueVisitor<'a> {
+    tail: Option<&'a mut bool>,
+}
 
-            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("any valid Sexp value")
-            }
+impl<'de, 'a> Visitor<'de> for ValueVisitor<'a> {
+    type Value = Sexp;
 
-            #[inline]
-            fn visit_bool<E>(self, value: bool) -> Result<Sexp, E> {
-                Ok(Sexp::Boolean(value))
-            }
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid Sexp value")
+    }
 
-            #[inline]
-            fn visit_i64<E>(self, value: i64) -> Result<Sexp, E> {
-                Ok(Sexp::Number(value.into()))
-            }
+    #[inline]
+    fn visit_bool<E>(self, value: bool) -> Result<Sexp, E> {
+        Ok(Sexp::Boolean(value))
+    }
 
-            #[inline]
-            fn visit_u64<E>(self, value: u64) -> Result<Sexp, E> {
-                Ok(Sexp::Number(value.into()))
-            }
+    #[inline]
+    fn visit_i64<E>(self, value: i64) -> Result<Sexp, E> {
+        Ok(Sexp::Number(value.into()))
+    }
 
-            #[inline]
-            fn visit_f64<E>(self, value: f64) -> Result<Sexp, E> {
-                Ok(Number::from_f64(value).map_or(Sexp::Nil, Sexp::Number))
-            }
+    #[inline]
+    fn visit_u64<E>(self, value: u64) -> Result<Sexp, E> {
+        Ok(Sexp::Number(value.into()))
+    }
 
-            #[inline]
-            fn visit_str<E>(self, value: &str) -> Result<Sexp, E>
-            where
-                E: serde::de::Error,
-            {
-                self.visit_string(String::from(value))
-            }
+    #[inline]
+    fn visit_f64<E>(self, value: f64) -> Result<Sexp, E> {
+        Ok(Number::from_f64(value).map_or(Sexp::Nil, Sexp::Number))
+    }
 
-            #[inline]
-            fn visit_string<E>(self, value: String) -> Result<Sexp, E> {
-                Ok(Sexp::Atom(Atom::new_string(value)))
-            }
+    #[inline]
+    fn visit_str<E>(self, value: &str) -> Result<Sexp, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_string(String::from(value))
+    }
 
-            #[inline]
-            fn visit_none<E>(self) -> Result<Sexp, E> {
-                Ok(Sexp::Nil)
-            }
+    #[inline]
+    fn visit_string<E>(self, value: String) -> Result<Sexp, E> {
+        Ok(Sexp::Atom(Atom::new_string(value)))
+    }
 
-            #[inline]
-            fn visit_some<D>(self, deserializer: D) -> Result<Sexp, D::Error>
-            where
-                D: serde::Deserializer<'de>,
-            {
-                Deserialize::deserialize(deserializer)
-            }
+    #[inline]
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Sexp, E> {
+        Ok(Sexp::Bytes(value.to_vec()))
+    }
 
-            #[inline]
-            fn visit_unit<E>(self) -> Result<Sexp, E> {
-                Ok(Sexp::Nil)
-            }
+    #[inline]
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Sexp, E> {
+        Ok(Sexp::Bytes(value))
+    }
 
-            #[inline]
-            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Sexp, D::Error>
-            where
-                D: serde::Deserializer<'de>,
-            {
-                // XXX something about this feels wrong
-                let result: String = Deserialize::deserialize(deserializer)?;
-                Ok(Sexp::Atom(Atom::new_symbol(result)))
-            }
+    #[inline]
+    fn visit_none<E>(self) -> Result<Sexp, E> {
+        Ok(Sexp::Nil)
+    }
 
-            #[inline]
-            fn visit_seq<V>(self, mut visitor: V) -> Result<Sexp, V::Error>
-            where
-                V: SeqAccess<'de>,
-            {
-                let mut vec = Vec::new();
+    #[inline]
+    fn visit_some<D>(self, deserializer: D) -> Result<Sexp, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Deserialize::deserialize(deserializer)
+    }
 
-                while let Some(elem) = visitor.next_element()? {
-                    vec.push(elem);
-                }
+    #[inline]
+    fn visit_unit<E>(self) -> Result<Sexp, E> {
+        Ok(Sexp::Nil)
+    }
 
-                Ok(Sexp::List(vec))
-            }
+    #[inline]
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Sexp, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        // XXX something about this feels wrong
+        let result: String = Deserialize::deserialize(deserializer)?;
+        Ok(Sexp::Atom(Atom::from_token_text(result)))
+    }
 
-            fn visit_map<V>(self, _visitor: V) -> Result<Sexp, V::Error>
-            where
-                V: MapAccess<'de>,
-            {
-                unimplemented!()
+    #[inline]
+    fn visit_seq<V>(self, visitor: V) -> Result<Sexp, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        read_list(Vec::new(), visitor)
+    }
+
+    /// A map is read as an alist.
+    fn visit_map<V>(mut self, mut visitor: V) -> Result<Sexp, V::Error>
+    where
+        V: MapAccess<'de>,
+    {
+        let mut entries = Vec::new();
+
+        while let Some(key) = visitor.next_key::<Sexp>()? {
+            if let (true, Sexp::Atom(Atom::String(ref token))) = (entries.is_empty(), &key) {
+                // A number kept as its literal text arrives as a map of
+                // one entry under `NUMBER_TOKEN`.
+                #[cfg(feature = "arbitrary_precision")]
+                {
+                    if token == NUMBER_TOKEN {
+                        let literal = visitor.next_value()?;
+                        return Number::from_literal_value(literal).map(Sexp::Number);
+                    }
+                }
+                // So does the tail of a dotted list, under `PAIR_TOKEN`.
+                if token == PAIR_TOKEN && self.tail.is_some() {
+                    *self.tail.take().unwrap() = true;
+                    return visitor.next_value();
+                }
             }
+            entries.push(Sexp::pair(key, visitor.next_value::<Sexp>()?));
         }
 
-        deserializer.deserialize_any(ValueVisitor)
+        Ok(Sexp::List(entries))
+    }
+}
+
+/// Reads an element of a list, noting in `tail` whether it was the tail
+/// after a `.`.
+struct ElementSeed<'a> {
+    tail: &'a mut bool,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for ElementSeed<'a> {
+    type Value = Sexp;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Sexp, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor {
+            tail: Some(self.tail),
+        })
     }
 }
 
+/// Reads the elements left in a list after `elems`. A list with a dotted
+/// tail, as in `(a b . c)`, becomes the pairs `Sexp::pair(a, Sexp::pair(b,
+/// c))`; the list must end after its tail, which is left to the caller.
+pub(crate) fn read_list<'de, A>(mut elems: Vec<Sexp>, mut seq: A) -> Result<Sexp, A::Error>
+where
+    A: SeqAccess<'de>,
+{
+    let mut tail = false;
+    while let Some(elem) = seq.next_element_seed(ElementSeed { tail: &mut tail })? {
+        if tail {
+            let pairs = elems
+                .into_iter()
+                .rev()
+                .fold(elem, |cdr, car| Sexp::pair(car, cdr));
+            return Ok(pairs);
+        }
+        elems.push(elem);
+    }
+    Ok(Sexp::List(elems))
+}
+
 struct WriterFormatter<'a, 'b: 'a> {
     inner: &'a mut fmt::Formatter<'b>,
 }
@@ -158,13 +236,191 @@ impl fmt::Display for Sexp {
     }
 }
 
+impl Sexp {
+    /// Reads a value from S-expression text, as `sexpr::from_str` does, for
+    /// callers that have not imported `FromStr`.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let form = Sexp::from_str("(a b)").unwrap();
+    /// assert_eq!(form, Sexp::List(vec![Sexp::from("a"), Sexp::from("b")]));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if the text is not exactly one well-formed value.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Sexp, Error> {
+        super::super::de::from_str(s)
+    }
+}
+
 impl str::FromStr for Sexp {
     type Err = Error;
     fn from_str(s: &str) -> Result<Sexp, Error> {
-        super::super::de::from_str(s)
+        Sexp::from_str(s)
+    }
+}
+
+/// Reads the text as exactly one atom, by the same rules as the parser: a
+/// string with its escapes, a symbol, a symbol between bars, or a keyword.
+/// Tools that split text into tokens themselves can read each token the way
+/// `from_str` would.
+///
+/// Unlike `Sexp`, which reads the text as a whole S-expression document,
+/// whitespace or comments around the atom are an error.
+///
+/// ```rust
+/// use sexpr::sexp::Atom;
+///
+/// assert_eq!("\"a\\nb\"".parse::<Atom>().unwrap(), Atom::string("a\nb"));
+/// assert_eq!("#:port".parse::<Atom>().unwrap(), Atom::keyword("port"));
+/// assert_eq!("|two words|".parse::<Atom>().unwrap(), Atom::symbol("two words"));
+/// assert!("42".parse::<Atom>().is_err());
+/// assert!("a b".parse::<Atom>().is_err());
+/// ```
+impl str::FromStr for Atom {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Atom, Error> {
+        match crate::de::from_token(s)? {
+            Sexp::Atom(atom) => Ok(atom),
+            sexp => Err(sexp.invalid_type(&"an atom")),
+        }
+    }
+}
+
+/// Reads the text as exactly one number, by the same rules as the parser, so
+/// an integer too large for a `u64` or an `i64` is read as a float. As for
+/// `Atom`, nothing may come before or after it.
+///
+/// ```rust
+/// use sexpr::Number;
+///
+/// assert_eq!("-12".parse::<Number>().unwrap().as_i64(), Some(-12));
+/// assert_eq!("2.5".parse::<Number>().unwrap().as_f64(), Some(2.5));
+/// assert!("12 ".parse::<Number>().is_err());
+/// assert!("x".parse::<Number>().is_err());
+/// ```
+impl str::FromStr for Number {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Number, Error> {
+        match crate::de::from_token(s)? {
+            Sexp::Number(n) => Ok(n),
+            sexp => Err(sexp.invalid_type(&"a number")),
+        }
+    }
+}
+
+impl Sexp {
+    /// How the value is described in a type error.
+    fn unexpected(&self) -> Unexpected<'_> {
+        match *self {
+            Sexp::Nil => Unexpected::Unit,
+            Sexp::Boolean(b) => Unexpected::Bool(b),
+            Sexp::Number(ref n) => n.unexpected(),
+            Sexp::Atom(Atom::Symbol(_)) => Unexpected::Other("symbol"),
+            Sexp::Atom(Atom::Keyword(_)) => Unexpected::Other("keyword"),
+            Sexp::Atom(Atom::String(ref s)) => Unexpected::Str(s),
+            Sexp::Bytes(ref bytes) => Unexpected::Bytes(bytes),
+            Sexp::List(_) => Unexpected::Seq,
+            Sexp::Pair(..) => Unexpected::Other("improper list"),
+        }
     }
+
+    fn invalid_type(&self, exp: &dyn Expected) -> Error {
+        serde::de::Error::invalid_type(self.unexpected(), exp)
+    }
+}
+
+/// The text the text deserializer passes to `visit_newtype_struct` for a
+/// symbol or keyword.
+fn token_text(atom: &Atom) -> String {
+    match *atom {
+        Atom::Keyword(ref s) => format!("#:{}", s),
+        Atom::Symbol(ref s) | Atom::String(ref s) => s.clone(),
+    }
+}
+
+/// Whether a list is read as a map by `deserialize_any`: it has elements
+/// and all of them are pairs.
+fn is_alist(elems: &[Sexp]) -> bool {
+    !elems.is_empty() && elems.iter().all(|elem| matches!(*elem, Sexp::Pair(..)))
 }
 
+fn deserialize_number<'de, V>(sexp: &Sexp, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    match *sexp {
+        Sexp::Number(ref n) => n.deserialize_any(visitor),
+        _ => Err(sexp.invalid_type(&visitor)),
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                deserialize_number(&self, visitor)
+            }
+        )*
+    };
+}
+
+/// Reads `elems` as a sequence, failing if the visitor stops early.
+fn visit_seq<'de, V>(elems: Vec<Sexp>, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    let len = elems.len();
+    let mut deserializer = SeqDeserializer::new(elems);
+    let seq = visitor.visit_seq(&mut deserializer)?;
+    let remaining = deserializer.iter.len();
+    if remaining == 0 {
+        Ok(seq)
+    } else {
+        Err(serde::de::Error::invalid_length(
+            len,
+            &"fewer elements in array",
+        ))
+    }
+}
+
+/// Reads the alist `entries` as a map, failing if the visitor stops early.
+fn visit_alist<'de, V>(entries: Vec<Sexp>, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    let len = entries.len();
+    let mut deserializer = MapDeserializer::new(entries);
+    let map = visitor.visit_map(&mut deserializer)?;
+    let remaining = deserializer.iter.len();
+    if remaining == 0 {
+        Ok(map)
+    } else {
+        Err(serde::de::Error::invalid_length(
+            len,
+            &"fewer elements in map",
+        ))
+    }
+}
+
+/// Deserializing from a `Sexp` follows the type's hints, so a value reads
+/// back as what it was serialized from:
+///
+/// - sequences, tuples and tuple structs are read from a list, a pair (as
+///   its car and cdr) or `#nil` (as empty);
+/// - maps and structs are read from an alist, whose entries are pairs or
+///   two-element lists, or from `#nil` as empty;
+/// - an enum variant is read from a symbol or string for a unit variant, a
+///   pair `(Variant . content)`, or a list `(Variant args ...)`.
+///
+/// `deserialize_any` reads a non-empty list of pairs as a map and any other
+/// list as a sequence.
 impl<'de> serde::Deserializer<'de> for Sexp {
     type Error = Error;
 
@@ -177,25 +433,83 @@ impl<'de> serde::Deserializer<'de> for Sexp {
             Sexp::Nil => visitor.visit_unit(),
             Sexp::Boolean(v) => visitor.visit_bool(v),
             Sexp::Number(n) => n.deserialize_any(visitor),
-            Sexp::Atom(a) => visitor.visit_string(a.as_string()),
-            Sexp::Pair(_, _) => unimplemented!(),
+            Sexp::Atom(Atom::String(s)) => visitor.visit_string(s),
+            Sexp::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            Sexp::Atom(ref atom) => {
+                visitor.visit_newtype_struct(token_text(atom).into_deserializer())
+            }
+            Sexp::Pair(..) => self.deserialize_seq(visitor),
             Sexp::List(v) => {
-                let len = v.len();
-                let mut deserializer = SeqDeserializer::new(v);
-                let seq = visitor.visit_seq(&mut deserializer)?;
-                let remaining = deserializer.iter.len();
-                if remaining == 0 {
-                    Ok(seq)
+                if is_alist(&v) {
+                    visit_alist(v, visitor)
                 } else {
-                    Err(serde::de::Error::invalid_length(
-                        len,
-                        &"fewer elements in array",
-                    ))
+                    visit_seq(v, visitor)
                 }
             }
         }
     }
 
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Sexp::Boolean(v) => visitor.visit_bool(v),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    /// Symbols and keywords are read as their name, without the `#:` prefix.
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Sexp::Atom(atom) => visitor.visit_string(atom.into_string()),
+            Sexp::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Sexp::Atom(atom) => visitor.visit_string(atom.into_string()),
+            Sexp::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            Sexp::List(v) => visit_seq(v, visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
     #[inline]
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
     where
@@ -207,79 +521,328 @@ impl<'de> serde::Deserializer<'de> for Sexp {
         }
     }
 
+    /// The unit is `#nil` or the empty list.
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Sexp::Nil => visitor.visit_unit(),
+            Sexp::List(ref v) if v.is_empty() => visitor.visit_unit(),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
     #[inline]
-    fn deserialize_enum<V>(
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Sexp::Atom(ref atom) if name == SYMBOL_TOKEN || name == KEYWORD_TOKEN => {
+                visitor.visit_newtype_struct(token_text(atom).into_deserializer())
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Sexp::Nil => visit_seq(Vec::new(), visitor),
+            Sexp::List(v) => visit_seq(v, visitor),
+            Sexp::Pair(..) => {
+                let (car, cdr) = self.into_pair().unwrap();
+                visit_seq(vec![car, cdr], visitor)
+            }
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
         self,
-        _name: &str,
-        _variants: &'static [&'static str],
-        _visitor: V,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
     ) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_seq(visitor)
     }
 
-    #[inline]
-    fn deserialize_newtype_struct<V>(
+    /// Maps and structs are read from an alist, a list of pairs whose cars
+    /// are the keys. `#nil` is read as an empty map.
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Sexp::Nil => visit_alist(Vec::new(), visitor),
+            Sexp::List(v) => visit_alist(v, visitor),
+            Sexp::Pair(..) => visit_alist(vec![self], visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_struct<V>(
         self,
         _name: &'static str,
+        _fields: &'static [&'static str],
         visitor: V,
-    ) -> Result<V::Value, Self::Error>
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        let (variant, content) = match self {
+            Sexp::Atom(atom) => (atom.into_string(), Content::Unit),
+            Sexp::Pair(..) => {
+                let (car, cdr) = self.into_pair().unwrap();
+                (variant_name(car)?, Content::Value(cdr))
+            }
+            // A one-entry alist, as a map keyed by the variant.
+            Sexp::List(mut v) if v.len() == 1 && matches!(v[0], Sexp::Pair(..)) => {
+                return v.pop().unwrap().deserialize_enum(name, variants, visitor);
+            }
+            Sexp::List(mut v) if matches!(v.first(), Some(&Sexp::Atom(_))) => {
+                let args = v.split_off(1);
+                (variant_name(v.pop().unwrap())?, Content::Args(args))
+            }
+            _ => return Err(self.invalid_type(&visitor)),
+        };
+        visitor.visit_enum(EnumDeserializer { variant, content })
     }
 
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
-        byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
-        ignored_any
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        drop(self);
+        visitor.visit_unit()
     }
 }
 
-struct SeqDeserializer {
+fn variant_name(sexp: Sexp) -> Result<String, Error> {
+    match sexp {
+        Sexp::Atom(atom) => Ok(atom.into_string()),
+        _ => Err(sexp.invalid_type(&"a variant name")),
+    }
+}
+
+/// What follows the name of an enum variant.
+enum Content {
+    /// Nothing, for a variant written as its bare name.
+    Unit,
+    /// The cdr of `(Variant . content)`.
+    Value(Sexp),
+    /// The elements after the head of `(Variant args ...)`.
+    Args(Vec<Sexp>),
+}
+
+struct EnumDeserializer {
+    variant: String,
+    content: Content,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantDeserializer), Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((
+            variant,
+            VariantDeserializer {
+                content: self.content,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer {
+    content: Content,
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.content {
+            Content::Unit | Content::Value(Sexp::Nil) => Ok(()),
+            Content::Args(ref args) if args.is_empty() => Ok(()),
+            Content::Value(value) => Err(value.invalid_type(&"unit variant")),
+            Content::Args(args) => Err(serde::de::Error::invalid_length(
+                args.len(),
+                &"unit variant",
+            )),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.content {
+            Content::Value(value) => seed.deserialize(value),
+            Content::Args(mut args) if args.len() == 1 => seed.deserialize(args.pop().unwrap()),
+            Content::Args(args) => Err(serde::de::Error::invalid_length(
+                args.len(),
+                &"newtype variant",
+            )),
+            Content::Unit => Err(serde::de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content {
+            Content::Value(value) => value.deserialize_seq(visitor),
+            Content::Args(args) => visit_seq(args, visitor),
+            Content::Unit => Err(serde::de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content {
+            Content::Value(value) => value.deserialize_map(visitor),
+            Content::Args(args) => visit_alist(args, visitor),
+            Content::Unit => Err(serde::de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
+
+/// Splits an alist entry, a pair or a two-element list, into its key and
+/// value.
+fn into_entry(entry: Sexp) -> Result<(Sexp, Sexp), Error> {
+    match entry {
+        Sexp::List(v) if v.len() == 2 => {
+            let mut iter = v.into_iter();
+            Ok((iter.next().unwrap(), iter.next().unwrap()))
+        }
+        entry => entry
+            .into_pair()
+            .ok_or_else(|| Error::syntax(ErrorCode::ExpectedAlist, 0, 0)),
+    }
+}
+
+struct MapDeserializer {
     iter: vec::IntoIter<Sexp>,
+    value: Option<Sexp>,
 }
 
-impl SeqDeserializer {
-    fn new(vec: Vec<Sexp>) -> Self {
-        SeqDeserializer {
-            iter: vec.into_iter(),
+impl MapDeserializer {
+    fn new(entries: Vec<Sexp>) -> Self {
+        MapDeserializer {
+            iter: entries.into_iter(),
+            value: None,
         }
     }
 }
 
-impl<'de> serde::Deserializer<'de> for SeqDeserializer {
+impl<'de> MapAccess<'de> for MapDeserializer {
     type Error = Error;
 
-    #[inline]
-    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value, Error>
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
     where
-        V: Visitor<'de>,
+        T: DeserializeSeed<'de>,
     {
-        let len = self.iter.len();
-        if len == 0 {
-            visitor.visit_unit()
-        } else {
-            let ret = visitor.visit_seq(&mut self)?;
-            let remaining = self.iter.len();
-            if remaining == 0 {
-                Ok(ret)
-            } else {
-                Err(serde::de::Error::invalid_length(
-                    len,
-                    &"fewer elements in array",
-                ))
+        match self.iter.next() {
+            Some(entry) => {
+                let (key, value) = into_entry(entry)?;
+                self.value = Some(value);
+                seed.deserialize(key).map(Some)
             }
+            None => Ok(None),
         }
     }
 
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
-        byte_buf option unit unit_struct newtype_struct seq tuple
-        tuple_struct map struct enum identifier ignored_any
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(serde::de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper),
+            _ => None,
+        }
+    }
+}
+
+struct SeqDeserializer {
+    iter: vec::IntoIter<Sexp>,
+}
+
+impl SeqDeserializer {
+    fn new(vec: Vec<Sexp>) -> Self {
+        SeqDeserializer {
+            iter: vec.into_iter(),
+        }
     }
 }
 
@@ -304,6 +867,52 @@ impl<'de> SeqAccess<'de> for SeqDeserializer {
     }
 }
 
+/// Reads the borrowed `elems` as a sequence, failing if the visitor stops
+/// early.
+fn visit_seq_ref<'de, I, V>(elems: I, visitor: V) -> Result<V::Value, Error>
+where
+    I: ExactSizeIterator<Item = &'de Sexp>,
+    V: Visitor<'de>,
+{
+    let len = elems.len();
+    let mut deserializer = SeqRefDeserializer { iter: elems };
+    let seq = visitor.visit_seq(&mut deserializer)?;
+    let remaining = deserializer.iter.len();
+    if remaining == 0 {
+        Ok(seq)
+    } else {
+        Err(serde::de::Error::invalid_length(
+            len,
+            &"fewer elements in array",
+        ))
+    }
+}
+
+/// Reads the borrowed alist `entries` as a map, failing if the visitor
+/// stops early.
+fn visit_alist_ref<'de, V>(entries: &'de [Sexp], visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    let len = entries.len();
+    let mut deserializer = MapRefDeserializer {
+        iter: entries.iter(),
+        value: None,
+    };
+    let map = visitor.visit_map(&mut deserializer)?;
+    let remaining = deserializer.iter.len();
+    if remaining == 0 {
+        Ok(map)
+    } else {
+        Err(serde::de::Error::invalid_length(
+            len,
+            &"fewer elements in map",
+        ))
+    }
+}
+
+/// Reads a borrowed `Sexp` the same way as an owned one, borrowing the text
+/// of atoms where the visitor allows.
 impl<'de> serde::Deserializer<'de> for &'de Sexp {
     type Error = Error;
 
@@ -315,25 +924,83 @@ impl<'de> serde::Deserializer<'de> for &'de Sexp {
             Sexp::Nil => visitor.visit_unit(),
             Sexp::Boolean(v) => visitor.visit_bool(v),
             Sexp::Number(ref n) => n.deserialize_any(visitor),
-            Sexp::Atom(ref a) => visitor.visit_borrowed_str(a.as_str()),
-            Sexp::Pair(_, _) => unimplemented!(),
+            Sexp::Atom(Atom::String(ref s)) => visitor.visit_borrowed_str(s),
+            Sexp::Bytes(ref bytes) => visitor.visit_borrowed_bytes(bytes),
+            Sexp::Atom(ref atom) => {
+                visitor.visit_newtype_struct(token_text(atom).into_deserializer())
+            }
+            Sexp::Pair(..) => self.deserialize_seq(visitor),
             Sexp::List(ref v) => {
-                let len = v.len();
-                let mut deserializer = SeqRefDeserializer::new(v);
-                let seq = visitor.visit_seq(&mut deserializer)?;
-                let remaining = deserializer.iter.len();
-                if remaining == 0 {
-                    Ok(seq)
+                if is_alist(v) {
+                    visit_alist_ref(v, visitor)
                 } else {
-                    Err(serde::de::Error::invalid_length(
-                        len,
-                        &"fewer elements in array",
-                    ))
+                    visit_seq_ref(v.iter(), visitor)
                 }
             }
         }
     }
 
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match *self {
+            Sexp::Boolean(v) => visitor.visit_bool(v),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    /// Symbols and keywords are read as their name, without the `#:` prefix.
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match *self {
+            Sexp::Atom(ref atom) => visitor.visit_borrowed_str(atom.as_str()),
+            Sexp::Bytes(ref bytes) => visitor.visit_borrowed_bytes(bytes),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match *self {
+            Sexp::Atom(ref atom) => visitor.visit_borrowed_str(atom.as_str()),
+            Sexp::Bytes(ref bytes) => visitor.visit_borrowed_bytes(bytes),
+            Sexp::List(ref v) => visit_seq_ref(v.iter(), visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
@@ -344,80 +1011,303 @@ impl<'de> serde::Deserializer<'de> for &'de Sexp {
         }
     }
 
-    fn deserialize_enum<V>(
-        self,
-        _name: &str,
-        _variants: &'static [&'static str],
-        _visitor: V,
-    ) -> Result<V::Value, Error>
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match *self {
+            Sexp::Nil => visitor.visit_unit(),
+            Sexp::List(ref v) if v.is_empty() => visitor.visit_unit(),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_unit(visitor)
     }
 
     #[inline]
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        match *self {
+            Sexp::Atom(ref atom) if name == SYMBOL_TOKEN || name == KEYWORD_TOKEN => {
+                visitor.visit_newtype_struct(token_text(atom).into_deserializer())
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match *self {
+            Sexp::Nil => visit_seq_ref([].iter(), visitor),
+            Sexp::List(ref v) => visit_seq_ref(v.iter(), visitor),
+            Sexp::Pair(..) => {
+                let (car, cdr) = self.as_pair().unwrap();
+                visit_seq_ref(vec![car, cdr].into_iter(), visitor)
+            }
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match *self {
+            Sexp::Nil => visit_alist_ref(&[], visitor),
+            Sexp::List(ref v) => visit_alist_ref(v, visitor),
+            Sexp::Pair(..) => visit_alist_ref(slice::from_ref(self), visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let (variant, content) = match *self {
+            Sexp::Atom(ref atom) => (atom.as_str(), ContentRef::Unit),
+            Sexp::Pair(..) => {
+                let (car, cdr) = self.as_pair().unwrap();
+                (variant_name_ref(car)?, ContentRef::Value(cdr))
+            }
+            // A one-entry alist, as a map keyed by the variant.
+            Sexp::List(ref v) if v.len() == 1 && matches!(v[0], Sexp::Pair(..)) => {
+                return (&v[0]).deserialize_enum(name, variants, visitor);
+            }
+            Sexp::List(ref v) if matches!(v.first(), Some(&Sexp::Atom(_))) => {
+                (variant_name_ref(&v[0])?, ContentRef::Args(&v[1..]))
+            }
+            _ => return Err(self.invalid_type(&visitor)),
+        };
+        visitor.visit_enum(EnumRefDeserializer { variant, content })
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
     }
+}
 
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
-        byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
-        ignored_any
+fn variant_name_ref(sexp: &Sexp) -> Result<&str, Error> {
+    match *sexp {
+        Sexp::Atom(ref atom) => Ok(atom.as_str()),
+        _ => Err(sexp.invalid_type(&"a variant name")),
     }
 }
 
-struct SeqRefDeserializer<'de> {
-    iter: slice::Iter<'de, Sexp>,
+/// What follows the name of a borrowed enum variant.
+enum ContentRef<'de> {
+    Unit,
+    Value(&'de Sexp),
+    Args(&'de [Sexp]),
 }
 
-impl<'de> SeqRefDeserializer<'de> {
-    fn new(slice: &'de [Sexp]) -> Self {
-        SeqRefDeserializer { iter: slice.iter() }
+struct EnumRefDeserializer<'de> {
+    variant: &'de str,
+    content: ContentRef<'de>,
+}
+
+impl<'de> EnumAccess<'de> for EnumRefDeserializer<'de> {
+    type Error = Error;
+    type Variant = VariantRefDeserializer<'de>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantRefDeserializer<'de>), Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(BorrowedStrDeserializer::new(self.variant))?;
+        Ok((
+            variant,
+            VariantRefDeserializer {
+                content: self.content,
+            },
+        ))
     }
 }
 
-impl<'de> serde::Deserializer<'de> for SeqRefDeserializer<'de> {
+struct VariantRefDeserializer<'de> {
+    content: ContentRef<'de>,
+}
+
+impl<'de> VariantAccess<'de> for VariantRefDeserializer<'de> {
     type Error = Error;
 
-    #[inline]
-    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value, Error>
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.content {
+            ContentRef::Unit | ContentRef::Value(Sexp::Nil) => Ok(()),
+            ContentRef::Args([]) => Ok(()),
+            ContentRef::Value(value) => Err(value.invalid_type(&"unit variant")),
+            ContentRef::Args(args) => Err(serde::de::Error::invalid_length(
+                args.len(),
+                &"unit variant",
+            )),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.content {
+            ContentRef::Value(value) => seed.deserialize(value),
+            ContentRef::Args([arg]) => seed.deserialize(arg),
+            ContentRef::Args(args) => Err(serde::de::Error::invalid_length(
+                args.len(),
+                &"newtype variant",
+            )),
+            ContentRef::Unit => Err(serde::de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
-        let len = self.iter.len();
-        if len == 0 {
-            visitor.visit_unit()
-        } else {
-            let ret = visitor.visit_seq(&mut self)?;
-            let remaining = self.iter.len();
-            if remaining == 0 {
-                Ok(ret)
-            } else {
-                Err(serde::de::Error::invalid_length(
-                    len,
-                    &"fewer elements in array",
-                ))
+        match self.content {
+            ContentRef::Value(value) => value.deserialize_seq(visitor),
+            ContentRef::Args(args) => visit_seq_ref(args.iter(), visitor),
+            ContentRef::Unit => Err(serde::de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content {
+            ContentRef::Value(value) => value.deserialize_map(visitor),
+            ContentRef::Args(args) => visit_alist_ref(args, visitor),
+            ContentRef::Unit => Err(serde::de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
+
+/// Splits a borrowed alist entry into its key and value.
+fn as_entry(entry: &Sexp) -> Result<(&Sexp, &Sexp), Error> {
+    match *entry {
+        Sexp::List(ref v) if v.len() == 2 => Ok((&v[0], &v[1])),
+        _ => entry
+            .as_pair()
+            .ok_or_else(|| Error::syntax(ErrorCode::ExpectedAlist, 0, 0)),
+    }
+}
+
+struct MapRefDeserializer<'de> {
+    iter: slice::Iter<'de, Sexp>,
+    value: Option<&'de Sexp>,
+}
+
+impl<'de> MapAccess<'de> for MapRefDeserializer<'de> {
+    type Error = Error;
+
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(entry) => {
+                let (key, value) = as_entry(entry)?;
+                self.value = Some(value);
+                seed.deserialize(key).map(Some)
             }
+            None => Ok(None),
         }
     }
 
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
-        byte_buf option unit unit_struct newtype_struct seq tuple
-        tuple_struct map struct enum identifier ignored_any
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(serde::de::Error::custom("value is missing")),
+        }
     }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct SeqRefDeserializer<I> {
+    iter: I,
 }
 
-impl<'de> SeqAccess<'de> for SeqRefDeserializer<'de> {
+impl<'de, I> SeqAccess<'de> for SeqRefDeserializer<I>
+where
+    I: ExactSizeIterator<Item = &'de Sexp>,
+{
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
@@ -431,9 +1321,6 @@ impl<'de> SeqAccess<'de> for SeqRefDeserializer<'de> {
     }
 
     fn size_hint(&self) -> Option<usize> {
-        match self.iter.size_hint() {
-            (lower, Some(upper)) if lower == upper => Some(upper),
-            _ => None,
-        }
+        Some(self.iter.len())
     }
 }
@@ -0,0 +1,103 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An indented rendering of a `Sexp` that names the kind of every value.
+
+use std::fmt::Write;
+
+use crate::atom::Atom;
+use crate::bytes::encode_hex;
+use crate::sexp::Sexp;
+
+const INDENT: &str = "  ";
+
+impl Sexp {
+    /// Renders this value one element per line, with each value labelled by
+    /// its kind and nested lists indented.
+    ///
+    /// The printed form of a value does not always show what it is: the
+    /// symbol `foo` and the string `"foo"` can look alike once they pass
+    /// through other tools, and so can `3` and `3.0`. This rendering is meant
+    /// for debugging such data; it is not S-expression text and cannot be
+    /// parsed back.
+    ///
+    /// ```rust
+    /// # use sexpr::Sexp;
+    /// let v: Sexp = sexpr::from_str(r#"(foo "x" (3 1.5))"#).unwrap();
+    /// assert_eq!(
+    ///     v.debug_pretty(),
+    ///     "List [\n  Symbol(foo)\n  Str(\"x\")\n  List [\n    Int(3)\n    Float(1.5)\n  ]\n]"
+    /// );
+    /// ```
+    pub fn debug_pretty(&self) -> String {
+        let mut out = String::new();
+        write_annotated(&mut out, self, 0);
+        out
+    }
+}
+
+fn write_annotated(out: &mut String, sexp: &Sexp, depth: usize) {
+    match *sexp {
+        Sexp::Nil => out.push_str("Nil"),
+        Sexp::Boolean(b) => {
+            let _ = write!(out, "Bool({})", b);
+        }
+        Sexp::Number(ref n) => {
+            let kind = if n.is_f64() { "Float" } else { "Int" };
+            let _ = write!(out, "{}({})", kind, n);
+        }
+        Sexp::Atom(ref atom) => {
+            let _ = match *atom {
+                Atom::Symbol(ref s) => write!(out, "Symbol({})", s),
+                Atom::Keyword(ref s) => write!(out, "Keyword({})", s),
+                Atom::String(ref s) => write!(out, "Str({:?})", s),
+            };
+        }
+        Sexp::Bytes(ref bytes) => {
+            let _ = write!(out, "Bytes({})", encode_hex(bytes));
+        }
+        Sexp::Pair(ref car, ref cdr) => {
+            out.push_str("Pair (");
+            write_line(out, depth + 1);
+            write_cell(out, car, depth + 1);
+            write_line(out, depth + 1);
+            out.push('.');
+            write_line(out, depth + 1);
+            write_cell(out, cdr, depth + 1);
+            write_line(out, depth);
+            out.push(')');
+        }
+        Sexp::List(ref elems) => {
+            if elems.is_empty() {
+                out.push_str("List []");
+                return;
+            }
+            out.push_str("List [");
+            for elem in elems {
+                write_line(out, depth + 1);
+                write_annotated(out, elem, depth + 1);
+            }
+            write_line(out, depth);
+            out.push(']');
+        }
+    }
+}
+
+fn write_cell(out: &mut String, cell: &Option<Box<Sexp>>, depth: usize) {
+    match *cell {
+        Some(ref sexp) => write_annotated(out, sexp, depth),
+        None => out.push_str("Empty"),
+    }
+}
+
+fn write_line(out: &mut String, depth: usize) {
+    out.push('\n');
+    for _ in 0..depth {
+        out.push_str(INDENT);
+    }
+}
@@ -0,0 +1,426 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Describing the shape of a serializable value as an S-expression schema.
+
+use serde::ser::{self, Serialize};
+
+use crate::atom::Atom;
+use crate::error::Error;
+use crate::sexp::Sexp;
+
+impl Sexp {
+    /// Returns a schema of `value`'s type: the names and types of its fields
+    /// in place of their values, as traced through its `Serialize` impl.
+    ///
+    /// Primitive types are the symbols `bool`, `i8` to `u64`, `f32`, `f64`,
+    /// `char`, `string`, `bytes` and `unit`. Compound types are lists:
+    ///
+    /// - `(struct Name (field type) ...)`, with `(field skipped)` for a field
+    ///   left out by `skip_serializing_if`
+    /// - `(option type)`, or `(option)` for `None`
+    /// - `(seq type)` and `(map key-type value-type)`, from their first
+    ///   element, or `(seq)` and `(map)` if they are empty
+    /// - `(tuple type ...)` and `(tuple-struct Name type ...)`
+    /// - `(newtype Name type)` and `(unit-struct Name)`
+    /// - `(enum Name variant)`, where the variant is a symbol for a unit
+    ///   variant and otherwise a list of the variant's name and then its
+    ///   types or fields as a tuple or struct lists them
+    ///
+    /// The schema comes from a sample value, so it only shows what the sample
+    /// holds: the variant of an enum it uses, and the element type of a
+    /// collection only if the collection has elements. A sample with every
+    /// option set and every collection filled gives the whole structure,
+    /// which can be kept with the documentation of a format or compared
+    /// against the schema of data read from elsewhere.
+    ///
+    /// ```rust
+    /// use serde_derive::Serialize;
+    /// use sexpr::Sexp;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Server {
+    ///     host: String,
+    ///     port: u16,
+    ///     tags: Vec<String>,
+    ///     backup: Option<Box<Server>>,
+    /// }
+    ///
+    /// let sample = Server {
+    ///     host: "a".to_owned(),
+    ///     port: 80,
+    ///     tags: vec!["web".to_owned()],
+    ///     backup: None,
+    /// };
+    /// assert_eq!(
+    ///     Sexp::describe(&sample).unwrap().to_string(),
+    ///     "(struct Server (host string) (port u16) (tags (seq string)) (backup (option)))"
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if `value`'s implementation of `Serialize` fails.
+    pub fn describe<T>(value: &T) -> Result<Sexp, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(Describer)
+    }
+}
+
+fn symbol(name: &str) -> Sexp {
+    Sexp::Atom(Atom::symbol(name))
+}
+
+/// `(head name rest...)`, or `(head rest...)` without a name.
+fn form(head: &str, name: Option<&str>, rest: Vec<Sexp>) -> Sexp {
+    let mut elems = vec![symbol(head)];
+    elems.extend(name.map(symbol));
+    elems.extend(rest);
+    Sexp::List(elems)
+}
+
+/// A serializer that describes the type of what it is given rather than the
+/// value.
+struct Describer;
+
+macro_rules! describe_primitive {
+    ($($method:ident($ty:ty) => $name:expr;)*) => {
+        $(
+            fn $method(self, _: $ty) -> Result<Sexp, Error> {
+                Ok(symbol($name))
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for Describer {
+    type Ok = Sexp;
+    type Error = Error;
+
+    type SerializeSeq = DescribeSeq;
+    type SerializeTuple = DescribeTuple;
+    type SerializeTupleStruct = DescribeTuple;
+    type SerializeTupleVariant = DescribeTuple;
+    type SerializeMap = DescribeMap;
+    type SerializeStruct = DescribeStruct;
+    type SerializeStructVariant = DescribeStruct;
+
+    describe_primitive! {
+        serialize_bool(bool) => "bool";
+        serialize_i8(i8) => "i8";
+        serialize_i16(i16) => "i16";
+        serialize_i32(i32) => "i32";
+        serialize_i64(i64) => "i64";
+        serialize_u8(u8) => "u8";
+        serialize_u16(u16) => "u16";
+        serialize_u32(u32) => "u32";
+        serialize_u64(u64) => "u64";
+        serialize_f32(f32) => "f32";
+        serialize_f64(f64) => "f64";
+        serialize_char(char) => "char";
+        serialize_str(&str) => "string";
+        serialize_bytes(&[u8]) => "bytes";
+    }
+
+    fn serialize_unit(self) -> Result<Sexp, Error> {
+        Ok(symbol("unit"))
+    }
+
+    fn serialize_none(self) -> Result<Sexp, Error> {
+        Ok(form("option", None, Vec::new()))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Sexp, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(form("option", None, vec![value.serialize(Describer)?]))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Sexp, Error> {
+        Ok(form("unit-struct", Some(name), Vec::new()))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Sexp, Error> {
+        Ok(form("enum", Some(name), vec![symbol(variant)]))
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Sexp, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(form(
+            "newtype",
+            Some(name),
+            vec![value.serialize(Describer)?],
+        ))
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Sexp, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let variant = form(variant, None, vec![value.serialize(Describer)?]);
+        Ok(form("enum", Some(name), vec![variant]))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<DescribeSeq, Error> {
+        Ok(DescribeSeq { elem: None })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<DescribeTuple, Error> {
+        Ok(DescribeTuple::new("tuple", None, None, len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<DescribeTuple, Error> {
+        Ok(DescribeTuple::new("tuple-struct", Some(name), None, len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<DescribeTuple, Error> {
+        Ok(DescribeTuple::new(variant, None, Some(name), len))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<DescribeMap, Error> {
+        Ok(DescribeMap { entry: None })
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<DescribeStruct, Error> {
+        Ok(DescribeStruct::new("struct", Some(name), None, len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<DescribeStruct, Error> {
+        Ok(DescribeStruct::new(variant, None, Some(name), len))
+    }
+}
+
+/// Describes a sequence by its first element.
+struct DescribeSeq {
+    elem: Option<Sexp>,
+}
+
+impl ser::SerializeSeq for DescribeSeq {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.elem.is_none() {
+            self.elem = Some(value.serialize(Describer)?);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(form("seq", None, self.elem.into_iter().collect()))
+    }
+}
+
+/// Describes a map by its first entry.
+struct DescribeMap {
+    entry: Option<(Sexp, Option<Sexp>)>,
+}
+
+impl ser::SerializeMap for DescribeMap {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.entry.is_none() {
+            self.entry = Some((key.serialize(Describer)?, None));
+        }
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Some((_, ref mut slot @ None)) = self.entry {
+            *slot = Some(value.serialize(Describer)?);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        let types = match self.entry {
+            Some((key, value)) => vec![key, value.unwrap_or_else(|| symbol("unit"))],
+            None => Vec::new(),
+        };
+        Ok(form("map", None, types))
+    }
+}
+
+/// Describes a tuple, tuple struct or tuple variant by the types of all of
+/// its fields.
+struct DescribeTuple {
+    head: &'static str,
+    name: Option<&'static str>,
+    /// The name of the enum, for a variant.
+    variant_of: Option<&'static str>,
+    types: Vec<Sexp>,
+}
+
+impl DescribeTuple {
+    fn new(
+        head: &'static str,
+        name: Option<&'static str>,
+        variant_of: Option<&'static str>,
+        len: usize,
+    ) -> Self {
+        DescribeTuple {
+            head,
+            name,
+            variant_of,
+            types: Vec::with_capacity(len),
+        }
+    }
+
+    fn push<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.types.push(value.serialize(Describer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Sexp, Error> {
+        let described = form(self.head, self.name, self.types);
+        Ok(match self.variant_of {
+            Some(name) => form("enum", Some(name), vec![described]),
+            None => described,
+        })
+    }
+}
+
+macro_rules! impl_describe_tuple {
+    ($($trait:ident :: $method:ident;)*) => {
+        $(
+            impl ser::$trait for DescribeTuple {
+                type Ok = Sexp;
+                type Error = Error;
+
+                fn $method<T>(&mut self, value: &T) -> Result<(), Error>
+                where
+                    T: ?Sized + Serialize,
+                {
+                    self.push(value)
+                }
+
+                fn end(self) -> Result<Sexp, Error> {
+                    self.finish()
+                }
+            }
+        )*
+    };
+}
+
+impl_describe_tuple! {
+    SerializeTuple::serialize_element;
+    SerializeTupleStruct::serialize_field;
+    SerializeTupleVariant::serialize_field;
+}
+
+/// Describes a struct or struct variant by the names and types of its
+/// fields.
+struct DescribeStruct {
+    fields: DescribeTuple,
+}
+
+impl DescribeStruct {
+    fn new(
+        head: &'static str,
+        name: Option<&'static str>,
+        variant_of: Option<&'static str>,
+        len: usize,
+    ) -> Self {
+        DescribeStruct {
+            fields: DescribeTuple::new(head, name, variant_of, len),
+        }
+    }
+
+    fn field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let field = form(key, None, vec![value.serialize(Describer)?]);
+        self.fields.types.push(field);
+        Ok(())
+    }
+
+    fn skip(&mut self, key: &'static str) -> Result<(), Error> {
+        self.fields
+            .types
+            .push(form(key, None, vec![symbol("skipped")]));
+        Ok(())
+    }
+}
+
+macro_rules! impl_describe_struct {
+    ($($trait:ident;)*) => {
+        $(
+            impl ser::$trait for DescribeStruct {
+                type Ok = Sexp;
+                type Error = Error;
+
+                fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+                where
+                    T: ?Sized + Serialize,
+                {
+                    self.field(key, value)
+                }
+
+                fn skip_field(&mut self, key: &'static str) -> Result<(), Error> {
+                    self.skip(key)
+                }
+
+                fn end(self) -> Result<Sexp, Error> {
+                    self.fields.finish()
+                }
+            }
+        )*
+    };
+}
+
+impl_describe_struct! {
+    SerializeStruct;
+    SerializeStructVariant;
+}
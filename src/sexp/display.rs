@@ -0,0 +1,78 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A rendering of a `Sexp` for people rather than for the reader.
+
+use std::fmt::Write;
+
+use crate::atom::Atom;
+use crate::bytes::encode_hex;
+use crate::sexp::Sexp;
+
+impl Sexp {
+    /// Renders this value for people to read, as Lisp's `princ` does, where
+    /// `to_string` renders it to be read back, as `prin1` does. Strings are
+    /// written without quotes or escapes and symbols without bars, so the
+    /// result suits log messages but may not read back as the same value.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let v: Sexp = sexpr::from_str(r#"(error "file \"a.txt\" not found" |two words| #:code 2)"#)
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     v.to_display_string(),
+    ///     r#"(error file "a.txt" not found two words #:code 2)"#
+    /// );
+    /// assert_eq!(
+    ///     v.to_string(),
+    ///     r#"(error "file \"a.txt\" not found" |two words| #:code 2)"#
+    /// );
+    /// ```
+    pub fn to_display_string(&self) -> String {
+        let mut out = String::new();
+        write_display(&mut out, self);
+        out
+    }
+}
+
+fn write_display(out: &mut String, sexp: &Sexp) {
+    match *sexp {
+        Sexp::Nil => out.push_str("#nil"),
+        Sexp::Boolean(b) => out.push_str(if b { "#t" } else { "#f" }),
+        Sexp::Number(ref n) => {
+            let _ = write!(out, "{}", n);
+        }
+        Sexp::Atom(Atom::Keyword(ref s)) => {
+            out.push_str("#:");
+            out.push_str(s);
+        }
+        Sexp::Atom(Atom::Symbol(ref s)) | Sexp::Atom(Atom::String(ref s)) => out.push_str(s),
+        Sexp::Bytes(ref bytes) => {
+            let _ = write!(out, "#x\"{}\"", encode_hex(bytes));
+        }
+        Sexp::Pair(..) => {
+            let (car, cdr) = sexp.as_pair().unwrap();
+            out.push('(');
+            write_display(out, car);
+            out.push_str(" . ");
+            write_display(out, cdr);
+            out.push(')');
+        }
+        Sexp::List(ref elems) => {
+            out.push('(');
+            for (i, elem) in elems.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_display(out, elem);
+            }
+            out.push(')');
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Whole files of top-level forms, with the comments between them.
+
+use std::iter::FromIterator;
+
+use crate::sexp::Sexp;
+
+/// The top-level forms of a file in order, together with the comments
+/// written between them, as read by `sexpr::from_str_document` and written
+/// by `sexpr::ser::to_string_document`.
+///
+/// Only comments outside of any form are kept; a comment inside a list has
+/// no place in its `Sexp` and is dropped, as it is by `from_str`.
+///
+/// ```rust
+/// use sexpr::sexp::{DocumentItem, SexpDocument};
+///
+/// let input = ";; settings\n(port 80)\n\n(host \"a\") ; primary\n";
+/// let mut doc = sexpr::from_str_document(input).unwrap();
+/// assert_eq!(doc.forms().count(), 2);
+/// assert_eq!(doc.0[0], DocumentItem::Comment("; settings".to_owned()));
+///
+/// doc.push_comment(" added");
+/// doc.push_form(sexpr::from_str("(user \"b\")").unwrap());
+/// assert_eq!(
+///     sexpr::ser::to_string_document(&doc).unwrap(),
+///     ";; settings\n(port 80)\n(host \"a\")\n; primary\n; added\n(user \"b\")\n"
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SexpDocument(pub Vec<DocumentItem>);
+
+/// A top-level form or comment of a `SexpDocument`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DocumentItem {
+    /// A top-level form.
+    Form(Sexp),
+    /// The text of a line comment after its first `;`, so `;; note` holds
+    /// `"; note"`. A comment of several lines is written as one comment per
+    /// line.
+    Comment(String),
+}
+
+impl SexpDocument {
+    /// Creates an empty document.
+    pub fn new() -> Self {
+        SexpDocument(Vec::new())
+    }
+
+    /// The top-level forms, in order, without the comments.
+    pub fn forms(&self) -> impl Iterator<Item = &Sexp> {
+        self.0.iter().filter_map(|item| match *item {
+            DocumentItem::Form(ref form) => Some(form),
+            DocumentItem::Comment(_) => None,
+        })
+    }
+
+    /// The top-level forms, in order, for modifying them in place.
+    pub fn forms_mut(&mut self) -> impl Iterator<Item = &mut Sexp> {
+        self.0.iter_mut().filter_map(|item| match *item {
+            DocumentItem::Form(ref mut form) => Some(form),
+            DocumentItem::Comment(_) => None,
+        })
+    }
+
+    /// Drops the comments and returns the forms.
+    pub fn into_forms(self) -> Vec<Sexp> {
+        self.0
+            .into_iter()
+            .filter_map(|item| match item {
+                DocumentItem::Form(form) => Some(form),
+                DocumentItem::Comment(_) => None,
+            })
+            .collect()
+    }
+
+    /// Appends a form to the end of the document.
+    pub fn push_form(&mut self, form: Sexp) {
+        self.0.push(DocumentItem::Form(form));
+    }
+
+    /// Appends a comment to the end of the document. `text` is what follows
+    /// the `;`.
+    pub fn push_comment<S: Into<String>>(&mut self, text: S) {
+        self.0.push(DocumentItem::Comment(text.into()));
+    }
+}
+
+impl From<Vec<Sexp>> for SexpDocument {
+    fn from(forms: Vec<Sexp>) -> Self {
+        SexpDocument(forms.into_iter().map(DocumentItem::Form).collect())
+    }
+}
+
+impl FromIterator<Sexp> for SexpDocument {
+    fn from_iter<I: IntoIterator<Item = Sexp>>(iter: I) -> Self {
+        SexpDocument(iter.into_iter().map(DocumentItem::Form).collect())
+    }
+}
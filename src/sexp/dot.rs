@@ -0,0 +1,92 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Drawing a `Sexp` as a tree with Graphviz.
+
+use std::fmt::Write;
+
+use crate::sexp::Sexp;
+
+impl Sexp {
+    /// Renders this value as a Graphviz DOT graph of its tree, for drawing
+    /// with `dot -Tsvg` to see how a deeply nested form was read.
+    ///
+    /// Each list is a node labelled `()` with an edge to each of its
+    /// elements in order, and each pair a node labelled `.` with edges to
+    /// its car and then its cdr. Everything else is a leaf drawn as a box
+    /// holding the value as `to_string` writes it.
+    ///
+    /// ```rust
+    /// let v: sexpr::Sexp = sexpr::from_str("(f \"a\" (g))").unwrap();
+    /// assert_eq!(
+    ///     v.to_dot(),
+    ///     "digraph sexp {\n    ordering=out;\n    n0 [label=\"()\"];\n    \
+    ///      n1 [label=\"f\", shape=box];\n    n0 -> n1;\n    \
+    ///      n2 [label=\"\\\"a\\\"\", shape=box];\n    n0 -> n2;\n    \
+    ///      n3 [label=\"()\"];\n    \
+    ///      n4 [label=\"g\", shape=box];\n    n3 -> n4;\n    n0 -> n3;\n}\n"
+    /// );
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph sexp {\n    ordering=out;\n");
+        let mut next = 0;
+        write_node(&mut out, &mut next, self);
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Writes the node for `sexp` and everything under it, and returns the
+/// node's id.
+fn write_node(out: &mut String, next: &mut usize, sexp: &Sexp) -> usize {
+    let id = *next;
+    *next += 1;
+    match *sexp {
+        Sexp::List(ref elems) => {
+            let _ = writeln!(out, "    n{} [label=\"()\"];", id);
+            for elem in elems {
+                write_edge(out, next, id, elem);
+            }
+        }
+        Sexp::Pair(..) => {
+            let (car, cdr) = sexp.as_pair().unwrap();
+            let _ = writeln!(out, "    n{} [label=\".\"];", id);
+            write_edge(out, next, id, car);
+            write_edge(out, next, id, cdr);
+        }
+        _ => {
+            let _ = writeln!(
+                out,
+                "    n{} [label=\"{}\", shape=box];",
+                id,
+                escape_label(&sexp.to_string())
+            );
+        }
+    }
+    id
+}
+
+fn write_edge(out: &mut String, next: &mut usize, parent: usize, child: &Sexp) {
+    let child = write_node(out, next, child);
+    let _ = writeln!(out, "    n{} -> n{};", parent, child);
+}
+
+/// Escapes `text` for a double-quoted DOT string.
+fn escape_label(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
@@ -9,8 +9,8 @@
 use std::borrow::Cow;
 
 use super::Sexp;
-use map::Map;
-use number::Number;
+use crate::atom::Atom;
+use crate::number::Number;
 
 macro_rules! from_integer {
     ($($ty:ident)*) => {
@@ -43,7 +43,7 @@ impl From<f32> for Sexp {
     /// # }
     /// ```
     fn from(f: f32) -> Self {
-        From::from(f as f64)
+        From::from(f64::from(f))
     }
 }
 
@@ -61,7 +61,7 @@ impl From<f64> for Sexp {
     /// # }
     /// ```
     fn from(f: f64) -> Self {
-        Number::from_f64(f).map_or(Sexp::Null, Sexp::Number)
+        Number::from_f64(f).map_or(Sexp::Nil, Sexp::Number)
     }
 }
 
@@ -84,7 +84,7 @@ impl From<bool> for Sexp {
 }
 
 impl From<String> for Sexp {
-    /// Convert `String` to `Sexp`
+    /// Convert `String` to a `Sexp` symbol with exactly that name
     ///
     /// # Examples
     ///
@@ -97,12 +97,12 @@ impl From<String> for Sexp {
     /// # }
     /// ```
     fn from(f: String) -> Self {
-        Sexp::Atom(Atom::from_string(f))
+        Sexp::Atom(Atom::from(f))
     }
 }
 
-impl<'a> From<&'a str> for Sexp {
-    /// Convert string slice to `Sexp`
+impl From<&str> for Sexp {
+    /// Convert string slice to a `Sexp` symbol with exactly that name
     ///
     /// # Examples
     ///
@@ -115,12 +115,12 @@ impl<'a> From<&'a str> for Sexp {
     /// # }
     /// ```
     fn from(f: &str) -> Self {
-        Sexp::Atom(Atom::from_str(f))
+        Sexp::Atom(Atom::from(f))
     }
 }
 
 impl<'a> From<Cow<'a, str>> for Sexp {
-    /// Convert copy-on-write string to `Sexp`
+    /// Convert copy-on-write string to a `Sexp` symbol with exactly that name
     ///
     /// # Examples
     ///
@@ -144,26 +144,34 @@ impl<'a> From<Cow<'a, str>> for Sexp {
     /// # }
     /// ```
     fn from(f: Cow<'a, str>) -> Self {
-        Sexp::Atom(Atom::from_string(f))
+        Sexp::Atom(Atom::from(f))
     }
 }
 
-impl From<Map<String, Sexp>> for Sexp {
-    /// Convert map (with string keys) to `Sexp`
+impl<K: Into<Atom>, V: Into<Sexp>> From<(K, V)> for Sexp {
+    /// Convert a key-value tuple to the dotted pair `(key . value)`
+    ///
+    /// Because a tuple converts to `Sexp`, a `Vec` of tuples, or an iterator
+    /// over them, converts to an association list.
     ///
     /// # Examples
     ///
-    /// ```rust,ignore
+    /// ```rust
     /// # fn main() {
-    /// use sexpr::{Map, Sexp};
+    /// use sexpr::Sexp;
+    ///
+    /// let x: Sexp = ("a", 1).into();
+    /// assert_eq!(x, Sexp::new_entry("a", 1));
+    ///
+    /// let alist: Sexp = vec![("a", 1), ("b", 2)].into();
+    /// assert_eq!(alist, Sexp::List(vec![Sexp::new_entry("a", 1), Sexp::new_entry("b", 2)]));
     ///
-    /// let mut m = Map::new();
-    /// m.insert("Lorem".to_string(), "ipsum".into());
-    /// let x: Sexp = m.into();
+    /// let alist: Sexp = vec![("a", 1), ("b", 2)].into_iter().collect();
+    /// assert_eq!(alist, Sexp::List(vec![Sexp::new_entry("a", 1), Sexp::new_entry("b", 2)]));
     /// # }
     /// ```
-    fn from(f: Map<String, Sexp>) -> Self {
-        unimplemented!()
+    fn from((key, value): (K, V)) -> Self {
+        Sexp::new_entry(key, value)
     }
 }
 
@@ -199,7 +207,7 @@ impl<'a, T: Clone + Into<Sexp>> From<&'a [T]> for Sexp {
     /// # }
     /// ```
     fn from(f: &'a [T]) -> Self {
-        Sexp::List(f.into_iter().cloned().map(Into::into).collect())
+        Sexp::List(f.iter().cloned().map(Into::into).collect())
     }
 }
 
@@ -123,6 +123,7 @@ impl<'a> fmt::Display for Type<'a> {
             Sexp::Boolean(_) => formatter.write_str("boolean"),
             Sexp::Number(_) => formatter.write_str("number"),
             Sexp::Atom(_) => formatter.write_str("atom"),
+            Sexp::Bytes(_) => formatter.write_str("bytes"),
             Sexp::List(_) => formatter.write_str("list"),
             Sexp::Pair(_, _) => formatter.write_str("pair"),
         }
@@ -0,0 +1,426 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parsing into trees whose structurally identical subtrees are shared.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::mem;
+use std::sync::Arc;
+
+use serde::de::{self, DeserializeSeed, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::atom::Atom;
+use crate::bytes::{ByteSlice, HEX_BYTES_TOKEN};
+use crate::de::Deserializer;
+use crate::error::Result;
+use crate::number::Number;
+#[cfg(feature = "arbitrary_precision")]
+use crate::number::NUMBER_TOKEN;
+use crate::sexp::{Sexp, PAIR_TOKEN};
+
+/// An S-expression value like `Sexp`, but holding its children through `Arc`
+/// so that identical subtrees can be shared. Built by an `Interner`.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum SharedSexp {
+    /// Represents a S-expression nil value.
+    Nil,
+
+    /// Represents a S-expression string, symbol or keyword.
+    Atom(Atom),
+
+    /// Represents a S-expression number, whether integer or floating point.
+    Number(Number),
+
+    /// Represents a S-expression boolean.
+    Boolean(bool),
+
+    /// Represents a S-expression byte string.
+    Bytes(Vec<u8>),
+
+    /// Represents a S-expression cons-pair. An empty cell is `Nil`.
+    Pair(Arc<SharedSexp>, Arc<SharedSexp>),
+
+    /// Represents a S-expression list.
+    List(Vec<Arc<SharedSexp>>),
+}
+
+impl SharedSexp {
+    /// Copies the value into a `Sexp`, which owns all of its subtrees.
+    pub fn to_sexp(&self) -> Sexp {
+        match *self {
+            SharedSexp::Nil => Sexp::Nil,
+            SharedSexp::Atom(ref a) => Sexp::Atom(a.clone()),
+            SharedSexp::Number(ref n) => Sexp::Number(n.clone()),
+            SharedSexp::Boolean(b) => Sexp::Boolean(b),
+            SharedSexp::Bytes(ref bytes) => Sexp::Bytes(bytes.clone()),
+            SharedSexp::Pair(ref car, ref cdr) => Sexp::pair(car.to_sexp(), cdr.to_sexp()),
+            SharedSexp::List(ref elems) => Sexp::List(elems.iter().map(|e| e.to_sexp()).collect()),
+        }
+    }
+}
+
+/// Writes the value like the `Sexp` it stands for, straight from the shared
+/// nodes, so a subtree shared many times is written each time without being
+/// copied.
+impl Serialize for SharedSexp {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            SharedSexp::Nil => serializer.serialize_unit(),
+            SharedSexp::Boolean(b) => serializer.serialize_bool(b),
+            SharedSexp::Number(ref n) => n.serialize(serializer),
+            SharedSexp::Atom(ref atom) => atom.serialize(serializer),
+            SharedSexp::Bytes(ref bytes) => {
+                serializer.serialize_newtype_struct(HEX_BYTES_TOKEN, &ByteSlice(bytes))
+            }
+            SharedSexp::List(ref elems) => serializer.collect_seq(elems.iter().map(|e| &**e)),
+            SharedSexp::Pair(ref car, ref cdr) => {
+                serializer.serialize_newtype_struct(PAIR_TOKEN, &(&**car, &**cdr))
+            }
+        }
+    }
+}
+
+/// A hash-consing table that hands out one shared `SharedSexp` node per
+/// distinct subtree.
+///
+/// Parsing through an `Interner` makes every subtree that occurs more than
+/// once, in one document or across many, a single allocation. This suits
+/// inputs such as netlists or program dumps where the same subexpression
+/// appears thousands of times.
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use sexpr::sexp::{Interner, SharedSexp};
+///
+/// let mut interner = Interner::new();
+/// let value = interner.from_str("((net a b) (net a b))").unwrap();
+/// match *value {
+///     SharedSexp::List(ref elems) => assert!(Arc::ptr_eq(&elems[0], &elems[1])),
+///     _ => unreachable!(),
+/// }
+/// ```
+///
+/// Nodes are compared structurally, except that floats are compared bit for
+/// bit, so `0.0` and `-0.0` are kept apart.
+#[derive(Default)]
+pub struct Interner {
+    nodes: HashSet<Node>,
+}
+
+impl Interner {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    /// Returns the number of distinct nodes in the table.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if no node has been interned yet.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Interns an existing `Sexp` tree.
+    pub fn intern(&mut self, sexp: &Sexp) -> Arc<SharedSexp> {
+        let node = match *sexp {
+            Sexp::Nil => SharedSexp::Nil,
+            Sexp::Atom(ref a) => SharedSexp::Atom(a.clone()),
+            Sexp::Number(ref n) => SharedSexp::Number(n.clone()),
+            Sexp::Boolean(b) => SharedSexp::Boolean(b),
+            Sexp::Bytes(ref bytes) => SharedSexp::Bytes(bytes.clone()),
+            Sexp::Pair(ref car, ref cdr) => {
+                let car = self.intern(car.as_ref().map_or(&Sexp::Nil, |car| &**car));
+                let cdr = self.intern(cdr.as_ref().map_or(&Sexp::Nil, |cdr| &**cdr));
+                SharedSexp::Pair(car, cdr)
+            }
+            Sexp::List(ref elems) => {
+                SharedSexp::List(elems.iter().map(|e| self.intern(e)).collect())
+            }
+        };
+        self.insert(node)
+    }
+
+    /// Parses a value from a string, interning each subtree as soon as it
+    /// has been read.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(&mut self, s: &str) -> Result<Arc<SharedSexp>> {
+        let mut de = Deserializer::from_str(s);
+        let value = self.seed().deserialize(&mut de)?;
+        de.end()?;
+        Ok(value)
+    }
+
+    /// Parses a value from bytes, interning each subtree as soon as it has
+    /// been read.
+    pub fn from_slice(&mut self, v: &[u8]) -> Result<Arc<SharedSexp>> {
+        let mut de = Deserializer::from_slice(v);
+        let value = self.seed().deserialize(&mut de)?;
+        de.end()?;
+        Ok(value)
+    }
+
+    /// Parses a value from an IO stream, interning each subtree as soon as
+    /// it has been read.
+    pub fn from_reader<R: io::Read>(&mut self, rdr: R) -> Result<Arc<SharedSexp>> {
+        let mut de = Deserializer::from_reader(rdr);
+        let value = self.seed().deserialize(&mut de)?;
+        de.end()?;
+        Ok(value)
+    }
+
+    /// Returns a `DeserializeSeed` that interns the value it deserializes,
+    /// for use with any serde `Deserializer`.
+    pub fn seed(&mut self) -> InternSeed<'_> {
+        InternSeed {
+            interner: self,
+            tail: None,
+        }
+    }
+
+    /// Returns the shared node equal to `node`, adding it if there is none.
+    /// The children of `node` must already have been interned.
+    fn insert(&mut self, node: SharedSexp) -> Arc<SharedSexp> {
+        let node = Node(Arc::new(node));
+        if let Some(existing) = self.nodes.get(&node) {
+            return existing.0.clone();
+        }
+        let shared = node.0.clone();
+        self.nodes.insert(node);
+        shared
+    }
+}
+
+/// A `DeserializeSeed` that builds a `SharedSexp` through an `Interner`.
+/// Created by `Interner::seed`.
+pub struct InternSeed<'a> {
+    interner: &'a mut Interner,
+    /// Where to note whether an element of a list was its dotted tail.
+    tail: Option<&'a mut bool>,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for InternSeed<'a> {
+    type Value = Arc<SharedSexp>;
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<Arc<SharedSexp>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for InternSeed<'a> {
+    type Value = Arc<SharedSexp>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid Sexp value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> std::result::Result<Arc<SharedSexp>, E> {
+        Ok(self.interner.insert(SharedSexp::Boolean(value)))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> std::result::Result<Arc<SharedSexp>, E> {
+        Ok(self.interner.insert(SharedSexp::Number(value.into())))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> std::result::Result<Arc<SharedSexp>, E> {
+        Ok(self.interner.insert(SharedSexp::Number(value.into())))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> std::result::Result<Arc<SharedSexp>, E> {
+        let node = Number::from_f64(value).map_or(SharedSexp::Nil, SharedSexp::Number);
+        Ok(self.interner.insert(node))
+    }
+
+    fn visit_str<E>(self, value: &str) -> std::result::Result<Arc<SharedSexp>, E>
+    where
+        E: de::Error,
+    {
+        self.visit_string(String::from(value))
+    }
+
+    fn visit_string<E>(self, value: String) -> std::result::Result<Arc<SharedSexp>, E> {
+        Ok(self
+            .interner
+            .insert(SharedSexp::Atom(Atom::new_string(value))))
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> std::result::Result<Arc<SharedSexp>, E>
+    where
+        E: de::Error,
+    {
+        self.visit_byte_buf(value.to_vec())
+    }
+
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> std::result::Result<Arc<SharedSexp>, E> {
+        Ok(self.interner.insert(SharedSexp::Bytes(value)))
+    }
+
+    fn visit_none<E>(self) -> std::result::Result<Arc<SharedSexp>, E> {
+        Ok(self.interner.insert(SharedSexp::Nil))
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> std::result::Result<Arc<SharedSexp>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<Arc<SharedSexp>, E> {
+        Ok(self.interner.insert(SharedSexp::Nil))
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Arc<SharedSexp>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        // Symbols and keywords arrive as a newtype around their text, as for
+        // `Sexp`.
+        let text: String = de::Deserialize::deserialize(deserializer)?;
+        Ok(self
+            .interner
+            .insert(SharedSexp::Atom(Atom::from_token_text(text))))
+    }
+
+    fn visit_seq<V>(self, mut visitor: V) -> std::result::Result<Arc<SharedSexp>, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let interner = self.interner;
+        let mut elems = Vec::new();
+        let mut tail = false;
+        while let Some(elem) = visitor.next_element_seed(InternSeed {
+            interner: &mut *interner,
+            tail: Some(&mut tail),
+        })? {
+            if tail {
+                // `(a b . c)` is the pair of `a` and the pair of `b` and `c`.
+                return Ok(elems
+                    .into_iter()
+                    .rev()
+                    .fold(elem, |cdr, car| interner.insert(SharedSexp::Pair(car, cdr))));
+            }
+            elems.push(elem);
+        }
+        Ok(interner.insert(SharedSexp::List(elems)))
+    }
+
+    /// The tail of a dotted list arrives as a map of one entry under
+    /// `PAIR_TOKEN`, as does a number kept as its literal text under
+    /// `NUMBER_TOKEN`.
+    fn visit_map<V>(self, mut visitor: V) -> std::result::Result<Arc<SharedSexp>, V::Error>
+    where
+        V: de::MapAccess<'de>,
+    {
+        match visitor.next_key::<String>()? {
+            Some(ref key) if key == PAIR_TOKEN && self.tail.is_some() => {
+                *self.tail.unwrap() = true;
+                visitor.next_value_seed(InternSeed {
+                    interner: self.interner,
+                    tail: None,
+                })
+            }
+            #[cfg(feature = "arbitrary_precision")]
+            Some(ref key) if key == NUMBER_TOKEN => {
+                let number = Number::from_literal_value(visitor.next_value()?)?;
+                Ok(self.interner.insert(SharedSexp::Number(number)))
+            }
+            _ => Err(de::Error::invalid_type(de::Unexpected::Map, &self)),
+        }
+    }
+}
+
+/// An entry of the `Interner` table. Children are compared and hashed by
+/// address, which is enough because they are interned before their parents.
+struct Node(Arc<SharedSexp>);
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(&*self.0).hash(state);
+        match *self.0 {
+            SharedSexp::Nil => {}
+            SharedSexp::Atom(ref a) => a.hash(state),
+            SharedSexp::Number(ref n) => number_key(n).hash(state),
+            SharedSexp::Boolean(b) => b.hash(state),
+            SharedSexp::Bytes(ref bytes) => bytes.hash(state),
+            SharedSexp::Pair(ref car, ref cdr) => {
+                Arc::as_ptr(car).hash(state);
+                Arc::as_ptr(cdr).hash(state);
+            }
+            SharedSexp::List(ref elems) => {
+                elems.len().hash(state);
+                for elem in elems {
+                    Arc::as_ptr(elem).hash(state);
+                }
+            }
+        }
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        match (&*self.0, &*other.0) {
+            (SharedSexp::Nil, SharedSexp::Nil) => true,
+            (SharedSexp::Atom(a), SharedSexp::Atom(b)) => a == b,
+            (SharedSexp::Number(a), SharedSexp::Number(b)) => {
+                number_key(a) == number_key(b) && same_literal(a, b)
+            }
+            (SharedSexp::Boolean(a), SharedSexp::Boolean(b)) => a == b,
+            (SharedSexp::Bytes(a), SharedSexp::Bytes(b)) => a == b,
+            (SharedSexp::Pair(a, b), SharedSexp::Pair(c, d)) => {
+                Arc::ptr_eq(a, c) && Arc::ptr_eq(b, d)
+            }
+            (SharedSexp::List(a), SharedSexp::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| Arc::ptr_eq(x, y))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Node {}
+
+/// Whether two numbers with the same value were read from the same literal,
+/// so that `1.50` and `1.5` stay apart.
+#[cfg(feature = "arbitrary_precision")]
+fn same_literal(a: &Number, b: &Number) -> bool {
+    a.as_literal() == b.as_literal()
+}
+
+#[cfg(not(feature = "arbitrary_precision"))]
+fn same_literal(_: &Number, _: &Number) -> bool {
+    true
+}
+
+/// Identifies a number by how it is stored and its exact bits.
+fn number_key(n: &Number) -> (u8, u64) {
+    if let Some(u) = n.as_u64() {
+        (0, u)
+    } else if let Some(i) = n.as_i64() {
+        (1, i as u64)
+    } else {
+        (2, n.as_f64().map_or(0, f64::to_bits))
+    }
+}
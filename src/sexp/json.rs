@@ -0,0 +1,143 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A best-effort JSON rendering of a `Sexp`.
+
+use std::fmt::Write;
+
+use crate::atom::Atom;
+use crate::sexp::Sexp;
+
+impl Sexp {
+    /// Renders this value as JSON, for tools that can display JSON but not
+    /// S-expressions.
+    ///
+    /// The mapping loses information and cannot be reversed:
+    ///
+    /// - a non-empty list whose elements are all pairs with an atom or
+    ///   integer car is an alist, and becomes an object;
+    /// - any other list becomes an array, and a pair becomes the array
+    ///   `[car, cdr]`;
+    /// - symbols, keywords and strings all become strings, keywords without
+    ///   their `#:` prefix;
+    /// - a byte string becomes an array of its bytes as numbers;
+    /// - `#nil` becomes `null`, and so does a NaN or infinite float.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let v = Sexp::List(vec![
+    ///     Sexp::new_entry("name", "widget"),
+    ///     Sexp::new_entry("sizes", Sexp::List(vec![Sexp::from(1), Sexp::from(2.5)])),
+    /// ]);
+    /// assert_eq!(v.to_json_string(), r#"{"name":"widget","sizes":[1,2.5]}"#);
+    /// ```
+    pub fn to_json_string(&self) -> String {
+        let mut out = String::new();
+        write_json(&mut out, self);
+        out
+    }
+}
+
+fn write_json(out: &mut String, sexp: &Sexp) {
+    match *sexp {
+        Sexp::Nil => out.push_str("null"),
+        Sexp::Boolean(b) => out.push_str(if b { "true" } else { "false" }),
+        Sexp::Number(ref n) => match n.as_f64() {
+            Some(f) if n.is_f64() && !f.is_finite() => out.push_str("null"),
+            _ => {
+                let _ = write!(out, "{}", n);
+            }
+        },
+        Sexp::Atom(ref atom) => write_string(out, atom_text(atom)),
+        Sexp::Bytes(ref bytes) => {
+            out.push('[');
+            for (i, b) in bytes.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "{}", b);
+            }
+            out.push(']');
+        }
+        Sexp::Pair(..) => {
+            let (car, cdr) = sexp.as_pair().unwrap();
+            out.push('[');
+            write_json(out, car);
+            out.push(',');
+            write_json(out, cdr);
+            out.push(']');
+        }
+        Sexp::List(ref elems) => {
+            if let Some(entries) = alist_entries(elems) {
+                out.push('{');
+                for (i, (key, value)) in entries.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_string(out, &key);
+                    out.push(':');
+                    write_json(out, value);
+                }
+                out.push('}');
+            } else {
+                out.push('[');
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_json(out, elem);
+                }
+                out.push(']');
+            }
+        }
+    }
+}
+
+/// The keys and values of `elems` if it is an alist.
+fn alist_entries(elems: &[Sexp]) -> Option<Vec<(String, &Sexp)>> {
+    if elems.is_empty() {
+        return None;
+    }
+    elems
+        .iter()
+        .map(|elem| {
+            let (car, cdr) = elem.as_pair()?;
+            let key = match *car {
+                Sexp::Atom(ref atom) => atom_text(atom).to_owned(),
+                Sexp::Number(ref n) if !n.is_f64() => n.to_string(),
+                _ => return None,
+            };
+            Some((key, cdr))
+        })
+        .collect()
+}
+
+fn atom_text(atom: &Atom) -> &str {
+    match *atom {
+        Atom::Symbol(ref s) | Atom::Keyword(ref s) | Atom::String(ref s) => s,
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch < ' ' => {
+                let _ = write!(out, "\\u{:04x}", ch as u32);
+            }
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
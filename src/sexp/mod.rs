@@ -69,17 +69,26 @@
 //! # }
 //! ```
 //!
-use std::string::String;
-
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 
-pub use crate::atom::Atom;
-use crate::error::Error;
+pub use crate::atom::{gensym, Atom, Keyword, Symbol};
+use crate::error::{Error, ErrorCode};
 pub use crate::number::Number;
 
 mod index;
+pub use self::borrowed::SexpRef;
+pub(crate) use self::de::read_list;
+pub use self::document::{DocumentItem, SexpDocument};
 pub use self::index::Index;
+pub use self::intern::{InternSeed, Interner, SharedSexp};
+pub use self::pattern::{Bindings, BindingsIter, Pattern};
+pub use self::rc::SexpRc;
+pub use self::rewrite::{Rule, RuleSet};
+pub use self::spanned::{SpannedSexp, SpannedValue};
+pub use self::template::Template;
+pub use self::visit::{SexpFolder, SexpVisitor};
+pub use self::write::WriteSexp;
 
 use self::ser::Serializer;
 
@@ -88,10 +97,16 @@ use self::ser::Serializer;
 type SexpPtr = Box<Sexp>;
 type ConsCell = Option<SexpPtr>;
 
+/// Name of the newtype struct a `Sexp::Pair` passes through serde as, around
+/// a tuple of its car and cdr. The S-expression serializer writes it as the
+/// dotted pair `(car . cdr)`.
+pub(crate) const PAIR_TOKEN: &str = "$sexpr::private::Pair";
+
 /// Represents any valid S-expression value.
 ///
 /// See the `sexpr::sexp` module documentation for usage examples.
 #[derive(PartialEq, Clone, Debug)]
+#[non_exhaustive]
 pub enum Sexp {
     /// Represents a S-expression nil value.
     ///
@@ -139,6 +154,19 @@ pub enum Sexp {
     /// ```
     Boolean(bool),
 
+    /// Represents a S-expression byte string, as read from `#x"DEADBEEF"`, or
+    /// from `#DEADBEEF#` with `DeserializerOptions::hex_escapes`. It is
+    /// written back in hex, so bytes that are not UTF-8 survive a round trip.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let v: Sexp = sexpr::from_str("#x\"DEADBEEF\"").unwrap();
+    /// assert_eq!(v, Sexp::Bytes(vec![0xde, 0xad, 0xbe, 0xef]));
+    /// assert_eq!(v.to_string(), "#x\"DEADBEEF\"");
+    /// ```
+    Bytes(Vec<u8>),
+
     /// Represents a S-expression cons-pair.
     ///
     /// ```rust,ignore
@@ -165,42 +193,519 @@ pub enum Sexp {
     List(Vec<Sexp>),
 }
 
+mod borrowed;
 mod de;
+mod debug;
+mod describe;
+mod display;
+mod document;
+mod dot;
+mod from;
+mod intern;
+mod json;
+mod pattern;
+mod rc;
+mod rewrite;
 mod ser;
+mod spanned;
+mod template;
+mod uneval;
+pub mod visit;
+mod write;
 
-impl From<String> for Sexp {
-    /// Convert `String` to `Sexp`
+impl Sexp {
+    /// Return a new Sexp::Pair with a symbol key
     ///
     /// # Examples
-    ///
     /// ```rust,ignore
     /// # fn main() {
     /// use sexpr::Sexp;
-    ///
-    /// let s: String = "lorem".to_string();
-    /// let x: Sexp = s.into();
+    /// let alist_1 = Sexp::new_entry("a", 1)
     /// # }
     /// ```
-    fn from(f: String) -> Self {
-        Sexp::Atom(Atom::from_string(f))
+    pub fn new_entry<A: Into<Atom>, I: Into<Sexp>>(key: A, value: I) -> Sexp {
+        Sexp::pair(Sexp::Atom(key.into()), value)
     }
-}
 
-impl Sexp {
-    /// Return a new Sexp::Pair with a symbol key
+    /// Return a new Sexp::Pair, the dotted pair `(car . cdr)`. A `Sexp::Nil`
+    /// half is stored as an empty cell.
     ///
     /// # Examples
-    /// ```rust,ignore
-    /// # fn main() {
+    /// ```rust
     /// use sexpr::Sexp;
-    /// let alist_1 = Sexp::new_entry("a", 1)
-    /// # }
+    ///
+    /// let pair = Sexp::pair("a", 1);
+    /// assert_eq!(pair.as_pair(), Some((&Sexp::from("a"), &Sexp::from(1))));
+    /// assert_eq!(Sexp::pair(1, Sexp::Nil).as_pair(), Some((&Sexp::from(1), &Sexp::Nil)));
     /// ```
-    pub fn new_entry<A: Into<Atom>, I: Into<Sexp>>(key: A, value: I) -> Sexp {
-        Sexp::Pair(
-            Some(Box::new(Sexp::Atom(key.into()))),
-            Some(Box::new(value.into())),
-        )
+    pub fn pair<A: Into<Sexp>, D: Into<Sexp>>(car: A, cdr: D) -> Sexp {
+        fn cell(sexp: Sexp) -> ConsCell {
+            match sexp {
+                Sexp::Nil => None,
+                sexp => Some(Box::new(sexp)),
+            }
+        }
+
+        Sexp::Pair(cell(car.into()), cell(cdr.into()))
+    }
+
+    /// If the `Sexp` is a pair, returns its car and cdr. An empty cell is
+    /// returned as `Sexp::Nil`. Returns `None` otherwise.
+    pub fn as_pair(&self) -> Option<(&Sexp, &Sexp)> {
+        static NIL: Sexp = Sexp::Nil;
+        match *self {
+            Sexp::Pair(ref car, ref cdr) => Some((
+                car.as_ref().map_or(&NIL, |car| &**car),
+                cdr.as_ref().map_or(&NIL, |cdr| &**cdr),
+            )),
+            _ => None,
+        }
+    }
+
+    /// If the `Sexp` is a pair, consumes it and returns its car and cdr. An
+    /// empty cell is returned as `Sexp::Nil`. Returns `None` otherwise.
+    pub fn into_pair(self) -> Option<(Sexp, Sexp)> {
+        match self {
+            Sexp::Pair(car, cdr) => Some((
+                car.map_or(Sexp::Nil, |car| *car),
+                cdr.map_or(Sexp::Nil, |cdr| *cdr),
+            )),
+            _ => None,
+        }
+    }
+
+    /// The first element of a list, usually the operator of a form such as
+    /// `(define x 1)`. Returns `None` for an empty list or a value that is
+    /// not a list.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let form: Sexp = sexpr::from_str("(define x 1)").unwrap();
+    /// assert_eq!(form.head(), Some(&Sexp::from("define")));
+    /// assert_eq!(form.rest(), Some(&[Sexp::from("x"), Sexp::from(1)][..]));
+    /// ```
+    pub fn head(&self) -> Option<&Sexp> {
+        match *self {
+            Sexp::List(ref elems) => elems.first(),
+            _ => None,
+        }
+    }
+
+    /// The elements of a list after its head, usually the arguments of a
+    /// form. Returns `None` for an empty list or a value that is not a list.
+    pub fn rest(&self) -> Option<&[Sexp]> {
+        match *self {
+            Sexp::List(ref elems) if !elems.is_empty() => Some(&elems[1..]),
+            _ => None,
+        }
+    }
+
+    /// The number of elements of a list, not counting the tail after the dot
+    /// of an improper list: 3 for both `(a b c)` and `(a b c . d)`.
+    ///
+    /// A list is a `Sexp::List`, `Sexp::Nil`, or a chain of `Sexp::Pair`s
+    /// whose cdrs lead to the next pair, with an improper list ending in a
+    /// cdr that is neither nil, a pair nor a list. The length of anything else
+    /// is 0.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let improper = Sexp::pair("a", Sexp::pair("b", "c"));
+    /// assert_eq!(improper.len(), 2);
+    /// assert!(!improper.is_proper());
+    /// assert_eq!(improper.proper_prefix(), [&Sexp::from("a"), &Sexp::from("b")]);
+    /// assert_eq!(improper.tail(), Some(&Sexp::from("c")));
+    ///
+    /// let form: Sexp = sexpr::from_str("(if c t e)").unwrap();
+    /// assert_eq!((form.len(), form.arity()), (4, Some(3)));
+    /// assert_eq!(form.tail(), None);
+    /// ```
+    pub fn len(&self) -> usize {
+        let mut len = 0;
+        self.walk_list(|_| len += 1);
+        len
+    }
+
+    /// Whether the value is the empty list, `()` or `#nil`. An atom is not
+    /// empty, although its `len` is 0.
+    pub fn is_empty(&self) -> bool {
+        match *self {
+            Sexp::Nil => true,
+            Sexp::List(ref elems) => elems.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Whether the value is a proper list: a list or nil, or a chain of
+    /// pairs that ends in nil or a list. False for an improper list such as
+    /// `(a . b)` and for anything that is not a list.
+    pub fn is_proper(&self) -> bool {
+        self.walk_list(|_| ()).is_none()
+    }
+
+    /// The elements of a list before the tail of an improper list, in
+    /// order. Empty for anything that is not a list.
+    pub fn proper_prefix(&self) -> Vec<&Sexp> {
+        let mut elems = Vec::new();
+        self.walk_list(|elem| elems.push(elem));
+        elems
+    }
+
+    /// The tail after the dot of an improper list, `d` in `(a b . d)`.
+    /// Returns `None` for a proper list and for anything that is not a list.
+    pub fn tail(&self) -> Option<&Sexp> {
+        match *self {
+            Sexp::Pair(..) => self.walk_list(|_| ()),
+            _ => None,
+        }
+    }
+
+    /// The number of elements after the head of a proper list, the number of
+    /// arguments a form such as `(f x y)` passes. Returns `None` for an empty
+    /// or improper list and for anything that is not a list.
+    pub fn arity(&self) -> Option<usize> {
+        match self.len() {
+            0 => None,
+            len if self.is_proper() => Some(len - 1),
+            _ => None,
+        }
+    }
+
+    // Calls `each` on the elements of a list in order, and returns what ends
+    // it: `None` for a proper list, the tail of an improper list, or `self`
+    // for anything that is not a list.
+    fn walk_list<'a>(&'a self, mut each: impl FnMut(&'a Sexp)) -> Option<&'a Sexp> {
+        static NIL: Sexp = Sexp::Nil;
+        let mut list = self;
+        loop {
+            match *list {
+                Sexp::Nil => return None,
+                Sexp::List(ref elems) => {
+                    elems.iter().for_each(each);
+                    return None;
+                }
+                Sexp::Pair(ref car, ref cdr) => {
+                    each(car.as_deref().unwrap_or(&NIL));
+                    match *cdr {
+                        Some(ref cdr) => list = cdr,
+                        None => return None,
+                    }
+                }
+                _ => return Some(list),
+            }
+        }
+    }
+
+    /// Looks up a keyword in a property list such as
+    /// `(#:name "web" #:port 80)` and returns the element after it.
+    ///
+    /// `key` is the keyword's name, with or without its `#:` prefix. If the
+    /// list starts with something other than a keyword, as in
+    /// `(server #:port 80)`, that head is skipped. The remaining elements are
+    /// read as keyword and value in turn, and the first matching keyword
+    /// wins. Returns `None` if `self` is not a list, the keyword is missing,
+    /// or it has no value after it.
+    ///
+    /// ```rust
+    /// use sexpr::sexp::{Atom, Sexp};
+    ///
+    /// let form: Sexp = sexpr::from_str("(server #:port 80 #:host \"example.org\")").unwrap();
+    /// assert_eq!(form.plist_get("#:port"), Some(&Sexp::from(80)));
+    /// assert_eq!(form.plist_get("host"), Some(&Sexp::Atom(Atom::string("example.org"))));
+    /// assert_eq!(form.plist_get("user"), None);
+    /// ```
+    pub fn plist_get(&self, key: &str) -> Option<&Sexp> {
+        let key = key.trim_start_matches("#:");
+        let elems = match *self {
+            Sexp::List(ref elems) => elems,
+            _ => return None,
+        };
+        let props = match elems.first() {
+            Some(&Sexp::Atom(Atom::Keyword(_))) => &elems[..],
+            Some(_) => &elems[1..],
+            None => return None,
+        };
+        props
+            .chunks(2)
+            .find(|prop| match prop[0] {
+                Sexp::Atom(Atom::Keyword(ref name)) => name == key,
+                _ => false,
+            })
+            .and_then(|prop| prop.get(1))
+    }
+
+    /// Looks up a key in an association list and returns its value.
+    ///
+    /// Each entry is either a pair `(key . value)` or a two-element list
+    /// `(key value)`, and matches if its key is an atom whose text is `key`,
+    /// whatever the kind of atom. If the list starts with an atom, as in
+    /// `(server (port 80))`, that head is skipped. The first matching entry
+    /// wins; entries of any other shape are ignored. Returns `None` if `self`
+    /// is not a list or no entry matches.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let form: Sexp = sexpr::from_str("(server (port 80) (host \"example.org\"))").unwrap();
+    /// assert_eq!(form.alist_get("port"), Some(&Sexp::from(80)));
+    /// assert_eq!(form.alist_get("user"), None);
+    ///
+    /// let alist = Sexp::List(vec![Sexp::new_entry("port", 80)]);
+    /// assert_eq!(alist.alist_get("port"), Some(&Sexp::from(80)));
+    /// ```
+    pub fn alist_get(&self, key: &str) -> Option<&Sexp> {
+        let elems = match *self {
+            Sexp::List(ref elems) => elems,
+            _ => return None,
+        };
+        let entries = match elems.first() {
+            Some(&Sexp::Atom(_)) => &elems[1..],
+            _ => &elems[..],
+        };
+        entries.iter().find_map(|entry| {
+            let (entry_key, value) = match *entry {
+                Sexp::Pair(..) => entry.as_pair()?,
+                Sexp::List(ref kv) if kv.len() == 2 => (&kv[0], &kv[1]),
+                _ => return None,
+            };
+            match *entry_key {
+                Sexp::Atom(ref atom) if atom.as_str() == key => Some(value),
+                _ => None,
+            }
+        })
+    }
+
+    /// Sorts the elements of a list by the key `f` extracts from each, in
+    /// place. The sort is stable, so elements with equal keys keep their
+    /// order. Does nothing if `self` is not a list.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let mut deps: Sexp = sexpr::from_str("((serde 1) (anyhow 1) (log 0))").unwrap();
+    /// deps.sort_list_by_key(|dep| dep.head().map(|name| name.to_string()));
+    /// assert_eq!(deps.to_string(), "((anyhow 1) (log 0) (serde 1))");
+    /// ```
+    pub fn sort_list_by_key<K, F>(&mut self, f: F)
+    where
+        K: Ord,
+        F: FnMut(&Sexp) -> K,
+    {
+        if let Sexp::List(ref mut elems) = *self {
+            elems.sort_by_key(f);
+        }
+    }
+
+    /// Removes every element of a list that equals an earlier one, in place,
+    /// keeping the first of each in its position. Unlike `Vec::dedup`, equal
+    /// elements need not be next to each other. Does nothing if `self` is
+    /// not a list.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let mut features: Sexp = sexpr::from_str("(std derive std alloc derive)").unwrap();
+    /// features.dedup_list();
+    /// assert_eq!(features.to_string(), "(std derive alloc)");
+    /// ```
+    pub fn dedup_list(&mut self) {
+        if let Sexp::List(ref mut elems) = *self {
+            let mut kept: Vec<Sexp> = Vec::with_capacity(elems.len());
+            for elem in elems.drain(..) {
+                if !kept.contains(&elem) {
+                    kept.push(elem);
+                }
+            }
+            *elems = kept;
+        }
+    }
+
+    /// Sorts the entries of an association list by the text of their keys,
+    /// in place, as `alist_get` reads them: pairs `(key . value)` and lists
+    /// `(key value ...)` with an atom as their key. A head atom, as in
+    /// `(server (port 80))`, stays first, and elements that are not entries
+    /// go last. The sort is stable and does not reach into the values. Does
+    /// nothing if `self` is not a list.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let mut form: Sexp = sexpr::from_str("(package (version \"1.0\") (name \"web\") (authors))").unwrap();
+    /// form.sort_alist_keys();
+    /// assert_eq!(form.to_string(), "(package (authors) (name \"web\") (version \"1.0\"))");
+    /// ```
+    pub fn sort_alist_keys(&mut self) {
+        fn entry_key(entry: &Sexp) -> Option<&str> {
+            let key = match *entry {
+                Sexp::Pair(Some(ref car), _) => &**car,
+                Sexp::List(ref elems) => elems.first()?,
+                _ => return None,
+            };
+            match *key {
+                Sexp::Atom(ref atom) => Some(atom.as_str()),
+                _ => None,
+            }
+        }
+
+        if let Sexp::List(ref mut elems) = *self {
+            let entries = match elems.first() {
+                Some(&Sexp::Atom(_)) => &mut elems[1..],
+                _ => &mut elems[..],
+            };
+            // `None` sorts first, so flip it to put non-entries last.
+            entries.sort_by(|a, b| match (entry_key(a), entry_key(b)) {
+                (Some(a), Some(b)) => a.cmp(b),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            });
+        }
+    }
+
+    /// Replaces each element of a list that is itself a list with that
+    /// list's elements, in place, one level deep: `(a (b (c)) d)` becomes
+    /// `(a b (c) d)`. A chain of pairs counts as a list, with the tail of an
+    /// improper one as its last element, so `(a (b . c))` becomes `(a b c)`.
+    /// `#nil` elements are kept as they are. Does nothing if `self` is not a
+    /// list.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let mut form: Sexp = sexpr::from_str("(a (b (c)) () d)").unwrap();
+    /// form.flatten_once();
+    /// assert_eq!(form.to_string(), "(a b (c) d)");
+    /// form.flatten_deep();
+    /// assert_eq!(form.to_string(), "(a b c d)");
+    /// ```
+    pub fn flatten_once(&mut self) {
+        if let Sexp::List(ref mut elems) = *self {
+            let mut flat = Vec::with_capacity(elems.len());
+            for elem in elems.drain(..) {
+                match elem {
+                    Sexp::List(_) | Sexp::Pair(..) => flat.extend(elem.into_elements()),
+                    elem => flat.push(elem),
+                }
+            }
+            *elems = flat;
+        }
+    }
+
+    /// Like `flatten_once`, but flattens lists inside lists at any depth, so
+    /// that no element of the list is a list or pair.
+    pub fn flatten_deep(&mut self) {
+        fn push_leaves(elem: Sexp, flat: &mut Vec<Sexp>) {
+            match elem {
+                Sexp::List(_) | Sexp::Pair(..) => {
+                    for elem in elem.into_elements() {
+                        push_leaves(elem, flat);
+                    }
+                }
+                elem => flat.push(elem),
+            }
+        }
+
+        if let Sexp::List(ref mut elems) = *self {
+            let mut flat = Vec::with_capacity(elems.len());
+            for elem in elems.drain(..) {
+                push_leaves(elem, &mut flat);
+            }
+            *elems = flat;
+        }
+    }
+
+    /// Returns a list or chain of pairs as a `Sexp::List` of its elements,
+    /// so that `(a . (b c))` and `(a b c)` come out the same. `#nil` is the
+    /// empty list.
+    ///
+    /// The tail of an improper list such as `(a b . c)` becomes the last
+    /// element if `append_tail` is set.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let improper = Sexp::pair("a", Sexp::pair("b", "c"));
+    /// assert_eq!(improper.to_proper_list(true).unwrap().to_string(), "(a b c)");
+    /// assert!(improper.to_proper_list(false).is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorCode::ExpectedProperList` if `self` is an improper
+    /// list and `append_tail` is not set, or is not a list at all.
+    pub fn to_proper_list(&self, append_tail: bool) -> Result<Sexp, Error> {
+        let mut elems = Vec::new();
+        match self.walk_list(|elem| elems.push(elem.clone())) {
+            None => Ok(Sexp::List(elems)),
+            Some(tail) if append_tail && matches!(*self, Sexp::Pair(..)) => {
+                elems.push(tail.clone());
+                Ok(Sexp::List(elems))
+            }
+            Some(_) => Err(Error::syntax(ErrorCode::ExpectedProperList, 0, 0)),
+        }
+    }
+
+    /// Compares two values as the structures they denote rather than by how
+    /// they are represented: a chain of pairs equals the list of the same
+    /// elements, so `(a . (b c))`, `(a b c)` and `(a . (b . (c . #nil)))`
+    /// are all equal, and `#nil` equals `()`. Lists are compared this way at
+    /// any depth; other values are compared with `==`.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let dotted: Sexp = sexpr::from_str("(a . (b c))").unwrap();
+    /// let plain: Sexp = sexpr::from_str("(a b c)").unwrap();
+    /// assert_ne!(dotted, plain);
+    /// assert!(dotted.structural_eq(&plain));
+    /// let pair: Sexp = sexpr::from_str("(a . b)").unwrap();
+    /// assert!(!pair.structural_eq(&sexpr::from_str("(a b)").unwrap()));
+    /// ```
+    pub fn structural_eq(&self, other: &Sexp) -> bool {
+        fn is_list(sexp: &Sexp) -> bool {
+            matches!(*sexp, Sexp::Nil | Sexp::List(_) | Sexp::Pair(..))
+        }
+
+        if !is_list(self) || !is_list(other) {
+            return self == other;
+        }
+        let (mut left, mut right) = (Vec::new(), Vec::new());
+        let left_tail = self.walk_list(|elem| left.push(elem));
+        let right_tail = other.walk_list(|elem| right.push(elem));
+        left.len() == right.len()
+            && left.iter().zip(&right).all(|(a, b)| a.structural_eq(b))
+            && match (left_tail, right_tail) {
+                (None, None) => true,
+                (Some(a), Some(b)) => a.structural_eq(b),
+                _ => false,
+            }
+    }
+
+    // The elements of a list or chain of pairs, with the tail of an improper
+    // list as the last of them.
+    fn into_elements(self) -> Vec<Sexp> {
+        let mut elems = Vec::new();
+        let mut list = self;
+        loop {
+            match list {
+                Sexp::List(rest) => {
+                    elems.extend(rest);
+                    return elems;
+                }
+                Sexp::Pair(car, cdr) => {
+                    elems.push(car.map_or(Sexp::Nil, |car| *car));
+                    match cdr {
+                        Some(cdr) => list = *cdr,
+                        None => return elems,
+                    }
+                }
+                Sexp::Nil => return elems,
+                tail => {
+                    elems.push(tail);
+                    return elems;
+                }
+            }
+        }
     }
 
     /// Index into a Sexp alist or list. A string index can be used to access a
@@ -307,22 +812,26 @@ impl Sexp {
 /// # }
 /// ```
 ///
-/// # Errors
-///
-/// This conversion can fail if `T`'s implementation of `Serialize` decides to
-/// fail, or if `T` contains a map with non-string keys.
+/// A map becomes an alist whose cars are its keys, whatever their type, so
+/// a map keyed by numbers or lists converts as well as one keyed by strings.
+/// The entries are in the order the map iterates over them, and the fields
+/// of a struct are in the order they are declared.
 ///
-/// ```rust,ignore
+/// ```rust
 /// use std::collections::BTreeMap;
+/// use sexpr::Sexp;
 ///
-/// fn main() {
-///     // The keys in this map are vectors, not strings.
-///     let mut map = BTreeMap::new();
-///     map.insert(vec![32, 64], "x86");
+/// let mut map = BTreeMap::new();
+/// map.insert(vec![32, 64], "x86");
 ///
-///     println!("{}", sexpr::to_value(map).unwrap_err());
-/// }
+/// let v = sexpr::to_value(map).unwrap();
+/// assert_eq!(v, Sexp::List(vec![Sexp::pair(vec![32, 64], "x86")]));
 /// ```
+///
+/// # Errors
+///
+/// This conversion can fail if `T`'s implementation of `Serialize` decides to
+/// fail.
 
 // Taking by value is more friendly to iterator adapters, option and result
 // consumers, etc.
@@ -330,7 +839,44 @@ pub fn to_value<T>(value: T) -> Result<Sexp, Error>
 where
     T: Serialize,
 {
-    value.serialize(Serializer)
+    to_value_with_options(value, &ToValueOptions::default())
+}
+
+/// Options changing how `to_value_with_options` builds a `Sexp`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ToValueOptions {
+    /// Hold map keys to JSON's rule, for values that will also be written as
+    /// JSON: integer keys become symbols of their digits, and any other key
+    /// that is not a string, symbol or keyword fails with
+    /// `KeyMustBeAString`.
+    pub string_keys: bool,
+}
+
+/// Like `to_value`, but builds the `Sexp` as described by `options`.
+///
+/// ```rust
+/// use std::collections::BTreeMap;
+/// use sexpr::sexp::Atom;
+/// use sexpr::{Sexp, ToValueOptions};
+///
+/// let options = ToValueOptions { string_keys: true };
+///
+/// let mut map = BTreeMap::new();
+/// map.insert(1, "one");
+/// let v = sexpr::to_value_with_options(&map, &options).unwrap();
+/// assert_eq!(v, Sexp::List(vec![Sexp::new_entry(Atom::symbol("1"), "one")]));
+///
+/// let mut map = BTreeMap::new();
+/// map.insert(vec![32, 64], "x86");
+/// assert!(sexpr::to_value_with_options(&map, &options).is_err());
+/// ```
+pub fn to_value_with_options<T>(value: T, options: &ToValueOptions) -> Result<Sexp, Error>
+where
+    T: Serialize,
+{
+    value.serialize(Serializer {
+        string_keys: options.string_keys,
+    })
 }
 
 /// Interpret a `sexpr::Sexp` as an instance of type `T`.
@@ -371,3 +917,82 @@ where
 {
     T::deserialize(value)
 }
+
+/// Convert a struct or map into its fields as key and value pairs.
+///
+/// `to_value` turns a struct into an alist, a `Sexp::List` of pairs; this
+/// takes the pairs apart so the fields can be looked up or iterated without
+/// matching on the `Sexp` structure. Fails with `ExpectedAlist` if `T` does
+/// not serialize as a struct or map, and with `KeyMustBeAString` if a map
+/// key cannot be made into an atom.
+///
+/// ```rust
+/// # use serde_derive::Serialize;
+/// use sexpr::sexp::Atom;
+/// use sexpr::Sexp;
+///
+/// #[derive(Serialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let fields = sexpr::to_alist(Point { x: 1, y: 2 }).unwrap();
+/// assert_eq!(
+///     fields,
+///     vec![
+///         (Atom::symbol("x"), Sexp::from(1)),
+///         (Atom::symbol("y"), Sexp::from(2)),
+///     ]
+/// );
+/// ```
+pub fn to_alist<T>(value: T) -> Result<Vec<(Atom, Sexp)>, Error>
+where
+    T: Serialize,
+{
+    let options = ToValueOptions { string_keys: true };
+    let entries = match to_value_with_options(value, &options)? {
+        Sexp::List(entries) => entries,
+        Sexp::Nil => Vec::new(),
+        _ => return Err(Error::syntax(ErrorCode::ExpectedAlist, 0, 0)),
+    };
+    entries
+        .into_iter()
+        .map(|entry| match entry.into_pair() {
+            Some((Sexp::Atom(key), value)) => Ok((key, value)),
+            _ => Err(Error::syntax(ErrorCode::ExpectedAlist, 0, 0)),
+        })
+        .collect()
+}
+
+/// Interpret key and value pairs as an instance of type `T`, the inverse of
+/// `to_alist`.
+///
+/// ```rust
+/// # use serde_derive::Deserialize;
+/// use sexpr::sexp::Atom;
+/// use sexpr::Sexp;
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let fields = vec![
+///     (Atom::symbol("y"), Sexp::from(2)),
+///     (Atom::symbol("x"), Sexp::from(1)),
+/// ];
+/// let point: Point = sexpr::from_alist(fields).unwrap();
+/// assert_eq!(point, Point { x: 1, y: 2 });
+/// ```
+pub fn from_alist<T>(alist: Vec<(Atom, Sexp)>) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    let entries = alist
+        .into_iter()
+        .map(|(key, value)| Sexp::new_entry(key, value))
+        .collect();
+    from_value(Sexp::List(entries))
+}
@@ -0,0 +1,327 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Destructuring a `Sexp` against a template with variables.
+
+use std::collections::btree_map;
+use std::collections::BTreeMap;
+
+use crate::atom::Atom;
+use crate::de;
+use crate::error::{Error, ErrorCode, Result};
+use crate::sexp::Sexp;
+
+/// A template that values can be matched against, capturing the parts that
+/// line up with its variables.
+///
+/// Patterns are S-expressions in which some symbols are variables:
+///
+/// - `?name` matches any single value and binds it to `name`. A name used
+///   twice must match equal values both times.
+/// - `??name`, as an element of a list, matches any number of elements and
+///   binds them to `name` as a list. A list can hold at most one.
+/// - `?_` and `??_` match the same way without binding anything.
+///
+/// A dotted list such as `(define ?name . ?rest)` matches a list that
+/// starts with the elements before the dot; its tail is matched against the
+/// list of the elements left over. Anything else matches only an equal
+/// value.
+///
+/// ```rust
+/// use sexpr::sexp::Pattern;
+/// use sexpr::Sexp;
+///
+/// let pattern = Pattern::parse("(define ?name ??body)").unwrap();
+/// let form: Sexp = sexpr::from_str("(define x (f 1) 2)").unwrap();
+///
+/// let bindings = pattern.matches(&form).unwrap();
+/// assert_eq!(bindings.get("name"), Some(&Sexp::from("x")));
+/// assert_eq!(bindings.get("body"), Some(&sexpr::from_str("((f 1) 2)").unwrap()));
+///
+/// assert!(pattern.matches(&sexpr::from_str("(lambda x)").unwrap()).is_none());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    sexp: Sexp,
+}
+
+/// The values captured by a successful `Pattern::matches`, by variable name
+/// without its `?` prefix.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bindings {
+    map: BTreeMap<String, Sexp>,
+}
+
+pub(super) enum Var<'a> {
+    /// `?name`, or `?_` for `None`.
+    One(Option<&'a str>),
+    /// `??name`, or `??_` for `None`.
+    Segment(Option<&'a str>),
+}
+
+impl Pattern {
+    /// Parses a pattern from S-expression text.
+    ///
+    /// The reader does not start a symbol at `?`, so words starting with `?`
+    /// are read as variables here even though `sexpr::from_str` rejects
+    /// them.
+    pub fn parse(text: &str) -> Result<Pattern> {
+        Pattern::new(de::from_str(&quote_vars(text))?)
+    }
+
+    /// Makes a pattern from a `Sexp` whose variables are symbols starting
+    /// with `?`.
+    pub fn new(sexp: Sexp) -> Result<Pattern> {
+        check(&sexp, false)?;
+        Ok(Pattern { sexp })
+    }
+
+    /// The pattern as a `Sexp`.
+    pub fn as_sexp(&self) -> &Sexp {
+        &self.sexp
+    }
+
+    /// Matches `value` against the pattern, returning what its variables
+    /// captured, or `None` if it does not match.
+    pub fn matches(&self, value: &Sexp) -> Option<Bindings> {
+        let mut bindings = Bindings::new();
+        if match_value(&self.sexp, value, &mut bindings) {
+            Some(bindings)
+        } else {
+            None
+        }
+    }
+}
+
+impl Bindings {
+    /// Creates an empty set of bindings.
+    pub fn new() -> Self {
+        Bindings::default()
+    }
+
+    /// The value bound to `name`, which does not include the `?` prefix.
+    pub fn get(&self, name: &str) -> Option<&Sexp> {
+        self.map.get(name)
+    }
+
+    /// Binds `name` to `value`, returning the value it was bound to before.
+    pub fn insert<S: Into<String>, V: Into<Sexp>>(&mut self, name: S, value: V) -> Option<Sexp> {
+        self.map.insert(name.into(), value.into())
+    }
+
+    /// The number of bound names.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether no names are bound.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Iterates over the bound names and their values, ordered by name.
+    pub fn iter(&self) -> BindingsIter<'_> {
+        BindingsIter {
+            iter: self.map.iter(),
+        }
+    }
+}
+
+/// An iterator over the names and values in `Bindings`.
+pub struct BindingsIter<'a> {
+    iter: btree_map::Iter<'a, String, Sexp>,
+}
+
+impl<'a> Iterator for BindingsIter<'a> {
+    type Item = (&'a str, &'a Sexp);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(name, value)| (name.as_str(), value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> IntoIterator for &'a Bindings {
+    type Item = (&'a str, &'a Sexp);
+    type IntoIter = BindingsIter<'a>;
+
+    fn into_iter(self) -> BindingsIter<'a> {
+        self.iter()
+    }
+}
+
+pub(super) fn var(sexp: &Sexp) -> Option<Var<'_>> {
+    let name = match *sexp {
+        Sexp::Atom(Atom::Symbol(ref name)) => name,
+        _ => return None,
+    };
+    let (segment, name) = match name.strip_prefix("??") {
+        Some(name) => (true, name),
+        None => (false, name.strip_prefix('?')?),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    let name = if name == "_" { None } else { Some(name) };
+    Some(if segment {
+        Var::Segment(name)
+    } else {
+        Var::One(name)
+    })
+}
+
+/// Checks that segment variables appear only as list elements, at most one
+/// to a list.
+fn check(sexp: &Sexp, in_list: bool) -> Result<()> {
+    match *sexp {
+        Sexp::List(ref elems) => {
+            let segments = elems
+                .iter()
+                .filter(|elem| matches!(var(elem), Some(Var::Segment(_))))
+                .count();
+            if segments > 1 {
+                return Err(invalid("a list has more than one segment variable"));
+            }
+            elems.iter().try_for_each(|elem| check(elem, true))
+        }
+        Sexp::Pair(..) => {
+            let (car, cdr) = sexp.as_pair().unwrap();
+            check(car, false)?;
+            check(cdr, false)
+        }
+        _ => match var(sexp) {
+            Some(Var::Segment(_)) if !in_list => {
+                Err(invalid("a segment variable is not in a list"))
+            }
+            _ => Ok(()),
+        },
+    }
+}
+
+pub(super) fn invalid(msg: &str) -> Error {
+    Error::syntax(ErrorCode::InvalidPattern(msg.to_owned()), 0, 0)
+}
+
+fn match_value(pattern: &Sexp, value: &Sexp, bindings: &mut Bindings) -> bool {
+    match var(pattern) {
+        Some(Var::One(None)) => return true,
+        Some(Var::One(Some(name))) => return bind(bindings, name, value.clone()),
+        _ => {}
+    }
+    match (pattern, value) {
+        (Sexp::List(patterns), Sexp::List(values)) => match_list(patterns, values, bindings),
+        (Sexp::Pair(..), Sexp::Pair(..)) => {
+            let (pattern_car, pattern_cdr) = pattern.as_pair().unwrap();
+            let (car, cdr) = value.as_pair().unwrap();
+            match_value(pattern_car, car, bindings) && match_value(pattern_cdr, cdr, bindings)
+        }
+        (Sexp::Pair(..), Sexp::List(values)) if !values.is_empty() => {
+            let (pattern_car, pattern_cdr) = pattern.as_pair().unwrap();
+            let rest = Sexp::List(values[1..].to_vec());
+            match_value(pattern_car, &values[0], bindings)
+                && match_value(pattern_cdr, &rest, bindings)
+        }
+        _ => pattern == value,
+    }
+}
+
+fn match_list(patterns: &[Sexp], values: &[Sexp], bindings: &mut Bindings) -> bool {
+    let segment = patterns
+        .iter()
+        .enumerate()
+        .find_map(|(i, pattern)| match var(pattern) {
+            Some(Var::Segment(name)) => Some((i, name)),
+            _ => None,
+        });
+    let (i, name) = match segment {
+        Some(segment) => segment,
+        None => {
+            return patterns.len() == values.len()
+                && patterns
+                    .iter()
+                    .zip(values)
+                    .all(|(pattern, value)| match_value(pattern, value, bindings));
+        }
+    };
+
+    let (before, after) = (&patterns[..i], &patterns[i + 1..]);
+    if values.len() < before.len() + after.len() {
+        return false;
+    }
+    let end = values.len() - after.len();
+    let fixed = before
+        .iter()
+        .zip(&values[..i])
+        .chain(after.iter().zip(&values[end..]))
+        .all(|(pattern, value)| match_value(pattern, value, bindings));
+    match name {
+        Some(name) if fixed => bind(bindings, name, Sexp::List(values[i..end].to_vec())),
+        _ => fixed,
+    }
+}
+
+fn bind(bindings: &mut Bindings, name: &str, value: Sexp) -> bool {
+    match bindings.map.get(name) {
+        Some(bound) => *bound == value,
+        None => {
+            bindings.map.insert(name.to_owned(), value);
+            true
+        }
+    }
+}
+
+/// Writes every word starting with `?` between bars, so that the reader
+/// takes it as a symbol.
+pub(super) fn quote_vars(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            // Strings and symbols between bars are copied as they are.
+            quote @ b'"' | quote @ b'|' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i = (i + 1).min(bytes.len());
+            }
+            b';' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'?' => {
+                while i < bytes.len() && !ends_word(bytes[i]) {
+                    i += 1;
+                }
+                out.push('|');
+                out.push_str(&text[start..i].replace('\\', "\\\\"));
+                out.push('|');
+                continue;
+            }
+            ch if ends_word(ch) => i += 1,
+            _ => {
+                // Copy the whole word, so a `?` inside it is left alone.
+                while i < bytes.len() && !ends_word(bytes[i]) {
+                    i += 1;
+                }
+            }
+        }
+        out.push_str(&text[start..i]);
+    }
+    out
+}
+
+fn ends_word(ch: u8) -> bool {
+    ch.is_ascii_whitespace() || matches!(ch, b'(' | b')' | b'[' | b']' | b'"' | b';' | b'|')
+}
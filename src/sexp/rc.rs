@@ -0,0 +1,139 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A reference-counted S-expression value that is cheap to clone.
+
+use std::sync::Arc;
+
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use crate::atom::Atom;
+use crate::bytes::{ByteSlice, HEX_BYTES_TOKEN};
+use crate::number::Number;
+use crate::sexp::{Sexp, PAIR_TOKEN};
+
+/// An S-expression value like `Sexp` whose lists and pairs are held through
+/// `Arc`, so that cloning a large tree, for example to hand it to several
+/// threads, only bumps a reference count.
+///
+/// `SexpRc` serializes and deserializes like `Sexp`, so it can be passed to
+/// `to_writer` or `from_str` directly, and is written straight from its
+/// shared nodes. See also `WriteSexp`.
+///
+/// ```rust
+/// use sexpr::sexp::SexpRc;
+/// use sexpr::Sexp;
+///
+/// let tree: SexpRc = sexpr::from_str("(a (b c) 1)").unwrap();
+/// let copy = tree.clone();
+/// assert_eq!(sexpr::to_string(&copy).unwrap(), "(a (b c) 1)");
+/// assert_eq!(Sexp::from(copy), sexpr::from_str::<Sexp>("(a (b c) 1)").unwrap());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum SexpRc {
+    /// Represents a S-expression nil value.
+    Nil,
+
+    /// Represents a S-expression string, symbol or keyword.
+    Atom(Atom),
+
+    /// Represents a S-expression number, whether integer or floating point.
+    Number(Number),
+
+    /// Represents a S-expression boolean.
+    Boolean(bool),
+
+    /// Represents a S-expression byte string.
+    Bytes(Vec<u8>),
+
+    /// Represents a S-expression cons-pair. An empty cell is `Nil`.
+    Pair(Arc<SexpRc>, Arc<SexpRc>),
+
+    /// Represents a S-expression list.
+    List(Arc<[SexpRc]>),
+}
+
+impl SexpRc {
+    /// Copies the value into a `Sexp`, which owns all of its subtrees.
+    pub fn to_sexp(&self) -> Sexp {
+        match *self {
+            SexpRc::Nil => Sexp::Nil,
+            SexpRc::Atom(ref a) => Sexp::Atom(a.clone()),
+            SexpRc::Number(ref n) => Sexp::Number(n.clone()),
+            SexpRc::Boolean(b) => Sexp::Boolean(b),
+            SexpRc::Bytes(ref bytes) => Sexp::Bytes(bytes.clone()),
+            SexpRc::Pair(ref car, ref cdr) => Sexp::pair(car.to_sexp(), cdr.to_sexp()),
+            SexpRc::List(ref elems) => Sexp::List(elems.iter().map(SexpRc::to_sexp).collect()),
+        }
+    }
+}
+
+impl From<Sexp> for SexpRc {
+    fn from(sexp: Sexp) -> Self {
+        match sexp {
+            Sexp::Nil => SexpRc::Nil,
+            Sexp::Atom(a) => SexpRc::Atom(a),
+            Sexp::Number(n) => SexpRc::Number(n),
+            Sexp::Boolean(b) => SexpRc::Boolean(b),
+            Sexp::Bytes(bytes) => SexpRc::Bytes(bytes),
+            Sexp::Pair(car, cdr) => {
+                let cell = |cell: Option<Box<Sexp>>| {
+                    Arc::new(cell.map_or(SexpRc::Nil, |s| SexpRc::from(*s)))
+                };
+                SexpRc::Pair(cell(car), cell(cdr))
+            }
+            Sexp::List(elems) => SexpRc::List(elems.into_iter().map(SexpRc::from).collect()),
+        }
+    }
+}
+
+impl<'a> From<&'a Sexp> for SexpRc {
+    fn from(sexp: &'a Sexp) -> Self {
+        SexpRc::from(sexp.clone())
+    }
+}
+
+impl From<SexpRc> for Sexp {
+    fn from(sexp: SexpRc) -> Self {
+        sexp.to_sexp()
+    }
+}
+
+impl Serialize for SexpRc {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            SexpRc::Nil => serializer.serialize_unit(),
+            SexpRc::Boolean(b) => serializer.serialize_bool(b),
+            SexpRc::Number(ref n) => n.serialize(serializer),
+            SexpRc::Atom(ref atom) => atom.serialize(serializer),
+            SexpRc::Bytes(ref bytes) => {
+                serializer.serialize_newtype_struct(HEX_BYTES_TOKEN, &ByteSlice(bytes))
+            }
+            SexpRc::List(ref v) => v.serialize(serializer),
+            SexpRc::Pair(ref car, ref cdr) => {
+                serializer.serialize_newtype_struct(PAIR_TOKEN, &(&**car, &**cdr))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SexpRc {
+    #[inline]
+    fn deserialize<D>(deserializer: D) -> Result<SexpRc, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Sexp::deserialize(deserializer).map(SexpRc::from)
+    }
+}
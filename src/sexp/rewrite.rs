@@ -0,0 +1,193 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rewriting a `Sexp` with pattern and template rules until none applies.
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::sexp::pattern::{var, Var};
+use crate::sexp::{Pattern, Sexp, Template};
+
+/// A rewrite rule: a value that matches the pattern is replaced by the
+/// template, filled with what the pattern captured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pattern: Pattern,
+    template: Template,
+}
+
+impl Rule {
+    /// Makes a rule from a pattern and a template. Fails with
+    /// `MissingVariable` if the template uses a variable that the pattern
+    /// does not bind.
+    pub fn new(pattern: Pattern, template: Template) -> Result<Rule> {
+        let mut bound = Vec::new();
+        var_names(pattern.as_sexp(), &mut bound);
+        let mut used = Vec::new();
+        var_names(template.as_sexp(), &mut used);
+        if let Some(name) = used.into_iter().find(|name| !bound.contains(name)) {
+            return Err(Error::syntax(
+                ErrorCode::MissingVariable(name.to_owned()),
+                0,
+                0,
+            ));
+        }
+        Ok(Rule { pattern, template })
+    }
+
+    /// Parses a rule from the text of its pattern and of its template.
+    pub fn parse(pattern: &str, template: &str) -> Result<Rule> {
+        Rule::new(Pattern::parse(pattern)?, Template::parse(template)?)
+    }
+
+    /// The pattern a value must match for the rule to apply.
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    /// The template that replaces a matching value.
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+}
+
+/// An ordered set of rules, applied to a value until none of them matches
+/// any part of it.
+///
+/// Each step rewrites one value: the first, in pre-order, that a rule
+/// matches, using the first rule added that matches it. Rewriting then
+/// starts again from the root. Variables are substituted as they are, with
+/// no renaming, so the same rules always give the same result.
+///
+/// Rules that keep matching their own output never reach a fixed point, so
+/// rewriting gives up with `StepLimitExceeded` after `max_steps` steps,
+/// 10000 by default.
+///
+/// ```rust
+/// use sexpr::sexp::{Rule, RuleSet};
+///
+/// let rules = RuleSet::new()
+///     .with_rule(Rule::parse("(when ?test ??body)", "(if ?test (begin ??body) ())").unwrap())
+///     .with_rule(Rule::parse("(begin ?only)", "?only").unwrap());
+///
+/// let program = sexpr::from_str("(when ready (when armed (fire)))").unwrap();
+/// assert_eq!(
+///     rules.apply(program).unwrap(),
+///     sexpr::from_str("(if ready (if armed (fire) ()) ())").unwrap()
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    max_steps: usize,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet {
+            rules: Vec::new(),
+            max_steps: 10_000,
+        }
+    }
+}
+
+impl RuleSet {
+    /// Creates an empty rule set.
+    pub fn new() -> Self {
+        RuleSet::default()
+    }
+
+    /// Adds a rule, tried after the rules already in the set.
+    pub fn with_rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Sets the number of steps after which `apply` gives up.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Adds a rule, tried after the rules already in the set.
+    pub fn push(&mut self, rule: Rule) {
+        self.rules.push(rule);
+    }
+
+    /// The rules in the order they are tried.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Rewrites `sexp` until no rule matches any part of it.
+    pub fn apply(&self, mut sexp: Sexp) -> Result<Sexp> {
+        let mut steps = 0;
+        while self.step(&mut sexp)? {
+            steps += 1;
+            if steps > self.max_steps {
+                return Err(Error::syntax(
+                    ErrorCode::StepLimitExceeded(self.max_steps),
+                    0,
+                    0,
+                ));
+            }
+        }
+        Ok(sexp)
+    }
+
+    /// Rewrites the first value in `sexp` that a rule matches, returning
+    /// whether there was one.
+    fn step(&self, sexp: &mut Sexp) -> Result<bool> {
+        for rule in &self.rules {
+            if let Some(bindings) = rule.pattern.matches(sexp) {
+                *sexp = rule.template.instantiate(&bindings)?;
+                return Ok(true);
+            }
+        }
+        match *sexp {
+            Sexp::List(ref mut elems) => {
+                for elem in elems {
+                    if self.step(elem)? {
+                        return Ok(true);
+                    }
+                }
+            }
+            Sexp::Pair(ref mut car, ref mut cdr) => {
+                for cell in [car, cdr] {
+                    if let Some(ref mut inner) = *cell {
+                        if self.step(inner)? {
+                            return Ok(true);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+}
+
+/// Collects the names of the variables in `sexp`, leaving out wildcards.
+fn var_names<'a>(sexp: &'a Sexp, names: &mut Vec<&'a str>) {
+    match var(sexp) {
+        Some(Var::One(Some(name))) | Some(Var::Segment(Some(name))) => names.push(name),
+        _ => {}
+    }
+    match *sexp {
+        Sexp::List(ref elems) => {
+            for elem in elems {
+                var_names(elem, names);
+            }
+        }
+        Sexp::Pair(..) => {
+            let (car, cdr) = sexp.as_pair().unwrap();
+            var_names(car, names);
+            var_names(cdr, names);
+        }
+        _ => {}
+    }
+}
@@ -6,9 +6,15 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use crate::atom::Atom;
+use crate::atom::{KEYWORD_TOKEN, SYMBOL_TOKEN};
+use crate::bytes::{ByteSlice, HEX_BYTES_TOKEN};
 use crate::error::{Error, ErrorCode};
 use crate::number::Number;
-use crate::sexp::{to_value, Sexp};
+#[cfg(feature = "arbitrary_precision")]
+use crate::number::NUMBER_TOKEN;
+use crate::raw::RAW_TOKEN;
+use crate::sexp::{Sexp, PAIR_TOKEN};
 use serde::{self, Serialize};
 
 impl Serialize for Sexp {
@@ -22,16 +28,34 @@ impl Serialize for Sexp {
             Sexp::Boolean(b) => serializer.serialize_bool(b),
             Sexp::Number(ref n) => n.serialize(serializer),
             Sexp::Atom(ref atom) => atom.serialize(serializer),
+            Sexp::Bytes(ref bytes) => {
+                serializer.serialize_newtype_struct(HEX_BYTES_TOKEN, &ByteSlice(bytes))
+            }
             Sexp::List(ref v) => v.serialize(serializer),
-            Sexp::Pair(_, _) => unimplemented!(),
-            // Sexp::Pair(Some(_), None) => unimplemented!(),
-            // Sexp::Pair(None, Some(_)) => unimplemented!(),
-            // Sexp::Pair(None, None)  => unimplemented!(),
+            Sexp::Pair(..) => {
+                let (car, cdr) = self.as_pair().unwrap();
+                serializer.serialize_newtype_struct(PAIR_TOKEN, &(car, cdr))
+            }
         }
     }
 }
 
-pub struct Serializer;
+/// Builds a `Sexp` from any `Serialize` value.
+#[derive(Clone, Copy)]
+pub struct Serializer {
+    /// Hold map keys to the string-key rule; see `ToValueOptions`.
+    pub(super) string_keys: bool,
+}
+
+// `serialize_str` guesses a kind of atom from the text and strips a keyword
+// prefix or quotes; this puts them back to recover the text it was given.
+fn atom_text(atom: Atom) -> String {
+    match atom {
+        Atom::Symbol(s) => s,
+        Atom::Keyword(k) => format!("#:{}", k),
+        Atom::String(s) => format!("\"{}\"", s),
+    }
+}
 
 impl serde::Serializer for Serializer {
     type Ok = Sexp;
@@ -109,12 +133,11 @@ impl serde::Serializer for Serializer {
 
     #[inline]
     fn serialize_str(self, value: &str) -> Result<Sexp, Error> {
-        Ok(Sexp::Atom(value.to_owned().into()))
+        Ok(Sexp::Atom(Atom::discriminate(value.to_owned())))
     }
 
     fn serialize_bytes(self, value: &[u8]) -> Result<Sexp, Error> {
-        let vec = value.iter().map(|&b| Sexp::Number(b.into())).collect();
-        Ok(Sexp::List(vec))
+        Ok(Sexp::Bytes(value.to_vec()))
     }
 
     #[inline]
@@ -140,26 +163,49 @@ impl serde::Serializer for Serializer {
     #[inline]
     fn serialize_newtype_struct<T: ?Sized>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<Sexp, Error>
     where
         T: Serialize,
     {
-        value.serialize(self)
+        match value.serialize(self)? {
+            // The text of a `RawSexp` is parsed into the value it stands for.
+            // `serialize_str` has guessed a kind of atom from the text, so the
+            // prefix or quotes it stripped are put back first.
+            Sexp::Atom(raw) if name == RAW_TOKEN => crate::from_str(&atom_text(raw)),
+            // Symbols and keywords keep their kind whatever the text looks
+            // like.
+            Sexp::Atom(atom) if name == SYMBOL_TOKEN => {
+                Ok(Sexp::Atom(Atom::Symbol(atom_text(atom))))
+            }
+            Sexp::Atom(atom) if name == KEYWORD_TOKEN => {
+                Ok(Sexp::Atom(Atom::Keyword(atom_text(atom))))
+            }
+            #[cfg(feature = "arbitrary_precision")]
+            Sexp::Atom(literal) if name == NUMBER_TOKEN => {
+                Number::from_literal_value(literal.into_string()).map(Sexp::Number)
+            }
+            Sexp::List(mut elems) if name == PAIR_TOKEN && elems.len() == 2 => {
+                let cdr = elems.pop().unwrap();
+                let car = elems.pop().unwrap();
+                Ok(Sexp::pair(car, cdr))
+            }
+            sexp => Ok(sexp),
+        }
     }
 
     fn serialize_newtype_variant<T: ?Sized>(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<Sexp, Error>
     where
         T: Serialize,
     {
-        unimplemented!()
+        Ok(Sexp::new_entry(variant, value.serialize(self)?))
     }
 
     #[inline]
@@ -177,6 +223,7 @@ impl serde::Serializer for Serializer {
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
         Ok(SerializeVec {
+            ser: self,
             vec: Vec::with_capacity(len.unwrap_or(0)),
         })
     }
@@ -201,13 +248,18 @@ impl serde::Serializer for Serializer {
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, Error> {
         Ok(SerializeTupleVariant {
+            ser: self,
             name: String::from(variant),
             vec: Vec::with_capacity(len),
         })
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
-        unimplemented!()
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(SerializeMap {
+            ser: self,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            next_key: None,
+        })
     }
 
     fn serialize_struct(
@@ -222,21 +274,26 @@ impl serde::Serializer for Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant, Error> {
-        unimplemented!()
+        Ok(SerializeStructVariant {
+            ser: self,
+            name: String::from(variant),
+            values: Vec::with_capacity(len),
+        })
     }
 }
 
 #[doc(hidden)]
 pub struct SerializeVec {
+    ser: Serializer,
     vec: Vec<Sexp>,
 }
 
 #[doc(hidden)]
-#[allow(dead_code)]
 pub struct SerializeTupleVariant {
+    ser: Serializer,
     name: String,
     vec: Vec<Sexp>,
 }
@@ -249,7 +306,7 @@ impl serde::ser::SerializeSeq for SerializeVec {
     where
         T: Serialize,
     {
-        self.vec.push(to_value(&value)?);
+        self.vec.push(value.serialize(self.ser)?);
         Ok(())
     }
 
@@ -298,18 +355,20 @@ impl serde::ser::SerializeTupleVariant for SerializeTupleVariant {
     where
         T: Serialize,
     {
-        self.vec.push(to_value(&value)?);
+        self.vec.push(value.serialize(self.ser)?);
         Ok(())
     }
 
     fn end(self) -> Result<Sexp, Error> {
-        unimplemented!()
+        Ok(Sexp::new_entry(self.name, Sexp::List(self.vec)))
     }
 }
 
 #[doc(hidden)]
 pub struct SerializeMap {
-    next_key: Option<String>,
+    ser: Serializer,
+    entries: Vec<Sexp>,
+    next_key: Option<Sexp>,
 }
 
 impl serde::ser::SerializeMap for SerializeMap {
@@ -320,29 +379,35 @@ impl serde::ser::SerializeMap for SerializeMap {
     where
         T: Serialize,
     {
-        match to_value(&key)? {
-            Sexp::Atom(a) => self.next_key = Some(a.as_string()),
-            Sexp::Number(n) => {
-                if n.is_u64() || n.is_i64() {
-                    self.next_key = Some(n.to_string())
-                } else {
-                    return Err(Error::syntax(ErrorCode::KeyMustBeAString, 0, 0));
-                }
+        let key = key.serialize(self.ser)?;
+        self.next_key = Some(match key {
+            // An alist's cars can be anything, so the key is kept as it is
+            // unless the caller asked for JSON's rule.
+            key if !self.ser.string_keys => key,
+            key @ Sexp::Atom(_) => key,
+            Sexp::Number(ref n) if n.is_u64() || n.is_i64() => {
+                Sexp::Atom(Atom::from(n.to_string()))
             }
             _ => return Err(Error::syntax(ErrorCode::KeyMustBeAString, 0, 0)),
-        };
+        });
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, _value: &T) -> Result<(), Error>
+    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
     where
         T: Serialize,
     {
-        unimplemented!()
+        let key = self
+            .next_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.entries
+            .push(Sexp::pair(key, value.serialize(self.ser)?));
+        Ok(())
     }
 
     fn end(self) -> Result<Sexp, Error> {
-        unimplemented!()
+        Ok(Sexp::List(self.entries))
     }
 }
 
@@ -365,6 +430,7 @@ impl serde::ser::SerializeStruct for SerializeMap {
 
 #[doc(hidden)]
 pub struct SerializeStructVariant {
+    ser: Serializer,
     name: String,
     values: Vec<Sexp>,
 }
@@ -377,10 +443,8 @@ impl serde::ser::SerializeStructVariant for SerializeStructVariant {
     where
         T: Serialize,
     {
-        self.values.push(Sexp::new_entry(
-            key,
-            to_value(&value).ok().unwrap_or(Sexp::Nil),
-        ));
+        self.values
+            .push(Sexp::new_entry(key, value.serialize(self.ser)?));
         Ok(())
     }
 
@@ -0,0 +1,139 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A `Sexp` that remembers where in the input each of its values was read.
+
+use crate::atom::Atom;
+use crate::de::Span;
+use crate::number::Number;
+use crate::sexp::Sexp;
+
+/// A value read by `sexpr::from_str_spanned`, together with the part of the
+/// input it was read from. The elements of a list carry their own spans.
+///
+/// ```rust
+/// use sexpr::sexp::SpannedValue;
+///
+/// let input = "(define x\n  (f 1))";
+/// let form = sexpr::from_str_spanned(input).unwrap();
+///
+/// if let SpannedValue::List(ref elems) = form.value {
+///     let call = &elems[2];
+///     assert_eq!(&input[call.span.start..call.span.end], "(f 1)");
+///     assert_eq!((call.span.line, call.span.column), (2, 3));
+/// }
+/// assert_eq!(form.into_sexp(), sexpr::from_str(input).unwrap());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpannedSexp {
+    /// Where the value was read, from its first byte to just past its last.
+    pub span: Span,
+    /// The value.
+    pub value: SpannedValue,
+}
+
+/// The value of a `SpannedSexp`, with the same kinds as a `Sexp` read from
+/// text.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum SpannedValue {
+    /// `#nil`.
+    Nil,
+    /// `#t` or `#f`.
+    Boolean(bool),
+    /// A number.
+    Number(Number),
+    /// A symbol, keyword or string.
+    Atom(Atom),
+    /// A byte string.
+    Bytes(Vec<u8>),
+    /// A list, whose elements have their own spans.
+    List(Vec<SpannedSexp>),
+    /// A dotted list such as `(a b . c)`: the elements before the dot and
+    /// the tail after it, each with its own span. It stands for the pairs
+    /// `Sexp::pair(a, Sexp::pair(b, c))`.
+    DottedList(Vec<SpannedSexp>, Box<SpannedSexp>),
+}
+
+impl SpannedSexp {
+    /// Converts to a plain `Sexp`, dropping the spans.
+    pub fn into_sexp(self) -> Sexp {
+        match self.value {
+            SpannedValue::Nil => Sexp::Nil,
+            SpannedValue::Boolean(b) => Sexp::Boolean(b),
+            SpannedValue::Number(n) => Sexp::Number(n),
+            SpannedValue::Atom(atom) => Sexp::Atom(atom),
+            SpannedValue::Bytes(bytes) => Sexp::Bytes(bytes),
+            SpannedValue::List(elems) => {
+                Sexp::List(elems.into_iter().map(SpannedSexp::into_sexp).collect())
+            }
+            SpannedValue::DottedList(elems, tail) => {
+                elems.into_iter().rev().fold(tail.into_sexp(), |cdr, car| {
+                    Sexp::pair(car.into_sexp(), cdr)
+                })
+            }
+        }
+    }
+
+    /// Copies into a plain `Sexp`, dropping the spans.
+    pub fn to_sexp(&self) -> Sexp {
+        self.clone().into_sexp()
+    }
+
+    /// The innermost value whose span holds the byte at `offset`, such as
+    /// the value under the cursor in an editor. Returns `None` if `offset`
+    /// is outside this value.
+    pub fn find(&self, offset: usize) -> Option<&SpannedSexp> {
+        if offset < self.span.start || offset >= self.span.end {
+            return None;
+        }
+        let inner = match self.value {
+            SpannedValue::List(ref elems) => elems.iter().find_map(|elem| elem.find(offset)),
+            SpannedValue::DottedList(ref elems, ref tail) => elems
+                .iter()
+                .find_map(|elem| elem.find(offset))
+                .or_else(|| tail.find(offset)),
+            _ => None,
+        };
+        inner.or(Some(self))
+    }
+
+    /// Makes a value from a `Sexp` read without spans. Lists are read with
+    /// spans for their elements, so any list here gives its elements the
+    /// span of the whole, and a pair becomes a dotted list of its car and
+    /// cdr.
+    pub(crate) fn leaf(sexp: Sexp, span: Span) -> SpannedSexp {
+        let value = match sexp {
+            Sexp::Nil => SpannedValue::Nil,
+            Sexp::Boolean(b) => SpannedValue::Boolean(b),
+            Sexp::Number(n) => SpannedValue::Number(n),
+            Sexp::Atom(atom) => SpannedValue::Atom(atom),
+            Sexp::Bytes(bytes) => SpannedValue::Bytes(bytes),
+            Sexp::List(elems) => SpannedValue::List(
+                elems
+                    .into_iter()
+                    .map(|elem| SpannedSexp::leaf(elem, span))
+                    .collect(),
+            ),
+            pair @ Sexp::Pair(..) => {
+                let (car, cdr) = pair.into_pair().unwrap_or((Sexp::Nil, Sexp::Nil));
+                SpannedValue::DottedList(
+                    vec![SpannedSexp::leaf(car, span)],
+                    Box::new(SpannedSexp::leaf(cdr, span)),
+                )
+            }
+        };
+        SpannedSexp { span, value }
+    }
+}
+
+impl From<SpannedSexp> for Sexp {
+    fn from(spanned: SpannedSexp) -> Sexp {
+        spanned.into_sexp()
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Building a `Sexp` by filling the variables of a template.
+
+use crate::de;
+use crate::error::{Error, ErrorCode, Result};
+use crate::sexp::pattern::{invalid, quote_vars, var, Var};
+use crate::sexp::{Bindings, Sexp};
+
+/// A `Sexp` with holes, written with the same variables as a `Pattern`, that
+/// builds values by filling the holes from `Bindings`.
+///
+/// - `?name` is replaced by the value bound to `name`.
+/// - `??name`, as an element of a list, is replaced by the elements of the
+///   list bound to `name`. A value that is not a list is inserted as a
+///   single element.
+///
+/// The wildcards `?_` and `??_` have nothing to fill them and are rejected.
+///
+/// ```rust
+/// use sexpr::sexp::{Atom, Bindings, Sexp, Template};
+///
+/// let template = Template::parse("(greet ?name at ?time)").unwrap();
+/// let mut bindings = Bindings::new();
+/// bindings.insert("name", Sexp::Atom(Atom::string("Ada")));
+/// bindings.insert("time", 9);
+///
+/// let reply = template.instantiate(&bindings).unwrap();
+/// assert_eq!(sexpr::to_string(&reply).unwrap(), "(greet \"Ada\" at 9)");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    sexp: Sexp,
+}
+
+impl Template {
+    /// Parses a template from S-expression text. As with `Pattern::parse`,
+    /// words starting with `?` are read as variables.
+    pub fn parse(text: &str) -> Result<Template> {
+        Template::new(de::from_str(&quote_vars(text))?)
+    }
+
+    /// Makes a template from a `Sexp` whose variables are symbols starting
+    /// with `?`.
+    pub fn new(sexp: Sexp) -> Result<Template> {
+        check(&sexp, false)?;
+        Ok(Template { sexp })
+    }
+
+    /// The template as a `Sexp`.
+    pub fn as_sexp(&self) -> &Sexp {
+        &self.sexp
+    }
+
+    /// Builds a value by replacing every variable with its binding. Fails
+    /// with `MissingVariable` if a variable is not bound.
+    ///
+    /// Matching a value with a `Pattern` and instantiating a template with
+    /// the same variables rewrites one form into another:
+    ///
+    /// ```rust
+    /// use sexpr::sexp::{Pattern, Template};
+    ///
+    /// let pattern = Pattern::parse("(unless ?test ??body)").unwrap();
+    /// let template = Template::parse("(if ?test () (begin ??body))").unwrap();
+    ///
+    /// let form = sexpr::from_str("(unless done (step) (log))").unwrap();
+    /// let bindings = pattern.matches(&form).unwrap();
+    /// let rewritten = template.instantiate(&bindings).unwrap();
+    /// assert_eq!(rewritten, sexpr::from_str("(if done () (begin (step) (log)))").unwrap());
+    /// ```
+    pub fn instantiate(&self, bindings: &Bindings) -> Result<Sexp> {
+        fill(&self.sexp, bindings)
+    }
+}
+
+/// Checks that the template has no wildcards and that segment variables
+/// appear only as list elements.
+fn check(sexp: &Sexp, in_list: bool) -> Result<()> {
+    match *sexp {
+        Sexp::List(ref elems) => elems.iter().try_for_each(|elem| check(elem, true)),
+        Sexp::Pair(..) => {
+            let (car, cdr) = sexp.as_pair().unwrap();
+            check(car, false)?;
+            check(cdr, false)
+        }
+        _ => match var(sexp) {
+            Some(Var::One(None)) | Some(Var::Segment(None)) => {
+                Err(invalid("a template has a wildcard"))
+            }
+            Some(Var::Segment(_)) if !in_list => {
+                Err(invalid("a segment variable is not in a list"))
+            }
+            _ => Ok(()),
+        },
+    }
+}
+
+fn fill(sexp: &Sexp, bindings: &Bindings) -> Result<Sexp> {
+    if let Some(Var::One(Some(name))) = var(sexp) {
+        return lookup(bindings, name).cloned();
+    }
+    match *sexp {
+        Sexp::List(ref elems) => {
+            let mut filled = Vec::with_capacity(elems.len());
+            for elem in elems {
+                match var(elem) {
+                    Some(Var::Segment(Some(name))) => match *lookup(bindings, name)? {
+                        Sexp::List(ref spliced) => filled.extend(spliced.iter().cloned()),
+                        ref value => filled.push(value.clone()),
+                    },
+                    _ => filled.push(fill(elem, bindings)?),
+                }
+            }
+            Ok(Sexp::List(filled))
+        }
+        Sexp::Pair(..) => {
+            let (car, cdr) = sexp.as_pair().unwrap();
+            Ok(Sexp::pair(fill(car, bindings)?, fill(cdr, bindings)?))
+        }
+        _ => Ok(sexp.clone()),
+    }
+}
+
+fn lookup<'a>(bindings: &'a Bindings, name: &str) -> Result<&'a Sexp> {
+    bindings
+        .get(name)
+        .ok_or_else(|| Error::syntax(ErrorCode::MissingVariable(name.to_owned()), 0, 0))
+}
@@ -0,0 +1,118 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rendering a `Sexp` as Rust source code that constructs it.
+
+use std::fmt::Write;
+
+use crate::atom::Atom;
+use crate::sexp::Sexp;
+
+impl Sexp {
+    /// Returns a Rust expression that evaluates to this value.
+    ///
+    /// This is meant for build scripts: a spec file can be parsed once at
+    /// build time and the expression written out to be `include!`d, so that
+    /// the program builds the value directly instead of parsing text when it
+    /// runs. Paths in the expression start with `::sexpr`.
+    ///
+    /// ```rust
+    /// # use sexpr::Sexp;
+    /// let spec: Sexp = sexpr::from_str("(port 80)").unwrap();
+    /// assert_eq!(
+    ///     spec.to_rust_expr(),
+    ///     "::sexpr::Sexp::List(::std::vec![\
+    ///      ::sexpr::Sexp::Atom(::sexpr::sexp::Atom::symbol(\"port\")), \
+    ///      ::sexpr::Sexp::Number(::sexpr::Number::from(80u64))])"
+    /// );
+    /// ```
+    ///
+    /// A build script would write the expression to a file in `OUT_DIR`:
+    ///
+    /// ```rust,ignore
+    /// // build.rs
+    /// let spec: sexpr::Sexp = sexpr::from_str(&fs::read_to_string("spec.scm")?)?;
+    /// let out = Path::new(&env::var("OUT_DIR")?).join("spec.rs");
+    /// fs::write(out, spec.to_rust_expr())?;
+    ///
+    /// // src/main.rs
+    /// fn spec() -> sexpr::Sexp {
+    ///     include!(concat!(env!("OUT_DIR"), "/spec.rs"))
+    /// }
+    /// ```
+    pub fn to_rust_expr(&self) -> String {
+        let mut out = String::new();
+        write_expr(&mut out, self);
+        out
+    }
+}
+
+fn write_expr(out: &mut String, sexp: &Sexp) {
+    match *sexp {
+        Sexp::Nil => out.push_str("::sexpr::Sexp::Nil"),
+        Sexp::Boolean(b) => {
+            let _ = write!(out, "::sexpr::Sexp::Boolean({})", b);
+        }
+        Sexp::Number(ref n) => {
+            out.push_str("::sexpr::Sexp::Number(");
+            if let Some(u) = n.as_u64() {
+                let _ = write!(out, "::sexpr::Number::from({}u64)", u);
+            } else if let Some(i) = n.as_i64() {
+                let _ = write!(out, "::sexpr::Number::from({}i64)", i);
+            } else if let Some(f) = n.as_f64() {
+                // Numbers hold only finite floats, whose `Debug` form is a
+                // valid float literal that reads back as the same value.
+                let _ = write!(out, "::sexpr::Number::from_f64({:?}f64).unwrap()", f);
+            }
+            out.push(')');
+        }
+        Sexp::Atom(ref atom) => {
+            let (constructor, text) = match *atom {
+                Atom::Symbol(ref s) => ("symbol", s),
+                Atom::Keyword(ref s) => ("keyword", s),
+                Atom::String(ref s) => ("string", s),
+            };
+            let _ = write!(
+                out,
+                "::sexpr::Sexp::Atom(::sexpr::sexp::Atom::{}({:?}))",
+                constructor, text
+            );
+        }
+        Sexp::Bytes(ref bytes) => {
+            let _ = write!(out, "::sexpr::Sexp::Bytes(::std::vec!{:?})", bytes);
+        }
+        Sexp::Pair(ref car, ref cdr) => {
+            out.push_str("::sexpr::Sexp::Pair(");
+            write_cell(out, car);
+            out.push_str(", ");
+            write_cell(out, cdr);
+            out.push(')');
+        }
+        Sexp::List(ref elems) => {
+            out.push_str("::sexpr::Sexp::List(::std::vec![");
+            for (i, elem) in elems.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_expr(out, elem);
+            }
+            out.push_str("])");
+        }
+    }
+}
+
+fn write_cell(out: &mut String, cell: &Option<Box<Sexp>>) {
+    match *cell {
+        Some(ref sexp) => {
+            out.push_str("::std::option::Option::Some(::std::boxed::Box::new(");
+            write_expr(out, sexp);
+            out.push_str("))");
+        }
+        None => out.push_str("::std::option::Option::None"),
+    }
+}
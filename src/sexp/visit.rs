@@ -0,0 +1,312 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Traversing and rebuilding `Sexp` trees.
+//!
+//! `SexpVisitor` walks a tree by reference and `SexpFolder` consumes one and
+//! builds a new one. Both have a method per kind of value whose default
+//! recurses into lists and pairs, so an implementation overrides only the
+//! kinds it cares about. The `walk_*` and `fold_*` functions hold the default
+//! behaviour, for an override that wants to recurse as well.
+//!
+//! ```rust
+//! use sexpr::sexp::visit::{self, SexpFolder, SexpVisitor};
+//! use sexpr::sexp::Atom;
+//! use sexpr::Sexp;
+//!
+//! /// Counts the lists in a tree.
+//! struct Lists(usize);
+//!
+//! impl SexpVisitor for Lists {
+//!     fn visit_list(&mut self, elems: &[Sexp]) {
+//!         self.0 += 1;
+//!         visit::walk_list(self, elems);
+//!     }
+//! }
+//!
+//! /// Replaces `(quote x)` with the string `"x"`.
+//! struct Unquote;
+//!
+//! impl SexpFolder for Unquote {
+//!     fn fold_list(&mut self, elems: Vec<Sexp>) -> Sexp {
+//!         match elems.as_slice() {
+//!             [Sexp::Atom(Atom::Symbol(head)), Sexp::Atom(Atom::Symbol(name))] if head == "quote" => {
+//!                 Sexp::Atom(Atom::string(name.as_str()))
+//!             }
+//!             _ => visit::fold_list(self, elems),
+//!         }
+//!     }
+//! }
+//!
+//! let program: Sexp = sexpr::from_str("(print (quote hello) (list 1 2))").unwrap();
+//!
+//! let mut lists = Lists(0);
+//! lists.visit_sexp(&program);
+//! assert_eq!(lists.0, 3);
+//!
+//! let folded = Unquote.fold_sexp(program);
+//! assert_eq!(folded, sexpr::from_str("(print \"hello\" (list 1 2))").unwrap());
+//! ```
+
+use crate::atom::Atom;
+use crate::number::Number;
+use crate::sexp::Sexp;
+
+/// Walks a `Sexp` tree by reference.
+///
+/// Every method does nothing by default, except that lists and pairs visit
+/// their elements in order.
+pub trait SexpVisitor {
+    /// Visits any value, dispatching on its kind.
+    fn visit_sexp(&mut self, sexp: &Sexp) {
+        walk_sexp(self, sexp)
+    }
+
+    /// Visits `#nil`.
+    fn visit_nil(&mut self) {}
+
+    /// Visits `#t` or `#f`.
+    fn visit_bool(&mut self, _b: bool) {}
+
+    /// Visits a number.
+    fn visit_number(&mut self, _n: &Number) {}
+
+    /// Visits a symbol, keyword or string.
+    fn visit_atom(&mut self, _atom: &Atom) {}
+
+    /// Visits a byte string.
+    fn visit_bytes(&mut self, _bytes: &[u8]) {}
+
+    /// Visits a list, then each of its elements.
+    fn visit_list(&mut self, elems: &[Sexp]) {
+        walk_list(self, elems)
+    }
+
+    /// Visits a pair, then its car and its cdr. An empty cell is visited as
+    /// `#nil`.
+    fn visit_pair(&mut self, car: &Sexp, cdr: &Sexp) {
+        walk_pair(self, car, cdr)
+    }
+}
+
+/// Calls the `visitor` method for the kind of `sexp`.
+pub fn walk_sexp<V: SexpVisitor + ?Sized>(visitor: &mut V, sexp: &Sexp) {
+    match *sexp {
+        Sexp::Nil => visitor.visit_nil(),
+        Sexp::Boolean(b) => visitor.visit_bool(b),
+        Sexp::Number(ref n) => visitor.visit_number(n),
+        Sexp::Atom(ref atom) => visitor.visit_atom(atom),
+        Sexp::Bytes(ref bytes) => visitor.visit_bytes(bytes),
+        Sexp::List(ref elems) => visitor.visit_list(elems),
+        Sexp::Pair(..) => {
+            let (car, cdr) = sexp.as_pair().unwrap();
+            visitor.visit_pair(car, cdr)
+        }
+    }
+}
+
+/// Visits each element of a list in order.
+pub fn walk_list<V: SexpVisitor + ?Sized>(visitor: &mut V, elems: &[Sexp]) {
+    for elem in elems {
+        visitor.visit_sexp(elem);
+    }
+}
+
+/// Visits the car and then the cdr of a pair.
+pub fn walk_pair<V: SexpVisitor + ?Sized>(visitor: &mut V, car: &Sexp, cdr: &Sexp) {
+    visitor.visit_sexp(car);
+    visitor.visit_sexp(cdr);
+}
+
+/// Consumes a `Sexp` tree and builds a new one.
+///
+/// Every method returns its value unchanged by default, except that lists
+/// and pairs are rebuilt from their folded elements. A method may return a
+/// value of another kind, such as a list in place of a symbol.
+pub trait SexpFolder {
+    /// Folds any value, dispatching on its kind.
+    fn fold_sexp(&mut self, sexp: Sexp) -> Sexp {
+        fold_sexp(self, sexp)
+    }
+
+    /// Folds `#nil`.
+    fn fold_nil(&mut self) -> Sexp {
+        Sexp::Nil
+    }
+
+    /// Folds `#t` or `#f`.
+    fn fold_bool(&mut self, b: bool) -> Sexp {
+        Sexp::Boolean(b)
+    }
+
+    /// Folds a number.
+    fn fold_number(&mut self, n: Number) -> Sexp {
+        Sexp::Number(n)
+    }
+
+    /// Folds a symbol, keyword or string.
+    fn fold_atom(&mut self, atom: Atom) -> Sexp {
+        Sexp::Atom(atom)
+    }
+
+    /// Folds a byte string.
+    fn fold_bytes(&mut self, bytes: Vec<u8>) -> Sexp {
+        Sexp::Bytes(bytes)
+    }
+
+    /// Folds a list by folding each of its elements.
+    fn fold_list(&mut self, elems: Vec<Sexp>) -> Sexp {
+        fold_list(self, elems)
+    }
+
+    /// Folds a pair by folding its car and its cdr. An empty cell is folded
+    /// as `#nil`.
+    fn fold_pair(&mut self, car: Sexp, cdr: Sexp) -> Sexp {
+        fold_pair(self, car, cdr)
+    }
+}
+
+/// Calls the `folder` method for the kind of `sexp`.
+pub fn fold_sexp<F: SexpFolder + ?Sized>(folder: &mut F, sexp: Sexp) -> Sexp {
+    match sexp {
+        Sexp::Nil => folder.fold_nil(),
+        Sexp::Boolean(b) => folder.fold_bool(b),
+        Sexp::Number(n) => folder.fold_number(n),
+        Sexp::Atom(atom) => folder.fold_atom(atom),
+        Sexp::Bytes(bytes) => folder.fold_bytes(bytes),
+        Sexp::List(elems) => folder.fold_list(elems),
+        pair @ Sexp::Pair(..) => {
+            let (car, cdr) = pair.into_pair().unwrap();
+            folder.fold_pair(car, cdr)
+        }
+    }
+}
+
+/// Rebuilds a list from its folded elements.
+pub fn fold_list<F: SexpFolder + ?Sized>(folder: &mut F, elems: Vec<Sexp>) -> Sexp {
+    Sexp::List(
+        elems
+            .into_iter()
+            .map(|elem| folder.fold_sexp(elem))
+            .collect(),
+    )
+}
+
+/// Rebuilds a pair from its folded car and cdr.
+pub fn fold_pair<F: SexpFolder + ?Sized>(folder: &mut F, car: Sexp, cdr: Sexp) -> Sexp {
+    let car = folder.fold_sexp(car);
+    let cdr = folder.fold_sexp(cdr);
+    Sexp::pair(car, cdr)
+}
+
+struct MapAtoms<F>(F);
+
+impl<F: FnMut(Atom) -> Sexp> SexpFolder for MapAtoms<F> {
+    fn fold_atom(&mut self, atom: Atom) -> Sexp {
+        (self.0)(atom)
+    }
+}
+
+impl Sexp {
+    /// Replaces every symbol, keyword and string in the tree with the value
+    /// `f` returns for it.
+    ///
+    /// ```rust
+    /// use sexpr::sexp::Atom;
+    /// use sexpr::Sexp;
+    ///
+    /// let v: Sexp = sexpr::from_str("(a \"b\" (#:c))").unwrap();
+    /// let upper = v.map_atoms(|atom| match atom {
+    ///     Atom::String(s) => Sexp::Atom(Atom::String(s.to_uppercase())),
+    ///     atom => Sexp::Atom(atom),
+    /// });
+    /// assert_eq!(upper, sexpr::from_str("(a \"B\" (#:c))").unwrap());
+    /// ```
+    pub fn map_atoms<F>(self, f: F) -> Sexp
+    where
+        F: FnMut(Atom) -> Sexp,
+    {
+        MapAtoms(f).fold_sexp(self)
+    }
+
+    /// Replaces every symbol in the tree with the value `f` returns for its
+    /// name. Keywords and strings are left alone.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// let v: Sexp = sexpr::from_str("(add x (mul x \"x\"))").unwrap();
+    /// let substituted = v.map_symbols(|name| match name.as_str() {
+    ///     "x" => Sexp::from(2),
+    ///     _ => Sexp::from(name),
+    /// });
+    /// assert_eq!(substituted, sexpr::from_str("(add 2 (mul 2 \"x\"))").unwrap());
+    /// ```
+    pub fn map_symbols<F>(self, mut f: F) -> Sexp
+    where
+        F: FnMut(String) -> Sexp,
+    {
+        self.map_atoms(|atom| match atom {
+            Atom::Symbol(name) => f(name),
+            atom => Sexp::Atom(atom),
+        })
+    }
+
+    /// Calls `f` on every value in the tree, children before the list or
+    /// pair holding them, so `f` sees lists whose elements are already
+    /// rewritten.
+    ///
+    /// ```rust
+    /// use sexpr::Sexp;
+    ///
+    /// // Flatten nested `(begin ...)` forms.
+    /// let mut v: Sexp = sexpr::from_str("(begin a (begin b (begin c)))").unwrap();
+    /// v.rewrite(|sexp| {
+    ///     if let Sexp::List(elems) = sexp {
+    ///         if elems.first() == Some(&Sexp::from("begin")) {
+    ///             let mut flat = Vec::new();
+    ///             for elem in elems.drain(..) {
+    ///                 match elem {
+    ///                     Sexp::List(ref inner) if inner.first() == Some(&Sexp::from("begin")) => {
+    ///                         flat.extend(inner[1..].iter().cloned())
+    ///                     }
+    ///                     elem => flat.push(elem),
+    ///                 }
+    ///             }
+    ///             *elems = flat;
+    ///         }
+    ///     }
+    /// });
+    /// assert_eq!(v, sexpr::from_str("(begin a b c)").unwrap());
+    /// ```
+    pub fn rewrite<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Sexp),
+    {
+        rewrite(self, &mut f)
+    }
+}
+
+fn rewrite<F: FnMut(&mut Sexp)>(sexp: &mut Sexp, f: &mut F) {
+    match *sexp {
+        Sexp::List(ref mut elems) => {
+            for elem in elems {
+                rewrite(elem, f);
+            }
+        }
+        Sexp::Pair(ref mut car, ref mut cdr) => {
+            for cell in [car, cdr] {
+                if let Some(ref mut inner) = *cell {
+                    rewrite(inner, f);
+                }
+            }
+        }
+        _ => {}
+    }
+    f(sexp)
+}
@@ -0,0 +1,78 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Writing any of the value trees as S-expression text.
+
+use std::io;
+
+use serde::ser::Serialize;
+
+use crate::error::Result;
+use crate::ser;
+use crate::sexp::{Sexp, SexpRc, SexpRef, SharedSexp};
+
+/// A tree of S-expression values that can be written as text: `Sexp`, the
+/// shared `SexpRc` and `SharedSexp`, or the borrowed `SexpRef`.
+///
+/// Each is written straight from its own nodes, so a shared tree is never
+/// copied into a `Sexp` first, and the text is the same as `sexpr::to_writer`
+/// writes for the equal `Sexp`. Code that may be handed any of them can take
+/// a `&impl WriteSexp`.
+///
+/// This trait is sealed and cannot be implemented for types outside of
+/// `sexpr`.
+///
+/// ```rust
+/// use sexpr::sexp::{Interner, Sexp, SexpRc, WriteSexp};
+///
+/// fn save(tree: &impl WriteSexp) -> String {
+///     let mut out = Vec::new();
+///     tree.to_writer(&mut out).unwrap();
+///     String::from_utf8(out).unwrap()
+/// }
+///
+/// let text = "((b c) (a (b c)))";
+/// let sexp: Sexp = sexpr::from_str(text).unwrap();
+/// assert_eq!(save(&sexp), text);
+/// assert_eq!(save(&SexpRc::from(&sexp)), text);
+/// assert_eq!(save(&*Interner::new().intern(&sexp)), text);
+/// ```
+pub trait WriteSexp: Serialize + private::Sealed {
+    /// Writes the value as compact S-expression text, as `sexpr::to_writer`
+    /// does.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the writer fails.
+    fn to_writer<W: io::Write>(&self, writer: W) -> Result<()> {
+        ser::to_writer(writer, self)
+    }
+
+    /// Writes the value as pretty-printed S-expression text, as
+    /// `sexpr::ser::to_writer_pretty` does.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the writer fails.
+    fn to_writer_pretty<W: io::Write>(&self, writer: W) -> Result<()> {
+        ser::to_writer_pretty(writer, self)
+    }
+}
+
+impl WriteSexp for Sexp {}
+impl WriteSexp for SexpRc {}
+impl WriteSexp for SharedSexp {}
+impl<'a> WriteSexp for SexpRef<'a> {}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for super::Sexp {}
+    impl Sealed for super::SexpRc {}
+    impl Sealed for super::SharedSexp {}
+    impl<'a> Sealed for super::SexpRef<'a> {}
+}
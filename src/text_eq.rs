@@ -0,0 +1,103 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Comparing S-expression text by structure rather than by characters.
+
+use serde::de::Deserialize;
+
+use crate::atom::Atom;
+use crate::de::Deserializer;
+use crate::error::Result;
+use crate::sexp::Sexp;
+
+/// Options loosening how `text_eq_with_options` compares two values.
+///
+/// The default options compare values exactly, ignoring only layout.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextEqOptions {
+    /// Treat symbols and keywords that differ only in ASCII case as equal.
+    /// Strings are still compared exactly.
+    pub case_insensitive_symbols: bool,
+
+    /// Treat numbers with the same value as equal, so that `1` matches `1.0`.
+    pub numeric: bool,
+}
+
+/// Parses two S-expression texts and decides whether they hold the same
+/// values, ignoring whitespace, comments and how the values are laid out.
+///
+/// Each text may hold any number of values, which are compared in order. An
+/// error is returned if either text does not parse.
+///
+/// ```rust
+/// assert!(sexpr::text_eq("(define (f x)\n  (g x))", "(define (f x) (g x)) ; f").unwrap());
+/// assert!(!sexpr::text_eq("(a b)", "(a \"b\")").unwrap());
+/// ```
+pub fn text_eq(a: &str, b: &str) -> Result<bool> {
+    text_eq_with_options(a, b, &TextEqOptions::default())
+}
+
+/// Like `text_eq`, but compares symbols and numbers as described by
+/// `options`.
+///
+/// ```rust
+/// use sexpr::TextEqOptions;
+///
+/// let options = TextEqOptions {
+///     case_insensitive_symbols: true,
+///     numeric: true,
+/// };
+/// assert!(sexpr::text_eq_with_options("(Scale 2)", "(scale 2.0)", &options).unwrap());
+/// ```
+pub fn text_eq_with_options(a: &str, b: &str, options: &TextEqOptions) -> Result<bool> {
+    let a = parse_all(a)?;
+    let b = parse_all(b)?;
+    Ok(a.len() == b.len() && a.iter().zip(&b).all(|(a, b)| sexp_eq(a, b, options)))
+}
+
+fn parse_all(input: &str) -> Result<Vec<Sexp>> {
+    let mut de = Deserializer::from_str(input);
+    let mut values = Vec::new();
+    while de.end().is_err() {
+        values.push(Sexp::deserialize(&mut de)?);
+    }
+    Ok(values)
+}
+
+fn sexp_eq(a: &Sexp, b: &Sexp, options: &TextEqOptions) -> bool {
+    match (a, b) {
+        (Sexp::Number(a), Sexp::Number(b)) if options.numeric => a.eq_numeric(b),
+        (Sexp::Atom(a), Sexp::Atom(b)) if options.case_insensitive_symbols => {
+            atom_eq_ignore_case(a, b)
+        }
+        (Sexp::List(a), Sexp::List(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| sexp_eq(a, b, options))
+        }
+        (Sexp::Pair(a_car, a_cdr), Sexp::Pair(b_car, b_cdr)) => {
+            cell_eq(a_car, b_car, options) && cell_eq(a_cdr, b_cdr, options)
+        }
+        _ => a == b,
+    }
+}
+
+fn cell_eq(a: &Option<Box<Sexp>>, b: &Option<Box<Sexp>>, options: &TextEqOptions) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => sexp_eq(a, b, options),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn atom_eq_ignore_case(a: &Atom, b: &Atom) -> bool {
+    match (a, b) {
+        (Atom::Symbol(a), Atom::Symbol(b)) | (Atom::Keyword(a), Atom::Keyword(b)) => {
+            a.eq_ignore_ascii_case(b)
+        }
+        _ => a == b,
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2017 Zephyr Pellerin
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Samples of S-expressions as other programs write them, in
+//! `tests/corpus/`.
+//!
+//! Each sample is read with the dialect options it needs and checked against
+//! the `debug_pretty` rendering of its values in the `.tree` file next to
+//! it. Its values are then written out and read back to check that they
+//! come back the same. A sample that needs options must not read the same
+//! without them, so that the corpus shows which options each dialect needs.
+//!
+//! Run the tests with `SEXPR_BLESS=1` set to write the `.tree` files afresh
+//! after a deliberate change, and review the difference.
+
+use std::fs;
+use std::path::Path;
+
+use sexpr::de::{Deserializer, DeserializerOptions};
+use sexpr::Sexp;
+
+fn read_all(text: &str, options: DeserializerOptions) -> sexpr::Result<Vec<Sexp>> {
+    Deserializer::from_str_with_options(text, options)
+        .into_iter()
+        .collect()
+}
+
+fn check_sample(name: &str, options: DeserializerOptions) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let text = fs::read_to_string(dir.join(name)).unwrap();
+    let values = read_all(&text, options.clone()).unwrap_or_else(|err| panic!("{}: {}", name, err));
+
+    let tree: Vec<String> = values.iter().map(Sexp::debug_pretty).collect();
+    let tree = tree.join("\n\n") + "\n";
+    let tree_path = dir.join(name).with_extension("tree");
+    if std::env::var_os("SEXPR_BLESS").is_some() {
+        fs::write(&tree_path, &tree).unwrap();
+    }
+    let expected = fs::read_to_string(&tree_path).unwrap();
+    assert!(
+        tree == expected,
+        "{} does not match {}",
+        name,
+        tree_path.display()
+    );
+
+    for value in &values {
+        let compact = sexpr::to_string(value).unwrap();
+        assert_eq!(
+            read_all(&compact, options.clone()).unwrap(),
+            [value.clone()],
+            "{} written as {}",
+            name,
+            compact
+        );
+        let pretty = sexpr::ser::to_string_pretty(value).unwrap();
+        assert_eq!(
+            read_all(&pretty, options.clone()).unwrap(),
+            [value.clone()],
+            "{} written as {}",
+            name,
+            pretty
+        );
+    }
+
+    if options != DeserializerOptions::default() {
+        if let Ok(plain) = read_all(&text, DeserializerOptions::default()) {
+            assert_ne!(plain, values, "{} does not need its options", name);
+        }
+    }
+}
+
+#[test]
+fn test_corpus_guix_package() {
+    check_sample("guix-hello.scm", DeserializerOptions::default());
+}
+
+#[test]
+fn test_corpus_kicad_board() {
+    check_sample("kicad-board.kicad_pcb", DeserializerOptions::default());
+}
+
+#[test]
+fn test_corpus_emacs_printed_data() {
+    check_sample(
+        "emacs-tramp-cache.el",
+        DeserializerOptions {
+            square_brackets: true,
+            nil_symbol: true,
+            ..DeserializerOptions::default()
+        },
+    );
+}
+
+#[test]
+fn test_corpus_smtlib_script() {
+    check_sample("smtlib-uf.smt2", DeserializerOptions::default());
+}
+
+/// Hex transport tokens such as `#03#` read as bytes. Base64 tokens such as
+/// `|Ut3vE9vfeHs8S8AJwT4V9Q==|` are not told apart from symbols written
+/// between bars, so a `Sexp` holds them as symbols with the base64 text as
+/// their name; only a byte target such as `Base64Bytes` decodes them.
+#[test]
+fn test_corpus_spki_certificate() {
+    check_sample(
+        "spki-cert.spki",
+        DeserializerOptions {
+            hex_escapes: true,
+            ..DeserializerOptions::default()
+        },
+    );
+
+    let digest: sexpr::Base64Bytes = sexpr::from_str("|Ut3vE9vfeHs8S8AJwT4V9Q==|").unwrap();
+    assert_eq!(digest.len(), 16);
+}
@@ -0,0 +1,15 @@
+;; -*- emacs-lisp -*- <14/03/04 09:12:44 /home/user/.emacs.d/tramp>
+;; Tramp connection history.  Don't change this file.
+;; You can delete it, forcing Tramp to reapply the checks.
+
+((["ssh" "user" "build.example.org" nil nil]
+  ("uname" "Linux 6.1.0-18-amd64")
+  ("test" "test")
+  ("perl-file-spec" t)
+  ("perl-integer" t)
+  ("file-exists" "test -e")
+  ("stat" nil)
+  ("remote-path"
+   ("/bin" "/usr/bin" "/usr/local/bin")))
+ (["ssh" "user" "build.example.org" nil nil]
+  ("tramp-version" "2.2.9-24.4")))
@@ -0,0 +1,22 @@
+;;; GNU Guix --- Functional package management for GNU
+;;; A package definition in the style of gnu/packages/base.scm.
+
+(define-public hello
+  (package
+    (name "hello")
+    (version "2.12.1")
+    (source (origin
+              (method url-fetch)
+              (uri (string-append "mirror://gnu/hello/hello-" version
+                                  ".tar.gz"))
+              (sha256
+               (base32
+                "086vqwk2wl8zfs47sq2xpjc9k066ilmb8z6dn0q6ymwjzlm196cd"))))
+    (build-system gnu-build-system)
+    (arguments (list #:tests? #f
+                     #:parallel-build? #t))
+    (synopsis "Example GNU package")
+    (description
+     "GNU Hello prints the message \"Hello, world!\" and then exits.")
+    (home-page "https://www.gnu.org/software/hello/")
+    (license license:gpl3+)))
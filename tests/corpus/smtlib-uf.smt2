@@ -0,0 +1,11 @@
+; Uninterpreted functions: f cannot tell a and b apart if they are equal.
+(set-logic QF_UF)
+(declare-sort U 0)
+(declare-fun a () U)
+(declare-fun b () U)
+(declare-fun f (U) U)
+(assert (distinct (f a) (f b)))
+(assert (not (distinct a b)))
+(check-sat)
+(echo "expected: unsat")
+(exit)
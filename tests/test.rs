@@ -12,7 +12,8 @@ use std::fmt::Debug;
 //use serde::de::{self, Deserialize};
 use serde::ser;
 
-use sexpr::to_string;
+use sexpr::ser::{FloatStyle, NonAsciiChars, NonFiniteFloats, PrettyFormatter};
+use sexpr::{to_string, Number, Serializer, Sexp};
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -105,3 +106,4171 @@ fn test_write_sym() {
 // /// assert!(Atom::String("string"), Atom::discriminate(r#""string""#));
 // /// # }
 // /// ```
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Meters(u32);
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Wrapper(Vec<u8>);
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Label(String);
+
+#[test]
+fn test_write_newtype_struct() {
+    test_encode_ok(&[(Meters(5), "5"), (Meters(0), "0")]);
+    test_encode_ok(&[(Wrapper(vec![1, 2]), "(1 2)"), (Wrapper(vec![]), "()")]);
+    test_encode_ok(&[(Label("a".into()), "\"a\""), (Label("".into()), "\"\"")]);
+}
+
+#[test]
+fn test_write_symbol() {
+    let sym: Sexp = sexpr::from_str("(abc)").unwrap();
+    test_encode_ok(&[(sym, "(abc)")]);
+}
+
+fn to_string_with_float_style<T>(value: &T, style: FloatStyle) -> String
+where
+    T: ser::Serialize,
+{
+    let formatter = PrettyFormatter::new().with_float_style(style);
+    let mut ser = Serializer::with_formatter(Vec::new(), formatter);
+    ser::Serialize::serialize(value, &mut ser).unwrap();
+    String::from_utf8(ser.into_inner()).unwrap()
+}
+
+#[test]
+fn test_write_float_styles() {
+    let cases: &[(f64, FloatStyle, &str)] = &[
+        (1.0, FloatStyle::Shortest, "1.0"),
+        (1.5, FloatStyle::Fixed(3), "1.500"),
+        (2.0, FloatStyle::Fixed(0), "2"),
+        (1.27, FloatStyle::Trimmed(6), "1.27"),
+        (2.0, FloatStyle::Trimmed(6), "2"),
+        (0.1234567, FloatStyle::Trimmed(6), "0.123457"),
+        (-0.0000001, FloatStyle::Trimmed(6), "0"),
+        (1500.0, FloatStyle::Scientific, "1.5e3"),
+    ];
+    for &(value, style, out) in cases {
+        assert_eq!(to_string_with_float_style(&value, style), out);
+    }
+    assert_eq!(
+        to_string_with_float_style(&1.5f32, FloatStyle::Fixed(2)),
+        "1.50"
+    );
+
+    // The serializer can set the style for any formatter.
+    let mut ser = Serializer::new(Vec::new()).with_float_style(FloatStyle::Fixed(2));
+    ser::Serialize::serialize(&(1.5, 2.0f32), &mut ser).unwrap();
+    assert_eq!(ser.into_inner(), b"(1.50 2.00)");
+    let formatter = PrettyFormatter::new().with_float_style(FloatStyle::Fixed(3));
+    let mut ser =
+        Serializer::with_formatter(Vec::new(), formatter).with_float_style(FloatStyle::Scientific);
+    ser::Serialize::serialize(&1500.0, &mut ser).unwrap();
+    assert_eq!(ser.into_inner(), b"1.5e3");
+}
+
+fn to_string_with_non_finite_floats<T>(value: &T, policy: NonFiniteFloats) -> sexpr::Result<String>
+where
+    T: ser::Serialize,
+{
+    let mut writer = Vec::new();
+    let mut ser = Serializer::new(&mut writer).with_non_finite_floats(policy);
+    value.serialize(&mut ser)?;
+    Ok(String::from_utf8(writer).unwrap())
+}
+
+#[test]
+fn test_write_non_finite_floats() {
+    assert!(to_string(&f64::NAN).is_err());
+    assert!(to_string(&f32::INFINITY).is_err());
+
+    let values = vec![f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1.5];
+    assert_eq!(
+        to_string_with_non_finite_floats(&values, NonFiniteFloats::WriteNil).unwrap(),
+        "(#nil #nil #nil 1.5)"
+    );
+    let out =
+        to_string_with_non_finite_floats(&values, NonFiniteFloats::WriteSpecialTokens).unwrap();
+    assert_eq!(out, "(+nan.0 +inf.0 -inf.0 1.5)");
+
+    let back: Vec<f64> = sexpr::from_str(&out).unwrap();
+    assert!(back[0].is_nan());
+    assert_eq!(&back[1..], &values[1..]);
+}
+
+#[test]
+fn test_serialize_into_buffers() {
+    let mut out = String::from("> ");
+    sexpr::ser::to_string_into(&mut out, &(1, "a")).unwrap();
+    assert_eq!(out, "> (1 \"a\")");
+    assert!(sexpr::ser::to_string_into(&mut out, &vec![1.0, f64::NAN]).is_err());
+    assert_eq!(out, "> (1 \"a\")");
+
+    let mut bytes = b"#".to_vec();
+    sexpr::ser::to_vec_into(&mut bytes, &vec![true]).unwrap();
+    assert_eq!(bytes, b"#(#t)");
+    assert!(sexpr::ser::to_vec_into(&mut bytes, &f32::INFINITY).is_err());
+    assert_eq!(bytes, b"#(#t)");
+
+    let mut ser = Serializer::new(Vec::new());
+    for n in 0..3u8 {
+        ser.get_mut().clear();
+        ser::Serialize::serialize(&vec![n], &mut ser).unwrap();
+        assert_eq!(ser.get_ref(), format!("({})", n).as_bytes());
+    }
+}
+
+#[test]
+fn test_serialize_non_ascii_escapes() {
+    let value = ("tab\tλ", 'é', "🦀");
+    assert_eq!(
+        sexpr::ser::to_string_ascii(&value).unwrap(),
+        r#"("tab\t\u03bb" "\u00e9" "\ud83e\udd80")"#
+    );
+
+    let mut ser = Serializer::new(Vec::new()).with_non_ascii_chars(NonAsciiChars::EscapeHex);
+    ser::Serialize::serialize(&value, &mut ser).unwrap();
+    let escaped = ser.into_inner();
+    assert_eq!(escaped, br#"("tab\t\x3bb;" "\xe9;" "\x1f980;")"#);
+    let (s, c, crab): (String, char, String) = sexpr::from_slice(&escaped).unwrap();
+    assert_eq!((s.as_str(), c, crab.as_str()), value);
+    assert!(sexpr::from_str::<String>(r#""\x;""#).is_err());
+    assert!(sexpr::from_str::<String>(r#""\x3bb""#).is_err());
+    assert!(sexpr::from_str::<String>(r#""\xd800;""#).is_err());
+    assert!(sexpr::from_str::<String>(r#""\x0000041;""#).is_err());
+
+    let mut ser = Serializer::new(Vec::new()).with_non_ascii_chars(NonAsciiChars::Write);
+    ser::Serialize::serialize(&value, &mut ser).unwrap();
+    assert_eq!(ser.into_inner(), "(\"tab\\tλ\" \"é\" \"🦀\")".as_bytes());
+
+    let s = sexpr::ser::to_string_ascii(&"naïve café").unwrap();
+    assert_eq!(sexpr::from_str::<String>(&s).unwrap(), "naïve café");
+}
+
+#[test]
+fn test_formatter_list_context() {
+    use sexpr::ser::{CompactFormatter, Formatter, ListContext};
+    use std::io;
+
+    // Puts each entry of a nested alist on its own line, indented by depth.
+    struct EntryPerLine;
+
+    impl Formatter for EntryPerLine {
+        fn begin_object_key_in<W: ?Sized>(
+            &mut self,
+            writer: &mut W,
+            cx: ListContext,
+        ) -> io::Result<()>
+        where
+            W: io::Write,
+        {
+            assert!(cx.is_alist());
+            if cx.depth() > 0 {
+                writer.write_all(b"\n")?;
+                writer.write_all(" ".repeat(2 * cx.depth()).as_bytes())
+            } else {
+                CompactFormatter.begin_object_key(writer, cx.index() == 0)
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Inner {
+        x: u8,
+        y: u8,
+    }
+
+    #[derive(Serialize)]
+    struct Outer {
+        name: &'static str,
+        inner: Inner,
+        tags: Vec<u8>,
+    }
+
+    let value = Outer {
+        name: "n",
+        inner: Inner { x: 1, y: 2 },
+        tags: vec![3],
+    };
+    let mut ser = Serializer::with_formatter(Vec::new(), EntryPerLine);
+    ser::Serialize::serialize(&value, &mut ser).unwrap();
+    let out = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(
+        out,
+        "(\"name\".\"n\" \"inner\".(\n  \"x\".1\n  \"y\".2) \"tags\".(3))"
+    );
+}
+
+#[test]
+fn test_debug_pretty() {
+    let v: Sexp = sexpr::from_str(r#"(foo "foo" 3 3.0 #t ())"#).unwrap();
+    assert_eq!(
+        v.debug_pretty(),
+        "List [\n  Symbol(foo)\n  Str(\"foo\")\n  Int(3)\n  Float(3)\n  Bool(true)\n  List []\n]"
+    );
+
+    let pair = Sexp::List(vec![Sexp::Pair(
+        Some(Box::new(Sexp::Atom(sexpr::sexp::Atom::keyword("k")))),
+        None,
+    )]);
+    assert_eq!(
+        pair.debug_pretty(),
+        "List [\n  Pair (\n    Keyword(k)\n    .\n    Empty\n  )\n]"
+    );
+}
+
+#[test]
+fn test_symbol_and_keyword_newtypes() {
+    use sexpr::sexp::{Atom, Keyword, Symbol};
+
+    let value = (Symbol::new("let"), Keyword::new("mode"), "let".to_owned());
+    let s = to_string(&value).unwrap();
+    assert_eq!(s, "(let #:mode \"let\")");
+    let back: (Symbol, Keyword, String) = sexpr::from_str(&s).unwrap();
+    assert_eq!(back, value);
+
+    // Strings are accepted where there are no symbols, keywords are not.
+    let loose: (Symbol, Keyword) = sexpr::from_str(r#"("a" "b")"#).unwrap();
+    assert_eq!(loose, (Symbol::new("a"), Keyword::new("b")));
+    assert!(sexpr::from_str::<(Symbol,)>("(#:a)").is_err());
+
+    let sexp: Sexp = sexpr::from_str(&s).unwrap();
+    assert_eq!(
+        sexp,
+        Sexp::List(vec![
+            Sexp::Atom(Atom::symbol("let")),
+            Sexp::Atom(Atom::keyword("mode")),
+            Sexp::Atom(Atom::string("let")),
+        ])
+    );
+    assert_eq!(
+        sexpr::to_value(&(Symbol::new("\"q\""), Keyword::new("k"))).unwrap(),
+        Sexp::List(vec![
+            Sexp::Atom(Atom::symbol("\"q\"")),
+            Sexp::Atom(Atom::keyword("k")),
+        ])
+    );
+    let list = Sexp::List(vec![
+        Sexp::Atom(Atom::symbol("x")),
+        Sexp::Atom(Atom::keyword("y")),
+    ]);
+    let from_value: (Symbol, Keyword) = sexpr::from_value(list).unwrap();
+    assert_eq!(from_value, (Symbol::new("x"), Keyword::new("y")));
+
+    // A user's own newtypes that happen to share the names are written as
+    // their inner value.
+    mod user {
+        #[derive(serde_derive::Serialize)]
+        pub struct Symbol(pub String);
+        #[derive(serde_derive::Serialize)]
+        pub struct Keyword(pub String);
+    }
+    let value = (
+        user::Symbol("a b".to_owned()),
+        user::Keyword("a".to_owned()),
+    );
+    assert_eq!(to_string(&value).unwrap(), r#"("a b" "a")"#);
+}
+
+#[test]
+fn test_symbol_keyword_round_trip() {
+    use sexpr::sexp::{Atom, Keyword, Symbol};
+
+    let atoms = vec![
+        Sexp::Atom(Atom::symbol("plain")),
+        Sexp::Atom(Atom::symbol("two words")),
+        Sexp::Atom(Atom::symbol("+")),
+        Sexp::Atom(Atom::symbol("")),
+        Sexp::Atom(Atom::symbol("a|b\\c")),
+        Sexp::Atom(Atom::symbol("(x)")),
+        Sexp::Atom(Atom::keyword("key")),
+        Sexp::Atom(Atom::keyword("odd key")),
+        Sexp::Atom(Atom::string("plain")),
+    ];
+    let list = Sexp::List(atoms.clone());
+    let s = to_string(&list).unwrap();
+    assert_eq!(
+        s,
+        r#"(plain |two words| |+| || |a\|b\\c| |(x)| #:key #:|odd key| "plain")"#
+    );
+    assert_eq!(sexpr::from_str::<Sexp>(&s).unwrap(), list);
+    // The value serializer keeps symbols and keywords as they are.
+    let names = Sexp::List(atoms[..8].to_vec());
+    assert_eq!(sexpr::to_value(&names).unwrap(), names);
+
+    // Skipped values and interned values see the same tokens.
+    let raw: (sexpr::RawSexp, u8) = sexpr::from_str("(#:|a ) b| 1)").unwrap();
+    assert_eq!(raw.0.get(), "#:|a ) b|");
+    let raw: (sexpr::RawSexp, u8) = sexpr::from_str("(|x y| 2)").unwrap();
+    assert_eq!(raw.0.get(), "|x y|");
+    let mut interner = sexpr::sexp::Interner::new();
+    let shared = interner.from_str(&s).unwrap();
+    assert_eq!(shared.to_sexp(), list);
+
+    let names = (Symbol::new("1st"), Keyword::new("b c"));
+    let s = to_string(&names).unwrap();
+    assert_eq!(s, "(|1st| #:|b c|)");
+    assert_eq!(sexpr::from_str::<(Symbol, Keyword)>(&s).unwrap(), names);
+}
+
+#[test]
+fn test_is_complete() {
+    use sexpr::{is_complete, Completeness};
+
+    for input in &[
+        "",
+        "  ; nothing yet\n",
+        "(a) (b \"c\")",
+        "42",
+        "|x (y|",
+        "(a . 1)",
+        "((a . 1) (b . (1 2)))",
+    ] {
+        assert_eq!(is_complete(input), Completeness::Complete, "{:?}", input);
+    }
+    let cases: &[(&str, usize, bool)] = &[
+        ("(", 1, false),
+        ("(a (b", 2, false),
+        ("(a \"(", 1, true),
+        ("(a |b)", 1, true),
+        ("(a ; )\n", 1, false),
+        ("(a) (b", 1, false),
+        ("\"", 0, true),
+        // A symbol ends at Unicode whitespace, as the deserializer reads it.
+        ("(a\u{a0}\"(", 1, true),
+        ("(a\u{2028}(b", 2, false),
+        ("((a . ", 2, false),
+        ("((a . 1) (b . (", 3, false),
+    ];
+    for &(input, open_parens, in_string) in cases {
+        assert_eq!(
+            is_complete(input),
+            Completeness::Incomplete {
+                open_parens,
+                in_string
+            },
+            "{:?}",
+            input
+        );
+    }
+    for input in &[")", "(a))", "(#q", "(\"\\q\")", "(a . 1 2)", "(. a)"] {
+        assert_eq!(is_complete(input), Completeness::Invalid, "{:?}", input);
+    }
+}
+
+#[test]
+fn test_reformat() {
+    use sexpr::{reformat, FormatStyle};
+
+    let style = FormatStyle::new().with_max_width(24);
+    let input = ";; header\n\n\n(module foo\n(export a b)   ; api\n\
+                 (define (a) '(1 2 3 4 5 6 7 8 9 10))\n  ; last\n  )";
+    let expected = ";; header\n\n(module foo\n  (export a b) ; api\n  (define (a)\n    \
+                    '(1 2 3 4 5 6 7 8 9\n       10))\n  ; last\n)\n";
+    let output = reformat(input, &style).unwrap();
+    assert_eq!(output, expected);
+    assert_eq!(reformat(&output, &style).unwrap(), output);
+
+    // Atoms, including strings with line breaks, are kept as written.
+    let input = "(a   \"two\nlines\"\t#:|k w|  [x  y])";
+    assert_eq!(
+        reformat(input, &FormatStyle::new().with_indent(4)).unwrap(),
+        "(a\n    \"two\nlines\"\n    #:|k w|\n    [x y])\n"
+    );
+    assert_eq!(
+        reformat(
+            "(f  x\n\n\n y)",
+            &FormatStyle::new().with_max_blank_lines(0)
+        )
+        .unwrap(),
+        "(f x y)\n"
+    );
+    assert_eq!(reformat("  \n", &style).unwrap(), "");
+
+    for (input, line, column) in &[("(a", 1, 3), ("(a\n b]", 2, 3), (")", 1, 1), ("(\"x", 1, 4)] {
+        let err = reformat(input, &style).unwrap_err();
+        assert_eq!((err.line(), err.column()), (*line, *column), "{:?}", input);
+    }
+}
+
+#[test]
+fn test_number_i128() {
+    assert_eq!(Number::from_i128(-5).unwrap().as_i64(), Some(-5));
+    assert_eq!(
+        Number::from_i128(i128::from(u64::MAX)).unwrap().as_u64(),
+        Some(u64::MAX)
+    );
+    assert!(Number::from_i128(i128::from(u64::MAX) + 1).is_none());
+    assert!(Number::from_i128(i128::from(i64::MIN) - 1).is_none());
+
+    assert_eq!(Number::from(u64::MAX).as_i128(), Some(i128::from(u64::MAX)));
+    assert_eq!(Number::from_f64(1.0).unwrap().as_i128(), None);
+}
+
+#[test]
+fn test_number_ordering() {
+    let float = |f| Number::from_f64(f).unwrap();
+
+    assert!(Number::from(-1) < float(0.5));
+    assert!(float(0.5) < Number::from(1));
+    assert!(Number::from(u64::MAX) > Number::from(i64::MIN));
+    assert!(float(1.5) > float(-2.0));
+
+    // 2^53 + 1 is not representable as a float and must not round to 2^53.
+    assert!(Number::from(9_007_199_254_740_993u64) > float(9_007_199_254_740_992.0));
+    assert!(float(1e300) > Number::from(u64::MAX));
+
+    assert_eq!(Number::from(1).partial_cmp(&float(1.0)), None);
+    assert!(Number::from(1).eq_numeric(&float(1.0)));
+    assert!(!Number::from(1).eq_numeric(&float(1.5)));
+}
+
+#[test]
+fn test_interner_shares_subtrees() {
+    use sexpr::sexp::{Interner, SharedSexp};
+    use std::sync::Arc;
+
+    let input = "((b c) (a (b c)) (b c) -0.0 0.0)";
+    let mut interner = Interner::new();
+    let value = interner.from_str(input).unwrap();
+    assert_eq!(value.to_sexp(), sexpr::from_str::<Sexp>(input).unwrap());
+
+    let elems = match *value {
+        SharedSexp::List(ref elems) => elems.clone(),
+        _ => panic!("expected a list"),
+    };
+    assert!(Arc::ptr_eq(&elems[0], &elems[2]));
+    match *elems[1] {
+        SharedSexp::List(ref inner) => assert!(Arc::ptr_eq(&inner[1], &elems[0])),
+        _ => panic!("expected a list"),
+    }
+    assert!(!Arc::ptr_eq(&elems[3], &elems[4]));
+
+    let again = interner.intern(&sexpr::from_str::<Sexp>("(a (b c))").unwrap());
+    assert!(Arc::ptr_eq(&again, &elems[1]));
+
+    let pair = interner.intern(&Sexp::pair(Sexp::List(vec![]), Sexp::Nil));
+    assert!(Arc::ptr_eq(
+        &pair,
+        &interner.intern(&Sexp::pair(Sexp::List(vec![]), Sexp::Nil))
+    ));
+    assert_eq!(pair.to_sexp(), Sexp::pair(Sexp::List(vec![]), Sexp::Nil));
+}
+
+#[test]
+fn test_sexp_rc() {
+    use sexpr::sexp::SexpRc;
+
+    let sexp: Sexp = sexpr::from_str("(a (b \"c\") 1.5 #t)").unwrap();
+    let rc = SexpRc::from(&sexp);
+    let copy = rc.clone();
+    match (&rc, &copy) {
+        (SexpRc::List(a), SexpRc::List(b)) => assert!(std::sync::Arc::ptr_eq(a, b)),
+        _ => panic!("expected a list"),
+    }
+    assert_eq!(Sexp::from(copy), sexp);
+
+    let mut from_rc = Vec::new();
+    let mut from_sexp = Vec::new();
+    sexpr::ser::to_writer(&mut from_rc, &rc).unwrap();
+    sexpr::ser::to_writer(&mut from_sexp, &sexp).unwrap();
+    assert_eq!(from_rc, from_sexp);
+
+    let pair = SexpRc::from(Sexp::pair(1, Sexp::Nil));
+    assert_eq!(pair.to_sexp(), Sexp::pair(1, Sexp::Nil));
+
+    // Every value tree writes the same text through `WriteSexp`.
+    use sexpr::sexp::{Interner, WriteSexp};
+    fn pretty(tree: &impl WriteSexp) -> Vec<u8> {
+        let mut out = Vec::new();
+        tree.to_writer_pretty(&mut out).unwrap();
+        out
+    }
+    let sexp = Sexp::List(vec![Sexp::pair("a", 1), Sexp::pair("b", sexp)]);
+    let shared = Interner::new().intern(&sexp);
+    assert_eq!(pretty(&SexpRc::from(&sexp)), pretty(&sexp));
+    assert_eq!(pretty(&*shared), pretty(&sexp));
+    assert_eq!(sexpr::to_string(&*shared).unwrap(), sexp.to_string());
+}
+
+#[test]
+fn test_loader_includes() {
+    use sexpr::loader::{FileResolver, Loader, MemoryResolver};
+
+    let mut files = MemoryResolver::new();
+    files.insert("main", "(include \"a\") (x (include \"b\") y)");
+    files.insert("a", "(1) (2)");
+    files.insert("b", "(b1) ; comment\n(use \"c\")");
+    files.insert("c", "");
+    let loader = Loader::new(files.clone());
+    let forms = loader.load("main").unwrap();
+    assert_eq!(
+        to_string(&forms).unwrap(),
+        "((1) (2) (x (b1) (use \"c\") y))"
+    );
+
+    let forms = Loader::new(files.clone())
+        .with_include_form("use")
+        .load("b")
+        .unwrap();
+    assert_eq!(to_string(&forms).unwrap(), "((b1))");
+
+    files.insert("loop", "(include \"main\")");
+    files.insert("a", "(include \"loop\")");
+    let err = Loader::new(files.clone()).load("main").unwrap_err();
+    assert_eq!(err.include_chain(), ["main", "a", "loop"]);
+    assert_eq!(
+        err.to_string(),
+        "`main` includes itself in `loop`, included from `a`, included from `main`"
+    );
+
+    files.insert("a", "(include)");
+    let err = Loader::new(files.clone()).load("main").unwrap_err();
+    assert!(err.error().is_data());
+    assert_eq!(err.include_chain(), ["main", "a"]);
+
+    files.insert("a", "\n  (1 \"2)");
+    let err = Loader::new(files.clone()).load("main").unwrap_err();
+    assert_eq!((err.error().line(), err.include_chain().len()), (2, 2));
+
+    files.insert("a", "(include \"missing\")");
+    let err = Loader::new(files).load("main").unwrap_err();
+    assert!(err.error().is_io());
+    assert!(err.to_string().starts_with("cannot load `missing`"));
+
+    let dir = std::env::temp_dir().join(format!("sexpr-loader-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("root.scm"), "(a (include \"sub/part.scm\"))").unwrap();
+    std::fs::write(dir.join("sub/part.scm"), "(include \"leaf.scm\")").unwrap();
+    std::fs::write(dir.join("sub/leaf.scm"), "(leaf)").unwrap();
+    let forms = Loader::new(FileResolver::new().with_base_dir(&dir))
+        .load("root.scm")
+        .unwrap();
+    assert_eq!(to_string(&forms).unwrap(), "((a (leaf)))");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_variable_substitution() {
+    use sexpr::loader::{MissingVariables, Substitution};
+
+    let vars = |name: &str| match name {
+        "USER" => Some("zv".to_owned()),
+        "EMPTY" => Some(String::new()),
+        _ => None,
+    };
+    let parse = |s: &str| sexpr::from_str::<Sexp>(s).unwrap();
+
+    let input = parse("((user (env \"USER\")) (dir \"/home/${USER}${EMPTY}/${\") (sym-${USER}))");
+    let out = Substitution::new(vars).apply(input).unwrap();
+    assert_eq!(
+        to_string(&out).unwrap(),
+        "((user \"zv\") (dir \"/home/zv/${\") (sym-zv))"
+    );
+
+    let input = parse("((a (env \"NOPE\")) (b \"x${NOPE}y\"))");
+    let err = Substitution::new(vars).apply(input.clone()).unwrap_err();
+    assert!(err.is_data());
+    assert_eq!(err.to_string(), "variable `NOPE` is not set");
+
+    let empty = Substitution::new(vars).with_missing_variables(MissingVariables::Empty);
+    assert_eq!(
+        to_string(&empty.apply(input.clone()).unwrap()).unwrap(),
+        "((a \"\") (b \"xy\"))"
+    );
+    let keep = Substitution::new(vars).with_missing_variables(MissingVariables::Keep);
+    assert_eq!(keep.apply(input.clone()).unwrap(), input);
+
+    let getenv = Substitution::new(vars).with_env_form("getenv");
+    let out = getenv
+        .apply_all(vec![parse("((getenv \"USER\") (env \"USER\"))")])
+        .unwrap();
+    assert_eq!(to_string(&out).unwrap(), "((\"zv\" (env \"USER\")))");
+    assert!(getenv.apply(parse("((getenv USER))")).is_err());
+}
+
+#[test]
+fn test_to_rust_expr() {
+    use sexpr::sexp::Atom;
+
+    let sexp = Sexp::List(vec![
+        Sexp::Atom(sexpr::sexp::Atom::keyword("k")),
+        Sexp::Atom(Atom::string("a \"q\"\n")),
+        Sexp::Number(Number::from(-3)),
+        Sexp::Number(Number::from_f64(1e300).unwrap()),
+        Sexp::pair(Sexp::Boolean(true), Sexp::Nil),
+    ]);
+    assert_eq!(
+        sexp.to_rust_expr(),
+        concat!(
+            "::sexpr::Sexp::List(::std::vec![",
+            "::sexpr::Sexp::Atom(::sexpr::sexp::Atom::keyword(\"k\")), ",
+            "::sexpr::Sexp::Atom(::sexpr::sexp::Atom::string(\"a \\\"q\\\"\\n\")), ",
+            "::sexpr::Sexp::Number(::sexpr::Number::from(-3i64)), ",
+            "::sexpr::Sexp::Number(::sexpr::Number::from_f64(1e300f64).unwrap()), ",
+            "::sexpr::Sexp::Pair(",
+            "::std::option::Option::Some(::std::boxed::Box::new(::sexpr::Sexp::Boolean(true))), ",
+            "::std::option::Option::None)])"
+        )
+    );
+
+    // The same expression, as a build script would have generated it.
+    let rebuilt = ::sexpr::Sexp::List(::std::vec![
+        ::sexpr::Sexp::Atom(::sexpr::sexp::Atom::keyword("k")),
+        ::sexpr::Sexp::Atom(::sexpr::sexp::Atom::string("a \"q\"\n")),
+        ::sexpr::Sexp::Number(::sexpr::Number::from(-3i64)),
+        ::sexpr::Sexp::Number(::sexpr::Number::from_f64(1e300f64).unwrap()),
+        ::sexpr::Sexp::Pair(
+            ::std::option::Option::Some(::std::boxed::Box::new(::sexpr::Sexp::Boolean(true))),
+            ::std::option::Option::None
+        )
+    ]);
+    assert_eq!(rebuilt, sexp);
+}
+
+#[test]
+fn test_sexp_ref_constants() {
+    use sexpr::sexp::{Atom, SexpRef};
+
+    const ITEMS: &[SexpRef<'static>] = &[
+        SexpRef::Number(Number::from_i64(-2)),
+        SexpRef::Number(Number::from_i64(7)),
+        SexpRef::Boolean(false),
+        SexpRef::Nil,
+    ];
+    static MESSAGE: SexpRef<'static> = SexpRef::List(&[
+        SexpRef::Symbol("reply"),
+        SexpRef::Keyword("status"),
+        SexpRef::String("ok"),
+        SexpRef::List(ITEMS),
+    ]);
+
+    let sexp = MESSAGE.to_sexp();
+    assert_eq!(to_string(&MESSAGE).unwrap(), to_string(&sexp).unwrap());
+    assert_eq!(
+        to_string(&MESSAGE).unwrap(),
+        "(reply #:status \"ok\" (-2 7 #f #nil))"
+    );
+    match sexp {
+        Sexp::List(ref elems) => {
+            assert_eq!(elems[1], Sexp::Atom(Atom::keyword("status")));
+            assert_eq!(elems[3][1], Sexp::Number(Number::from(7u64)));
+        }
+        _ => panic!("expected a list"),
+    }
+
+    static PAIR: SexpRef<'static> = SexpRef::Pair(&SexpRef::Symbol("a"), &SexpRef::Nil);
+    assert_eq!(
+        Sexp::from(&PAIR),
+        Sexp::pair(Sexp::Atom(Atom::symbol("a")), Sexp::Nil)
+    );
+}
+
+#[test]
+fn test_text_eq() {
+    use sexpr::{text_eq, text_eq_with_options, TextEqOptions};
+
+    assert!(text_eq("(a  (b\n c)) ; done\n(d)", "(a (b c))\n(d)").unwrap());
+    assert!(!text_eq("(a b)", "(a b) (c)").unwrap());
+    assert!(!text_eq("(a b)", "(b a)").unwrap());
+    assert!(!text_eq("(Scale 1)", "(scale 1.0)").unwrap());
+    assert!(text_eq("(a", "(a)").is_err());
+
+    // Dotted pairs are compared like any other value.
+    assert!(text_eq("((a . 1) (b . (1 2)))", "((a . 1)\n (b . (1 2))) ; alist").unwrap());
+    assert!(text_eq("(a b . c)", "(a . (b . c))").unwrap());
+    assert!(!text_eq("(a . 1)", "(a 1)").unwrap());
+    assert!(!text_eq("(a . 1)", "(a . 2)").unwrap());
+    assert!(text_eq("(a . 1 2)", "(a 1 2)").is_err());
+
+    let options = TextEqOptions {
+        case_insensitive_symbols: true,
+        numeric: true,
+    };
+    assert!(text_eq_with_options("(Scale #:X 1)", "(scale #:x 1.0)", &options).unwrap());
+    assert!(!text_eq_with_options("(\"A\")", "(\"a\")", &options).unwrap());
+    assert!(text_eq_with_options("((k 2))", "((K 2.0))", &options).unwrap());
+    assert!(text_eq_with_options("((k . 2))", "((K . 2.0))", &options).unwrap());
+}
+
+#[test]
+fn test_to_json_string() {
+    use sexpr::sexp::Atom;
+
+    let v: Sexp = sexpr::from_str("(foo \"a\\\"b\\n\" 3 -1.5 #t ())").unwrap();
+    assert_eq!(v.to_json_string(), r#"["foo","a\"b\n",3,-1.5,true,[]]"#);
+    assert_eq!(Sexp::Nil.to_json_string(), "null");
+
+    let alist = Sexp::List(vec![
+        Sexp::new_entry(Atom::keyword("id"), 7),
+        Sexp::new_entry("tags", Sexp::List(vec![Sexp::from("x")])),
+        Sexp::pair(2, Sexp::Nil),
+    ]);
+    assert_eq!(alist.to_json_string(), r#"{"id":7,"tags":["x"],"2":null}"#);
+
+    let mixed = Sexp::List(vec![Sexp::pair("a", 1), Sexp::from(f64::NAN)]);
+    assert_eq!(mixed.to_json_string(), r#"[["a",1],null]"#);
+}
+
+#[test]
+fn test_alist_round_trip() {
+    use sexpr::sexp::Atom;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        ports: Vec<u16>,
+        limit: Option<u32>,
+    }
+
+    let config = Config {
+        name: "web".to_owned(),
+        ports: vec![80, 443],
+        limit: None,
+    };
+    let alist = sexpr::to_alist(&config).unwrap();
+    let keys: Vec<&str> = alist.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, ["name", "ports", "limit"]);
+    assert_eq!(
+        alist[1].1,
+        Sexp::List(vec![Sexp::from(80), Sexp::from(443)])
+    );
+    assert_eq!(sexpr::from_alist::<Config>(alist).unwrap(), config);
+
+    let mut map = BTreeMap::new();
+    map.insert(1, "one");
+    let alist = sexpr::to_alist(&map).unwrap();
+    assert_eq!(alist[0].0, Atom::symbol("1"));
+
+    let err = sexpr::to_alist(vec![1, 2]).unwrap_err();
+    assert_eq!(err.to_string(), "expected an alist");
+    assert!(sexpr::from_alist::<Config>(vec![(Atom::symbol("name"), Sexp::from(1))]).is_err());
+}
+
+#[test]
+fn test_parse_map() {
+    use std::collections::{BTreeMap, HashMap};
+
+    let map: BTreeMap<String, u8> = sexpr::from_str("((a . 1) (\"b\" . 2))").unwrap();
+    let expected: BTreeMap<String, u8> = vec![("a".to_owned(), 1), ("b".to_owned(), 2)]
+        .into_iter()
+        .collect();
+    assert_eq!(map, expected);
+    let map: HashMap<String, u8> = sexpr::from_str("((a . 1) (b . 2))").unwrap();
+    assert_eq!(map.len(), 2);
+    assert_eq!(map["b"], 2);
+    assert!(sexpr::from_str::<BTreeMap<String, u8>>("()")
+        .unwrap()
+        .is_empty());
+
+    // A map reads back from what the serializer writes for it, in either
+    // layout.
+    let mut map = BTreeMap::new();
+    map.insert("x".to_owned(), vec![1u8, 2]);
+    map.insert("y".to_owned(), vec![]);
+    let text = sexpr::to_string(&map).unwrap();
+    assert_eq!(text, "(\"x\".(1 2) \"y\".())");
+    assert_eq!(
+        sexpr::from_str::<BTreeMap<String, Vec<u8>>>(&text).unwrap(),
+        map
+    );
+    let value = sexpr::sexp::to_value(&map).unwrap();
+    assert_eq!(value.to_string(), "((x . (1 2)) (y . ()))");
+    assert_eq!(
+        sexpr::from_str::<BTreeMap<String, Vec<u8>>>(&value.to_string()).unwrap(),
+        map
+    );
+    let nested: HashMap<String, BTreeMap<String, u8>> =
+        sexpr::from_str("((outer . ((inner . 3))))").unwrap();
+    assert_eq!(nested["outer"]["inner"], 3);
+
+    let err = sexpr::from_str::<BTreeMap<String, u8>>("((a . 1) b)").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "alist entry 1 is not a (key . value) pair at line 1 column 10"
+    );
+    assert!(sexpr::from_str::<BTreeMap<String, u8>>("(a 1)").is_err());
+    assert!(sexpr::from_str::<BTreeMap<String, u8>>("((a . x))").is_err());
+}
+
+#[test]
+fn test_form_accessors() {
+    let plist: Sexp = sexpr::from_str("(#:a 1 #:b #:c 2)").unwrap();
+    assert_eq!(plist.plist_get("a"), Some(&Sexp::from(1)));
+    // Values are read in turn, so a keyword in value position is not a key.
+    assert_eq!(
+        plist.plist_get("b"),
+        Some(&Sexp::Atom(sexpr::sexp::Atom::keyword("c")))
+    );
+    assert_eq!(plist.plist_get("c"), None);
+
+    let dangling: Sexp = sexpr::from_str("(f #:a)").unwrap();
+    assert_eq!(dangling.plist_get("a"), None);
+
+    let alist: Sexp = sexpr::from_str("((a 1) (b 2 3) \"x\" (\"c\" 4) (a 5))").unwrap();
+    assert_eq!(alist.alist_get("a"), Some(&Sexp::from(1)));
+    assert_eq!(alist.alist_get("b"), None);
+    assert_eq!(alist.alist_get("c"), Some(&Sexp::from(4)));
+
+    let empty = Sexp::List(vec![]);
+    assert_eq!(empty.head(), None);
+    assert_eq!(empty.rest(), None);
+    assert_eq!(Sexp::from(1).rest(), None);
+    assert_eq!(Sexp::from(1).alist_get("a"), None);
+}
+
+#[test]
+fn test_pattern() {
+    use sexpr::sexp::Pattern;
+
+    let sexp = |text: &str| -> Sexp { sexpr::from_str(text).unwrap() };
+
+    let define = Pattern::parse("(define (?name ??args) ?_)").unwrap();
+    let bindings = define.matches(&sexp("(define (f x y) (g x))")).unwrap();
+    let names: Vec<&str> = bindings.iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["args", "name"]);
+    assert_eq!(bindings.get("name"), Some(&sexp("f")));
+    assert_eq!(bindings.get("args"), Some(&sexp("(x y)")));
+    assert!(define.matches(&sexp("(define f 1)")).is_none());
+
+    // A segment can sit between fixed elements and match nothing.
+    let around = Pattern::parse("(begin ?first ??middle ?last)").unwrap();
+    let bindings = around.matches(&sexp("(begin 1 2)")).unwrap();
+    assert_eq!(bindings.get("middle"), Some(&Sexp::List(vec![])));
+    assert!(around.matches(&sexp("(begin 1)")).is_none());
+
+    // A repeated variable must match equal values.
+    let same = Pattern::parse("(eq ?x ?x)").unwrap();
+    assert!(same.matches(&sexp("(eq a a)")).is_some());
+    assert!(same.matches(&sexp("(eq a b)")).is_none());
+
+    // `?` is only a variable at the start of a word, and not in strings.
+    let literal = Pattern::parse("(is-ok? \"?x\" ?x)").unwrap();
+    let bindings = literal.matches(&sexp("(is-ok? \"?x\" 1)")).unwrap();
+    assert_eq!(bindings.len(), 1);
+
+    // A dotted tail matches the rest of a list, or the cdr of a pair.
+    let dotted = Pattern::parse("(define ?name . ?rest)").unwrap();
+    let bindings = dotted.matches(&sexp("(define x (f 1) 2)")).unwrap();
+    assert_eq!(bindings.get("name"), Some(&sexp("x")));
+    assert_eq!(bindings.get("rest"), Some(&sexp("((f 1) 2)")));
+    let bindings = dotted.matches(&sexp("(define x)")).unwrap();
+    assert_eq!(bindings.get("rest"), Some(&Sexp::List(vec![])));
+    assert!(dotted.matches(&sexp("(define)")).is_none());
+    let entry = Pattern::parse("(?key . ?value)").unwrap();
+    let bindings = entry.matches(&sexp("(port . 80)")).unwrap();
+    assert_eq!(bindings.get("key"), Some(&sexp("port")));
+    assert_eq!(bindings.get("value"), Some(&sexp("80")));
+
+    assert!(Pattern::parse("(a ??x ??y)").is_err());
+    assert!(Pattern::parse("??x").is_err());
+    assert!(Pattern::parse("(a . ??x)").is_err());
+}
+
+#[test]
+fn test_template() {
+    use sexpr::sexp::{Bindings, Template};
+
+    let sexp = |text: &str| -> Sexp { sexpr::from_str(text).unwrap() };
+
+    let template = Template::parse("(reply ?id (items ??items) ??rest)").unwrap();
+    let mut bindings = Bindings::new();
+    bindings.insert("id", 7);
+    bindings.insert("items", sexp("(a (b c))"));
+    bindings.insert("rest", sexp("done"));
+    assert_eq!(
+        template.instantiate(&bindings).unwrap(),
+        sexp("(reply 7 (items a (b c)) done)")
+    );
+
+    bindings.insert("items", Sexp::List(vec![]));
+    assert_eq!(
+        template.instantiate(&bindings).unwrap(),
+        sexp("(reply 7 (items) done)")
+    );
+
+    let err = Template::parse("(f ?missing)")
+        .unwrap()
+        .instantiate(&Bindings::new())
+        .unwrap_err();
+    assert_eq!(err.to_string(), "variable `missing` is not set");
+
+    assert!(Template::parse("(f ?_)").is_err());
+    assert!(Template::parse("??x").is_err());
+}
+
+#[test]
+fn test_visit_and_fold() {
+    use sexpr::sexp::visit::{self, SexpFolder, SexpVisitor};
+    use sexpr::sexp::Atom;
+
+    struct Collect(Vec<String>);
+
+    impl SexpVisitor for Collect {
+        fn visit_atom(&mut self, atom: &Atom) {
+            self.0.push(atom.as_str().to_owned());
+        }
+        fn visit_number(&mut self, n: &Number) {
+            self.0.push(n.to_string());
+        }
+        fn visit_pair(&mut self, car: &Sexp, cdr: &Sexp) {
+            self.0.push(".".to_owned());
+            visit::walk_pair(self, car, cdr);
+        }
+    }
+
+    let tree = Sexp::List(vec![Sexp::from("a"), Sexp::pair("b", 1), Sexp::from(2)]);
+    let mut collect = Collect(Vec::new());
+    collect.visit_sexp(&tree);
+    assert_eq!(collect.0, ["a", ".", "b", "1", "2"]);
+
+    struct Negate;
+
+    impl SexpFolder for Negate {
+        fn fold_bool(&mut self, b: bool) -> Sexp {
+            Sexp::Boolean(!b)
+        }
+    }
+
+    let tree = Sexp::List(vec![
+        Sexp::Boolean(true),
+        Sexp::pair(Sexp::Boolean(false), 1),
+    ]);
+    assert_eq!(
+        Negate.fold_sexp(tree),
+        Sexp::List(vec![
+            Sexp::Boolean(false),
+            Sexp::pair(Sexp::Boolean(true), 1)
+        ])
+    );
+
+    // `rewrite` visits children before their parent.
+    let mut tree: Sexp = sexpr::from_str("(a (b c))").unwrap();
+    let mut order = Vec::new();
+    tree.rewrite(|sexp| {
+        order.push(sexp.to_string());
+        if let Sexp::Atom(Atom::Symbol(name)) = sexp {
+            name.make_ascii_uppercase();
+        }
+    });
+    assert_eq!(order, ["a", "b", "c", "(B C)", "(A (B C))"]);
+    assert_eq!(tree, sexpr::from_str("(A (B C))").unwrap());
+
+    let keywords = sexpr::from_str::<Sexp>("(f #:k x)")
+        .unwrap()
+        .map_symbols(|name| Sexp::from(name.len() as u64));
+    assert_eq!(keywords, sexpr::from_str("(1 #:k 1)").unwrap());
+}
+
+#[test]
+fn test_rule_set() {
+    use sexpr::sexp::{Rule, RuleSet};
+
+    let sexp = |text: &str| -> Sexp { sexpr::from_str(text).unwrap() };
+
+    // The first rule added wins when several match the same value.
+    let rules = RuleSet::new()
+        .with_rule(Rule::parse("(double ?x)", "(add ?x ?x)").unwrap())
+        .with_rule(Rule::parse("(double 0)", "0").unwrap());
+    assert_eq!(
+        rules.apply(sexp("(double (double 0))")).unwrap(),
+        sexp("(add (add 0 0) (add 0 0))")
+    );
+
+    // Outer values are rewritten before inner ones.
+    let mut rules = RuleSet::new();
+    rules.push(Rule::parse("(f ?x)", "(g ?x)").unwrap());
+    rules.push(Rule::parse("(g (f ?x))", "(done ?x)").unwrap());
+    assert_eq!(rules.apply(sexp("(f (f a))")).unwrap(), sexp("(done a)"));
+
+    let looping = RuleSet::new()
+        .with_rule(Rule::parse("(grow ?x)", "(grow (s ?x))").unwrap())
+        .with_max_steps(5);
+    let err = looping.apply(sexp("(grow z)")).unwrap_err();
+    assert_eq!(err.to_string(), "rewriting did not finish within 5 steps");
+
+    let err = Rule::parse("(f ?x)", "(g ?y)").unwrap_err();
+    assert_eq!(err.to_string(), "variable `y` is not set");
+}
+
+#[test]
+fn test_gensym() {
+    use sexpr::sexp::Atom;
+    use std::collections::HashSet;
+    use std::thread;
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| thread::spawn(|| (0..100).map(|_| sexpr::gensym("g")).collect::<Vec<_>>()))
+        .collect();
+    let mut seen = HashSet::new();
+    for handle in handles {
+        for atom in handle.join().unwrap() {
+            assert!(atom.is_gensym());
+            assert!(seen.insert(atom));
+        }
+    }
+
+    assert!(!Atom::symbol("g#").is_gensym());
+    assert!(!Atom::symbol("g#1x").is_gensym());
+    assert!(!Atom::string("g#1").is_gensym());
+    assert!(Atom::symbol("a#b#12").is_gensym());
+
+    let gensym = Sexp::Atom(sexpr::gensym("x"));
+    let text = sexpr::to_string(&gensym).unwrap();
+    assert_eq!(sexpr::from_str::<Sexp>(&text).unwrap(), gensym);
+}
+
+#[test]
+fn test_from_str_spanned() {
+    use sexpr::sexp::SpannedValue;
+
+    let input = "; header\n(a \"s t\" (#:k 1.5)\n   [x])";
+    let err = sexpr::from_str_spanned(input).unwrap_err();
+    assert!(err.is_syntax());
+
+    let input = "; header\n(a \"s t\" (#:k 1.5) ())  ";
+    let form = sexpr::from_str_spanned(input).unwrap();
+    assert_eq!(
+        &input[form.span.start..form.span.end],
+        "(a \"s t\" (#:k 1.5) ())"
+    );
+    assert_eq!((form.span.line, form.span.column), (2, 1));
+
+    let texts: Vec<&str> = match form.value {
+        SpannedValue::List(ref elems) => elems
+            .iter()
+            .map(|elem| &input[elem.span.start..elem.span.end])
+            .collect(),
+        _ => panic!("expected a list"),
+    };
+    assert_eq!(texts, ["a", "\"s t\"", "(#:k 1.5)", "()"]);
+
+    let keyword = form.find(input.find("#:k").unwrap() + 1).unwrap();
+    assert_eq!(
+        keyword.value,
+        SpannedValue::Atom(sexpr::sexp::Atom::keyword("k"))
+    );
+    assert_eq!(keyword.span.column, 11);
+    assert!(form.find(0).is_none());
+
+    assert_eq!(Sexp::from(form), sexpr::from_str::<Sexp>(input).unwrap());
+    assert!(sexpr::from_str_spanned("(a) b").is_err());
+    assert!(sexpr::from_str_spanned("(a (b)").unwrap_err().is_eof());
+
+    // A bytevector is a list whose bytes have spans of their own.
+    let form = sexpr::from_str_spanned("#u8(1 2)").unwrap();
+    assert_eq!((form.span.start, form.span.end), (0, 8));
+    match form.value {
+        SpannedValue::List(ref elems) => {
+            let starts: Vec<usize> = elems.iter().map(|elem| elem.span.start).collect();
+            assert_eq!(starts, [4, 6]);
+        }
+        _ => panic!("expected a list"),
+    }
+    assert_eq!(
+        form.into_sexp(),
+        sexpr::from_str::<Sexp>("#u8(1 2)").unwrap()
+    );
+    let form = sexpr::from_str_spanned("(k #u8(1))").unwrap();
+    let byte = form.find(7).unwrap();
+    assert_eq!((byte.span.start, byte.span.end), (7, 8));
+    assert_eq!(
+        form.into_sexp(),
+        sexpr::from_str::<Sexp>("(k #u8(1))").unwrap()
+    );
+    assert!(sexpr::from_str_spanned("#u8 1").is_err());
+
+    // A dotted list keeps the spans of its elements and of its tail, and
+    // reads as the same pairs as without spans.
+    for input in &["(a . 1)", "((a . 1) (b . (1 2)))", "(a b . c)"] {
+        let form = sexpr::from_str_spanned(input).unwrap();
+        assert!(sexpr::reformat(input, &sexpr::FormatStyle::default()).is_ok());
+        assert_eq!(form.into_sexp(), sexpr::from_str::<Sexp>(input).unwrap());
+    }
+    let input = "(a b . (c))";
+    let form = sexpr::from_str_spanned(input).unwrap();
+    match form.value {
+        SpannedValue::DottedList(ref elems, ref tail) => {
+            assert_eq!(elems.len(), 2);
+            assert_eq!(&input[tail.span.start..tail.span.end], "(c)");
+        }
+        _ => panic!("expected a dotted list"),
+    }
+    assert_eq!(form.find(input.find('c').unwrap()).unwrap().span.column, 9);
+    assert!(sexpr::from_str_spanned("(a . b c)").is_err());
+    assert!(sexpr::from_str_spanned("(. a)").is_err());
+}
+
+#[test]
+fn test_from_value_hints() {
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    fn round_trip<T>(value: T)
+    where
+        T: ser::Serialize + serde::de::DeserializeOwned + PartialEq + Debug,
+    {
+        let sexp = sexpr::to_value(&value).unwrap();
+        assert_eq!(T::deserialize(&sexp).unwrap(), value);
+        assert_eq!(sexpr::from_value::<T>(sexp).unwrap(), value);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Unit;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Meters(f64);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Point(i32, i32);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(u32),
+        Rect(u32, u32),
+        Poly { points: Vec<Point>, closed: bool },
+    }
+
+    round_trip(Unit);
+    round_trip(Meters(2.5));
+    round_trip(Point(-1, 2));
+    round_trip((1u8, -2i64, true));
+    round_trip(Some(3u16));
+    round_trip(None::<u16>);
+    round_trip(vec![vec![1u32, 2], vec![], vec![3]]);
+    round_trip(Shape::Empty);
+    round_trip(Shape::Circle(3));
+    round_trip(Shape::Rect(1, 2));
+    round_trip(Shape::Poly {
+        points: vec![Point(0, 0), Point(1, 1)],
+        closed: true,
+    });
+    round_trip(vec![Shape::Empty, Shape::Circle(1)]);
+    round_trip(Outer {
+        inner: vec![Inner {
+            a: (),
+            b: 7,
+            c: vec!["x".to_owned()],
+        }],
+    });
+    round_trip(
+        vec![("one".to_owned(), 1u32), ("two".to_owned(), 2)]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>(),
+    );
+    round_trip(
+        vec![(1u32, "one".to_owned()), (2, "two".to_owned())]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>(),
+    );
+    round_trip(Animal::Frog("Henry".to_owned(), vec![1, -2]));
+    round_trip(Animal::Cat {
+        age: 3,
+        name: "Kate".to_owned(),
+    });
+
+    // Enums written by hand, as a head-tagged list or a one-entry alist.
+    let rect: Sexp = sexpr::from_str("(Rect 3 4)").unwrap();
+    assert_eq!(Shape::deserialize(&rect).unwrap(), Shape::Rect(3, 4));
+    let circle: Sexp = sexpr::from_str("(Circle 5)").unwrap();
+    assert_eq!(Shape::deserialize(&circle).unwrap(), Shape::Circle(5));
+    let poly: Sexp = sexpr::from_str("(Poly (points ((0 1))) (closed #f))").unwrap();
+    assert_eq!(
+        sexpr::from_value::<Shape>(poly).unwrap(),
+        Shape::Poly {
+            points: vec![Point(0, 1)],
+            closed: false,
+        }
+    );
+    let tagged = Sexp::List(vec![Sexp::new_entry("Circle", 6)]);
+    assert_eq!(Shape::deserialize(&tagged).unwrap(), Shape::Circle(6));
+
+    // A pair is read as a two-element tuple, and a list of two-element
+    // lists as a map.
+    let pair = Sexp::pair(1, 2);
+    assert_eq!(<(u8, u8)>::deserialize(&pair).unwrap(), (1, 2));
+    let map: Sexp = sexpr::from_str("((a 1) (b 2))").unwrap();
+    let map = BTreeMap::<String, u8>::deserialize(&map).unwrap();
+    assert_eq!(map.len(), 2);
+    assert_eq!(map["b"], 2);
+
+    // `deserialize_any` keeps the kinds of atoms and reads an alist as a map.
+    let value = Sexp::List(vec![
+        Sexp::new_entry("k", Sexp::Atom(sexpr::sexp::Atom::keyword("v"))),
+        Sexp::new_entry("s", Sexp::Atom(sexpr::sexp::Atom::string("t"))),
+    ]);
+    assert_eq!(Sexp::deserialize(&value).unwrap(), value);
+    assert_eq!(sexpr::from_value::<Sexp>(value.clone()).unwrap(), value);
+
+    // Type errors name the kind of value that was found.
+    let symbol = Sexp::Atom(sexpr::sexp::Atom::symbol("x"));
+    let err = u32::deserialize(&symbol).unwrap_err();
+    assert_eq!(err.to_string(), "invalid type: symbol, expected u32");
+    let keyword = Sexp::Atom(sexpr::sexp::Atom::keyword("x"));
+    let err = sexpr::from_value::<bool>(keyword).unwrap_err();
+    assert_eq!(err.to_string(), "invalid type: keyword, expected a boolean");
+    let err = String::deserialize(&pair).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: improper list, expected a string"
+    );
+    let string = Sexp::Atom(sexpr::sexp::Atom::string("x"));
+    let err = Vec::<u8>::deserialize(&string).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"x\", expected a sequence"
+    );
+    let err = sexpr::from_value::<Shape>(Sexp::from(1)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: integer `1`, expected enum Shape"
+    );
+    let err = Shape::deserialize(&sexpr::from_str::<Sexp>("(Circle 1 2)").unwrap()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid length 2, expected newtype variant"
+    );
+}
+
+#[test]
+fn test_to_value_map_keys() {
+    use sexpr::sexp::Atom;
+    use sexpr::ToValueOptions;
+    use std::collections::BTreeMap;
+
+    let mut map = BTreeMap::new();
+    map.insert(vec![1u32, 2], "a".to_owned());
+    map.insert(vec![], "b".to_owned());
+    let v = sexpr::to_value(&map).unwrap();
+    assert_eq!(
+        v,
+        Sexp::List(vec![
+            Sexp::pair(Sexp::List(vec![]), "b"),
+            Sexp::pair(vec![1u32, 2], "a"),
+        ])
+    );
+    assert_eq!(
+        sexpr::from_value::<BTreeMap<Vec<u32>, String>>(v).unwrap(),
+        map
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert(-1i32, true);
+    map.insert(2, false);
+    let v = sexpr::to_value(&map).unwrap();
+    assert_eq!(
+        v,
+        Sexp::List(vec![Sexp::pair(-1, true), Sexp::pair(2, false)])
+    );
+    assert_eq!(sexpr::from_value::<BTreeMap<i32, bool>>(v).unwrap(), map);
+
+    let options = ToValueOptions { string_keys: true };
+    assert_eq!(
+        sexpr::to_value_with_options(&map, &options).unwrap(),
+        Sexp::List(vec![
+            Sexp::new_entry(Atom::symbol("-1"), true),
+            Sexp::new_entry(Atom::symbol("2"), false),
+        ])
+    );
+    let mut map = BTreeMap::new();
+    map.insert((1, 2), ());
+    let err = sexpr::to_value_with_options(&map, &options).unwrap_err();
+    assert_eq!(err.to_string(), "key must be a string");
+}
+
+#[test]
+fn test_field_and_entry_order() {
+    use serde::Serialize;
+    use std::collections::{BTreeMap, HashMap};
+
+    fn to_sorted_string<T: Serialize>(value: &T, pretty: bool) -> String {
+        let out = if pretty {
+            let mut ser = Serializer::pretty(Vec::new()).with_sort_keys(true);
+            value.serialize(&mut ser).unwrap();
+            ser.into_inner()
+        } else {
+            let mut ser = Serializer::new(Vec::new()).with_sort_keys(true);
+            value.serialize(&mut ser).unwrap();
+            ser.into_inner()
+        };
+        String::from_utf8(out).unwrap()
+    }
+
+    #[derive(Serialize)]
+    struct Fields {
+        zeta: u8,
+        alpha: u8,
+        mid: u8,
+    }
+
+    // Struct fields keep their declared order, sorted or not.
+    let fields = Fields {
+        zeta: 1,
+        alpha: 2,
+        mid: 3,
+    };
+    let text = to_string(&fields).unwrap();
+    assert_eq!(text, r#"("zeta".1 "alpha".2 "mid".3)"#);
+    assert_eq!(to_sorted_string(&fields, false), text);
+    let names: Vec<String> = sexpr::to_alist(&fields)
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| key.into_string())
+        .collect();
+    assert_eq!(names, ["zeta", "alpha", "mid"]);
+
+    // Map entries are written in iteration order unless keys are sorted.
+    let hash: HashMap<String, u8> = (b'a'..=b'z')
+        .map(|c| ((c as char).to_string(), c))
+        .collect();
+    let iteration: Vec<Sexp> = hash.keys().map(|key| Sexp::from(key.as_str())).collect();
+    let cars: Vec<Sexp> = match sexpr::to_value(&hash).unwrap() {
+        Sexp::List(entries) => entries
+            .into_iter()
+            .map(|entry| entry.into_pair().unwrap().0)
+            .collect(),
+        v => panic!("expected an alist, got {:?}", v),
+    };
+    assert_eq!(cars, iteration);
+
+    let by_key: BTreeMap<&String, &u8> = hash.iter().collect();
+    let expected = to_string(&by_key).unwrap();
+    assert_eq!(to_sorted_string(&hash, false), expected);
+    assert!(expected.starts_with("(\"a\""));
+
+    // Sorting reaches maps nested in values, and the pretty formatter lays
+    // the sorted entries out as it would a `BTreeMap`.
+    #[derive(Serialize)]
+    struct Nested<M> {
+        zeta: M,
+        alpha: Vec<M>,
+    }
+    let inner: HashMap<&str, Vec<u8>> = vec![("y", vec![1]), ("x", vec![]), ("w", vec![2, 3])]
+        .into_iter()
+        .collect();
+    let hashed = Nested {
+        zeta: inner.clone(),
+        alpha: vec![inner.clone(), HashMap::new()],
+    };
+    let ordered: BTreeMap<&str, Vec<u8>> = inner.into_iter().collect();
+    let ordered = Nested {
+        zeta: ordered.clone(),
+        alpha: vec![ordered, BTreeMap::new()],
+    };
+    assert_eq!(
+        to_sorted_string(&hashed, false),
+        to_string(&ordered).unwrap()
+    );
+    assert_eq!(
+        to_sorted_string(&hashed, true),
+        sexpr::ser::to_string_pretty(&ordered).unwrap()
+    );
+}
+
+#[test]
+fn test_error_kind() {
+    use sexpr::error::ErrorKind;
+
+    let kind = |input: &str| sexpr::from_str::<Sexp>(input).unwrap_err().kind();
+    assert_eq!(kind("(1 2"), ErrorKind::EofWhileParsingList);
+    assert_eq!(kind("\"abc"), ErrorKind::EofWhileParsingString);
+    assert_eq!(kind("(1 2))"), ErrorKind::TrailingCharacters);
+    assert_eq!(kind(")"), ErrorKind::ExpectedSomeValue);
+
+    let err = sexpr::from_str::<u32>("\"x\"").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Custom);
+    assert!(err.is_data());
+
+    let err = sexpr::to_string(&std::f64::NAN).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::FloatMustBeFinite);
+
+    let rules = sexpr::sexp::RuleSet::new()
+        .with_rule(sexpr::sexp::Rule::parse("(loop)", "(loop)").unwrap())
+        .with_max_steps(3);
+    let err = rules.apply(sexpr::from_str("(loop)").unwrap()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::StepLimitExceeded);
+}
+
+#[test]
+fn test_parse_stats() {
+    use serde::Deserialize;
+    use sexpr::de::{Deserializer, ParseStats};
+
+    let input = "(1 (\"a\\nb\" \"\\u00e9\") (((#:k))))";
+    let (_, stats): (Sexp, _) = sexpr::from_str_with_stats(input).unwrap();
+    assert_eq!(
+        stats,
+        ParseStats {
+            bytes_read: input.len(),
+            atoms: 4,
+            max_depth: 4,
+            string_escapes: 2,
+        }
+    );
+
+    // Counting is off unless asked for.
+    let mut de = Deserializer::from_str("(1 2)");
+    Sexp::deserialize(&mut de).unwrap();
+    assert_eq!(de.stats(), None);
+
+    // Readers that copy every string count escapes as well.
+    let mut de = Deserializer::from_reader("(\"\\t\\t\" x)".as_bytes()).with_stats(true);
+    Sexp::deserialize(&mut de).unwrap();
+    let stats = de.stats().unwrap();
+    assert_eq!((stats.atoms, stats.string_escapes), (2, 2));
+
+    #[derive(Deserialize)]
+    struct Point {
+        x: i32,
+    }
+    let (point, stats): (Point, _) =
+        sexpr::from_str_with_stats("((x . 1) (note . \"skipped\\n\"))").unwrap();
+    assert_eq!(point.x, 1);
+    // The keys and `1` are read; the unknown field's value is skipped.
+    assert_eq!(stats.atoms, 3);
+    assert_eq!(stats.string_escapes, 0);
+}
+
+#[test]
+fn test_read_nil() {
+    let v: Sexp = sexpr::from_str("(#nil #t)").unwrap();
+    assert_eq!(v, Sexp::List(vec![Sexp::Nil, Sexp::Boolean(true)]));
+    let unit: () = sexpr::from_str("#nil").unwrap();
+    assert_eq!(unit, ());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_cli() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    fn sexp(args: &[&str], input: &str) -> (i32, String, String) {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_sexp"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let out = child.wait_with_output().unwrap();
+        (
+            out.status.code().unwrap(),
+            String::from_utf8(out.stdout).unwrap(),
+            String::from_utf8(out.stderr).unwrap(),
+        )
+    }
+
+    let config = "(config (port 80) (hosts (\"a\" \"b\")))  ; main\n(other #:k 3)";
+    assert_eq!(
+        sexp(&["fmt"], config).1,
+        "(config (port 80) (hosts (\"a\" \"b\"))) ; main\n(other #:k 3)\n"
+    );
+    assert_eq!(sexp(&["validate", "-"], config).0, 0);
+    assert_eq!(
+        sexp(&["validate"], "(1\n(2"),
+        (
+            1,
+            String::new(),
+            "sexp: <stdin>:2:2: EOF while parsing a list\n".to_owned()
+        )
+    );
+    assert_eq!(
+        sexp(&["to-json"], config).1,
+        "[\"config\",[\"port\",80],[\"hosts\",[\"a\",\"b\"]]]\n[\"other\",\"k\",3]\n"
+    );
+    assert_eq!(sexp(&["get", "hosts.1"], config).1, "\"b\"\n");
+    assert_eq!(sexp(&["get", "k"], config).1, "3\n");
+
+    let (code, sexp_text, _) = sexp(&["from-json"], r#"{"a": [1, 2.5, null], "b": {"c": true}}"#);
+    assert_eq!(code, 0);
+    assert_eq!(sexp_text, "((\"a\" (1 2.5 #nil)) (\"b\" ((\"c\" #t))))\n");
+    assert_eq!(sexp(&["get", "b.c"], &sexp_text).1, "#t\n");
+
+    assert_eq!(sexp(&["bogus"], "").0, 2);
+}
+
+#[cfg(feature = "capi")]
+#[test]
+fn test_capi() {
+    use sexpr::capi::*;
+    use std::ffi::CStr;
+    use std::ptr;
+
+    unsafe {
+        let text = "(define greeting \"hi\\u0000there\" 42 -1.5 #t)";
+        let value = sexpr_parse(text.as_ptr() as *const _, text.len(), ptr::null_mut());
+        assert!(!value.is_null());
+        assert_eq!(sexpr_kind(value), SexprKind::List);
+        assert_eq!(sexpr_list_len(value), 6);
+        assert!(sexpr_list_get(value, 6).is_null());
+        assert_eq!(sexpr_kind(sexpr_list_get(value, 6)), SexprKind::Invalid);
+
+        let mut len = 0;
+        let head = sexpr_atom_text(sexpr_list_get(value, 0), &mut len);
+        assert_eq!(
+            std::slice::from_raw_parts(head as *const u8, len),
+            b"define"
+        );
+        let string = sexpr_list_get(value, 2);
+        assert_eq!(sexpr_kind(string), SexprKind::String);
+        sexpr_atom_text(string, &mut len);
+        assert_eq!(len, 8);
+
+        let (mut int, mut float, mut b) = (0i64, 0f64, false);
+        assert!(sexpr_as_i64(sexpr_list_get(value, 3), &mut int));
+        assert_eq!(int, 42);
+        assert!(!sexpr_as_i64(sexpr_list_get(value, 4), &mut int));
+        assert!(sexpr_as_f64(sexpr_list_get(value, 4), &mut float));
+        assert_eq!(float, -1.5);
+        assert!(!sexpr_as_bool(sexpr_list_get(value, 4), &mut b));
+        assert!(sexpr_as_bool(sexpr_list_get(value, 5), &mut b));
+        assert!(b);
+
+        let out = sexpr_to_string(sexpr_list_get(value, 1), false);
+        assert_eq!(CStr::from_ptr(out).to_str().unwrap(), "greeting");
+        sexpr_string_free(out);
+        sexpr_free(value);
+
+        let mut error = ptr::null_mut();
+        let text = "(1 2";
+        assert!(sexpr_parse(text.as_ptr() as *const _, text.len(), &mut error).is_null());
+        assert_eq!(
+            CStr::from_ptr(error).to_str().unwrap(),
+            "EOF while parsing a list at line 1 column 4"
+        );
+        sexpr_string_free(error);
+    }
+}
+
+#[test]
+fn test_enum_head_forms() {
+    use serde::Deserialize as _;
+    use sexpr::de::{Deserializer, DeserializerOptions};
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Shape {
+        Empty,
+        Square(u32),
+        Rect(u32, u32),
+        Circle { radius: u32 },
+    }
+
+    let shape = |input: &str| sexpr::from_str::<Shape>(input);
+
+    // Unit variants are a name, or a list holding only the name.
+    assert_eq!(shape("empty").unwrap(), Shape::Empty);
+    assert_eq!(shape("\"empty\"").unwrap(), Shape::Empty);
+    assert_eq!(shape("(empty)").unwrap(), Shape::Empty);
+
+    // Otherwise the head of the list names the variant.
+    assert_eq!(shape("(square 2)").unwrap(), Shape::Square(2));
+    assert_eq!(shape("(rect 1 2)").unwrap(), Shape::Rect(1, 2));
+    assert_eq!(
+        shape("(circle (radius . 3))").unwrap(),
+        Shape::Circle { radius: 3 }
+    );
+    assert_eq!(
+        sexpr::from_str::<Vec<Shape>>("(empty (square 1) (rect 3 4))").unwrap(),
+        [Shape::Empty, Shape::Square(1), Shape::Rect(3, 4)]
+    );
+
+    // The dotted form takes the content as one value.
+    assert_eq!(shape("(square . 2)").unwrap(), Shape::Square(2));
+    assert_eq!(shape("(rect . (1 2))").unwrap(), Shape::Rect(1, 2));
+    assert_eq!(
+        shape("(circle . ((radius . 3)))").unwrap(),
+        Shape::Circle { radius: 3 }
+    );
+
+    assert_eq!(
+        shape("(rect 1)").unwrap_err().to_string(),
+        "expected 2 elements, found 1 at line 1 column 8"
+    );
+    assert_eq!(
+        shape("(square 1 2)").unwrap_err().to_string(),
+        "trailing characters at line 1 column 11"
+    );
+    assert_eq!(
+        shape("(oval 1)").unwrap_err().to_string(),
+        "unknown variant `oval`, expected one of `empty`, `square`, `rect`, `circle` at line 1 column 5"
+    );
+
+    // Names match the variant's serde name exactly, so an enum that is not
+    // renamed needs its names capitalized.
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Shape2 {
+        Empty,
+        Circle(f64),
+    }
+
+    assert_eq!(
+        sexpr::from_str::<Shape2>("(Circle 1.0)").unwrap(),
+        Shape2::Circle(1.0)
+    );
+    assert_eq!(
+        sexpr::from_str::<Shape2>("(circle 1.0)")
+            .unwrap_err()
+            .to_string(),
+        "unknown variant `circle`, expected `Empty` or `Circle` at line 1 column 7"
+    );
+    assert_eq!(
+        sexpr::from_str::<Shape2>("empty").unwrap_err().to_string(),
+        "unknown variant `empty`, expected `Empty` or `Circle` at line 1 column 5"
+    );
+
+    // Folding symbols to lower case folds the names too.
+    let options = DeserializerOptions {
+        case_insensitive: true,
+        ..DeserializerOptions::default()
+    };
+    let folded = |input: &str| {
+        let mut de = Deserializer::from_str_with_options(input, options.clone());
+        Shape::deserialize(&mut de)
+    };
+    assert_eq!(
+        folded("(Circle (radius . 3))").unwrap(),
+        Shape::Circle { radius: 3 }
+    );
+    assert_eq!(folded("EMPTY").unwrap(), Shape::Empty);
+    let mut de = Deserializer::from_str_with_options("(Circle 1.0)", options.clone());
+    assert!(Shape2::deserialize(&mut de).is_err());
+}
+
+#[test]
+fn test_unit_symbols() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct EndMarker;
+
+    fn to_unit_symbols<T: ser::Serialize>(value: &T) -> String {
+        let mut ser = Serializer::new(Vec::new()).with_unit_symbols(true);
+        value.serialize(&mut ser).unwrap();
+        String::from_utf8(ser.into_inner()).unwrap()
+    }
+
+    assert_eq!(to_string(&EndMarker).unwrap(), "#nil");
+    assert_eq!(to_string(&Animal::Dog).unwrap(), "\"Dog\"");
+    assert_eq!(to_unit_symbols(&EndMarker), "end-marker");
+    assert_eq!(to_unit_symbols(&Animal::Dog), "Dog");
+    assert_eq!(
+        to_unit_symbols(&vec![Some(Animal::Dog), None]),
+        "(Dog #nil)"
+    );
+
+    // Both forms read back.
+    let marker: EndMarker = sexpr::from_str("end-marker").unwrap();
+    assert_eq!(marker, EndMarker);
+    let marker: EndMarker = sexpr::from_str("#nil").unwrap();
+    assert_eq!(marker, EndMarker);
+    let dogs: Vec<Animal> = sexpr::from_str(&to_unit_symbols(&[Animal::Dog, Animal::Dog])).unwrap();
+    assert_eq!(dogs, [Animal::Dog, Animal::Dog]);
+
+    let err = sexpr::from_str::<EndMarker>("marker").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid value: string \"marker\", expected unit struct EndMarker at line 1 column 6"
+    );
+}
+
+#[test]
+fn test_nil_style() {
+    use sexpr::de::{Deserializer, DeserializerOptions};
+    use sexpr::ser::NilStyle;
+
+    fn write<T: ser::Serialize>(value: &T, nil_style: NilStyle) -> String {
+        let mut ser = Serializer::new(Vec::new()).with_nil_style(nil_style);
+        value.serialize(&mut ser).unwrap();
+        String::from_utf8(ser.into_inner()).unwrap()
+    }
+
+    fn read<'a, T: serde::Deserialize<'a>>(s: &'a str, options: DeserializerOptions) -> T {
+        let mut de = Deserializer::from_str_with_options(s, options);
+        let value = T::deserialize(&mut de).unwrap();
+        de.end().unwrap();
+        value
+    }
+
+    let empty_list = DeserializerOptions {
+        empty_list_as_nil: true,
+        ..DeserializerOptions::default()
+    };
+    let nil_symbol = DeserializerOptions {
+        nil_symbol: true,
+        ..DeserializerOptions::default()
+    };
+
+    // By default `#nil` is nil and `()` is the empty list.
+    let nested = Sexp::List(vec![Sexp::Nil, Sexp::List(vec![])]);
+    assert_eq!(to_string(&nested).unwrap(), "(#nil ())");
+    assert_eq!(sexpr::from_str::<Sexp>("(#nil ())").unwrap(), nested);
+    assert_eq!(sexpr::from_str::<Sexp>("nil").unwrap(), Sexp::from("nil"));
+
+    assert_eq!(write(&nested, NilStyle::EmptyList), "(() ())");
+    assert_eq!(write(&nested, NilStyle::Symbol), "(nil ())");
+    let mut ser = Serializer::new(Vec::new())
+        .with_nil_style(NilStyle::Symbol)
+        .with_non_finite_floats(NonFiniteFloats::WriteNil);
+    ser::Serialize::serialize(&f64::NAN, &mut ser).unwrap();
+    assert_eq!(ser.into_inner(), b"nil");
+
+    let mixed: Sexp = read("(() ( ) #nil)", empty_list.clone());
+    assert_eq!(
+        mixed,
+        Sexp::List(vec![Sexp::Nil, Sexp::List(vec![]), Sexp::Nil])
+    );
+    let mixed: Sexp = read("(nil #nil never)", nil_symbol.clone());
+    assert_eq!(
+        mixed,
+        Sexp::List(vec![Sexp::Nil, Sexp::Nil, Sexp::from("never")])
+    );
+
+    // Each style reads back with the matching option.
+    let entries = vec![(None, Some(vec![1]), ()), (Some("n".to_owned()), None, ())];
+    for (style, options) in [
+        (NilStyle::Hash, DeserializerOptions::default()),
+        (NilStyle::EmptyList, empty_list.clone()),
+        (NilStyle::Symbol, nil_symbol),
+    ] {
+        let text = write(&entries, style);
+        assert_eq!(
+            read::<Vec<(Option<String>, Option<Vec<u8>>, ())>>(&text, options),
+            entries,
+            "{}",
+            text
+        );
+    }
+
+    let mut de = Deserializer::from_reader_with_options(&b"(#nil () 1)"[..], empty_list.clone());
+    let options: Vec<Option<u8>> = serde::Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(options, [None, None, Some(1)]);
+
+    // A list type still reads `()` as empty.
+    assert_eq!(read::<Vec<u8>>("()", empty_list.clone()), Vec::<u8>::new());
+    assert_eq!(read::<Vec<Vec<u8>>>("(())", empty_list), vec![vec![]]);
+
+    // Only the symbol `nil` itself is a `None`.
+    assert_eq!(
+        sexpr::from_str::<Option<Sexp>>("nab").unwrap(),
+        Some(Sexp::from("nab"))
+    );
+    assert_eq!(sexpr::from_str::<Option<Sexp>>("nil").unwrap(), None);
+    assert_eq!(
+        sexpr::from_str::<Option<Animal>>("nab")
+            .unwrap_err()
+            .to_string(),
+        "unknown variant `nab`, expected one of `Dog`, `Frog`, `Cat`, `AntHive` at line 1 column 3"
+    );
+}
+
+#[test]
+fn test_write_empty_lists() {
+    use serde::ser::{SerializeMap, SerializeSeq};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize)]
+    struct NoFields {}
+
+    #[derive(Debug, PartialEq, Serialize)]
+    struct NoElements();
+
+    #[derive(Debug, PartialEq, Serialize)]
+    enum Empty {
+        Tuple(),
+        Struct {},
+    }
+
+    // Collections whose length hint is missing or wrong.
+    #[derive(Debug, PartialEq)]
+    enum Hinted {
+        Seq(Option<usize>, Vec<u8>),
+        Map(Option<usize>, Vec<(u8, u8)>),
+    }
+
+    impl ser::Serialize for Hinted {
+        fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match *self {
+                Hinted::Seq(len, ref elems) => {
+                    let mut seq = serializer.serialize_seq(len)?;
+                    for elem in elems {
+                        seq.serialize_element(elem)?;
+                    }
+                    seq.end()
+                }
+                Hinted::Map(len, ref entries) => {
+                    let mut map = serializer.serialize_map(len)?;
+                    for (key, value) in entries {
+                        map.serialize_entry(key, value)?;
+                    }
+                    map.end()
+                }
+            }
+        }
+    }
+
+    test_encode_ok(&[(Vec::<u8>::new(), "()")]);
+    test_encode_ok(&[(BTreeMap::<String, u8>::new(), "()")]);
+    test_encode_ok(&[(NoFields {}, "()")]);
+    test_encode_ok(&[(NoElements(), "()")]);
+    test_encode_ok(&[
+        (Empty::Tuple(), "(\"Tuple\".())"),
+        (Empty::Struct {}, "(\"Struct\".())"),
+    ]);
+    test_encode_ok(&[(Sexp::List(vec![]), "()")]);
+    test_encode_ok(&[
+        (vec![vec![Vec::<u8>::new()], vec![]], "((()) ())"),
+        (vec![vec![], vec![vec![1u8]]], "(() ((1)))"),
+    ]);
+    test_encode_ok(&[(vec![BTreeMap::<String, u8>::new(); 2], "(() ())")]);
+    test_encode_ok(&[(
+        (Vec::<u8>::new(), NoFields {}, BTreeMap::<String, u8>::new()),
+        "(() () ())",
+    )]);
+    test_encode_ok(&[
+        (Hinted::Seq(None, vec![]), "()"),
+        (Hinted::Seq(Some(3), vec![]), "()"),
+        (Hinted::Seq(Some(0), vec![1, 2]), "(1 2)"),
+        (Hinted::Map(None, vec![]), "()"),
+        (Hinted::Map(Some(0), vec![(1, 2)]), "(\"1\".2)"),
+    ]);
+
+    // Sorting the keys of an empty map writes nothing extra either.
+    let mut ser = Serializer::new(Vec::new()).with_sort_keys(true);
+    ser::Serialize::serialize(&Hinted::Map(Some(0), vec![]), &mut ser).unwrap();
+    assert_eq!(ser.into_inner(), b"()");
+
+    // The pretty printer opens and closes empty lists on one line.
+    let value = vec![Hinted::Seq(None, vec![]), Hinted::Seq(Some(0), vec![])];
+    assert_eq!(
+        sexpr::ser::to_string_pretty(&value).unwrap(),
+        "(\n  ()\n  ()\n)"
+    );
+}
+
+#[test]
+fn test_bytes_styles() {
+    use serde_bytes::ByteBuf;
+    use sexpr::ser::BytesStyle;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Blob(#[serde(with = "serde_bytes")] Vec<u8>);
+
+    fn write(bytes: &[u8], bytes_style: BytesStyle) -> String {
+        let mut ser = Serializer::new(Vec::new()).with_bytes_style(bytes_style);
+        ser::Serialize::serialize(&serde_bytes::Bytes::new(bytes), &mut ser).unwrap();
+        String::from_utf8(ser.into_inner()).unwrap()
+    }
+
+    assert_eq!(write(b"hi!", BytesStyle::List), "(104 105 33)");
+    assert_eq!(write(b"hi!", BytesStyle::ByteVector), "#u8(104 105 33)");
+    assert_eq!(write(b"hi!", BytesStyle::Hex), "#x\"686921\"");
+    assert_eq!(write(b"hi!", BytesStyle::Base64), "|aGkh|");
+    assert_eq!(write(b"", BytesStyle::Hex), "#x\"\"");
+    assert_eq!(write(b"", BytesStyle::Base64), "||");
+    assert_eq!(write(b"\xff\x00", BytesStyle::Base64), "|/wA=|");
+    assert_eq!(write(b"\xfa", BytesStyle::Base64), "|+g==|");
+
+    // There are no empty hex strings between `#` characters, so the empty
+    // string stands in.
+    let mut ser = Serializer::new(Vec::new())
+        .with_bytes_style(BytesStyle::Hex)
+        .with_hex_style(sexpr::ser::HexStyle::Hash);
+    ser::Serialize::serialize(&serde_bytes::Bytes::new(b"hi!"), &mut ser).unwrap();
+    ser::Serialize::serialize(&serde_bytes::Bytes::new(b""), &mut ser).unwrap();
+    assert_eq!(ser.into_inner(), b"#686921#\"\"");
+
+    // Every style reads back, whichever was chosen.
+    let samples: &[&[u8]] = &[b"", b"h", b"hi", b"hi!", b"\x00\xff\x10\x7f"];
+    for &bytes in samples {
+        for &style in &[
+            BytesStyle::List,
+            BytesStyle::ByteVector,
+            BytesStyle::Hex,
+            BytesStyle::Base64,
+        ] {
+            let text = write(bytes, style);
+            let buf: ByteBuf = sexpr::from_str(&text).unwrap();
+            assert_eq!(&buf[..], bytes, "{}", text);
+            let buf: ByteBuf = sexpr::from_reader(text.as_bytes()).unwrap();
+            assert_eq!(&buf[..], bytes, "{}", text);
+        }
+    }
+
+    let blobs: Vec<Blob> =
+        sexpr::from_str(r#"("ab" (97 98) #u8(97 98) #6162# |YWI=| |YWI|)"#).unwrap();
+    assert!(blobs.iter().all(|blob| blob.0 == b"ab"));
+
+    // A plain `Vec<u8>` and a `Sexp` read a bytevector as its list.
+    let bytes: Vec<u8> = sexpr::from_str("#u8(1 2 3)").unwrap();
+    assert_eq!(bytes, [1, 2, 3]);
+    let sexp: Sexp = sexpr::from_str("(#u8() x)").unwrap();
+    assert_eq!(sexp, Sexp::List(vec![Sexp::List(vec![]), Sexp::from("x")]));
+    let skipped: (u8, serde::de::IgnoredAny, u8) = sexpr::from_str("(1 #u8(2 3) 4)").unwrap();
+    assert_eq!((skipped.0, skipped.2), (1, 4));
+
+    let err = sexpr::from_str::<ByteBuf>("|a!==|").unwrap_err();
+    assert_eq!(err.to_string(), "invalid base64 at line 1 column 6");
+    let err = sexpr::from_str::<ByteBuf>("#f").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: boolean `false`, expected byte array at line 1 column 2"
+    );
+}
+
+#[test]
+fn test_hex_and_base64_bytes() {
+    use serde::Deserialize as _;
+    use sexpr::de::{Deserializer, DeserializerOptions};
+    use sexpr::ser::{BytesStyle, HexStyle};
+    use sexpr::{Base64Bytes, HexBytes};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Key(HexBytes, Base64Bytes, Vec<u8>);
+
+    fn write(value: &Key, bytes_style: BytesStyle, hex_style: HexStyle) -> String {
+        let mut ser = Serializer::new(Vec::new())
+            .with_bytes_style(bytes_style)
+            .with_hex_style(hex_style);
+        ser::Serialize::serialize(value, &mut ser).unwrap();
+        String::from_utf8(ser.into_inner()).unwrap()
+    }
+
+    let key = Key(
+        HexBytes(vec![0xde, 0xad, 0xbe, 0xef]),
+        Base64Bytes(b"hello".to_vec()),
+        vec![1, 2],
+    );
+
+    // The wrappers keep their encoding whatever the serializer's style.
+    assert_eq!(
+        write(&key, BytesStyle::List, HexStyle::Hash),
+        "(#DEADBEEF# |aGVsbG8=| (1 2))"
+    );
+    assert_eq!(
+        write(&key, BytesStyle::Base64, HexStyle::Prefixed),
+        "(#x\"DEADBEEF\" |aGVsbG8=| (1 2))"
+    );
+    assert_eq!(
+        write(
+            &Key(HexBytes::default(), Base64Bytes::default(), vec![]),
+            BytesStyle::List,
+            HexStyle::Prefixed
+        ),
+        "(#x\"\" || ())"
+    );
+
+    for &hex_style in &[HexStyle::Hash, HexStyle::Prefixed] {
+        let text = write(&key, BytesStyle::List, hex_style);
+        assert_eq!(sexpr::from_str::<Key>(&text).unwrap(), key, "{}", text);
+        assert_eq!(sexpr::from_reader::<_, Key>(text.as_bytes()).unwrap(), key);
+    }
+
+    // Either wrapper reads any form of byte string.
+    let hex: HexBytes = sexpr::from_str("|3q2+7w==|").unwrap();
+    assert_eq!(hex, key.0);
+    let base64: Base64Bytes = sexpr::from_str("#x\"68656c6c6f\"").unwrap();
+    assert_eq!(base64, key.1);
+    let base64: Base64Bytes = sexpr::from_str("\"hello\"").unwrap();
+    assert_eq!(*base64, b"hello");
+
+    // Other serializers see plain bytes.
+    assert_eq!(
+        sexpr::to_value(&key.0).unwrap(),
+        Sexp::Bytes(key.0.to_vec())
+    );
+
+    // `#x"..."` is always a byte string, even when it is UTF-8, though a
+    // string or an atom still reads it as text. Skipping one works.
+    let sexp: Sexp = sexpr::from_str("#x\"6869\"").unwrap();
+    assert_eq!(sexp, Sexp::Bytes(b"hi".to_vec()));
+    let text: String = sexpr::from_str("#x\"6869\"").unwrap();
+    assert_eq!(text, "hi");
+    let atom: sexpr::sexp::Atom = sexpr::from_str("#x\"6869\"").unwrap();
+    assert_eq!(atom, sexpr::sexp::Atom::string("hi"));
+    assert!(sexpr::from_str::<sexpr::sexp::Atom>("#x\"FF\"").is_err());
+    let skipped: (serde::de::IgnoredAny, u8) = sexpr::from_str("(#x\"6 9\" 4)").unwrap();
+    assert_eq!(skipped.1, 4);
+
+    let err = sexpr::from_str::<HexBytes>("#x\"6\"").unwrap_err();
+    assert_eq!(err.to_string(), "invalid escape at line 1 column 5");
+
+    // Base64 between bars is a symbol to a `Sexp` unless the options read
+    // bars as base64, when it round trips as bytes.
+    let text = sexpr::to_string(&key.1).unwrap();
+    assert_eq!(text, "|aGVsbG8=|");
+    assert_eq!(
+        sexpr::from_str::<Sexp>(&text).unwrap(),
+        Sexp::from("aGVsbG8=")
+    );
+    let options = DeserializerOptions {
+        base64_bars: true,
+        ..DeserializerOptions::default()
+    };
+    let read = |input: &str| {
+        Sexp::deserialize(&mut Deserializer::from_str_with_options(
+            input,
+            options.clone(),
+        ))
+    };
+    let sexp = read(&text).unwrap();
+    assert_eq!(sexp, Sexp::Bytes(b"hello".to_vec()));
+    assert_eq!(sexp, sexpr::to_value(&key.1).unwrap());
+    let base64: Base64Bytes = sexpr::from_value(sexp).unwrap();
+    assert_eq!(base64, key.1);
+    assert_eq!(
+        read("(a || \"b\")").unwrap(),
+        Sexp::List(vec![
+            Sexp::from("a"),
+            Sexp::Bytes(vec![]),
+            Sexp::Atom(sexpr::sexp::Atom::string("b"))
+        ])
+    );
+    let err = read("|a b|").unwrap_err();
+    assert_eq!(err.kind(), sexpr::error::ErrorKind::InvalidBase64);
+}
+
+#[test]
+fn test_sexp_bytes() {
+    use sexpr::sexp::{SexpRc, SexpRef};
+    use sexpr::HexBytes;
+
+    // Bytes that are not UTF-8 read into a `Sexp` and are written back as
+    // they were.
+    let sexp: Sexp = sexpr::from_str("(key #x\"00FF80\")").unwrap();
+    assert_eq!(
+        sexp,
+        Sexp::List(vec![Sexp::from("key"), Sexp::Bytes(vec![0x00, 0xff, 0x80])])
+    );
+    assert_eq!(sexp.to_string(), "(key #x\"00FF80\")");
+    assert_eq!(sexpr::from_str::<Sexp>(&sexp.to_string()).unwrap(), sexp);
+    assert_eq!(
+        sexp.debug_pretty(),
+        "List [\n  Symbol(key)\n  Bytes(00FF80)\n]"
+    );
+    assert_eq!(sexp.to_json_string(), "[\"key\",[0,255,128]]");
+
+    // The other trees keep them too.
+    let rc: SexpRc = sexpr::from_str("#x\"00FF\"").unwrap();
+    assert_eq!(rc, SexpRc::Bytes(vec![0x00, 0xff]));
+    assert_eq!(sexpr::to_string(&rc).unwrap(), "#x\"00FF\"");
+    assert_eq!(
+        sexpr::to_string(&SexpRef::Bytes(&[0xab])).unwrap(),
+        "#x\"AB\""
+    );
+    let shared = sexpr::sexp::Interner::new().from_str("#x\"00FF\"").unwrap();
+    assert_eq!(shared.to_sexp(), Sexp::Bytes(vec![0x00, 0xff]));
+
+    // A byte string in a `Sexp` reads back into any byte type.
+    let bytes = Sexp::Bytes(vec![0xde, 0xad]);
+    let hex: HexBytes = sexpr::from_value(bytes.clone()).unwrap();
+    assert_eq!(*hex, [0xde, 0xad]);
+    let buf: serde_bytes::ByteBuf = sexpr::from_value(bytes.clone()).unwrap();
+    assert_eq!(*buf, [0xde, 0xad]);
+    assert_eq!(sexpr::to_value(&hex).unwrap(), bytes);
+
+    // By default a plain byte buffer is written in hex, so it reads back
+    // into a `Sexp` as bytes rather than as a list of integers.
+    let text = sexpr::to_string(&serde_bytes::ByteBuf::from(vec![1, 2])).unwrap();
+    assert_eq!(text, "#x\"0102\"");
+    assert_eq!(
+        sexpr::from_str::<Sexp>(&text).unwrap(),
+        Sexp::Bytes(vec![1, 2])
+    );
+}
+
+#[test]
+fn test_to_display_string() {
+    use sexpr::sexp::Atom;
+
+    let v: Sexp = sexpr::from_str("(say \"a\\\"b\\n\" |x y| 3 -1.5 #t #nil ())").unwrap();
+    assert_eq!(v.to_display_string(), "(say a\"b\n x y 3 -1.5 #t #nil ())");
+
+    let v = Sexp::List(vec![
+        Sexp::pair(Sexp::Atom(Atom::keyword("id")), "seven"),
+        Sexp::pair("k", Sexp::List(vec![Sexp::from(1)])),
+    ]);
+    assert_eq!(v.to_display_string(), "((#:id . seven) (k . (1)))");
+    assert_eq!(Sexp::from("").to_display_string(), "");
+}
+
+#[test]
+fn test_datum_labels() {
+    use serde::de::IgnoredAny;
+    use serde::Serialize;
+    use sexpr::de::{Deserializer, DeserializerOptions};
+    use sexpr::ser::{BytesStyle, HexStyle};
+    use sexpr::sexp::Atom;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Unit {
+        Celsius,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Reading {
+        greenhouse: u8,
+    }
+
+    let records: Vec<Sexp> = (0..3)
+        .map(|n| {
+            Sexp::List(vec![
+                Sexp::Atom(Atom::symbol("temperature")),
+                Sexp::Atom(Atom::keyword("location")),
+                Sexp::Atom(Atom::string("greenhouse")),
+                Sexp::Atom(Atom::symbol("id")),
+                Sexp::from(n),
+            ])
+        })
+        .collect();
+
+    let mut ser = Serializer::new(Vec::new()).with_datum_labels(Some(4));
+    for record in &records {
+        record.serialize(&mut ser).unwrap();
+    }
+    let text = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(
+        text,
+        "(temperature #:location \"greenhouse\" id 0)\
+         (#0=temperature #1=#:location #2=\"greenhouse\" id 1)\
+         (#0# #1# #2# id 2)"
+    );
+
+    let options = DeserializerOptions {
+        datum_labels: true,
+        ..DeserializerOptions::default()
+    };
+    let read: Vec<Sexp> = Deserializer::from_str_with_options(&text, options.clone())
+        .into_iter()
+        .collect::<sexpr::Result<_>>()
+        .unwrap();
+    assert_eq!(read, records);
+
+    // Labels reach field names, unit variants and skipped values.
+    let text = "(#0=Celsius #0#) (#1=\"greenhouse\" 1) ((#1# . 2))";
+    let mut de = Deserializer::from_str_with_options(text, options.clone());
+    let units: (Unit, Unit) = serde::Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(units, (Unit::Celsius, Unit::Celsius));
+    let skipped: (IgnoredAny, u8) = serde::Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(skipped.1, 1);
+    let reading: Reading = serde::Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(reading, Reading { greenhouse: 2 });
+
+    // Without the option, or before a definition, a label is an error.
+    assert!(sexpr::from_str::<Sexp>("#0=abc").is_err());
+    let err = Deserializer::from_str_with_options("(#0# 1)", options)
+        .into_iter::<Sexp>()
+        .next()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "unknown datum label at line 1 column 4");
+
+    // Hex bytes that start with a decimal digit are written so that they
+    // do not read as a label.
+    let mut ser = Serializer::new(Vec::new())
+        .with_bytes_style(BytesStyle::Hex)
+        .with_hex_style(HexStyle::Hash)
+        .with_datum_labels(Some(4));
+    serde_bytes::Bytes::new(&[0x12, 0xab])
+        .serialize(&mut ser)
+        .unwrap();
+    serde_bytes::Bytes::new(&[0xab, 0x12])
+        .serialize(&mut ser)
+        .unwrap();
+    assert_eq!(ser.into_inner(), b"#x\"12AB\"#AB12#");
+}
+
+#[test]
+fn test_journal() {
+    use sexpr::error::ErrorKind;
+    use sexpr::journal::{JournalReader, JournalWriter};
+
+    let mut journal = JournalWriter::new(Vec::new());
+    journal.append(&(1, "a")).unwrap();
+    let first = journal.get_ref().len();
+    assert_eq!(journal.get_ref(), b"7 c9f74b67 (1 \"a\")\n");
+    journal.append(&(2, "line\nbreak")).unwrap();
+    let second = journal.get_ref().len();
+    journal.append(&(3, "c")).unwrap();
+    let log = journal.into_inner();
+
+    let read_all = |log: &[u8]| {
+        let mut reader = JournalReader::new(log);
+        let mut records = Vec::new();
+        while let Some(record) = reader.read_record::<(u8, String)>()? {
+            records.push(record.0);
+        }
+        Ok::<_, sexpr::Error>((records, reader.is_torn(), reader.valid_len()))
+    };
+    assert_eq!(
+        read_all(&log).unwrap(),
+        (vec![1, 2, 3], false, log.len() as u64)
+    );
+    assert_eq!(read_all(b"").unwrap(), (vec![], false, 0));
+
+    // Cutting the last record anywhere tears it.
+    for cut in second + 1..log.len() {
+        assert_eq!(
+            read_all(&log[..cut]).unwrap(),
+            (vec![1, 2], true, second as u64),
+            "cut at {}",
+            cut
+        );
+    }
+
+    // A last record that fails its checksum is torn too, but one followed
+    // by more records is damaged.
+    let mut damaged = log.clone();
+    let at = damaged.len() - 3;
+    damaged[at] = b'd';
+    assert_eq!(
+        read_all(&damaged).unwrap(),
+        (vec![1, 2], true, second as u64)
+    );
+    let mut damaged = log.clone();
+    damaged[first + 13] = b'X';
+    let err = read_all(&damaged).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ChecksumMismatch);
+    assert_eq!(
+        err.to_string(),
+        format!("checksum mismatch in journal record at byte {}", first)
+    );
+
+    let err = read_all(b"x 00000000 #t\n").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidRecordFrame);
+    assert!(read_all(b"2 00000000 #t#t\n").is_err());
+}
+
+#[test]
+fn test_symbol_case() {
+    use serde::Serialize;
+    use sexpr::de::{Deserializer, DeserializerOptions};
+    use sexpr::ser::{NilStyle, SymbolCase};
+    use sexpr::sexp::Atom;
+
+    let value = (
+        Atom::symbol("Reg-A"),
+        Atom::keyword("Wide"),
+        Atom::symbol("a b"),
+        "Text",
+        (),
+    );
+    let write = |symbol_case| {
+        let mut ser = Serializer::new(Vec::new())
+            .with_nil_style(NilStyle::Symbol)
+            .with_symbol_case(symbol_case);
+        value.serialize(&mut ser).unwrap();
+        String::from_utf8(ser.into_inner()).unwrap()
+    };
+    assert_eq!(
+        write(SymbolCase::Preserve),
+        "(Reg-A #:Wide |a b| \"Text\" nil)"
+    );
+    assert_eq!(
+        write(SymbolCase::Upcase),
+        "(REG-A #:WIDE |A B| \"Text\" NIL)"
+    );
+    assert_eq!(
+        write(SymbolCase::Downcase),
+        "(reg-a #:wide |a b| \"Text\" nil)"
+    );
+
+    // Folding on input reads upcased symbols back in lower case.
+    let options = DeserializerOptions {
+        case_insensitive: true,
+        nil_symbol: true,
+        ..DeserializerOptions::default()
+    };
+    let text = write(SymbolCase::Upcase);
+    let mut de = Deserializer::from_str_with_options(&text, options);
+    let read: (Atom, Atom, Atom, String, ()) = serde::Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(read.0, Atom::symbol("reg-a"));
+    assert_eq!(read.1, Atom::keyword("wide"));
+    assert_eq!(read.2, Atom::symbol("A B"));
+}
+
+#[test]
+fn test_invalid_symbols() {
+    use serde::Serialize;
+    use sexpr::error::ErrorKind;
+    use sexpr::ser::InvalidSymbols;
+    use sexpr::sexp::Atom;
+
+    let err = Atom::try_new_symbol("has space)".into()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidSymbol);
+    assert_eq!(err.to_string(), "`has space)` is not a valid bare symbol");
+    assert!(Atom::try_new_symbol(String::new()).is_err());
+    assert!(Atom::try_new_symbol("1st".into()).is_err());
+    assert_eq!(
+        Atom::try_new_keyword("port".into()).unwrap(),
+        Atom::keyword("port")
+    );
+    assert!(Atom::try_new_keyword("a;b".into()).is_err());
+
+    let write = |value: &Sexp, invalid_symbols| {
+        let mut ser = Serializer::new(Vec::new()).with_invalid_symbols(invalid_symbols);
+        value
+            .serialize(&mut ser)
+            .map(|()| String::from_utf8(ser.into_inner()).unwrap())
+    };
+    let value = Sexp::List(vec![
+        Sexp::Atom(Atom::symbol("ok")),
+        Sexp::Atom(Atom::keyword("a b")),
+    ]);
+    assert_eq!(
+        write(&value, InvalidSymbols::Quote).unwrap(),
+        "(ok #:|a b|)"
+    );
+    let err = write(&value, InvalidSymbols::Reject).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidSymbol);
+    let fine = Sexp::List(vec![
+        Sexp::Atom(Atom::symbol("ok")),
+        Sexp::Atom(Atom::string("a b")),
+    ]);
+    assert_eq!(
+        write(&fine, InvalidSymbols::Reject).unwrap(),
+        "(ok \"a b\")"
+    );
+}
+
+#[test]
+fn test_malformed_alist_entries() {
+    use sexpr::de::{Deserializer, DeserializerOptions};
+    use sexpr::error::{Category, ErrorKind};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        a: u8,
+        c: Vec<u8>,
+    }
+
+    let input = "((a . 1)\n b () ((x) 2) [q . 3] (c 4 5))";
+    let err = sexpr::from_str::<Config>(input).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MalformedAlistEntry);
+    assert_eq!(err.classify(), Category::Data);
+    assert_eq!(
+        err.to_string(),
+        "alist entry 1 is not a (key . value) pair at line 2 column 2"
+    );
+    let err = sexpr::from_str::<Config>("((a . 1) () (c))").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "alist entry 1 is not a (key . value) pair at line 1 column 10"
+    );
+    let err = sexpr::from_str::<Config>("((a . 1) (c) ((x) 2))").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "alist entry 2 is not a (key . value) pair at line 1 column 14"
+    );
+
+    let options = DeserializerOptions {
+        skip_malformed_entries: true,
+        square_brackets: true,
+        ..DeserializerOptions::default()
+    };
+    let mut de = Deserializer::from_str_with_options(input, options.clone());
+    let config: Config = serde::Deserialize::deserialize(&mut de).unwrap();
+    de.end().unwrap();
+    assert_eq!(
+        config,
+        Config {
+            a: 1,
+            c: vec![4, 5]
+        }
+    );
+
+    // Skipping still checks the brackets of the entries it skips.
+    let mut de = Deserializer::from_str_with_options("((a . 1) ((x) 2] (c))", options);
+    assert!(<Config as serde::Deserialize>::deserialize(&mut de).is_err());
+}
+
+#[test]
+fn test_dotted_tuples() {
+    use sexpr::error::ErrorKind;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Point(i32, i32);
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Shape {
+        a: (i32, i32),
+        b: Point,
+        c: (i32, (i32, i32)),
+    }
+
+    assert_eq!(sexpr::from_str::<(i32, i32)>("(1 . 2)").unwrap(), (1, 2));
+    assert_eq!(sexpr::from_str::<(i32, i32)>("(1 2)").unwrap(), (1, 2));
+    assert_eq!(
+        sexpr::from_str::<(u8, String, bool)>("(1 \"x\" . #t)").unwrap(),
+        (1, "x".to_owned(), true)
+    );
+
+    let expected = Shape {
+        a: (1, 2),
+        b: Point(3, 4),
+        c: (5, (6, 7)),
+    };
+    let input = "((a . (1 . 2)) (b . (3 . 4)) (c . (5 . (6 . 7))))";
+    assert_eq!(sexpr::from_str::<Shape>(input).unwrap(), expected);
+    let input = "((a 1 . 2) (b 3 . 4) (c 5 . (6 . 7)))";
+    assert_eq!(sexpr::from_str::<Shape>(input).unwrap(), expected);
+    let input = "((a 1 2) (b 3 4) (c 5 (6 7)))";
+    assert_eq!(sexpr::from_str::<Shape>(input).unwrap(), expected);
+
+    // The tail must be the last element, and only one element long.
+    let err = sexpr::from_str::<(i32, i32, i32)>("(1 . 2)").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidLength);
+    let err = sexpr::from_str::<(i32, i32)>("(1 . 2 3)").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TrailingCharacters);
+    let err = sexpr::from_str::<(i32, i32)>("(. 2)").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ExpectedSomeValue);
+    assert!(sexpr::from_str::<Shape>("((a 1 . 2 3) (b 3 4) (c 5 (6 7)))").is_err());
+
+    // The values of a map may be dotted too, in each of those layouts.
+    let input = "((a . (1 . 2)) (b 3 . 4) (c 5 6))";
+    let map: BTreeMap<String, (i32, i32)> = sexpr::from_str(input).unwrap();
+    let expected: BTreeMap<String, (i32, i32)> = vec![
+        ("a".to_owned(), (1, 2)),
+        ("b".to_owned(), (3, 4)),
+        ("c".to_owned(), (5, 6)),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(map, expected);
+    let map: HashMap<String, Sexp> = sexpr::from_str("((a . (1 . 2)) (b 3 . 4))").unwrap();
+    assert_eq!(map["a"], Sexp::pair(1, 2));
+    assert_eq!(map["b"], Sexp::pair(3, 4));
+    let input = "((outer . ((inner . (1 . (2 . 3))))))";
+    let nested: BTreeMap<String, BTreeMap<String, (i32, (i32, i32))>> =
+        sexpr::from_str(input).unwrap();
+    assert_eq!(nested["outer"]["inner"], (1, (2, 3)));
+    let err = sexpr::from_str::<BTreeMap<String, (i32, i32)>>("((a 1 . 2 3))").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TrailingCharacters);
+
+    // A `.` is only a dot when a delimiter follows it.
+    let err = sexpr::from_str::<(i32, f64)>("(1 .5)").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ExpectedSomeValue);
+    assert_eq!(
+        sexpr::from_str::<(i32, Vec<i32>)>("(1 .(2))").unwrap(),
+        (1, vec![2])
+    );
+}
+
+#[test]
+fn test_from_str_impls() {
+    use sexpr::sexp::Atom;
+    use sexpr::Number;
+
+    fn parse<T: std::str::FromStr>(s: &str) -> Option<T> {
+        s.parse().ok()
+    }
+
+    assert_eq!(
+        parse::<Sexp>("(a b)"),
+        Some(Sexp::List(vec![Sexp::from("a"), Sexp::from("b")]))
+    );
+    assert_eq!(parse::<Sexp>("(a b"), None);
+    assert_eq!(Sexp::from_str("(a b)").ok(), parse::<Sexp>("(a b)"));
+
+    // The inherent guessing constructor no longer hides `FromStr`.
+    assert_eq!(Atom::from_str_guess("\"a b\""), Atom::string("a b"));
+    assert!(<Atom as std::str::FromStr>::from_str("\"a b").is_err());
+
+    assert_eq!(parse::<Atom>("a"), Some(Atom::symbol("a")));
+    assert_eq!(parse::<Atom>("#:port"), Some(Atom::keyword("port")));
+    assert_eq!(parse::<Atom>("\"a b\""), Some(Atom::string("a b")));
+    assert_eq!(parse::<Atom>("|a b|"), Some(Atom::symbol("a b")));
+    let err = "(a)".parse::<Atom>().unwrap_err();
+    assert_eq!(err.to_string(), "invalid type: sequence, expected an atom");
+    assert_eq!(parse::<Atom>("1"), None);
+
+    assert_eq!(parse::<Number>("-12"), Some(Number::from(-12)));
+    assert_eq!(parse::<Number>("2.5"), Number::from_f64(2.5));
+    let err = "a".parse::<Number>().unwrap_err();
+    assert_eq!(err.to_string(), "invalid type: symbol, expected a number");
+    assert_eq!(parse::<Number>("1 2"), None);
+}
+
+#[test]
+fn test_sexp_display_from_str_round_trip() {
+    use sexpr::sexp::Atom;
+    use sexpr::Number;
+
+    let values = vec![
+        Sexp::Nil,
+        Sexp::Atom(Atom::symbol("a")),
+        Sexp::Atom(Atom::symbol("a b")),
+        Sexp::Atom(Atom::keyword("port")),
+        Sexp::Atom(Atom::string("a \"b\"")),
+        Sexp::Number(Number::from(-12)),
+        Sexp::Number(Number::from(u64::MAX)),
+        Sexp::Number(Number::from_f64(2.5).unwrap()),
+        Sexp::Boolean(true),
+        Sexp::Boolean(false),
+        Sexp::Bytes(vec![0, 1, 0xff]),
+        Sexp::Bytes(vec![]),
+        Sexp::pair("a", 1),
+        Sexp::pair(Sexp::pair(1, 2), Sexp::Nil),
+        Sexp::pair("a", Sexp::pair("b", "c")),
+        Sexp::List(vec![]),
+        Sexp::List(vec![
+            Sexp::from("a"),
+            Sexp::pair("b", vec![1, 2]),
+            Sexp::Nil,
+        ]),
+    ];
+    for value in values {
+        let text = value.to_string();
+        assert_eq!(text.parse::<Sexp>().unwrap(), value, "{}", text);
+        let pretty = format!("{:#}", value);
+        assert_eq!(pretty.parse::<Sexp>().unwrap(), value, "{}", pretty);
+    }
+}
+
+#[test]
+fn test_atom_conversions_agree() {
+    use sexpr::sexp::Atom;
+
+    // Reading an atom keeps the kind written in the text, as for a `Sexp`.
+    for (text, atom) in vec![
+        ("\"abc\"", Atom::string("abc")),
+        ("\"#:abc\"", Atom::string("#:abc")),
+        ("\"'q'\"", Atom::string("'q'")),
+        ("abc", Atom::symbol("abc")),
+        ("#:abc", Atom::keyword("abc")),
+    ] {
+        assert_eq!(sexpr::from_str::<Atom>(text).unwrap(), atom, "{}", text);
+        assert_eq!(
+            sexpr::from_str::<Sexp>(text).unwrap(),
+            Sexp::Atom(atom),
+            "{}",
+            text
+        );
+    }
+
+    // The conversions from text never guess.
+    for &text in &["abc", "\"abc\"", "#:abc"] {
+        assert_eq!(Atom::from_string(text.to_owned()), Atom::symbol(text));
+        assert_eq!(
+            Atom::from(text.to_owned()),
+            Atom::from_string(text.to_owned())
+        );
+        assert_eq!(Atom::from(text), Atom::symbol(text));
+    }
+}
+
+#[test]
+fn test_unicode_whitespace() {
+    use sexpr::de::{Deserializer, DeserializerOptions};
+    use sexpr::sexp::Atom;
+
+    fn symbols(names: &[&str]) -> Sexp {
+        Sexp::List(names.iter().map(|&name| Sexp::from(name)).collect())
+    }
+
+    let input = "(a\u{a0}b\u{2028}c\u{c}d\u{b}e\u{3000}caf\u{e9}\u{85}na\u{ef}ve)";
+    let expected = symbols(&["a", "b", "c", "d", "e", "caf\u{e9}", "na\u{ef}ve"]);
+    assert_eq!(sexpr::from_str::<Sexp>(input).unwrap(), expected);
+    assert_eq!(
+        sexpr::from_slice::<Sexp>(input.as_bytes()).unwrap(),
+        expected
+    );
+    assert_eq!(
+        sexpr::from_reader::<_, Sexp>(input.as_bytes()).unwrap(),
+        expected
+    );
+    assert_eq!(
+        sexpr::from_str::<Vec<u32>>("\u{a0}(1\u{2029}2\u{c})\u{205f}").unwrap(),
+        vec![1, 2]
+    );
+
+    // Skipped values end where parsed ones do.
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        a: u8,
+        b: u8,
+    }
+    let input = "((a . 1) (junk x\u{a0}\"y ) z\") (b . 2))";
+    let expected = Config { a: 1, b: 2 };
+    assert_eq!(sexpr::from_str::<Config>(input).unwrap(), expected);
+    assert_eq!(
+        sexpr::from_reader::<_, Config>(input.as_bytes()).unwrap(),
+        expected
+    );
+
+    let strict = DeserializerOptions {
+        strict_whitespace: true,
+        ..DeserializerOptions::default()
+    };
+    let mut de = Deserializer::from_str_with_options("(a\u{a0}b)", strict.clone());
+    let sexp: Sexp = serde::Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(sexp, symbols(&["a\u{a0}b"]));
+    let mut de = Deserializer::from_str_with_options("(1\u{c}2)", strict);
+    assert!(<Vec<u32> as serde::Deserialize>::deserialize(&mut de).is_err());
+
+    // Such symbols are written between bars to read back as themselves.
+    let sexp = Sexp::Atom(Atom::symbol("a\u{a0}b"));
+    assert_eq!(sexpr::to_string(&sexp).unwrap(), "|a\u{a0}b|");
+}
+
+#[test]
+fn test_line_endings() {
+    fn positions(input: &str) -> [(usize, usize); 2] {
+        let err = sexpr::from_str::<Vec<u32>>(input).unwrap_err();
+        let io_err = sexpr::from_reader::<_, Vec<u32>>(input.as_bytes()).unwrap_err();
+        [(err.line(), err.column()), (io_err.line(), io_err.column())]
+    }
+
+    let expected = positions("(1\n2\n x)");
+    assert_eq!(expected[0].0, 3);
+    assert_eq!(positions("(1\r\n2\r\n x)"), expected);
+    assert_eq!(positions("(1\r2\r x)"), expected);
+
+    // A comment ends at a CR too.
+    assert_eq!(
+        sexpr::from_str::<Vec<u32>>("(1 ; one\r2 ; two\r\n3)").unwrap(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(
+        sexpr::from_reader::<_, Vec<u32>>(&b"(1 ; one\r2)"[..]).unwrap(),
+        vec![1, 2]
+    );
+}
+
+#[test]
+fn test_position_of() {
+    use sexpr::de::{Deserializer, Position, SliceRead, StrRead};
+
+    let input = "(a)\n(b\r\n c)\r\r(d ; e\n)\n\n  (f)";
+    let read = SliceRead::new(input.as_bytes());
+    let spans: Vec<_> = Deserializer::from_str(input)
+        .into_iter::<Sexp>()
+        .spanned()
+        .map(|item| item.unwrap().1)
+        .collect();
+    assert_eq!(spans.len(), 4);
+    for span in &spans {
+        let expected = Position {
+            line: span.line,
+            column: span.column,
+        };
+        assert_eq!(read.position_of(span.start), expected);
+        assert_eq!(StrRead::new(input).position_of(span.start), expected);
+    }
+    assert_eq!(
+        spans
+            .iter()
+            .map(|span| (span.line, span.column))
+            .collect::<Vec<_>>(),
+        vec![(1, 1), (2, 1), (5, 1), (8, 3)]
+    );
+
+    // The LF of a CR LF pair is at the end of the line the CR ended.
+    assert_eq!(read.position_of(6), Position { line: 2, column: 3 });
+    assert_eq!(read.position_of(7), Position { line: 2, column: 4 });
+    assert_eq!(read.position_of(8), Position { line: 3, column: 1 });
+    assert_eq!(read.position_of(0), Position { line: 1, column: 1 });
+    assert_eq!(read.position_of(usize::MAX), read.position_of(input.len()));
+}
+
+#[test]
+fn test_sort_and_dedup_lists() {
+    let mut deps = Sexp::List(vec![
+        Sexp::new_entry("serde", 1),
+        Sexp::from(7),
+        Sexp::new_entry("anyhow", 2),
+        Sexp::new_entry("log", 3),
+        Sexp::new_entry("anyhow", 4),
+    ]);
+    deps.sort_alist_keys();
+    assert_eq!(
+        deps,
+        Sexp::List(vec![
+            Sexp::new_entry("anyhow", 2),
+            Sexp::new_entry("anyhow", 4),
+            Sexp::new_entry("log", 3),
+            Sexp::new_entry("serde", 1),
+            Sexp::from(7),
+        ])
+    );
+
+    let mut numbers: Sexp = sexpr::from_str("(3 1 2 1 3.5 2)").unwrap();
+    numbers.dedup_list();
+    assert_eq!(numbers.to_string(), "(3 1 2 3.5)");
+    numbers.sort_list_by_key(|n| std::cmp::Reverse(n.to_string()));
+    assert_eq!(numbers.to_string(), "(3.5 3 2 1)");
+
+    // Anything but a list is left alone.
+    let mut atom = Sexp::from("a");
+    atom.dedup_list();
+    atom.sort_alist_keys();
+    atom.sort_list_by_key(|_| 0);
+    assert_eq!(atom, Sexp::from("a"));
+}
+
+#[test]
+fn test_project() {
+    use std::collections::BTreeMap;
+
+    let config: Sexp = sexpr::from_str(
+        "(package (name \"web\") (tags \"a\" \"b\") 7 (db (host \"db1\") (port 5432)))",
+    )
+    .unwrap();
+    assert_eq!(
+        sexpr::project(&config, &["tags", "db.port", "missing"]).to_string(),
+        "(package (tags \"a\" \"b\") (db (port 5432)))"
+    );
+    // A path into something that is not a list keeps nothing of it.
+    assert_eq!(
+        sexpr::project(&config, &["name.first"]).to_string(),
+        "(package (name))"
+    );
+    assert_eq!(sexpr::project(&Sexp::from(1), &["a"]).to_string(), "()");
+
+    let pairs = Sexp::List(vec![Sexp::pair("a", 1), Sexp::pair("b", 2)]);
+    assert_eq!(
+        sexpr::project(&pairs, &["b"]),
+        Sexp::List(vec![Sexp::pair("b", 2)])
+    );
+
+    #[derive(Serialize)]
+    struct Login {
+        user: String,
+        password: Option<String>,
+        limits: BTreeMap<String, u32>,
+    }
+
+    let mut limits = BTreeMap::new();
+    limits.insert("files".to_owned(), 10);
+    limits.insert("procs".to_owned(), 2);
+    let login = Login {
+        user: "ada".to_owned(),
+        password: Some("hunter2".to_owned()),
+        limits,
+    };
+    assert_eq!(
+        to_string(&sexpr::Projected::new(&login, &["user", "limits.procs"])).unwrap(),
+        "(\"user\".\"ada\" \"limits\".(\"procs\".2))"
+    );
+}
+
+#[test]
+fn test_redacting_formatter() {
+    use sexpr::ser::{CompactFormatter, NonAsciiChars, Redacting};
+    use std::collections::BTreeMap;
+
+    fn redacted<T: ser::Serialize>(value: &T) -> String {
+        let formatter = Redacting::new(CompactFormatter)
+            .with_key("password")
+            .with_key("pass\"word")
+            .with_key_matching(|key| key.to_lowercase().contains("token"));
+        let mut ser = Serializer::with_formatter(Vec::new(), formatter)
+            .with_non_ascii_chars(NonAsciiChars::EscapeUnicode);
+        value.serialize(&mut ser).unwrap();
+        String::from_utf8(ser.into_inner()).unwrap()
+    }
+
+    #[derive(Serialize)]
+    struct Request {
+        user: String,
+        password: Option<String>,
+        tokens: Vec<String>,
+        headers: BTreeMap<String, String>,
+    }
+
+    let mut headers = BTreeMap::new();
+    headers.insert("X-Auth-Token".to_owned(), "s3cr\u{e9}t".to_owned());
+    headers.insert("pass\"word".to_owned(), "x".to_owned());
+    headers.insert("Accept".to_owned(), "*/*".to_owned());
+    let request = Request {
+        user: "ada".to_owned(),
+        password: Some("hunter2".to_owned()),
+        tokens: vec!["a".to_owned(), "b".to_owned()],
+        headers,
+    };
+    assert_eq!(
+        redacted(&request),
+        "(\"user\".\"ada\" \"password\".\"***\" \"tokens\".(\"***\" \"***\") \
+         \"headers\".(\"Accept\".\"*/*\" \"X-Auth-Token\".\"***\" \"pass\\\"word\".\"***\"))"
+    );
+
+    // The heads of a `Sexp` alist's entries are keys too.
+    let config: Sexp = sexpr::from_str(
+        "((user \"ada\") (password \"hunter2\" (old \"a\" 1)) (#:token \"t\") (\"password\" \"p\"))",
+    )
+    .unwrap();
+    assert_eq!(
+        redacted(&config),
+        "((user \"ada\") (password \"***\" (old \"***\" 1)) (#:token \"***\") (\"password\" \"***\"))"
+    );
+
+    // Nothing is redacted until keys are chosen.
+    let mut ser = Serializer::with_formatter(Vec::new(), Redacting::new(CompactFormatter));
+    ser::Serialize::serialize(&config, &mut ser).unwrap();
+    assert_eq!(
+        String::from_utf8(ser.into_inner()).unwrap(),
+        config.to_string()
+    );
+}
+
+#[test]
+fn test_find_form() {
+    use sexpr::de::find_form;
+    use sexpr::Deserializer;
+
+    let netlist = "; board\n(export (version \"E\") (design (tool \"(module fake)\"))\n \
+                   (libparts (part #:module [1 2]))\n \
+                   (modules (|module| (name a) (pads (pad 1) (pad 2))) (module (name b))))";
+    let module = find_form(netlist.as_bytes(), "module").unwrap().unwrap();
+    assert_eq!(
+        module.to_string(),
+        "(module (name a) (pads (pad 1) (pad 2)))"
+    );
+
+    // Searching again goes on from the end of the form found.
+    let mut de = Deserializer::from_str(netlist);
+    assert_eq!(
+        de.find_form("name").unwrap().unwrap().to_string(),
+        "(name a)"
+    );
+    assert_eq!(
+        de.find_form("name").unwrap().unwrap().to_string(),
+        "(name b)"
+    );
+
+    assert_eq!(find_form(netlist.as_bytes(), "missing").unwrap(), None);
+    assert_eq!(find_form(&b""[..], "module").unwrap(), None);
+
+    for bad in &["(a (b 1)", "(a (b 1]", "(a))", "(a (module 1"] {
+        assert!(find_form(bad.as_bytes(), "module").is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_split_top_level() {
+    let input = "#t \"a (\" |b)| (c ; )\n d) [1] #u8(1 2)\n; (trailing\n";
+    let mut forms = sexpr::split_top_level(input);
+    assert_eq!(
+        forms.by_ref().collect::<Vec<_>>(),
+        ["#t", "\"a (\"", "|b)|", "(c ; )\n d)", "[1]", "#u8(1 2)"]
+    );
+    assert_eq!(forms.byte_offset(), input.len());
+    assert_eq!(forms.next(), None);
+
+    assert_eq!(sexpr::split_top_level("  ; nothing\n").count(), 0);
+
+    // Whatever cannot be split is handed back whole.
+    assert_eq!(
+        sexpr::split_top_level("(a) (b (c)").collect::<Vec<_>>(),
+        ["(a)", "(b (c)"]
+    );
+    assert_eq!(
+        sexpr::split_top_level("(a)) (b)").collect::<Vec<_>>(),
+        ["(a)", ") (b)"]
+    );
+    assert_eq!(
+        sexpr::split_top_level("(a) \"b").collect::<Vec<_>>(),
+        ["(a)", "\"b"]
+    );
+}
+
+#[test]
+fn test_escaped_for() {
+    use sexpr::ser::{to_string_escaped_for, EscapeTarget};
+
+    let form = (Sexp::from("echo"), "tab\t'q' \"x\" \\ <&> é\u{1}");
+    assert_eq!(
+        to_string_escaped_for(&form, EscapeTarget::JsonString).unwrap(),
+        "(echo \\\"tab\\\\t'q' \\\\\\\"x\\\\\\\" \\\\\\\\ <&> é\\\\u0001\\\")"
+    );
+    assert_eq!(
+        to_string_escaped_for(&form, EscapeTarget::ShellSingleQuoted).unwrap(),
+        "(echo \"tab\\t'\\''q'\\'' \\\"x\\\" \\\\ <&> é\\u0001\")"
+    );
+    assert_eq!(
+        to_string_escaped_for(&form, EscapeTarget::Html).unwrap(),
+        "(echo &quot;tab\\t&#39;q&#39; \\&quot;x\\&quot; \\\\ &lt;&amp;&gt; é\\u0001&quot;)"
+    );
+    // Text with nothing to escape is written as it is.
+    assert_eq!(
+        to_string_escaped_for(&(1, Sexp::from("a")), EscapeTarget::JsonString).unwrap(),
+        to_string(&(1, Sexp::from("a"))).unwrap()
+    );
+}
+
+#[test]
+fn test_pretty_inline_atoms() {
+    use sexpr::ser::{Formatter, Redacting};
+
+    fn pretty<T: ser::Serialize, F: Formatter>(value: &T, formatter: F) -> String {
+        let mut ser = Serializer::with_formatter(Vec::new(), formatter);
+        value.serialize(&mut ser).unwrap();
+        String::from_utf8(ser.into_inner()).unwrap()
+    }
+    let formatter = || PrettyFormatter::new().with_inline_atoms(3, 8);
+
+    assert_eq!(pretty(&vec![1.5, 2.0, 3.25], formatter()), "(1.5 2.0 3.25)");
+    assert_eq!(pretty(&Vec::<u8>::new(), formatter()), "()");
+    // Too many atoms, or one too long, and the list is laid out as before.
+    assert_eq!(
+        pretty(&vec![1, 2, 3, 4], formatter()),
+        pretty(&vec![1, 2, 3, 4], PrettyFormatter::new())
+    );
+    assert_eq!(
+        pretty(&("short", "much too long"), formatter()),
+        "(\n  \"short\"\n  \"much too long\"\n)"
+    );
+
+    let tree: Sexp = sexpr::from_str("(a (b c) (d (e)) ())").unwrap();
+    assert_eq!(
+        pretty(&tree, formatter()),
+        "(\n  a\n  (b c)\n  (\n    d\n    (e)\n  )\n  ()\n)"
+    );
+
+    #[derive(Serialize)]
+    enum Shape {
+        Point(i32, i32),
+    }
+    assert_eq!(
+        pretty(&Shape::Point(1, -2), formatter()),
+        "{\n  \"Point\": (1 -2)\n}"
+    );
+
+    // Keys at the head of a list held on one line are still seen.
+    let login: Sexp = sexpr::from_str("((user \"ada\") (password \"hunter2\"))").unwrap();
+    assert_eq!(
+        pretty(&login, Redacting::new(formatter()).with_key("password")),
+        "(\n  (user \"ada\")\n  (password \"***\")\n)"
+    );
+}
+
+#[test]
+fn test_pretty_indent_presets() {
+    fn pretty<T: ser::Serialize>(value: &T, formatter: PrettyFormatter) -> String {
+        let mut ser = Serializer::with_formatter(Vec::new(), formatter);
+        value.serialize(&mut ser).unwrap();
+        String::from_utf8(ser.into_inner()).unwrap()
+    }
+
+    assert_eq!(
+        pretty(&vec![vec![1]], PrettyFormatter::tabs()),
+        "(\n\t(\n\t\t1\n\t)\n)"
+    );
+    assert_eq!(pretty(&vec![1], PrettyFormatter::spaces(4)), "(\n    1\n)");
+    assert_eq!(pretty(&vec![1], PrettyFormatter::spaces(0)), "(\n1\n)");
+    assert_eq!(
+        pretty(&vec![1], PrettyFormatter::spaces(2)),
+        pretty(&vec![1], PrettyFormatter::new())
+    );
+
+    let formatter = || PrettyFormatter::new().with_trailing_newline(true);
+    assert_eq!(
+        pretty(&vec![vec![1]], formatter()),
+        "(\n  (\n    1\n  )\n)\n"
+    );
+    assert_eq!(
+        pretty(&vec![vec![1, 2]], formatter().with_inline_atoms(4, 4)),
+        "(\n  (1 2)\n)\n"
+    );
+    assert_eq!(pretty(&Vec::<u8>::new(), formatter()), "()\n");
+    assert_eq!(pretty(&1, formatter()), "1");
+
+    let mut map = std::collections::BTreeMap::new();
+    map.insert("a", 1);
+    assert_eq!(pretty(&map, formatter()), "{\n  \"a\": 1\n}\n");
+}
+
+#[test]
+#[should_panic(expected = "indent must be spaces and tabs only")]
+fn test_pretty_indent_rejects_non_whitespace() {
+    PrettyFormatter::with_indent(b" )");
+}
+
+#[test]
+fn test_describe() {
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    struct Meters(f64);
+
+    #[derive(Serialize)]
+    struct Marker;
+
+    #[derive(Serialize)]
+    enum Shape {
+        Empty,
+        Circle(Meters),
+        Rect(u32, u32),
+        Polygon { sides: u8 },
+    }
+
+    #[derive(Serialize)]
+    struct Drawing {
+        name: String,
+        shapes: Vec<Shape>,
+        layers: BTreeMap<String, (bool, char)>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        marker: Marker,
+        empty: Vec<u8>,
+    }
+
+    fn describe<T: ser::Serialize>(value: &T) -> String {
+        Sexp::describe(value).unwrap().to_string()
+    }
+
+    let mut layers = BTreeMap::new();
+    layers.insert("base".to_owned(), (true, 'x'));
+    let drawing = Drawing {
+        name: "d".to_owned(),
+        shapes: vec![Shape::Circle(Meters(1.0)), Shape::Empty],
+        layers,
+        note: None,
+        marker: Marker,
+        empty: Vec::new(),
+    };
+    assert_eq!(
+        describe(&drawing),
+        "(struct Drawing (name string) \
+         (shapes (seq (enum Shape (Circle (newtype Meters f64))))) \
+         (layers (map string (tuple bool char))) (note skipped) \
+         (marker (unit-struct Marker)) (empty (seq)))"
+    );
+
+    assert_eq!(describe(&Shape::Empty), "(enum Shape Empty)");
+    assert_eq!(describe(&Shape::Rect(1, 2)), "(enum Shape (Rect u32 u32))");
+    assert_eq!(
+        describe(&Shape::Polygon { sides: 3 }),
+        "(enum Shape (Polygon (sides u8)))"
+    );
+    assert_eq!(describe(&Some(1i64)), "(option i64)");
+    assert_eq!(describe(&()), "unit");
+}
+
+#[test]
+fn test_error_expected_found_path() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Server {
+        host: String,
+        port: u32,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Config {
+        servers: Vec<Server>,
+        pair: (u8, u8),
+    }
+
+    let err = sexpr::from_str::<Config>(
+        "((servers ((host . \"a\") (port . 80))\n ((host . \"b\") (port . \"abc\"))))",
+    )
+    .unwrap_err();
+    assert_eq!(err.expected(), Some("u32"));
+    assert_eq!(err.found(), Some("string \"abc\""));
+    assert_eq!(err.path().as_deref(), Some("servers.1.port"));
+    assert_eq!(err.line(), 2);
+    assert!(err.is_data());
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"abc\", expected u32 at line 2 column 28"
+    );
+
+    let err = sexpr::from_str::<Config>("((servers) (pair 1 -2))").unwrap_err();
+    assert_eq!(err.expected(), Some("u8"));
+    assert_eq!(err.found(), Some("integer `-2`"));
+    assert_eq!(err.path().as_deref(), Some("pair.1"));
+
+    let err = sexpr::from_str::<u32>("\"x\"").unwrap_err();
+    assert_eq!(err.path(), None);
+    assert_eq!(err.found(), Some("string \"x\""));
+
+    let err = sexpr::from_str::<Vec<Vec<u8>>>("((1) (2 x))").unwrap_err();
+    assert_eq!(err.path().as_deref(), Some("1.1"));
+    assert_eq!(err.expected(), Some("u8"));
+
+    // Errors other than a mismatch carry a path but no summaries.
+    let err = sexpr::from_str::<Vec<Vec<u8>>>("((1) (2").unwrap_err();
+    assert_eq!(err.path().as_deref(), Some("1"));
+    assert_eq!((err.expected(), err.found()), (None, None));
+
+    let err = sexpr::from_str::<Vec<bool>>("(#t 7)").unwrap_err();
+    assert_eq!(err.path().as_deref(), Some("1"));
+}
+
+#[test]
+fn test_lenient_scalars() {
+    use sexpr::de::{Deserializer, DeserializerOptions};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        port: u16,
+        offset: i32,
+        ratio: f64,
+        scale: f32,
+        verbose: bool,
+        quiet: bool,
+        debug: Option<bool>,
+        name: String,
+    }
+
+    let input = "((port . \"8080\") (offset . \" -3 \") (ratio . 1) (scale . \"0.5\") \
+                 (verbose . yes) (quiet . NIL) (debug . no) (name . \"42\"))";
+    let lenient = DeserializerOptions {
+        lenient_scalars: true,
+        ..DeserializerOptions::default()
+    };
+    let read = |input: &str| {
+        let mut de = Deserializer::from_str_with_options(input, lenient.clone());
+        <Config as serde::Deserialize>::deserialize(&mut de)
+    };
+    assert_eq!(
+        read(input).unwrap(),
+        Config {
+            port: 8080,
+            offset: -3,
+            ratio: 1.0,
+            scale: 0.5,
+            verbose: true,
+            quiet: false,
+            debug: Some(false),
+            name: "42".to_owned(),
+        }
+    );
+    assert!(sexpr::from_str::<Config>(input).is_err());
+
+    let mut de = Deserializer::from_str_with_options("(t \"True\" false #nil #t)", lenient.clone());
+    let flags: Vec<bool> = serde::Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(flags, [true, true, false, false, true]);
+
+    // What cannot be coerced fails as it would without the option.
+    let err = read(&input.replace("\"8080\"", "\"80x\"")).unwrap_err();
+    assert_eq!(err.found(), Some("string \"80x\""));
+    assert_eq!(err.expected(), Some("u16"));
+    let err = read(&input.replace("\"8080\"", "\"70000\"")).unwrap_err();
+    assert_eq!(err.found(), Some("integer `70000`"));
+    let err = read(&input.replace("yes", "maybe")).unwrap_err();
+    assert_eq!(err.expected(), Some("a boolean"));
+    assert_eq!(err.path().as_deref(), Some("verbose"));
+}
+
+#[test]
+fn test_nil_as_default() {
+    use sexpr::de::{Deserializer, DeserializerOptions};
+
+    fn default_host() -> String {
+        "localhost".to_owned()
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        #[serde(default)]
+        port: u16,
+        #[serde(default = "default_host")]
+        host: String,
+        user: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        name: String,
+    }
+
+    let options = DeserializerOptions {
+        nil_as_default: true,
+        ..DeserializerOptions::default()
+    };
+    let read = |input: &str, options: DeserializerOptions| {
+        let mut de = Deserializer::from_str_with_options(input, options);
+        <Config as serde::Deserialize>::deserialize(&mut de)
+    };
+
+    // Missing keys take their defaults with or without the option.
+    let expected = Config {
+        port: 0,
+        host: "localhost".to_owned(),
+        user: None,
+        tags: Vec::new(),
+        name: "web".to_owned(),
+    };
+    assert_eq!(
+        sexpr::from_str::<Config>("((name . \"web\"))").unwrap(),
+        expected
+    );
+
+    let input = "((port . #nil) (host . #nil) (user . #nil) (tags . #nil) (name . \"web\"))";
+    assert_eq!(read(input, options.clone()).unwrap(), expected);
+    assert!(sexpr::from_str::<Config>(input).is_err());
+
+    let input = "((port . 8080) (host . \"a\") (tags \"x\" \"y\") (name . \"web\"))";
+    let config = read(input, options.clone()).unwrap();
+    assert_eq!((config.port, config.tags.len()), (8080, 2));
+
+    let with_nil_lists = DeserializerOptions {
+        empty_list_as_nil: true,
+        ..options.clone()
+    };
+    assert_eq!(
+        read("((tags . ()) (name . \"web\"))", with_nil_lists).unwrap(),
+        expected
+    );
+
+    // With `nil_symbol`, the symbol `nil` is nil too, but other symbols that
+    // start with `n` are still values.
+    let with_nil_symbol = DeserializerOptions {
+        nil_symbol: true,
+        ..options.clone()
+    };
+    let input = "((port . nil) (host . nil) (user . nil) (tags . nil) (name . \"web\"))";
+    assert_eq!(read(input, with_nil_symbol.clone()).unwrap(), expected);
+    assert!(read(input, options.clone()).is_err());
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Form {
+        head: Option<sexpr::sexp::Symbol>,
+    }
+    let mut de = Deserializer::from_str_with_options("((head . none))", with_nil_symbol);
+    let form = <Form as serde::Deserialize>::deserialize(&mut de).unwrap();
+    assert_eq!(form.head.unwrap().as_str(), "none");
+
+    // A required field that is nil is missing.
+    let err = read("((name . #nil))", options).unwrap_err();
+    assert_eq!(err.to_string(), "missing field `name` at line 1 column 14");
+}
+
+#[test]
+fn test_rest_of_list() {
+    use sexpr::sexp::Symbol;
+    use sexpr::Rest;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Call(Symbol, Rest<Sexp>);
+
+    let calls: Vec<Call> = sexpr::from_str("((f) (g 1) (h 1 (x y)))").unwrap();
+    let arities: Vec<_> = calls
+        .iter()
+        .map(|call| (call.0.as_str(), call.1.len()))
+        .collect();
+    assert_eq!(arities, [("f", 0), ("g", 1), ("h", 2)]);
+    assert_eq!(to_string(&calls).unwrap(), "((f) (g 1) (h 1 (x y)))");
+
+    // The elements before a `Rest` are read by position as usual.
+    let form: (Symbol, u8, Rest<u8>) = sexpr::from_str("(f 1 2 3)").unwrap();
+    assert_eq!(form.2, Rest(vec![2, 3]));
+    assert_eq!(to_string(&form).unwrap(), "(f 1 2 3)");
+
+    // A dotted tail holds the rest as one list, as in Lisp.
+    let form: (Symbol, Rest<u8>) = sexpr::from_str("(f . (1 2))").unwrap();
+    assert_eq!(form.1, Rest(vec![1, 2]));
+
+    assert!(sexpr::from_str::<(Symbol, u8)>("(f)").is_err());
+
+    let pretty = sexpr::ser::to_string_pretty(&(Symbol::new("f"), Rest(Vec::<u8>::new()))).unwrap();
+    assert_eq!(pretty, "(\n  f\n)");
+
+    // Outside of a list a `Rest` is a list of its own.
+    assert_eq!(to_string(&Rest(vec![1, 2])).unwrap(), "(1 2)");
+}
+
+#[test]
+fn test_document() {
+    use sexpr::sexp::{DocumentItem, SexpDocument};
+
+    let input = ";;; config\n(port 80) ; web\n\n(host \"a\")\n;; end\n";
+    let doc = sexpr::from_str_document(input).unwrap();
+    assert_eq!(
+        doc.0,
+        [
+            DocumentItem::Comment(";; config".to_owned()),
+            DocumentItem::Form(sexpr::from_str("(port 80)").unwrap()),
+            DocumentItem::Comment(" web".to_owned()),
+            DocumentItem::Form(sexpr::from_str("(host \"a\")").unwrap()),
+            DocumentItem::Comment("; end".to_owned()),
+        ]
+    );
+
+    let text = sexpr::to_string_document(&doc).unwrap();
+    assert_eq!(text, ";;; config\n(port 80)\n; web\n(host \"a\")\n;; end\n");
+    assert_eq!(sexpr::from_str_document(&text).unwrap(), doc);
+
+    // Comments of several lines are split, and nothing is still a document.
+    let mut doc = SexpDocument::new();
+    doc.push_comment("a\nb");
+    doc.push_form(Sexp::from(1));
+    assert_eq!(sexpr::to_string_document(&doc).unwrap(), ";a\n;b\n1\n");
+    assert_eq!(
+        sexpr::from_str_document(" \n").unwrap(),
+        SexpDocument::new()
+    );
+    assert_eq!(doc.into_forms(), [Sexp::from(1)]);
+
+    let err = sexpr::from_str_document("(a)\n(b").unwrap_err();
+    assert_eq!((err.line(), err.column()), (2, 2));
+}
+
+#[test]
+fn test_list_shape() {
+    let improper = Sexp::pair(1, Sexp::pair(2, Sexp::pair(3, "rest")));
+    assert_eq!(improper.len(), 3);
+    assert!(!improper.is_proper() && !improper.is_empty());
+    assert_eq!(improper.tail(), Some(&Sexp::from("rest")));
+    assert_eq!(improper.arity(), None);
+
+    // A chain of pairs ending in nil or a list is proper.
+    let proper = Sexp::pair(1, Sexp::pair(2, Sexp::List(vec![Sexp::from(3)])));
+    assert_eq!(proper.len(), 3);
+    assert!(proper.is_proper());
+    assert_eq!(proper.tail(), None);
+    assert_eq!(proper.arity(), Some(2));
+    assert_eq!(
+        proper.proper_prefix(),
+        [&Sexp::from(1), &Sexp::from(2), &Sexp::from(3)]
+    );
+    assert_eq!(Sexp::pair(1, Sexp::Nil).len(), 1);
+
+    for empty in &[Sexp::Nil, Sexp::List(Vec::new())] {
+        assert!(empty.is_empty() && empty.is_proper());
+        assert_eq!((empty.len(), empty.arity()), (0, None));
+    }
+
+    let atom = Sexp::from("a");
+    assert!(!atom.is_empty() && !atom.is_proper());
+    assert_eq!((atom.len(), atom.tail(), atom.arity()), (0, None, None));
+    assert!(atom.proper_prefix().is_empty());
+}
+
+#[test]
+fn test_flatten_and_proper_list() {
+    use sexpr::error::ErrorKind;
+
+    let mut form: Sexp = sexpr::from_str("(a (b (c (d))) #nil e)").unwrap();
+    form.flatten_once();
+    assert_eq!(form.to_string(), "(a b (c (d)) #nil e)");
+    form.flatten_deep();
+    assert_eq!(form.to_string(), "(a b c d #nil e)");
+
+    // Pairs count as lists, tail and all.
+    let mut form = Sexp::List(vec![Sexp::pair(1, Sexp::pair(2, 3)), Sexp::from(4)]);
+    form.flatten_once();
+    assert_eq!(form.to_string(), "(1 2 3 4)");
+
+    let mut atom = Sexp::from("a");
+    atom.flatten_deep();
+    assert_eq!(atom, Sexp::from("a"));
+
+    // `(a . (b c))` and `(a b c)` normalize to the same list.
+    let dotted = Sexp::pair("a", sexpr::from_str::<Sexp>("(b c)").unwrap());
+    let plain: Sexp = sexpr::from_str("(a b c)").unwrap();
+    assert_eq!(dotted.to_proper_list(false).unwrap(), plain);
+    assert_eq!(plain.to_proper_list(false).unwrap(), plain);
+    assert_eq!(
+        Sexp::Nil.to_proper_list(false).unwrap(),
+        Sexp::List(Vec::new())
+    );
+
+    let improper = Sexp::pair("a", Sexp::pair("b", "c"));
+    assert_eq!(improper.to_proper_list(true).unwrap(), plain);
+    let err = improper.to_proper_list(false).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ExpectedProperList);
+    assert_eq!(err.to_string(), "expected a proper list");
+    assert!(Sexp::from(1).to_proper_list(true).is_err());
+}
+
+#[test]
+fn test_structural_eq() {
+    let plain: Sexp = sexpr::from_str("(a (b c) d)").unwrap();
+    let inner = Sexp::pair("b", Sexp::pair("c", Sexp::Nil));
+    let dotted = Sexp::pair("a", Sexp::List(vec![inner, Sexp::from("d")]));
+    assert_ne!(dotted, plain);
+    assert!(dotted.structural_eq(&plain) && plain.structural_eq(&dotted));
+
+    assert!(Sexp::Nil.structural_eq(&Sexp::List(Vec::new())));
+    assert!(Sexp::pair(1, 2).structural_eq(&Sexp::pair(1, 2)));
+    assert!(!Sexp::pair(1, 2).structural_eq(&Sexp::List(vec![Sexp::from(1), Sexp::from(2)])));
+    assert!(!plain.structural_eq(&sexpr::from_str("(a (b c))").unwrap()));
+    assert!(Sexp::from("a").structural_eq(&Sexp::from("a")));
+    assert!(!Sexp::from("a").structural_eq(&Sexp::List(vec![Sexp::from("a")])));
+
+    // The same holds for values read from text.
+    let parse = |text: &str| sexpr::from_str::<Sexp>(text).unwrap();
+    assert!(parse("(a . (b c))").structural_eq(&parse("(a b c)")));
+    assert!(parse("(a b . (c))").structural_eq(&parse("(a b c)")));
+    assert!(parse("(a . (b . (c . #nil)))").structural_eq(&parse("(a b c)")));
+    assert!(parse("((a . (b)) c)").structural_eq(&parse("((a b) c)")));
+    assert!(!parse("(a b . c)").structural_eq(&parse("(a b c)")));
+}
+
+#[test]
+fn test_parse_single_atom() {
+    use sexpr::sexp::Atom;
+
+    assert_eq!("sym".parse::<Atom>().unwrap(), Atom::symbol("sym"));
+    assert_eq!("#:key".parse::<Atom>().unwrap(), Atom::keyword("key"));
+    assert_eq!(
+        "\"\\u00e9\\t\"".parse::<Atom>().unwrap(),
+        Atom::string("é\t")
+    );
+    assert_eq!("-7".parse::<Number>().unwrap(), Number::from(-7));
+    assert!("18446744073709551616".parse::<Number>().unwrap().is_f64());
+
+    // Exactly one token: no surrounding space, comments or other values.
+    let err = " sym".parse::<Atom>().unwrap_err();
+    assert_eq!(err.to_string(), "expected value at line 1 column 2");
+    let err = "1 ; one".parse::<Number>().unwrap_err();
+    assert_eq!(err.to_string(), "trailing characters at line 1 column 2");
+    assert!("".parse::<Atom>().is_err());
+    assert!(" sym ".parse::<Atom>().is_err());
+
+    let err = "(a)".parse::<Atom>().unwrap_err();
+    assert_eq!(err.found(), Some("sequence"));
+    assert_eq!(
+        "sym".parse::<Number>().unwrap_err().expected(),
+        Some("a number")
+    );
+
+    // `Sexp` still reads a whole document.
+    assert_eq!(" sym ".parse::<Sexp>().unwrap(), Sexp::from("sym"));
+}
+
+#[test]
+fn test_always_decimal_point() {
+    fn write<T: ser::Serialize>(value: &T, style: FloatStyle, always: bool) -> String {
+        let formatter = PrettyFormatter::new().with_float_style(style);
+        let mut ser =
+            Serializer::with_formatter(Vec::new(), formatter).with_always_decimal_point(always);
+        value.serialize(&mut ser).unwrap();
+        String::from_utf8(ser.into_inner()).unwrap()
+    }
+
+    // The default shortest form drops the point only in exponent form.
+    let cases: &[(f64, &str, &str)] = &[
+        (0.0, "0.0", "0.0"),
+        (-0.0, "-0.0", "-0.0"),
+        (3.0, "3.0", "3.0"),
+        (-2.5, "-2.5", "-2.5"),
+        (1e16, "10000000000000000.0", "10000000000000000.0"),
+        (1e300, "1e300", "1.0e300"),
+        (1e-7, "1e-7", "1.0e-7"),
+        (5e-324, "5e-324", "5.0e-324"),
+        (f64::MAX, "1.7976931348623157e308", "1.7976931348623157e308"),
+    ];
+    for &(value, plain, pointed) in cases {
+        assert_eq!(write(&value, FloatStyle::Shortest, false), plain);
+        assert_eq!(write(&value, FloatStyle::Shortest, true), pointed);
+    }
+    assert_eq!(write(&1e-7f32, FloatStyle::Shortest, true), "1.0e-7");
+    assert_eq!(
+        write(&16777216f32, FloatStyle::Shortest, true),
+        "16777216.0"
+    );
+
+    // Other styles write integral values as bare integers without it.
+    assert_eq!(write(&2.0, FloatStyle::Trimmed(3), false), "2");
+    assert_eq!(write(&2.0, FloatStyle::Trimmed(3), true), "2.0");
+    assert_eq!(write(&-0.0001, FloatStyle::Trimmed(2), true), "0.0");
+    assert_eq!(write(&2.25, FloatStyle::Trimmed(3), true), "2.25");
+    assert_eq!(write(&7.0, FloatStyle::Fixed(0), true), "7.0");
+    assert_eq!(write(&1500.0, FloatStyle::Scientific, true), "1.5e3");
+    assert_eq!(write(&3.0, FloatStyle::Scientific, true), "3.0e0");
+
+    // Integers and non-finite tokens are left alone.
+    let formatter = PrettyFormatter::new();
+    let mut ser = Serializer::with_formatter(Vec::new(), formatter)
+        .with_always_decimal_point(true)
+        .with_non_finite_floats(NonFiniteFloats::WriteSpecialTokens);
+    ser::Serialize::serialize(&(3, f64::INFINITY), &mut ser).unwrap();
+    assert_eq!(
+        String::from_utf8(ser.into_inner()).unwrap(),
+        "(\n  3\n  +inf.0\n)"
+    );
+}
+
+#[cfg(feature = "arbitrary_precision")]
+#[test]
+fn test_arbitrary_precision() {
+    let input = "(123456789012345678901234567890 -0.10000000000000000001 1.50 2.5 7)";
+    let sexp: Sexp = sexpr::from_str(input).unwrap();
+    assert_eq!(sexp.to_string(), input);
+    assert_eq!(to_string(&sexp).unwrap(), input);
+    assert_eq!(sexpr::to_value(&sexp).unwrap(), sexp);
+
+    let big: Number = sexpr::from_str("123456789012345678901234567890").unwrap();
+    assert_eq!(big.as_literal(), Some("123456789012345678901234567890"));
+    assert_eq!(big.as_f64(), Some(1.2345678901234568e29));
+    let plain: Number = sexpr::from_str("2.5").unwrap();
+    assert_eq!(plain.as_literal(), None);
+    assert_eq!(plain, Number::from_f64(2.5).unwrap());
+
+    // Typed numbers still read the nearest value.
+    let floats: Vec<f64> = sexpr::from_str("(1.50 123456789012345678901234567890)").unwrap();
+    assert_eq!(floats, [1.5, 1.2345678901234568e29]);
+    let opt: Option<f64> = sexpr::from_str("0.10000000000000000001").unwrap();
+    assert_eq!(opt, Some(0.1));
+
+    assert_eq!(Number::from_literal("-0.50").unwrap().to_string(), "-0.50");
+    assert!(Number::from_literal("007").is_none());
+    assert!(Number::from_literal("1.").is_none());
+}
+
+#[test]
+fn test_boolean_dialects() {
+    use sexpr::de::{Deserializer, DeserializerOptions};
+    use sexpr::sexp::Symbol;
+
+    fn read<T>(input: &str, options: &DeserializerOptions) -> sexpr::Result<T>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        let mut de = Deserializer::from_str_with_options(input, options.clone());
+        let value = T::deserialize(&mut de)?;
+        de.end()?;
+        Ok(value)
+    }
+
+    let scheme = DeserializerOptions {
+        long_booleans: true,
+        ..DeserializerOptions::default()
+    };
+    let flags: Vec<bool> = read("(#true #false #t #f)", &scheme).unwrap();
+    assert_eq!(flags, [true, false, true, false]);
+    let sexp: Sexp = read("(#true #false)", &scheme).unwrap();
+    assert_eq!(sexp.to_string(), "(#t #f)");
+    assert!(read::<bool>("#tru", &scheme).is_err());
+    assert!(sexpr::from_str::<bool>("#true").is_err());
+
+    let elisp = DeserializerOptions {
+        symbol_booleans: true,
+        ..DeserializerOptions::default()
+    };
+    let flags: Vec<bool> = read("(t nil #t #f)", &elisp).unwrap();
+    assert_eq!(flags, [true, false, true, false]);
+    let err = read::<bool>("tea", &elisp).unwrap_err();
+    assert_eq!(err.expected(), Some("a boolean"));
+    assert!(sexpr::from_str::<Vec<bool>>("(t nil)").is_err());
+
+    // Only a bool reads the symbols as booleans.
+    let (flag, name, maybe): (bool, Symbol, Option<bool>) = read("(t t nil)", &elisp).unwrap();
+    assert_eq!((flag, name.as_str(), maybe), (true, "t", None));
+    let sexp: Sexp = read("(t nil)", &elisp).unwrap();
+    // Bare, the symbol `nil` would read back as nil where an option is
+    // expected.
+    assert_eq!(sexp.to_string(), "(t |nil|)");
+
+    let folded = DeserializerOptions {
+        case_insensitive: true,
+        long_booleans: true,
+        symbol_booleans: true,
+        ..DeserializerOptions::default()
+    };
+    let flags: Vec<bool> = read("(T NIL #TRUE #False)", &folded).unwrap();
+    assert_eq!(flags, [true, false, true, false]);
+}
+
+#[test]
+fn test_trailing_colon_keywords() {
+    use sexpr::de::{Deserializer, DeserializerOptions};
+    use sexpr::ser::KeywordStyle;
+    use sexpr::sexp::{Atom, Keyword};
+
+    let options = DeserializerOptions {
+        trailing_colon_keywords: true,
+        ..DeserializerOptions::default()
+    };
+    let read = |input: &str| -> Sexp {
+        let mut de = Deserializer::from_str_with_options(input, options.clone());
+        serde::Deserialize::deserialize(&mut de).unwrap()
+    };
+
+    let input = "(make-box width: 80 #:height 20 |label:| name:)";
+    let sexp = read(input);
+    assert_eq!(
+        sexp.to_string(),
+        "(make-box #:width 80 #:height 20 label: #:name)"
+    );
+    assert_eq!(
+        sexpr::from_str::<Sexp>(input).unwrap().to_string(),
+        "(make-box width: 80 #:height 20 label: name:)"
+    );
+
+    let mut de = Deserializer::from_str_with_options("(width: nice:)", options.clone());
+    let (width, nice): (Keyword, Option<Keyword>) =
+        serde::Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!((width.as_str(), nice.unwrap().as_str()), ("width", "nice"));
+
+    let mut ser = Serializer::new(Vec::new()).with_keyword_style(KeywordStyle::TrailingColon);
+    ser::Serialize::serialize(&sexp, &mut ser).unwrap();
+    let written = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(written, "(make-box width: 80 height: 20 |label:| name:)");
+    assert_eq!(read(&written), sexp);
+
+    // A keyword that needs bars keeps its prefix.
+    let mut ser = Serializer::new(Vec::new()).with_keyword_style(KeywordStyle::TrailingColon);
+    ser::Serialize::serialize(&Atom::keyword("a b"), &mut ser).unwrap();
+    assert_eq!(ser.into_inner(), b"#:|a b|");
+}
+
+#[test]
+fn test_strict_options() {
+    use sexpr::de::{Deserializer, DeserializerOptions};
+    use sexpr::error::ErrorKind;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Request {
+        id: u64,
+        ok: bool,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    fn read<T>(input: &str) -> sexpr::Result<T>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        let mut de = Deserializer::from_str_with_options(input, DeserializerOptions::strict());
+        let value = T::deserialize(&mut de)?;
+        de.end()?;
+        Ok(value)
+    }
+
+    let request: Request = read("((id . 7) (ok . #t) (tags \"a\" \"b\"))").unwrap();
+    assert_eq!(request.tags, ["a", "b"]);
+
+    let err = read::<Request>("((id . 7) (ok . #t) (id . 8))").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DuplicateKey);
+    assert_eq!(err.to_string(), "duplicate key `id` at line 1 column 23");
+    let err = read::<Request>("((id . 7) (\"id\" . 8) (ok . #t))").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DuplicateKey);
+    // A key the struct has no field for is a duplicate too.
+    assert!(sexpr::from_str::<Request>("((id . 7) (ok . #t) (x . 1) (x . 2))").is_ok());
+    assert!(read::<Request>("((id . 7) (ok . #t) (x . 1) (x . 2))").is_err());
+    // So is a key repeated in a map, where the last value wins otherwise.
+    let err = read::<BTreeMap<String, u8>>("((a . 1) (b . 2) (a . 3))").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DuplicateKey);
+    assert_eq!(err.to_string(), "duplicate key `a` at line 1 column 19");
+    let err = read::<HashMap<String, u8>>("(\"a\".1 \"a\".2)").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DuplicateKey);
+    let map: BTreeMap<String, u8> = sexpr::from_str("((a . 1) (a . 3))").unwrap();
+    assert_eq!(map["a"], 3);
+
+    let err = read::<Request>("((id . 7) ; the id\n (ok . #t))").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::CommentNotAllowed);
+    assert_eq!((err.line(), err.column()), (1, 11));
+    // Comments inside a skipped value are rejected too.
+    let err = read::<Request>("((id . 7) (ok . #t) (extra 1 ; one\n))").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::CommentNotAllowed);
+    let err = read::<Request>("((id . 7) (ok . #t)) ; done").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::CommentNotAllowed);
+
+    assert!(read::<Request>("[(id . 7) (ok . #t)]").is_err());
+    assert!(read::<Request>("((id . 7) (ok . #true))").is_err());
+    assert!(read::<Request>("((id . 7) (ok . #T))").is_err());
+    assert!(read::<Request>("((id . 7) (ok . t))").is_err());
+    assert!(read::<Request>("((id . 7)\u{a0}(ok . #t))").is_err());
+    let err = read::<Sexp>("18446744073709551616").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::IntegerOutOfRange);
+}
+
+#[test]
+fn test_validate_syntax() {
+    assert!(sexpr::validate_syntax("").is_ok());
+    assert!(sexpr::validate_syntax("(a (b c) \"d\" #:e 1.5) ; done\n42").is_ok());
+
+    let input = "(a 1)\n(b 1..2\n  (c 2))\n(d \"ok\")\n(e))\n(f #q)\n(g";
+    let errors = sexpr::validate_syntax(input).unwrap_err();
+    let lines: Vec<usize> = errors.iter().map(|err| err.line()).collect();
+    assert_eq!(lines, [2, 5, 6, 7]);
+    assert!(errors[3].is_eof());
+
+    // Elements deep inside a list are parsed too, not only scanned.
+    assert!(sexpr::validate_syntax("(a (b (c #q)))").is_err());
+    assert!(sexpr::from_str::<Sexp>("(a (b (c #q)))").is_err());
+
+    let errors = sexpr::validate_syntax_reader(input.as_bytes()).unwrap_err();
+    assert_eq!(errors.len(), 4);
+    assert!(sexpr::validate_syntax_reader("(a 1) (b 2)".as_bytes()).is_ok());
+
+    // Dotted pairs and alists are well formed; a misplaced dot is not.
+    assert!(sexpr::validate_syntax("(a . 1)").is_ok());
+    assert!(sexpr::validate_syntax("((a . 1) (b . (1 2)))").is_ok());
+    assert!(sexpr::validate_syntax("(a b . c)").is_ok());
+    assert_eq!(sexpr::validate_syntax("(a . 1 2)").unwrap_err().len(), 1);
+    assert_eq!(sexpr::validate_syntax("(. 1)").unwrap_err().len(), 1);
+}
+
+#[test]
+fn test_to_dot() {
+    let v: Sexp = sexpr::from_str(r#"(a "b \"c\"" () 1.5)"#).unwrap();
+    let dot = v.to_dot();
+    assert!(dot.starts_with("digraph sexp {\n"));
+    assert!(dot.ends_with("}\n"));
+    assert!(dot.contains(r#"n2 [label="\"b \\\"c\\\"\"", shape=box];"#));
+    assert!(dot.contains("n3 [label=\"()\"];\n    n0 -> n3;\n    n4 [label=\"1.5\""));
+    assert_eq!(dot.matches("->").count(), 4);
+
+    let pair = Sexp::new_entry("k", 1);
+    let dot = pair.to_dot();
+    assert!(dot.contains("n0 [label=\".\"];"));
+    assert!(dot.contains("n0 -> n1;\n    n2 [label=\"1\", shape=box];\n    n0 -> n2;"));
+
+    assert_eq!(
+        Sexp::Boolean(true).to_dot(),
+        "digraph sexp {\n    ordering=out;\n    n0 [label=\"#t\", shape=box];\n}\n"
+    );
+}
+
+#[test]
+fn test_pair_to_string() {
+    let pair = Sexp::pair("a", 1);
+    assert_eq!(pair.to_string(), "(a . 1)");
+    assert_eq!(sexpr::to_string(&pair).unwrap(), "(a . 1)");
+    assert_eq!(
+        Sexp::pair(Sexp::pair(1, 2), "x").to_string(),
+        "((1 . 2) . x)"
+    );
+    assert_eq!(Sexp::from(vec![("a", 1)]).to_string(), "((a . 1))");
+    assert_eq!(sexpr::sexp::to_value(&pair).unwrap(), pair);
+
+    let mut map = std::collections::BTreeMap::new();
+    map.insert("k", vec![1, 2]);
+    let value = sexpr::sexp::to_value(&map).unwrap();
+    assert_eq!(value.to_string(), "((k . (1 2)))");
+    assert_eq!(sexpr::to_string(&value).unwrap(), "((k . (1 2)))");
+    let doc = sexpr::sexp::SexpDocument::from(vec![value]);
+    assert_eq!(
+        sexpr::ser::to_string_document(&doc).unwrap(),
+        "((k . (1 2)))\n"
+    );
+}
+
+#[test]
+fn test_pair_from_str() {
+    let pairs = vec![
+        Sexp::pair("a", 1),
+        Sexp::pair(Sexp::pair(1, 2), "x"),
+        Sexp::pair("k", vec![1, 2]),
+        Sexp::pair(1, Sexp::Nil),
+        Sexp::from(vec![("a", 1), ("b", 2)]),
+    ];
+    for pair in pairs {
+        let text = sexpr::to_string(&pair).unwrap();
+        assert_eq!(sexpr::from_str::<Sexp>(&text).unwrap(), pair, "{}", text);
+        let shared = sexpr::sexp::Interner::new().from_str(&text).unwrap();
+        assert_eq!(shared.to_sexp(), pair, "{}", text);
+    }
+
+    // A dotted list is a chain of pairs ending in its tail.
+    assert_eq!(
+        sexpr::from_str::<Sexp>("(a b . c)").unwrap(),
+        Sexp::pair("a", Sexp::pair("b", "c"))
+    );
+
+    // Only one element may follow the dot, and one must come before it.
+    let err = sexpr::from_str::<Sexp>("(a . b c)").unwrap_err();
+    assert_eq!(err.to_string(), "trailing characters at line 1 column 8");
+    assert!(sexpr::from_str::<Sexp>("(. a)").is_err());
+    assert!(sexpr::from_str::<Sexp>("(a .)").is_err());
+}
+
+#[test]
+fn test_nil_symbol_bars() {
+    use sexpr::sexp::{Atom, Symbol};
+
+    let some_nil = Some(Symbol("nil".to_owned()));
+    let text = sexpr::to_string(&some_nil).unwrap();
+    assert_eq!(text, "|nil|");
+    assert_eq!(sexpr::from_str::<Option<Symbol>>(&text).unwrap(), some_nil);
+    assert_eq!(sexpr::from_str::<Option<Symbol>>("nil").unwrap(), None);
+
+    assert_eq!(sexpr::to_string(&Atom::symbol("NIL")).unwrap(), "|NIL|");
+    assert_eq!(sexpr::to_string(&Atom::symbol("nils")).unwrap(), "nils");
+    assert!(Atom::try_new_symbol("nil".to_owned()).is_err());
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn test_config_format() {
+    use ::config::{Config, File};
+
+    use sexpr::config::SexpFormat;
+
+    let text = "((server (host \"example.org\") (ports 80 443) (tls #:cert \"a.pem\"))
+                 (retries 3) (ratio 0.5) (debug #f) (tags (x)))";
+    let base = File::from_str(text, SexpFormat);
+    let local = File::from_str("((server (host \"localhost\")))", SexpFormat);
+    let settings = Config::builder()
+        .add_source(base)
+        .add_source(local)
+        .set_override("debug", true)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(settings.get_string("server.host").unwrap(), "localhost");
+    assert_eq!(settings.get::<Vec<u16>>("server.ports").unwrap(), [80, 443]);
+    assert_eq!(settings.get_string("server.tls.cert").unwrap(), "a.pem");
+    assert_eq!(settings.get_int("retries").unwrap(), 3);
+    assert_eq!(settings.get_float("ratio").unwrap(), 0.5);
+    assert!(settings.get_bool("debug").unwrap());
+    assert_eq!(settings.get::<Vec<String>>("tags").unwrap(), ["x"]);
+
+    let empty = Config::builder()
+        .add_source(File::from_str("()", SexpFormat))
+        .build()
+        .unwrap();
+    assert!(empty.get_string("server.host").is_err());
+
+    for text in &["(1 2)", "\"x\"", "((a 1)"] {
+        let built = Config::builder()
+            .add_source(File::from_str(text, SexpFormat))
+            .build();
+        assert!(built.is_err(), "{}", text);
+    }
+}
+
+#[test]
+fn test_list_and_alist_macros() {
+    use sexpr::sexp::Atom;
+    use sexpr::{alist, list};
+
+    assert_eq!(list![], Sexp::List(vec![]));
+    assert_eq!(
+        list![1, "a", -2.5, true,],
+        Sexp::List(vec![
+            Sexp::from(1),
+            Sexp::from("a"),
+            Sexp::from(-2.5),
+            Sexp::from(true),
+        ])
+    );
+    let n = 4;
+    let nested = list![n * 2, list![], list![list!["deep"]]];
+    assert_eq!(nested.to_string(), "(8 () ((deep)))");
+
+    assert_eq!(alist! {}, Sexp::List(vec![]));
+    let name = "width";
+    let value = alist! {
+        sym height => 3,
+        name => list![1, 2],
+        Atom::string("label") => "box",
+        sym nested => alist! { sym x => 1 },
+    };
+    assert_eq!(
+        value,
+        Sexp::List(vec![
+            Sexp::new_entry(Atom::symbol("height"), 3),
+            Sexp::new_entry("width", list![1, 2]),
+            Sexp::new_entry(Atom::string("label"), "box"),
+            Sexp::new_entry(
+                Atom::symbol("nested"),
+                Sexp::List(vec![Sexp::new_entry(Atom::symbol("x"), 1)])
+            ),
+        ])
+    );
+
+    assert_eq!(
+        value.to_string(),
+        "((height . 3) (width . (1 2)) (\"label\" . box) (nested . ((x . 1))))"
+    );
+
+    // What the macros build reads back from its text.
+    for value in &[nested, value] {
+        let text = value.to_string();
+        assert_eq!(sexpr::from_str::<Sexp>(&text).unwrap(), *value, "{}", text);
+    }
+}
+
+#[test]
+fn test_from_tuples_and_iterators() {
+    use sexpr::sexp::{Atom, Keyword};
+    use std::collections::BTreeMap;
+
+    // A tuple is a dotted pair keyed by an atom.
+    assert_eq!(Sexp::from(("a", 1)), Sexp::new_entry(Atom::symbol("a"), 1));
+    assert_eq!(
+        Sexp::from((String::from("b"), "x")),
+        Sexp::new_entry(Atom::symbol("b"), "x")
+    );
+    assert_eq!(
+        Sexp::from((Keyword::from("k"), true)).to_string(),
+        "(#:k . #t)"
+    );
+    assert_eq!(
+        Sexp::from((Atom::string("s"), vec![1, 2])).to_string(),
+        "(\"s\" . (1 2))"
+    );
+
+    // A vec, slice or iterator of tuples is an alist.
+    let entries = vec![("x", 1), ("y", 2)];
+    let alist = Sexp::List(vec![Sexp::new_entry("x", 1), Sexp::new_entry("y", 2)]);
+    assert_eq!(Sexp::from(entries.clone()), alist);
+    assert_eq!(Sexp::from(&entries[..]), alist);
+    assert_eq!(entries.iter().cloned().collect::<Sexp>(), alist);
+    assert_eq!(Sexp::from(Vec::<(&str, i32)>::new()), Sexp::List(vec![]));
+
+    let map: BTreeMap<&str, Vec<i32>> = vec![("b", vec![2]), ("a", vec![])].into_iter().collect();
+    let from_map: Sexp = map.clone().into_iter().collect();
+    assert_eq!(from_map.to_string(), "((a . ()) (b . (2)))");
+
+    // Other elements collect into a plain list.
+    assert_eq!((1..4).collect::<Sexp>().to_string(), "(1 2 3)");
+    assert_eq!(
+        vec!["p", "q"].into_iter().collect::<Sexp>(),
+        Sexp::List(vec![Sexp::from("p"), Sexp::from("q")])
+    );
+
+    // The alists read back from their text, both as a tree and as a map.
+    for value in &[alist, from_map] {
+        let text = value.to_string();
+        assert_eq!(sexpr::from_str::<Sexp>(&text).unwrap(), *value, "{}", text);
+    }
+    let read: BTreeMap<String, Vec<i32>> = sexpr::from_str("((a . ()) (b . (2)))").unwrap();
+    assert_eq!(
+        read,
+        map.into_iter().map(|(k, v)| (k.to_owned(), v)).collect()
+    );
+}
+
+#[test]
+fn test_skip_unknown_fields() {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Known {
+        a: u8,
+        b: String,
+    }
+
+    let input = "((junk . (1 (2 (3 [4])) \"nested ( list\"))\n\
+                 (a . 7) ; a comment ) with a paren\n\
+                 (text . \"closing ) and \\\" quote\")\n\
+                 (sym . |odd ) symbol|)\n\
+                 (pairs . ((x . 1) (y . (2 . 3)) ; (z . 4\n\
+                           #:k #x\"ab\" #u8(1 2) #t -1.5e3))\n\
+                 (b . \"kept\")\n\
+                 (empty . ()))";
+    let expected = Known {
+        a: 7,
+        b: "kept".to_owned(),
+    };
+
+    // Each source of input skips with its own scanner.
+    assert_eq!(sexpr::from_str::<Known>(input).unwrap(), expected);
+    assert_eq!(
+        sexpr::from_slice::<Known>(input.as_bytes()).unwrap(),
+        expected
+    );
+    assert_eq!(
+        sexpr::from_reader::<_, Known>(input.as_bytes()).unwrap(),
+        expected
+    );
+
+    // A value that never ends is still an error while skipping it.
+    for input in &[
+        "((junk . (1 (2)) (a . 1) (b . \"x\"))",
+        "((junk . \"open ) (a . 1) (b . \"x\"))",
+        "((junk . |open ) (a . 1) (b . \"x\"))",
+    ] {
+        assert!(sexpr::from_str::<Known>(input).is_err(), "{}", input);
+        assert!(
+            sexpr::from_reader::<_, Known>(input.as_bytes()).is_err(),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_custom_read() {
+    use serde::Deserialize as _;
+    use sexpr::de::{Deserializer, Position, Read};
+    use std::io;
+
+    // Hands out its input from separate chunks, so that looking ahead has to
+    // cross from one chunk into the next.
+    struct ChunkRead {
+        chunks: Vec<Vec<u8>>,
+        chunk: usize,
+        index: usize,
+        offset: usize,
+        line: usize,
+        column: usize,
+    }
+
+    impl ChunkRead {
+        fn new(input: &str, size: usize) -> Self {
+            ChunkRead {
+                chunks: input.as_bytes().chunks(size).map(<[u8]>::to_vec).collect(),
+                chunk: 0,
+                index: 0,
+                offset: 0,
+                line: 1,
+                column: 0,
+            }
+        }
+
+        fn get(&self, ahead: usize) -> Option<u8> {
+            let (mut chunk, mut index) = (self.chunk, self.index + ahead);
+            while index >= self.chunks.get(chunk)?.len() {
+                index -= self.chunks[chunk].len();
+                chunk += 1;
+            }
+            Some(self.chunks[chunk][index])
+        }
+    }
+
+    impl<'de> Read<'de> for ChunkRead {
+        fn next(&mut self) -> io::Result<Option<u8>> {
+            let ch = self.get(0);
+            if let Some(ch) = ch {
+                self.index += 1;
+                if self.index == self.chunks[self.chunk].len() {
+                    self.chunk += 1;
+                    self.index = 0;
+                }
+                self.offset += 1;
+                if ch == b'\n' {
+                    self.line += 1;
+                    self.column = 0;
+                } else {
+                    self.column += 1;
+                }
+            }
+            Ok(ch)
+        }
+
+        fn peek(&mut self) -> io::Result<Option<u8>> {
+            Ok(self.get(0))
+        }
+
+        fn peek_second(&mut self) -> io::Result<Option<u8>> {
+            Ok(self.get(1))
+        }
+
+        fn peek_third(&mut self) -> io::Result<Option<u8>> {
+            Ok(self.get(2))
+        }
+
+        fn discard(&mut self) {
+            let _ = self.next();
+        }
+
+        fn position(&self) -> Position {
+            Position {
+                line: self.line,
+                column: self.column,
+            }
+        }
+
+        fn peek_position(&self) -> Position {
+            Position {
+                line: self.line,
+                column: self.column + 1,
+            }
+        }
+
+        fn byte_offset(&self) -> usize {
+            self.offset
+        }
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    // Every split of the input reads the same values as a string does.
+    let input = "(() (a) #t #f \"esc\\\"aped\\n\" ; comment\n\
+                 (x\u{2028}y) |bar sym| #:key -1.5 #x\"00ff\" #u8(1 2) (a . b)) (c)";
+    let expected: Vec<Sexp> = Deserializer::from_str(input)
+        .into_iter()
+        .collect::<sexpr::Result<_>>()
+        .unwrap();
+    assert_eq!(expected.len(), 2);
+    for size in 1..=4 {
+        let read: Vec<Sexp> = Deserializer::new(ChunkRead::new(input, size))
+            .into_iter()
+            .collect::<sexpr::Result<_>>()
+            .unwrap();
+        assert_eq!(read, expected, "chunks of {}", size);
+
+        // Skipping unknown fields and reading a struct go through the
+        // provided methods too.
+        let text = "((z . (1 \"2)\" (3))) (y . 2) (x . 1))";
+        let mut de = Deserializer::new(ChunkRead::new(text, size));
+        assert_eq!(Point::deserialize(&mut de).unwrap(), Point { x: 1, y: 2 });
+        de.end().unwrap();
+
+        // Errors point into the input as they would in a string.
+        let text = "((x . 1)\n (y . \"2\"))";
+        let err = Point::deserialize(&mut Deserializer::new(ChunkRead::new(text, size)));
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            sexpr::from_str::<Point>(text).unwrap_err().to_string()
+        );
+    }
+}
+
+#[test]
+fn test_atom_kind_accessors() {
+    use sexpr::sexp::Atom;
+
+    // Each kind of atom read from text answers to its own accessor only.
+    let read = |input: &str| input.parse::<Atom>().unwrap();
+    let symbol = read("name");
+    let keyword = read("#:name");
+    let string = read("\"name\"");
+    let bar_symbol = read("|two words|");
+
+    assert!(symbol.is_symbol() && !symbol.is_keyword() && !symbol.is_string());
+    assert!(!keyword.is_symbol() && keyword.is_keyword() && !keyword.is_string());
+    assert!(!string.is_symbol() && !string.is_keyword() && string.is_string());
+
+    assert_eq!(
+        (symbol.as_symbol(), symbol.as_keyword(), symbol.as_string()),
+        (Some("name"), None, None)
+    );
+    assert_eq!(
+        (
+            keyword.as_symbol(),
+            keyword.as_keyword(),
+            keyword.as_string()
+        ),
+        (None, Some("name"), None)
+    );
+    assert_eq!(
+        (string.as_symbol(), string.as_keyword(), string.as_string()),
+        (None, None, Some("name"))
+    );
+    assert_eq!(bar_symbol.as_symbol(), Some("two words"));
+
+    // The text is the same whatever the kind, but the atoms differ.
+    for atom in &[&symbol, &keyword, &string] {
+        assert_eq!(atom.as_str(), "name");
+        assert_eq!((*atom).clone().into_string(), "name");
+    }
+    assert_ne!(symbol, keyword);
+    assert_ne!(symbol, string);
+    assert_ne!(keyword, string);
+
+    // Atoms inside a tree keep their kind.
+    let sexp: Sexp = sexpr::from_str("(name #:name \"name\")").unwrap();
+    let kinds: Vec<_> = match sexp {
+        Sexp::List(elems) => elems
+            .into_iter()
+            .map(|elem| match elem {
+                Sexp::Atom(atom) => atom,
+                other => panic!("not an atom: {}", other),
+            })
+            .collect(),
+        other => panic!("not a list: {}", other),
+    };
+    assert_eq!(kinds, [symbol, keyword, string]);
+}